@@ -0,0 +1,20 @@
+#!/bin/sh
+# Run the Crafting Interpreters test suite and print how many tests pass in every
+# directory. The suite is cloned into `target/` the first time, `LOX_SUITE` points at
+# another checkout. Error messages are worded differently from jlox, so only the lines
+# of errors are compared. `--book` makes the interpreter accept `class B < A`, return
+# seconds from `clock()` and stop at the first runtime error like jlox.
+set -eu
+
+suite=${LOX_SUITE:-target/craftinginterpreters}
+if [ ! -d "$suite" ]; then
+    git clone --depth 1 https://github.com/munificent/craftinginterpreters.git "$suite"
+fi
+
+# scanning and expressions are for the interpreters of earlier chapters, limit is for
+# clox and benchmarks only measure speed
+dirs=$(find "$suite/test" -mindepth 1 -maxdepth 1 -type d \
+    ! -name scanning ! -name expressions ! -name limit ! -name benchmark | sort)
+
+# shellcheck disable=SC2086
+cargo run --release --quiet -- test --book --ignore-messages --matrix "$@" $dirs "$suite"/test/*.lox
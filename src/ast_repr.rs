@@ -1,4 +1,13 @@
-use crate::{expr::Expr, object::Object, stmt::Stmt, visitor::Visitor};
+use crate::{
+    cache,
+    expr::Expr,
+    formatter,
+    object::Object,
+    stack,
+    stmt::{Function, Stmt},
+    token::Token,
+    visitor::Visitor,
+};
 
 #[derive(Default)]
 pub(crate) struct AstRepr;
@@ -20,13 +29,25 @@ impl AstRepr {
             .collect::<Vec<String>>()
             .join("\n")
     }
+
+    fn function(&mut self, fun: &Function) -> String {
+        let name = fun.name.name();
+        let params = fun
+            .params
+            .iter()
+            .map(|token| token.name())
+            .collect::<Vec<_>>()
+            .join(",");
+        let body = self.visit_stmt(&fun.body);
+        format!("Stmt::Function(name={name} params={params} body={body})")
+    }
 }
 
 impl Visitor<String, String> for AstRepr {
     fn visit_expr(&mut self, e: &Expr) -> String {
         match e {
             Expr::Binary(binary) => {
-                let left = self.visit_expr(&binary.left);
+                let left = stack::grow(|| self.visit_expr(&binary.left));
                 let right = self.visit_expr(&binary.right);
                 let operator = binary.operator.lexeme();
                 format!("Expr::Binary({left} {operator} {right})")
@@ -43,20 +64,22 @@ impl Visitor<String, String> for AstRepr {
                 Object::Bool(b) => b.to_string(),
                 Object::Callable(callable) => callable.to_string(),
                 Object::LoxInstance(instance) => instance.to_string(),
+                Object::List(list) => list.to_string(),
+                Object::Map(map) => map.to_string(),
             },
             Expr::Grouping(group) => {
                 let expr = self.visit_expr(&group.expr);
                 format!("Expr::Group({expr})")
             }
             Expr::Variable(var) => {
-                format!("Expr::Variable({})", var.name.lexeme())
+                format!("Expr::Variable({})", var.name.name())
             }
             Expr::Assign(assign) => {
                 let value = self.visit_expr(&assign.value);
-                format!("Expr::Assign({} = {})", assign.name.lexeme(), value)
+                format!("Expr::Assign({} = {})", assign.name.name(), value)
             }
             Expr::Logical(logical) => {
-                let left = self.visit_expr(&logical.left);
+                let left = stack::grow(|| self.visit_expr(&logical.left));
                 let right = self.visit_expr(&logical.right);
                 format!(
                     "Expr::Logical({} {} {})",
@@ -66,7 +89,7 @@ impl Visitor<String, String> for AstRepr {
                 )
             }
             Expr::Call(call) => {
-                let callee = self.visit_expr(&call.callee);
+                let callee = stack::grow(|| self.visit_expr(&call.callee));
                 let arguments = call
                     .arguments
                     .iter()
@@ -76,19 +99,19 @@ impl Visitor<String, String> for AstRepr {
                 format!("Expr::Call(callee={callee} arguments={arguments})")
             }
             Expr::Get(get) => {
-                let object = self.visit_expr(&get.object);
-                let name = get.name.lexeme();
+                let object = stack::grow(|| self.visit_expr(&get.object));
+                let name = get.name.name();
                 format!("Expr::Get(object={object}, name={name})")
             }
             Expr::Set(set) => {
-                let object = self.visit_expr(&set.object);
-                let name = set.name.lexeme();
+                let object = stack::grow(|| self.visit_expr(&set.object));
+                let name = set.name.name();
                 let value = self.visit_expr(&set.value);
                 format!("Expr::Set(object={object}, name={name}, value={value})")
             }
             Expr::This(_) => "Expr::This".to_string(),
             Expr::Super(super_call) => {
-                format!("Expr::Super(method={})", super_call.method.lexeme())
+                format!("Expr::Super(method={})", super_call.method.name())
             }
         }
     }
@@ -99,12 +122,12 @@ impl Visitor<String, String> for AstRepr {
                 let expr = self.visit_expr(e);
                 format!("Stmt::Expr({expr})")
             }
-            Stmt::Print(e) => {
-                let value = self.visit_expr(e);
+            Stmt::Print(print) => {
+                let value = self.visit_expr(&print.expression);
                 format!("Stmt::Print({value})")
             }
             Stmt::Var(var) => {
-                let name = var.identifier.lexeme();
+                let name = var.identifier.name();
                 let value = self.visit_expr(&var.expression);
                 format!("Stmt::Var({name} = {value})")
             }
@@ -141,17 +164,13 @@ impl Visitor<String, String> for AstRepr {
                 let body = self.visit_stmt(&while_statement.body);
                 format!("Stmt::While(cond={condition}, body={body})")
             }
-            Stmt::Function(fun) => {
-                let name = fun.name.lexeme();
-                let params = fun
-                    .params
-                    .iter()
-                    .map(|token| token.lexeme())
-                    .collect::<Vec<_>>()
-                    .join(",");
-                let body = self.visit_stmt(&fun.body);
-                format!("Stmt::Function(name={name} params={params} body={body})")
+            Stmt::ForIn(for_in) => {
+                let name = for_in.name.name();
+                let iterable = self.visit_expr(&for_in.iterable);
+                let body = self.visit_stmt(&for_in.body);
+                format!("Stmt::ForIn({name} in {iterable}, body={body})")
             }
+            Stmt::Function(fun) => self.function(fun),
             Stmt::Return(return_statement) => {
                 let value = self.visit_expr(&return_statement.value);
                 format!("Stmt::Return({value})")
@@ -160,20 +179,368 @@ impl Visitor<String, String> for AstRepr {
                 let methods = class
                     .methods
                     .iter()
-                    .map(|fun| self.visit_stmt(fun))
+                    .map(|fun| self.function(fun))
                     .collect::<Vec<_>>()
                     .join(", ");
                 let superclass = class
                     .superclass
                     .as_ref()
-                    .map(|ident| ident.name.lexeme())
+                    .map(|ident| ident.name.name())
                     .unwrap_or("Null");
                 format!(
                     "
                     Stmt::Class(name={}, superclass={superclass}, methods=({methods}))",
-                    class.name.lexeme()
+                    class.name.name()
                 )
             }
         }
     }
 }
+
+// how `--ast` prints the syntax tree
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub(crate) enum AstFormat {
+    #[default]
+    Tree,
+    Sexpr,
+    Json,
+    // lox source, with `for` loops rewritten the way the interpreter runs them
+    Source,
+    // json of every token and position, which runs without parsing again, see `cache`
+    Full,
+}
+
+enum Value {
+    Name(String),
+    Names(Vec<String>),
+    Literal(Object),
+}
+
+impl Value {
+    fn text(&self) -> String {
+        match self {
+            Value::Name(name) => name.clone(),
+            Value::Names(names) => format!("({})", names.join(" ")),
+            Value::Literal(object) => object.repr(),
+        }
+    }
+
+    fn json(&self) -> String {
+        match self {
+            Value::Name(name) => json_string(name),
+            Value::Names(names) => {
+                let names: Vec<_> = names.iter().map(|name| json_string(name)).collect();
+                format!("[{}]", names.join(","))
+            }
+            Value::Literal(object) => match object {
+                Object::Null => "null".to_string(),
+                Object::String(s) => json_string(s),
+                object => object.to_string(),
+            },
+        }
+    }
+}
+
+enum Child {
+    One(Node),
+    Many(Vec<Node>),
+}
+
+// the syntax tree without positions or resolver ids, ready to be written in any format
+struct Node {
+    kind: &'static str,
+    values: Vec<(&'static str, Value)>,
+    children: Vec<(&'static str, Child)>,
+}
+
+impl Node {
+    fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            values: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn value(mut self, label: &'static str, value: Value) -> Self {
+        self.values.push((label, value));
+        self
+    }
+
+    fn name(self, label: &'static str, token: &Token) -> Self {
+        self.value(label, Value::Name(token.name().to_string()))
+    }
+
+    fn child(mut self, label: &'static str, node: Node) -> Self {
+        self.children.push((label, Child::One(node)));
+        self
+    }
+
+    fn children(mut self, label: &'static str, nodes: Vec<Node>) -> Self {
+        self.children.push((label, Child::Many(nodes)));
+        self
+    }
+
+    fn head(&self) -> String {
+        let mut head = self.kind.to_string();
+        for (_, value) in &self.values {
+            head.push(' ');
+            head.push_str(&value.text());
+        }
+        head
+    }
+
+    fn tree(&self, out: &mut String, indent: &str) {
+        out.push_str(&self.head());
+        out.push('\n');
+        for (i, (label, child)) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            let (branch, next) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            out.push_str(&format!("{indent}{branch}{label}: "));
+            let indent = format!("{indent}{next}");
+            match child {
+                Child::One(node) => node.tree(out, &indent),
+                Child::Many(nodes) => {
+                    out.push_str(&format!("[{}]\n", nodes.len()));
+                    for (i, node) in nodes.iter().enumerate() {
+                        let (branch, next) = if i + 1 == nodes.len() {
+                            ("└── ", "    ")
+                        } else {
+                            ("├── ", "│   ")
+                        };
+                        out.push_str(&format!("{indent}{branch}"));
+                        node.tree(out, &format!("{indent}{next}"));
+                    }
+                }
+            }
+        }
+    }
+
+    fn sexpr(&self) -> String {
+        let mut parts = vec![self.head()];
+        for (_, child) in &self.children {
+            parts.push(match child {
+                Child::One(node) => node.sexpr(),
+                Child::Many(nodes) => {
+                    let nodes: Vec<_> = nodes.iter().map(Node::sexpr).collect();
+                    format!("[{}]", nodes.join(" "))
+                }
+            });
+        }
+        format!("({})", parts.join(" "))
+    }
+
+    fn json(&self) -> String {
+        let mut fields = vec![format!("\"type\":{}", json_string(self.kind))];
+        for (label, value) in &self.values {
+            fields.push(format!("{}:{}", json_string(label), value.json()));
+        }
+        for (label, child) in &self.children {
+            let child = match child {
+                Child::One(node) => node.json(),
+                Child::Many(nodes) => {
+                    let nodes: Vec<_> = nodes.iter().map(Node::json).collect();
+                    format!("[{}]", nodes.join(","))
+                }
+            };
+            fields.push(format!("{}:{child}", json_string(label)));
+        }
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            ch if ch.is_control() => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+    json
+}
+
+struct NodeBuilder;
+
+impl NodeBuilder {
+    fn exprs(&mut self, exprs: &[Expr]) -> Vec<Node> {
+        exprs.iter().map(|e| self.visit_expr(e)).collect()
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) -> Vec<Node> {
+        statements.iter().map(|s| self.visit_stmt(s)).collect()
+    }
+
+    fn function(&mut self, fun: &Function) -> Node {
+        let params = fun.params.iter().map(|p| p.name().to_string()).collect();
+        Node::new("Function")
+            .name("name", &fun.name)
+            .value("params", Value::Names(params))
+            .child("body", self.visit_stmt(&fun.body))
+    }
+}
+
+impl Visitor<Node, Node> for NodeBuilder {
+    fn visit_expr(&mut self, e: &Expr) -> Node {
+        match e {
+            Expr::Binary(binary) => Node::new("Binary")
+                .name("operator", &binary.operator)
+                .child("left", stack::grow(|| self.visit_expr(&binary.left)))
+                .child("right", self.visit_expr(&binary.right)),
+            Expr::Unary(unary) => Node::new("Unary")
+                .name("operator", &unary.operator)
+                .child("right", self.visit_expr(&unary.right)),
+            Expr::Literal(object) => {
+                Node::new("Literal").value("value", Value::Literal(object.clone()))
+            }
+            Expr::Grouping(group) => {
+                Node::new("Grouping").child("expression", self.visit_expr(&group.expr))
+            }
+            Expr::Variable(var) => Node::new("Variable").name("name", &var.name),
+            Expr::Assign(assign) => Node::new("Assign")
+                .name("name", &assign.name)
+                .child("value", self.visit_expr(&assign.value)),
+            Expr::Logical(logical) => Node::new("Logical")
+                .name("operator", &logical.operator)
+                .child("left", stack::grow(|| self.visit_expr(&logical.left)))
+                .child("right", self.visit_expr(&logical.right)),
+            Expr::Call(call) => Node::new("Call")
+                .child("callee", stack::grow(|| self.visit_expr(&call.callee)))
+                .children("arguments", self.exprs(&call.arguments)),
+            Expr::Get(get) => Node::new("Get")
+                .name("name", &get.name)
+                .child("object", stack::grow(|| self.visit_expr(&get.object))),
+            Expr::Set(set) => Node::new("Set")
+                .name("name", &set.name)
+                .child("object", stack::grow(|| self.visit_expr(&set.object)))
+                .child("value", self.visit_expr(&set.value)),
+            Expr::This(_) => Node::new("This"),
+            Expr::Super(super_call) => Node::new("Super").name("method", &super_call.method),
+        }
+    }
+
+    fn visit_stmt(&mut self, s: &Stmt) -> Node {
+        match s {
+            Stmt::Expression(e) => Node::new("Expression").child("expression", self.visit_expr(e)),
+            Stmt::Print(print) => {
+                Node::new("Print").child("expression", self.visit_expr(&print.expression))
+            }
+            Stmt::Var(var) => Node::new("Var")
+                .name("name", &var.identifier)
+                .child("initializer", self.visit_expr(&var.expression)),
+            Stmt::Block(block) => {
+                Node::new("Block").children("statements", self.stmts(&block.statements))
+            }
+            Stmt::If(if_statement) => {
+                let node = Node::new("If")
+                    .child("condition", self.visit_expr(&if_statement.condition))
+                    .child("then", self.visit_stmt(&if_statement.then_branch));
+                match &if_statement.else_branch {
+                    Some(else_branch) => node.child("else", self.visit_stmt(else_branch)),
+                    None => node,
+                }
+            }
+            Stmt::While(while_statement) => Node::new("While")
+                .child("condition", self.visit_expr(&while_statement.condition))
+                .child("body", self.visit_stmt(&while_statement.body)),
+            Stmt::ForIn(for_in) => Node::new("ForIn")
+                .name("name", &for_in.name)
+                .child("iterable", self.visit_expr(&for_in.iterable))
+                .child("body", self.visit_stmt(&for_in.body)),
+            Stmt::Function(fun) => self.function(fun),
+            Stmt::Return(return_statement) => {
+                Node::new("Return").child("value", self.visit_expr(&return_statement.value))
+            }
+            Stmt::Class(class) => {
+                let node = Node::new("Class").name("name", &class.name);
+                let node = match &class.superclass {
+                    Some(superclass) => node.name("superclass", &superclass.name),
+                    None => node,
+                };
+                let methods = class.methods.iter().map(|fun| self.function(fun)).collect();
+                node.children("methods", methods)
+            }
+        }
+    }
+}
+
+// every statement in `format`, json is a single array
+pub(crate) fn render(source: &str, statements: &[Stmt], format: AstFormat) -> String {
+    let nodes = || NodeBuilder.stmts(statements);
+    match format {
+        AstFormat::Tree => {
+            let mut out = String::new();
+            for node in &nodes() {
+                node.tree(&mut out, "");
+            }
+            out
+        }
+        AstFormat::Sexpr => {
+            let nodes: Vec<_> = nodes().iter().map(|node| node.sexpr() + "\n").collect();
+            nodes.concat()
+        }
+        AstFormat::Json => {
+            let nodes: Vec<_> = nodes().iter().map(Node::json).collect();
+            format!("[{}]\n", nodes.join(","))
+        }
+        AstFormat::Source => formatter::desugar(statements, formatter::DEFAULT_INDENT),
+        AstFormat::Full => cache::export_ast(source, statements) + "\n",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        Parser::from(&scanner).parse()
+    }
+
+    #[test]
+    fn render_formats() {
+        let source = "var a = -1; print f(a, \"x\");";
+        let statements = parse(source);
+
+        let tree = r#"
+Var a
+└── initializer: Unary -
+    └── right: Literal 1
+Print
+└── expression: Call
+    ├── callee: Variable f
+    └── arguments: [2]
+        ├── Variable a
+        └── Literal "x"
+"#;
+        assert_eq!(
+            render(source, &statements, AstFormat::Tree).trim(),
+            tree.trim()
+        );
+
+        let sexpr = r#"
+(Var a (Unary - (Literal 1)))
+(Print (Call (Variable f) [(Variable a) (Literal "x")]))
+"#;
+        assert_eq!(
+            render(source, &statements, AstFormat::Sexpr).trim(),
+            sexpr.trim()
+        );
+
+        let json = r#"[{"type":"Var","name":"a","initializer":{"type":"Unary","operator":"-","right":{"type":"Literal","value":1}}},{"type":"Print","expression":{"type":"Call","callee":{"type":"Variable","name":"f"},"arguments":[{"type":"Variable","name":"a"},{"type":"Literal","value":"x"}]}}]"#;
+        assert_eq!(render(source, &statements, AstFormat::Json).trim(), json);
+    }
+}
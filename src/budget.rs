@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use crate::time::Instant;
+
+// the clock is only read once every this many steps
+const CLOCK_INTERVAL: u64 = 1024;
+
+// optional limits that stop runaway scripts.
+// a step is a loop iteration or a call, straight-line code always finishes on its own
+#[derive(Debug, Default)]
+pub(crate) struct Budget {
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    steps: u64,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    pub fn new(max_steps: Option<u64>, timeout: Option<Duration>) -> Self {
+        Self {
+            max_steps,
+            timeout,
+            ..Default::default()
+        }
+    }
+
+    // every call to `interpret` gets the whole budget
+    pub fn start(&mut self) {
+        self.steps = 0;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    // returns false once the budget is exceeded
+    pub fn step(&mut self) -> bool {
+        self.steps += 1;
+        if self
+            .max_steps
+            .is_some_and(|max_steps| self.steps > max_steps)
+        {
+            return false;
+        }
+        match self.deadline {
+            Some(deadline) if self.steps.is_multiple_of(CLOCK_INTERVAL) => {
+                Instant::now() < deadline
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn step_limit() {
+        let mut budget = Budget::new(Some(2), None);
+        budget.start();
+        assert!(budget.step());
+        assert!(budget.step());
+        assert!(!budget.step());
+
+        budget.start();
+        assert!(budget.step());
+    }
+
+    #[test]
+    fn timeout() {
+        let mut budget = Budget::new(None, Some(Duration::ZERO));
+        budget.start();
+        assert!((0..CLOCK_INTERVAL).any(|_| !budget.step()));
+    }
+
+    #[test]
+    fn unlimited() {
+        let mut budget = Budget::default();
+        budget.start();
+        assert!((0..CLOCK_INTERVAL * 2).all(|_| budget.step()));
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    object::Number,
+    source::CharPos,
+    stmt::Stmt,
+    token::{Token, TokenType},
+};
+
+// compiled scripts store the scanned tokens together with the source,
+// the source is needed for error messages and to rebuild stale caches.
+//
+// layout: magic | key | source length | source | token count | tokens
+// only the part after the source may change between versions.
+const MAGIC: &[u8; 4] = b"LOXC";
+const FORMAT_VERSION: &str = "3";
+
+pub(crate) const EXTENSION: &str = "loxc";
+
+// syntax trees exported with `--ast --format full` are json holding the source as well,
+// for the same reasons
+pub(crate) const AST_EXTENSION: &str = "loxast";
+const AST_FORMAT_VERSION: &str = "4";
+
+// tokens without payload, encoded by their index
+const SIMPLE_TOKENS: [TokenType; 43] = [
+    TokenType::LeftParen,
+    TokenType::RightParen,
+    TokenType::LeftBrace,
+    TokenType::RightBrace,
+    TokenType::Comma,
+    TokenType::Dot,
+    TokenType::Minus,
+    TokenType::Plus,
+    TokenType::Semicolon,
+    TokenType::Slash,
+    TokenType::Star,
+    TokenType::Colon,
+    TokenType::Ampersand,
+    TokenType::Pipe,
+    TokenType::Caret,
+    TokenType::Tilde,
+    TokenType::Bang,
+    TokenType::BangEqual,
+    TokenType::Equal,
+    TokenType::EqualEqual,
+    TokenType::Greater,
+    TokenType::GreaterEqual,
+    TokenType::Less,
+    TokenType::LessEqual,
+    TokenType::LessLess,
+    TokenType::GreaterGreater,
+    TokenType::And,
+    TokenType::Class,
+    TokenType::Else,
+    TokenType::False,
+    TokenType::Fun,
+    TokenType::For,
+    TokenType::If,
+    TokenType::Nil,
+    TokenType::Or,
+    TokenType::Print,
+    TokenType::Return,
+    TokenType::Super,
+    TokenType::This,
+    TokenType::True,
+    TokenType::Var,
+    TokenType::While,
+    TokenType::Eof,
+];
+
+const IDENTIFIER_TAG: u8 = 200;
+const STRING_TAG: u8 = 201;
+const NUMBER_TAG: u8 = 202;
+
+// fnv-1a of the version and the source, stable across rust versions unlike `DefaultHasher`.
+// tokens are only reused for the source and the version they were scanned with
+fn key(source: &str) -> u64 {
+    let version = format!("{}-{FORMAT_VERSION}\0", env!("CARGO_PKG_VERSION"));
+    version
+        .bytes()
+        .chain(source.bytes())
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+pub(crate) struct Compiled {
+    pub source: String,
+    // `None` if the file was compiled by another version or from another source
+    pub tokens: Option<Vec<Token>>,
+}
+
+pub(crate) fn compile(source: &str, tokens: &[Token]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    write_u64(&mut bytes, key(source));
+    write_str(&mut bytes, source);
+    write_u64(&mut bytes, tokens.len() as u64);
+    for token in tokens {
+        write_token(&mut bytes, token);
+    }
+    bytes
+}
+
+// `script` is the source the file was compiled from as it is now, if it is still around.
+// it is run instead of the stored one once they differ
+pub(crate) fn load(bytes: &[u8], script: Option<String>) -> Result<Compiled> {
+    let mut reader = Reader { bytes, current: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        bail!("Not a compiled lox file");
+    }
+    let stored_key = reader.u64()?;
+    let stored = reader.string()?;
+    let source = script.unwrap_or(stored);
+    if stored_key != key(&source) {
+        return Ok(Compiled {
+            source,
+            tokens: None,
+        });
+    }
+
+    let count = reader.u64()?;
+    let tokens = (0..count)
+        .map(|_| reader.token())
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Compiled {
+        source,
+        tokens: Some(tokens),
+    })
+}
+
+fn ast_version() -> String {
+    format!("{}-{AST_FORMAT_VERSION}", env!("CARGO_PKG_VERSION"))
+}
+
+#[derive(Serialize)]
+struct ExportedAst<'a> {
+    version: String,
+    source: &'a str,
+    statements: &'a [Stmt],
+}
+
+// read first, the statements are only understood by the version that wrote them
+#[derive(Deserialize)]
+struct AstHeader {
+    version: String,
+    source: String,
+}
+
+#[derive(Deserialize)]
+struct AstStatements {
+    statements: Vec<Stmt>,
+}
+
+pub(crate) struct ImportedAst {
+    pub source: String,
+    // `None` if the tree was exported by another version
+    pub statements: Option<Vec<Stmt>>,
+}
+
+pub(crate) fn export_ast(source: &str, statements: &[Stmt]) -> String {
+    let exported = ExportedAst {
+        version: ast_version(),
+        source,
+        statements,
+    };
+    serde_json::to_string(&exported).expect("parsed literals are nil, numbers, strings or bools")
+}
+
+pub(crate) fn import_ast(text: &str) -> Result<ImportedAst> {
+    let header: AstHeader = serde_json::from_str(text).context("Not an exported syntax tree")?;
+    if header.version != ast_version() {
+        return Ok(ImportedAst {
+            source: header.source,
+            statements: None,
+        });
+    }
+    let tree: AstStatements = serde_json::from_str(text).context("Invalid syntax tree")?;
+    Ok(ImportedAst {
+        source: header.source,
+        statements: Some(tree.statements),
+    })
+}
+
+fn write_u64(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_str(bytes: &mut Vec<u8>, s: &str) {
+    write_u64(bytes, s.len() as u64);
+    bytes.extend_from_slice(s.as_bytes());
+}
+
+fn write_char_pos(bytes: &mut Vec<u8>, pos: CharPos) {
+    write_u64(bytes, pos.index as u64);
+    write_u64(bytes, pos.line as u64);
+}
+
+fn write_token(bytes: &mut Vec<u8>, token: &Token) {
+    match token.token_type() {
+        TokenType::Identifier(name) => {
+            bytes.push(IDENTIFIER_TAG);
+            write_str(bytes, name);
+        }
+        TokenType::String(s) => {
+            bytes.push(STRING_TAG);
+            write_str(bytes, s);
+        }
+        TokenType::Number(number) => {
+            bytes.push(NUMBER_TAG);
+            write_u64(bytes, number.to_bits());
+        }
+        token_type => {
+            let tag = SIMPLE_TOKENS
+                .iter()
+                .position(|simple| simple == token_type)
+                .expect("every token without payload must be in `SIMPLE_TOKENS`");
+            bytes.push(tag as u8);
+        }
+    }
+    write_char_pos(bytes, token.start_pos());
+    write_char_pos(bytes, token.end_pos());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    current: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.current..self.current + len)
+            .context("Unexpected end of compiled file")?;
+        self.current += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        Ok(usize::try_from(self.u64()?)?)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.usize()?;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+
+    fn char_pos(&mut self) -> Result<CharPos> {
+        Ok(CharPos {
+            index: self.usize()?,
+            line: self.usize()?,
+        })
+    }
+
+    fn token(&mut self) -> Result<Token> {
+        let token_type = match self.u8()? {
+            IDENTIFIER_TAG => TokenType::Identifier(self.string()?.into()),
+            STRING_TAG => TokenType::String(self.string()?.into()),
+            NUMBER_TAG => TokenType::Number(Number::from(f64::from_bits(self.u64()?))),
+            tag => SIMPLE_TOKENS
+                .get(tag as usize)
+                .cloned()
+                .with_context(|| format!("Invalid token tag {tag}"))?,
+        };
+        let start_pos = self.char_pos()?;
+        let end_pos = self.char_pos()?;
+        Ok(Token::new(token_type, start_pos, end_pos))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{error::reporter::ErrorReporter, parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    #[test]
+    fn compiled_tokens_round_trip() -> Result<()> {
+        let source = r#"
+class A : B { init(x) { this.x = x >> 1; } }
+var s = "héllo"; // comment
+print s + 1.5 & ~2;
+"#;
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        let mut parser = Parser::from(&scanner);
+        parser.parse();
+        assert!(!scanner.had_error() && !parser.had_error());
+
+        let compiled = load(&compile(source, scanner.tokens()), None)?;
+        assert_eq!(compiled.source, source);
+        assert_eq!(compiled.tokens.as_deref(), Some(scanner.tokens()));
+
+        let compiled = load(&compile(source, scanner.tokens()), Some(source.to_string()))?;
+        assert_eq!(compiled.tokens.as_deref(), Some(scanner.tokens()));
+        Ok(())
+    }
+
+    #[test]
+    fn stale_version_keeps_source() -> Result<()> {
+        let mut bytes = compile("print 1;", &[]);
+        bytes[MAGIC.len()] ^= 0xff;
+
+        let compiled = load(&bytes, None)?;
+        assert_eq!(compiled.source, "print 1;");
+        assert!(compiled.tokens.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn changed_script_is_stale() -> Result<()> {
+        let bytes = compile("print 1;", &[]);
+
+        let compiled = load(&bytes, Some("print 2;".to_string()))?;
+        assert_eq!(compiled.source, "print 2;");
+        assert!(compiled.tokens.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn exported_ast_round_trip() -> Result<()> {
+        let source = "class A : B { init(x) { this.x = super.f(-x, \"s\"); } }
+for (var i = 0; i < 2; i = i + 1) if (!nil) print i; else i = 1.5;";
+        let mut parser = Parser::new(Scanner::new(source));
+        let statements = parser.parse();
+        assert!(!parser.had_error());
+
+        let imported = import_ast(&export_ast(source, &statements))?;
+        assert_eq!(imported.source, source);
+        let imported = imported.statements.unwrap();
+        // tokens and positions are kept, expressions get new ids
+        assert_eq!(
+            format!("{imported:?}").split("id: ExprId").count(),
+            format!("{statements:?}").split("id: ExprId").count()
+        );
+        assert_eq!(
+            export_ast(source, &imported),
+            export_ast(source, &statements)
+        );
+
+        let stale = export_ast(source, &statements).replace(&ast_version(), "0");
+        assert!(import_ast(&stale)?.statements.is_none());
+        assert!(import_ast("[]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid_file() {
+        assert!(load(b"print 1;", None).is_err());
+        let bytes = compile("print 1;", &[]);
+        assert!(load(&bytes[..bytes.len() - 1], None).is_err());
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde_json::json;
+
+use crate::{
+    expr::Expr,
+    stack,
+    stmt::{Function, Stmt},
+    token::Token,
+};
+
+// how `--call-graph` prints the graph
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub(crate) enum GraphFormat {
+    #[default]
+    Dot,
+    Json,
+}
+
+// the code outside of every function, it is the first node
+const SCRIPT: usize = 0;
+
+// what a name refers to where it is used
+#[derive(Debug, Clone)]
+enum Target {
+    Function(usize),
+    Class(usize),
+    // globals are looked up once the whole script is walked, functions can call the ones
+    // declared after them
+    Global(String),
+    Other,
+}
+
+#[derive(Debug, Clone)]
+enum Callee {
+    // a function, or a class running its `init`
+    Name(Target),
+    // `this.name()`, or `super.name()` looking from the superclass
+    Method {
+        class: usize,
+        name: String,
+        from_superclass: bool,
+    },
+    // a method of an object whose class is not known, found when only one class has it
+    AnyMethod(String),
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    // `None` for the script
+    line: Option<usize>,
+}
+
+#[derive(Debug)]
+struct ClassInfo {
+    superclass: Option<Target>,
+    methods: HashMap<String, usize>,
+}
+
+// every function and method of a script and which of them call each other
+#[derive(Debug)]
+pub(crate) struct CallGraph {
+    nodes: Vec<Node>,
+    // caller and callee, calls made several times are kept once
+    edges: BTreeSet<(usize, usize)>,
+}
+
+struct Builder {
+    nodes: Vec<Node>,
+    classes: Vec<ClassInfo>,
+    globals: HashMap<String, Target>,
+    scopes: Vec<HashMap<String, Target>>,
+    calls: Vec<(usize, Callee)>,
+    // the function whose body is walked and the class of its method
+    caller: usize,
+    class: Option<usize>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                name: "<script>".to_string(),
+                line: None,
+            }],
+            classes: Vec::new(),
+            globals: HashMap::new(),
+            scopes: Vec::new(),
+            calls: Vec::new(),
+            caller: SCRIPT,
+            class: None,
+        }
+    }
+
+    fn declare(&mut self, name: &Token, target: Target) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.name().to_string(), target),
+            None => self.globals.insert(name.name().to_string(), target),
+        };
+    }
+
+    fn lookup(&self, name: &Token) -> Target {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name.name()).cloned())
+            .unwrap_or_else(|| Target::Global(name.name().to_string()))
+    }
+
+    // functions inside other functions are named after them
+    fn add_node(&mut self, name: &str, token: &Token) -> usize {
+        let name = match self.caller {
+            SCRIPT => name.to_string(),
+            caller => format!("{}::{name}", self.nodes[caller].name),
+        };
+        self.nodes.push(Node {
+            name,
+            line: Some(token.start_pos().line + 1),
+        });
+        self.nodes.len() - 1
+    }
+
+    fn function(&mut self, function: &Function, node: usize) {
+        let caller = std::mem::replace(&mut self.caller, node);
+        self.scopes.push(
+            function
+                .params
+                .iter()
+                .map(|param| (param.name().to_string(), Target::Other))
+                .collect(),
+        );
+        self.stmt(&function.body);
+        self.scopes.pop();
+        self.caller = caller;
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) {
+        for s in statements {
+            self.stmt(s);
+        }
+    }
+
+    fn stmt(&mut self, s: &Stmt) {
+        match s {
+            Stmt::Expression(e) => self.expr(e),
+            Stmt::Print(print) => self.expr(&print.expression),
+            Stmt::Var(var) => {
+                self.expr(&var.expression);
+                self.declare(&var.identifier, Target::Other);
+            }
+            Stmt::Block(block) => {
+                self.scopes.push(HashMap::new());
+                self.stmts(&block.statements);
+                self.scopes.pop();
+            }
+            Stmt::If(if_statement) => {
+                self.expr(&if_statement.condition);
+                self.stmt(&if_statement.then_branch);
+                if let Some(else_branch) = &if_statement.else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            Stmt::While(while_loop) => {
+                self.expr(&while_loop.condition);
+                self.stmt(&while_loop.body);
+            }
+            Stmt::ForIn(for_in) => {
+                self.expr(&for_in.iterable);
+                self.scopes.push(HashMap::new());
+                self.declare(&for_in.name, Target::Other);
+                self.stmt(&for_in.body);
+                self.scopes.pop();
+            }
+            Stmt::Return(r) => self.expr(&r.value),
+            Stmt::Function(function) => {
+                let node = self.add_node(function.name.name(), &function.name);
+                self.declare(&function.name, Target::Function(node));
+                self.function(function, node);
+            }
+            Stmt::Class(class) => {
+                let index = self.classes.len();
+                self.classes.push(ClassInfo {
+                    superclass: class
+                        .superclass
+                        .as_ref()
+                        .map(|superclass| self.lookup(&superclass.name)),
+                    methods: HashMap::new(),
+                });
+                self.declare(&class.name, Target::Class(index));
+                let outer_class = self.class.replace(index);
+                for method in &class.methods {
+                    let name = format!("{}.{}", class.name.name(), method.name.name());
+                    let node = self.add_node(&name, &method.name);
+                    self.classes[index]
+                        .methods
+                        .insert(method.name.name().to_string(), node);
+                    self.function(method, node);
+                }
+                self.class = outer_class;
+            }
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Binary(binary) | Expr::Logical(binary) => {
+                stack::grow(|| self.expr(&binary.left));
+                self.expr(&binary.right);
+            }
+            Expr::Unary(unary) => self.expr(&unary.right),
+            Expr::Grouping(group) => self.expr(&group.expr),
+            Expr::Assign(assign) => self.expr(&assign.value),
+            Expr::Get(get) => stack::grow(|| self.expr(&get.object)),
+            Expr::Set(set) => {
+                stack::grow(|| self.expr(&set.object));
+                self.expr(&set.value);
+            }
+            Expr::Call(call) => {
+                let callee = match (&*call.callee, self.class) {
+                    (Expr::Variable(var), _) => Some(Callee::Name(self.lookup(&var.name))),
+                    (Expr::Get(get), Some(class)) if matches!(*get.object, Expr::This(_)) => {
+                        Some(Callee::Method {
+                            class,
+                            name: get.name.name().to_string(),
+                            from_superclass: false,
+                        })
+                    }
+                    (Expr::Get(get), _) => Some(Callee::AnyMethod(get.name.name().to_string())),
+                    (Expr::Super(super_call), Some(class)) => Some(Callee::Method {
+                        class,
+                        name: super_call.method.name().to_string(),
+                        from_superclass: true,
+                    }),
+                    _ => None,
+                };
+                if let Some(callee) = callee {
+                    self.calls.push((self.caller, callee));
+                }
+                stack::grow(|| self.expr(&call.callee));
+                for argument in &call.arguments {
+                    self.expr(argument);
+                }
+            }
+            Expr::Literal(_) | Expr::Variable(_) | Expr::This(_) | Expr::Super(_) => (),
+        }
+    }
+
+    fn resolve(&self, target: &Target) -> Target {
+        match target {
+            Target::Global(name) => self.globals.get(name).cloned().unwrap_or(Target::Other),
+            target => target.clone(),
+        }
+    }
+
+    fn superclass(&self, class: usize) -> Option<usize> {
+        match self.resolve(self.classes[class].superclass.as_ref()?) {
+            Target::Class(superclass) => Some(superclass),
+            _ => None,
+        }
+    }
+
+    // the method found first in `class` and its superclasses
+    fn method(&self, class: Option<usize>, name: &str) -> Option<usize> {
+        // a class inheriting from itself is an error, but the graph is made anyway
+        std::iter::successors(class, |class| self.superclass(*class))
+            .take(self.classes.len())
+            .find_map(|class| self.classes[class].methods.get(name).copied())
+    }
+
+    fn callee(&self, callee: &Callee) -> Option<usize> {
+        match callee {
+            Callee::Name(target) => match self.resolve(target) {
+                Target::Function(node) => Some(node),
+                Target::Class(class) => self.method(Some(class), "init"),
+                Target::Global(_) | Target::Other => None,
+            },
+            Callee::Method {
+                class,
+                name,
+                from_superclass,
+            } => {
+                let class = match from_superclass {
+                    true => self.superclass(*class),
+                    false => Some(*class),
+                };
+                self.method(class, name)
+            }
+            Callee::AnyMethod(name) => {
+                let mut methods = self
+                    .classes
+                    .iter()
+                    .filter_map(|class| class.methods.get(name));
+                match (methods.next(), methods.next()) {
+                    (Some(method), None) => Some(*method),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn build(self) -> CallGraph {
+        let edges = self
+            .calls
+            .iter()
+            .filter_map(|(caller, callee)| Some((*caller, self.callee(callee)?)))
+            .collect();
+        CallGraph {
+            nodes: self.nodes,
+            edges,
+        }
+    }
+}
+
+impl CallGraph {
+    pub fn new(statements: &[Stmt]) -> Self {
+        let mut builder = Builder::new();
+        builder.stmts(statements);
+        builder.build()
+    }
+
+    // functions calling each other in a loop, or calling themselves, with tarjan's
+    // strongly connected components
+    fn cycles(&self) -> Vec<Vec<usize>> {
+        struct Search<'a> {
+            graph: &'a CallGraph,
+            index: Vec<Option<usize>>,
+            low: Vec<usize>,
+            stack: Vec<usize>,
+            on_stack: Vec<bool>,
+            next: usize,
+            cycles: Vec<Vec<usize>>,
+        }
+
+        impl Search<'_> {
+            fn visit(&mut self, node: usize) {
+                self.index[node] = Some(self.next);
+                self.low[node] = self.next;
+                self.next += 1;
+                self.stack.push(node);
+                self.on_stack[node] = true;
+                for &(_, callee) in self.graph.edges.range((node, 0)..(node + 1, 0)) {
+                    match self.index[callee] {
+                        None => {
+                            self.visit(callee);
+                            self.low[node] = self.low[node].min(self.low[callee]);
+                        }
+                        Some(index) if self.on_stack[callee] => {
+                            self.low[node] = self.low[node].min(index);
+                        }
+                        Some(_) => (),
+                    }
+                }
+                if Some(self.low[node]) != self.index[node] {
+                    return;
+                }
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                if component.len() > 1 || self.graph.edges.contains(&(node, node)) {
+                    component.sort();
+                    self.cycles.push(component);
+                }
+            }
+        }
+
+        let count = self.nodes.len();
+        let mut search = Search {
+            graph: self,
+            index: vec![None; count],
+            low: vec![0; count],
+            stack: Vec::new(),
+            on_stack: vec![false; count],
+            next: 0,
+            cycles: Vec::new(),
+        };
+        for node in 0..count {
+            if search.index[node].is_none() {
+                search.visit(node);
+            }
+        }
+        let mut cycles = search.cycles;
+        cycles.sort();
+        cycles
+    }
+
+    // calls inside a cycle are drawn in red
+    fn dot(&self) -> String {
+        let cycles = self.cycles();
+        let in_cycle = |caller: usize, callee: usize| {
+            cycles
+                .iter()
+                .any(|cycle| cycle.contains(&caller) && cycle.contains(&callee))
+        };
+        let mut out = String::from("digraph calls {\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            out.push_str(&format!("    n{id} [label={:?}];\n", node.name));
+        }
+        for &(caller, callee) in &self.edges {
+            let color = match in_cycle(caller, callee) {
+                true => " [color=red]",
+                false => "",
+            };
+            out.push_str(&format!("    n{caller} -> n{callee}{color};\n"));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn json(&self) -> String {
+        let functions: Vec<_> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| json!({"id": id, "name": node.name, "line": node.line}))
+            .collect();
+        let calls: Vec<_> = self
+            .edges
+            .iter()
+            .map(|(caller, callee)| json!({"caller": caller, "callee": callee}))
+            .collect();
+        let graph = json!({
+            "functions": functions,
+            "calls": calls,
+            "cycles": self.cycles(),
+        });
+        format!("{graph}\n")
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.dot(),
+            GraphFormat::Json => self.json(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn graph(source: &str) -> CallGraph {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        CallGraph::new(&Parser::from(&scanner).parse())
+    }
+
+    #[test]
+    fn calls_between_functions_and_methods() {
+        let graph = graph(
+            "fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+class Cake {
+  init() { this.slices = 8; }
+  taste() { return this.flavor(); }
+  flavor() { fun helper() { return isEven(1); } return helper(); }
+}
+class Pie : Cake {
+  flavor() { return super.flavor(); }
+}
+var pie = Pie();
+pie.taste();",
+        );
+        assert_eq!(
+            graph.render(GraphFormat::Dot),
+            r#"digraph calls {
+    n0 [label="<script>"];
+    n1 [label="isEven"];
+    n2 [label="isOdd"];
+    n3 [label="Cake.init"];
+    n4 [label="Cake.taste"];
+    n5 [label="Cake.flavor"];
+    n6 [label="Cake.flavor::helper"];
+    n7 [label="Pie.flavor"];
+    n0 -> n3;
+    n0 -> n4;
+    n1 -> n2 [color=red];
+    n2 -> n1 [color=red];
+    n4 -> n5;
+    n5 -> n6;
+    n6 -> n1;
+    n7 -> n5;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn json_with_recursion() {
+        let graph = graph(
+            "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\nprint fib(10);",
+        );
+        assert_eq!(
+            graph.render(GraphFormat::Json),
+            r#"{"calls":[{"callee":1,"caller":0},{"callee":1,"caller":1}],"cycles":[[1]],"functions":[{"id":0,"line":null,"name":"<script>"},{"id":1,"line":1,"name":"fib"}]}
+"#
+        );
+    }
+}
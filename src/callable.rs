@@ -1,18 +1,24 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use crate::{
     class::LoxClass,
     environment::EnvironmentTree,
     error::runtime_error::RuntimeError,
-    function::{LoxFunction, NativeFunction},
+    function::LoxFunction,
     interpreter::Interpreter,
+    native::NativeFunction,
     object::Object,
+    shared::Shared,
     stmt::{Class, Function},
 };
 
 pub(crate) trait Callable {
     fn name(&self) -> &str;
     fn arity(&self) -> usize;
+    // variadic functions take `arity` or more arguments
+    fn variadic(&self) -> bool {
+        false
+    }
     fn call<W>(
         &mut self,
         interpreter: &mut Interpreter<W>,
@@ -30,7 +36,7 @@ pub(crate) enum LoxCallable {
 }
 
 impl LoxCallable {
-    pub fn lox_function(declaration: Function, closure: EnvironmentTree) -> Self {
+    pub fn lox_function(declaration: Shared<Function>, closure: EnvironmentTree) -> Self {
         LoxCallable::LoxFunction(LoxFunction::new(declaration, closure, false))
     }
 
@@ -47,13 +53,13 @@ impl LoxCallable {
     }
 }
 
-impl ToString for LoxCallable {
-    fn to_string(&self) -> String {
+impl fmt::Display for LoxCallable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             LoxCallable::LoxFunction(_) | LoxCallable::NativeFunction(_) => {
-                format!("<fn {}>", self.name())
+                write!(f, "<fn {}>", self.name())
             }
-            LoxCallable::LoxClass(_) => format!("<class {}>", self.name()),
+            LoxCallable::LoxClass(_) => write!(f, "<class {}>", self.name()),
         }
     }
 }
@@ -75,6 +81,14 @@ impl Callable for LoxCallable {
         }
     }
 
+    fn variadic(&self) -> bool {
+        match self {
+            LoxCallable::LoxFunction(fun) => fun.variadic(),
+            LoxCallable::NativeFunction(fun) => fun.variadic(),
+            LoxCallable::LoxClass(class) => class.variadic(),
+        }
+    }
+
     fn call<W>(
         &mut self,
         interpreter: &mut Interpreter<W>,
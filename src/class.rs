@@ -1,5 +1,6 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt,
     hash::{Hash, Hasher},
 };
 
@@ -9,20 +10,27 @@ use crate::{
     function::LoxFunction,
     interpreter::Interpreter,
     object::Object,
+    shared::{Mutable, Shared},
     stmt,
     token::Token,
 };
 
-#[derive(Debug, Clone)]
-pub(crate) struct LoxClass {
-    superclass: Option<Box<LoxClass>>,
+#[derive(Debug)]
+struct ClassData {
+    superclass: Option<LoxClass>,
     declaration: stmt::Class,
     methods: HashMap<String, LoxFunction>,
 }
 
+// every instance and every variable holding the class share the same data
+#[derive(Debug, Clone)]
+pub(crate) struct LoxClass {
+    data: Shared<ClassData>,
+}
+
 impl PartialEq for LoxClass {
     fn eq(&self, other: &Self) -> bool {
-        self.declaration == other.declaration
+        self.data.declaration == other.data.declaration
     }
 }
 
@@ -30,7 +38,7 @@ impl Eq for LoxClass {}
 
 impl Hash for LoxClass {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.declaration.hash(state)
+        self.data.declaration.hash(state)
     }
 }
 
@@ -41,31 +49,58 @@ impl LoxClass {
         methods: HashMap<String, LoxFunction>,
     ) -> Self {
         Self {
-            superclass: superclass.map(Box::new),
-            declaration,
-            methods,
+            data: Shared::new(ClassData {
+                superclass,
+                declaration,
+                methods,
+            }),
         }
     }
 
+    // declared in this class or inherited from the nearest superclass declaring it
     pub fn get_method(&self, name: &str) -> Option<&LoxFunction> {
-        self.methods.get(name)
+        self.chain().find_map(|class| class.data.methods.get(name))
+    }
+
+    // methods declared in this class, without the inherited ones
+    pub fn methods(&self) -> impl Iterator<Item = &LoxFunction> {
+        self.data.methods.values()
+    }
+
+    pub fn superclass(&self) -> Option<&LoxClass> {
+        self.data.superclass.as_ref()
+    }
+
+    // this class then its superclasses, nearest first
+    pub fn chain(&self) -> impl Iterator<Item = &LoxClass> {
+        std::iter::successors(Some(self), |class| class.superclass())
     }
 
-    pub fn new_instance(&mut self, id: usize) -> LoxInstance {
+    // identity of the shared data
+    pub fn address(&self) -> usize {
+        Shared::as_ptr(&self.data) as usize
+    }
+
+    pub fn strong_count(&self) -> usize {
+        Shared::strong_count(&self.data)
+    }
+
+    pub fn new_instance(&self, id: usize) -> LoxInstance {
         LoxInstance {
             id,
             lox_class: self.clone(),
+            fields: Default::default(),
         }
     }
 }
 
 impl Callable for LoxClass {
     fn name(&self) -> &str {
-        self.declaration.name.lexeme()
+        self.data.declaration.name.name()
     }
 
     fn arity(&self) -> usize {
-        let init = self.methods.get("init");
+        let init = self.get_method("init");
         match init {
             Some(init) => init.arity(),
             None => 0,
@@ -81,71 +116,121 @@ impl Callable for LoxClass {
         W: std::io::Write,
     {
         let lox_instance = self.new_instance(interpreter.instance_id());
-        interpreter.add_new_instance(lox_instance.clone());
+        interpreter.track_instance(&lox_instance);
 
-        if let Some(init) = self.methods.get("init") {
+        if let Some(init) = self.get_method("init") {
             return init.bind(lox_instance).call(interpreter, arguments);
         };
 
-        Ok(Object::LoxInstance(lox_instance))
+        Ok(Object::instance(lox_instance))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub(crate) type Fields = HashMap<String, Object>;
+
+// instances are shared by reference, every copy sees the same fields
+#[derive(Debug, Clone)]
 pub(crate) struct LoxInstance {
     id: usize,
     lox_class: LoxClass,
+    fields: Shared<Mutable<Fields>>,
 }
 
-impl LoxInstance {
-    pub fn find_method(&self, name: &str) -> Option<&LoxFunction> {
-        let method = self.lox_class.methods.get(name);
-        if method.is_some() {
-            return method;
-        }
-
-        self.lox_class
-            .superclass
-            .as_ref()
-            .and_then(|superclass| superclass.methods.get(name))
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.fields, &other.fields)
     }
 }
 
-impl ToString for LoxInstance {
-    fn to_string(&self) -> String {
-        format!("<{} instance, id {}>", self.lox_class.name(), self.id)
+impl Eq for LoxInstance {}
+
+impl Hash for LoxInstance {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Shared::as_ptr(&self.fields).hash(state)
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct LoxInstanceFields {
-    instance: LoxInstance,
-    fields: HashMap<String, Object>,
-}
+impl LoxInstance {
+    pub fn class(&self) -> &LoxClass {
+        &self.lox_class
+    }
 
-impl From<LoxInstance> for LoxInstanceFields {
-    fn from(instance: LoxInstance) -> Self {
-        LoxInstanceFields {
-            instance,
-            fields: Default::default(),
+    // shared storage of the fields, tracked by the collector
+    pub fn fields(&self) -> &Shared<Mutable<Fields>> {
+        &self.fields
+    }
+
+    // a new instance of the same class, its fields hold the same values
+    pub fn copy(&self, id: usize) -> LoxInstance {
+        LoxInstance {
+            id,
+            lox_class: self.lox_class.clone(),
+            fields: Shared::new(Mutable::new(self.fields.borrow().clone())),
         }
     }
-}
 
-impl LoxInstanceFields {
+    pub fn find_method(&self, name: &str) -> Option<&LoxFunction> {
+        self.lox_class.get_method(name)
+    }
+
+    // the class, the fields sorted by name and every method that can be called on the
+    // instance, named after the class declaring it. overridden methods are left out
+    pub fn describe(&self) -> String {
+        let fields = self.fields.borrow();
+        let mut names: Vec<&String> = fields.keys().collect();
+        names.sort();
+        let fields: Vec<String> = names
+            .into_iter()
+            .map(|name| format!("{name} = {}", fields[name].repr()))
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut methods = Vec::new();
+        for class in self.lox_class.chain() {
+            let mut names: Vec<&str> = class.data.methods.keys().map(String::as_str).collect();
+            names.sort();
+            for name in names {
+                if seen.insert(name) {
+                    methods.push(format!("{}.{name}", class.name()));
+                }
+            }
+        }
+
+        let list = |items: Vec<String>| match items.is_empty() {
+            true => "none".to_string(),
+            false => items.join(", "),
+        };
+        format!(
+            "{} instance\nfields: {}\nmethods: {}",
+            self.lox_class.name(),
+            list(fields),
+            list(methods)
+        )
+    }
+
     pub fn get(&self, name: &Token) -> Option<Object> {
-        let object = self.fields.get(name.lexeme());
+        let object = self.fields.borrow().get(name.name()).cloned();
         if object.is_some() {
-            return object.cloned();
+            return object;
         }
         let method = self
-            .instance
-            .find_method(name.lexeme())
-            .map(|fun| fun.bind(self.instance.clone()));
-        method.map(|fun| Object::Callable(LoxCallable::LoxFunction(fun)))
+            .find_method(name.name())
+            .map(|fun| fun.bind(self.clone()));
+        method.map(|fun| Object::callable(LoxCallable::LoxFunction(fun)))
     }
 
-    pub fn set(&mut self, name: &Token, value: Object) {
-        self.fields.insert(name.lexeme().to_string(), value);
+    pub fn set(&self, name: &Token, value: Object) {
+        // the old value is dropped outside of the borrow
+        let previous = self
+            .fields
+            .borrow_mut()
+            .insert(name.name().to_string(), value);
+        drop(previous);
+    }
+}
+
+impl fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} instance, id {}>", self.lox_class.name(), self.id)
     }
 }
@@ -1,24 +1,368 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
-use crate::lox;
+use std::{
+    io::{IsTerminal, StdoutLock},
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use crate::{
+    ast_repr::AstFormat,
+    call_graph::GraphFormat,
+    color::ColorChoice,
+    coverage::Coverage,
+    error::reporter::{self, DiagnosticFormat},
+    formatter, gc,
+    interpreter::{self, Interpreter},
+    lox::{self, FormatMode, ReportStyle},
+    lsp,
+    native::Capability,
+    prompt::{self, Editor},
+};
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s.parse().map_err(|err| format!("{err}"))?;
+    Duration::try_from_secs_f64(seconds).map_err(|err| format!("{err}"))
+}
+
+const DEFAULT_MAX_ERRORS: usize = 20;
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub(crate) struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Script to run, `-` reads it from stdin. Without one the prompt starts
     file_path: Option<std::path::PathBuf>,
+    /// Arguments passed to the script, available through `args()`
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+    /// Make `clock()` and `monotonic()` return seconds like book Lox instead of milliseconds
+    #[arg(long)]
+    clock_seconds: bool,
+    /// Behave like book Lox: accept `class B < A`, return seconds from `clock()` and stop
+    /// at the first runtime error
+    #[arg(long)]
+    book: bool,
+    /// Run this file before the prompt appears, keeping what it defines. Can be repeated
+    #[arg(long, value_name = "FILE", conflicts_with = "file_path")]
+    preload: Vec<std::path::PathBuf>,
+    /// Run this code instead of a file, every argument after it is passed to the code
+    #[arg(short, long, value_name = "CODE", conflicts_with = "preload")]
+    eval: Option<String>,
+    /// Write the scanned script to a `.loxc` file next to it instead of running it, the file
+    /// is scanned again when run after the script changed
+    #[arg(long, requires = "file_path")]
+    compile: bool,
+    /// Print every token of the script with its line and column instead of running it
+    #[arg(long, requires = "file_path", conflicts_with = "compile")]
+    tokens: bool,
+    /// Also list whitespace and comments with `--tokens`
+    #[arg(long, requires = "tokens")]
+    trivia: bool,
+    /// Print the syntax tree of the script instead of running it
+    #[arg(long, requires = "file_path", conflicts_with_all = ["compile", "tokens"])]
+    ast: bool,
+    /// How `--ast` prints the syntax tree
+    #[arg(long, value_enum, default_value_t = AstFormat::Tree, requires = "ast")]
+    format: AstFormat,
+    /// Fail before running when the script uses a global it never declares
+    #[arg(long)]
+    strict: bool,
+    /// Leave out the global functions and classes the script never uses before running it
+    #[arg(long)]
+    prune: bool,
+    /// Print which functions and methods call each other instead of running the script,
+    /// marking the calls in recursion cycles
+    #[arg(long, requires = "file_path", conflicts_with_all = ["compile", "tokens", "ast"])]
+    call_graph: bool,
+    /// How `--call-graph` prints the graph
+    #[arg(long, value_enum, default_value_t = GraphFormat::Dot, requires = "call_graph")]
+    graph_format: GraphFormat,
+    /// Collect garbage after this many environments and instances were allocated
+    #[arg(long, default_value_t = gc::DEFAULT_THRESHOLD)]
+    gc_threshold: usize,
+    /// Fail with a runtime error when calls are nested deeper than this
+    #[arg(long, default_value_t = interpreter::DEFAULT_MAX_CALL_DEPTH)]
+    max_call_depth: usize,
+    /// Stop the script after this many loop iterations and calls
+    #[arg(long)]
+    max_steps: Option<u64>,
+    /// Stop the script after running for this many seconds
+    #[arg(long, value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+    /// Stop the script once strings, lists, instances and environments take roughly this many bytes
+    #[arg(long)]
+    memory_limit: Option<usize>,
+    /// Only let the script compute, natives reading the clock, input, the environment or
+    /// arguments are not defined unless their capability is given to `--allow`
+    #[arg(long)]
+    sandbox: bool,
+    /// Capabilities natives may use with `--sandbox`, comma separated
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CAPABILITY",
+        value_delimiter = ',',
+        requires = "sandbox"
+    )]
+    allow: Vec<Capability>,
+    /// Define `exec(program, args)`, running other programs. It still needs the process
+    /// capability
+    #[arg(long)]
+    allow_exec: bool,
+    /// Capabilities natives may not use, comma separated
+    #[arg(long, value_enum, value_name = "CAPABILITY", value_delimiter = ',')]
+    deny: Vec<Capability>,
+    /// Print call counts and time spent in every function to stderr after running
+    #[arg(long)]
+    profile: bool,
+    /// Print counts of statements, expressions, calls, allocations and environments to stderr after running
+    #[arg(long)]
+    stats: bool,
+    /// Print how long scanning, parsing, resolving and interpreting took to stderr after running
+    #[arg(long)]
+    time: bool,
+    /// Print which lines of the script ran to stderr after running
+    #[arg(long)]
+    coverage: bool,
+    /// Write how many times every line of the script ran to this file in lcov format
+    #[arg(long, value_name = "FILE")]
+    coverage_lcov: Option<std::path::PathBuf>,
+    /// When to color errors and the prompt, `auto` colors them when writing to a terminal
+    /// unless `NO_COLOR` is set
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+    /// Show this many lines of the script before and after the lines in error, numbered
+    #[arg(long, value_name = "LINES", default_value_t = 0, global = true)]
+    context_lines: usize,
+    /// Columns a tab takes in the lines of the script shown with errors
+    #[arg(long, value_name = "COLUMNS", default_value_t = reporter::DEFAULT_TAB_WIDTH, global = true)]
+    tab_width: usize,
+    /// Treat warnings as errors, the script does not run and fails when there are any
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+    /// Report only this many errors and count the rest, `0` reports all of them
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_ERRORS, global = true)]
+    max_errors: usize,
+    /// How errors and warnings are printed, `json` prints one object per line for tools
+    #[arg(long, value_enum, default_value_t = DiagnosticFormat::Human, global = true)]
+    diagnostics: DiagnosticFormat,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print scripts formatted the canonical way
+    Fmt(FmtArgs),
+    /// Run `.lox` test scripts, checking their `// expect: ` and `// expect runtime error: ` comments
+    Test(TestArgs),
+    /// Serve the language server protocol on stdin and stdout, for editors
+    Lsp,
+}
+
+#[derive(Debug, Args)]
+struct TestArgs {
+    /// Test scripts, or directories searched for them
+    #[arg(required = true)]
+    paths: Vec<std::path::PathBuf>,
+    /// Only check that runtime errors happen on the expected lines, for suites written for
+    /// other implementations whose messages are worded differently
+    #[arg(long)]
+    ignore_messages: bool,
+    /// Print how many tests passed and failed in every directory
+    #[arg(long)]
+    matrix: bool,
+    /// Run the tests like book Lox, see `--book` of the interpreter
+    #[arg(long)]
+    book: bool,
+}
+
+#[derive(Debug, Args)]
+struct FmtArgs {
+    /// Scripts to format, `-` reads one from stdin
+    #[arg(required = true)]
+    file_paths: Vec<std::path::PathBuf>,
+    /// Spaces per indentation level
+    #[arg(long, default_value_t = formatter::DEFAULT_INDENT)]
+    indent: usize,
+    /// Print the scripts that are not formatted and fail instead of printing them formatted
+    #[arg(long, conflicts_with = "write")]
+    check: bool,
+    /// Rewrite the scripts that are not formatted in place
+    #[arg(long)]
+    write: bool,
+}
+
+impl FmtArgs {
+    fn mode(&self) -> FormatMode {
+        match (self.check, self.write) {
+            (true, _) => FormatMode::Check,
+            (_, true) => FormatMode::Write,
+            _ => FormatMode::Print,
+        }
+    }
 }
 
 impl Cli {
+    // there is no script path when running code from `--eval`, its place is taken by arguments
+    fn script_args(&self) -> Vec<String> {
+        match self.eval {
+            Some(_) => self
+                .file_path
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .chain(self.args.iter().cloned())
+                .collect(),
+            None => self.args.clone(),
+        }
+    }
+
+    // everything is allowed unless sandboxed, then only what `--allow` names
+    fn capabilities(&self) -> Vec<Capability> {
+        let allowed: &[Capability] = match self.sandbox {
+            true => &self.allow,
+            false => &Capability::ALL,
+        };
+        allowed
+            .iter()
+            .filter(|capability| !self.deny.contains(capability))
+            .copied()
+            .collect()
+    }
+
+    fn interpreter(&self) -> Interpreter<StdoutLock<'static>> {
+        Interpreter::default()
+            .with_args(self.script_args())
+            .with_clock_seconds(self.clock_seconds)
+            .with_book(self.book)
+            .with_exec(self.allow_exec)
+            .with_capabilities(&self.capabilities())
+            .with_strict(self.strict)
+            .with_prune(self.prune)
+            .with_gc_threshold(self.gc_threshold)
+            .with_max_call_depth(self.max_call_depth)
+            .with_budget(self.max_steps, self.timeout)
+            .with_memory_limit(self.memory_limit)
+            .with_profiler(self.profile)
+            .with_stats(self.stats)
+            .with_timings(self.time)
+            .with_coverage(self.coverage())
+    }
+
+    // the lcov report names the script, code from `--eval` and the prompt have no file
+    fn coverage(&self) -> Option<Coverage> {
+        if !self.coverage && self.coverage_lcov.is_none() {
+            return None;
+        }
+        let source = match (&self.eval, &self.file_path) {
+            (None, Some(path)) => path.to_string_lossy().into_owned(),
+            _ => "-".to_string(),
+        };
+        let lcov = self.coverage_lcov.clone().map(|path| (path, source));
+        Some(Coverage::new(self.coverage, lcov))
+    }
+
     fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let style = ReportStyle {
+            colored: self.color.enabled(),
+            context: self.context_lines,
+            deny_warnings: self.deny_warnings,
+            max_errors: self.max_errors,
+            format: self.diagnostics,
+            tab_width: self.tab_width,
+        };
+        match &self.command {
+            Some(Command::Fmt(fmt)) => {
+                return Ok(lox::format_files(
+                    &fmt.file_paths,
+                    fmt.indent,
+                    fmt.mode(),
+                    style,
+                )?)
+            }
+            Some(Command::Test(test)) => {
+                return Ok(lox::run_tests(
+                    &test.paths,
+                    test.ignore_messages,
+                    test.matrix,
+                    test.book,
+                )?)
+            }
+            Some(Command::Lsp) => return Ok(lsp::serve()?),
+            None => (),
+        }
+        if let Some(source) = &self.eval {
+            return Ok(lox::run_source(source, self.interpreter(), style)?);
+        }
         match &self.file_path {
-            Some(path) => lox::run_file(path)?,
-            None => lox::run_prompt(std::io::stdin().lock(), std::io::stdout().lock())?,
+            Some(path) if self.compile => lox::compile_file(path, style)?,
+            Some(path) if self.tokens => lox::print_tokens(path, self.trivia, style)?,
+            Some(path) if self.ast => lox::print_ast(path, self.format, style)?,
+            Some(path) if self.call_graph => lox::print_call_graph(path, self.graph_format, style)?,
+            Some(path) => lox::run_file(path, self.interpreter(), style)?,
+            // line editing only makes sense when someone is typing,
+            // ctrl-c then stops the running input instead of the whole prompt
+            None if std::io::stdin().is_terminal() => {
+                let editor = Editor::new(style.colored)?;
+                // after the editor, which replaces the handler when it is created
+                let interrupt = Arc::new(AtomicBool::new(false));
+                signal_hook::flag::register(signal_hook::consts::SIGINT, interrupt.clone())?;
+                lox::run_prompt(
+                    editor,
+                    std::io::stdout().lock(),
+                    self.interpreter().with_interrupt(interrupt),
+                    &self.preload,
+                    style,
+                )?
+            }
+            // `readLine` reads the lines following the input that calls it
+            None => {
+                let stdin = prompt::SharedLines::new(std::io::BufReader::new(std::io::stdin()));
+                lox::run_prompt(
+                    prompt::Lines::new(stdin.clone(), std::io::stdout().lock()),
+                    std::io::stdout().lock(),
+                    self.interpreter().with_reader(stdin),
+                    &self.preload,
+                    style,
+                )?
+            }
         }
         Ok(())
     }
 }
 
+// parsing, resolving and formatting recurse as deep as the script nests, calls grow the
+// stack themselves. only the pages actually used are committed
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
 pub fn exec() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    cli.run()?;
+    // the main thread stack can be smaller
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || cli.run().map_err(|err| err.to_string()))
+        .map_err(|err| format!("Could not start the interpreter: {err}"))?
+        .join()
+        .map_err(|_| "interpreter thread panicked")??;
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn script_args(args: &[&str]) -> Vec<String> {
+        Cli::try_parse_from(std::iter::once("lox").chain(args.iter().copied()))
+            .unwrap()
+            .script_args()
+    }
+
+    #[test]
+    fn pass_arguments_to_the_script() {
+        assert_eq!(script_args(&["a.lox", "--", "-x", "1"]), ["-x", "1"]);
+        assert_eq!(script_args(&["a.lox", "b", "--", "c"]), ["b", "--", "c"]);
+        assert_eq!(script_args(&["-e", "print 1;", "a", "-x"]), ["a", "-x"]);
+        assert_eq!(script_args(&["-e", "print 1;", "--", "a"]), ["a"]);
+        assert!(script_args(&["a.lox"]).is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::IsTerminal;
+
+use crate::{
+    callable::LoxCallable,
+    object::Object,
+    scanner::Scanner,
+    token::{Token, TokenType},
+};
+
+const RESET: &str = "\x1b[0m";
+
+// ANSI colors used by the prompt and error reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Color {
+    Keyword,
+    Literal,
+    Number,
+    String,
+    Callable,
+    Instance,
+    Error,
+    Warning,
+    // labels and notes under an error
+    Label,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Keyword => "35",
+            Color::Literal => "36",
+            Color::Number => "33",
+            Color::String => "32",
+            Color::Callable => "34",
+            Color::Instance => "1;34",
+            Color::Error => "1;31",
+            Color::Warning => "1;33",
+            Color::Label => "1;36",
+        }
+    }
+
+    pub fn paint(self, text: &str) -> String {
+        format!("\x1b[{}m{text}{RESET}", self.code())
+    }
+
+    // names and punctuation are left alone
+    fn of_token(token: &Token) -> Option<Self> {
+        match token.token_type() {
+            TokenType::And
+            | TokenType::Class
+            | TokenType::Else
+            | TokenType::For
+            | TokenType::Fun
+            | TokenType::If
+            | TokenType::Or
+            | TokenType::Print
+            | TokenType::Return
+            | TokenType::Super
+            | TokenType::This
+            | TokenType::Var
+            | TokenType::While => Some(Color::Keyword),
+            TokenType::True | TokenType::False | TokenType::Nil => Some(Color::Literal),
+            TokenType::Number(_) => Some(Color::Number),
+            TokenType::String(_) => Some(Color::String),
+            _ => None,
+        }
+    }
+
+    // lists and maps are left alone, their elements could be anything
+    fn of_object(object: &Object) -> Option<Self> {
+        match object {
+            Object::Null | Object::Bool(_) => Some(Color::Literal),
+            Object::Number(_) => Some(Color::Number),
+            Object::String(_) => Some(Color::String),
+            Object::Callable(callable) => match &**callable {
+                LoxCallable::LoxClass(_) => Some(Color::Instance),
+                _ => Some(Color::Callable),
+            },
+            Object::LoxInstance(_) => Some(Color::Instance),
+            Object::List(_) | Object::Map(_) => None,
+        }
+    }
+}
+
+// colors are only written to a terminal, and never when `NO_COLOR` is set
+pub(crate) fn enabled() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+// when `--color` writes colors
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub(crate) enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => enabled(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+// `source` with keywords, literals, numbers and strings colored
+pub(crate) fn highlight(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut painted = String::new();
+    let mut next = 0;
+    for token in Scanner::new(source) {
+        let Some(color) = Color::of_token(&token) else {
+            continue;
+        };
+        let (start, end) = (token.start_pos().index, token.end_pos().index);
+        painted.extend(&chars[next..start]);
+        painted.push_str(&color.paint(&chars[start..=end].iter().collect::<String>()));
+        next = end + 1;
+    }
+    painted.extend(&chars[next..]);
+    painted
+}
+
+// how the prompt shows a result
+pub(crate) fn repr(object: &Object) -> String {
+    match Color::of_object(object) {
+        Some(color) => color.paint(&object.repr()),
+        None => object.repr(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn highlight_tokens() {
+        let source = "var é = \"a\" + 12; // nil";
+        let expected = format!(
+            "{} é = {} + {}; // nil",
+            Color::Keyword.paint("var"),
+            Color::String.paint("\"a\""),
+            Color::Number.paint("12"),
+        );
+        assert_eq!(highlight(source), expected);
+        assert_eq!(
+            highlight("print \"open"),
+            Color::Keyword.paint("print") + " \"open"
+        );
+    }
+
+    #[test]
+    fn repr_by_type() {
+        assert_eq!(repr(&Object::string("a")), Color::String.paint("\"a\""));
+        assert_eq!(repr(&Object::Bool(true)), Color::Literal.paint("true"));
+    }
+}
@@ -0,0 +1,88 @@
+// payloads of `Object` bigger than a pointer.
+// with the `compact-object` feature they are kept behind an `Rc` or an `Arc`, which makes `Object`
+// two words instead of six and turns every clone into a reference count increment,
+// at the cost of an allocation when the value is created and one more indirection
+#[cfg(feature = "compact-object")]
+pub(crate) type Compact<T> = crate::shared::Shared<T>;
+
+#[cfg(not(feature = "compact-object"))]
+pub(crate) type Compact<T> = inline::Inline<T>;
+
+// identity and strong count of a payload behind a pointer, `None` when it is in place
+pub(crate) fn shared<T>(value: &Compact<T>) -> Option<(usize, usize)> {
+    #[cfg(feature = "compact-object")]
+    return Some((
+        crate::shared::Shared::as_ptr(value) as *const () as usize,
+        crate::shared::Shared::strong_count(value),
+    ));
+    #[cfg(not(feature = "compact-object"))]
+    return {
+        let _ = value;
+        None
+    };
+}
+
+// the value itself, cloned only if it is shared
+pub(crate) fn into_inner<T: Clone>(value: Compact<T>) -> T {
+    #[cfg(feature = "compact-object")]
+    return crate::shared::Shared::unwrap_or_clone(value);
+    #[cfg(not(feature = "compact-object"))]
+    return value.into_inner();
+}
+
+#[cfg(not(feature = "compact-object"))]
+mod inline {
+    use std::{fmt, ops::Deref};
+
+    // stored in place, only here so both representations have the same interface
+    #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Default)]
+    pub(crate) struct Inline<T>(T);
+
+    impl<T> Inline<T> {
+        pub fn new(value: T) -> Self {
+            Self(value)
+        }
+
+        pub fn into_inner(self) -> T {
+            self.0
+        }
+    }
+
+    impl<T> Deref for Inline<T> {
+        type Target = T;
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    // formatted as the value itself, like `Rc`
+    impl<T: fmt::Debug> fmt::Debug for Inline<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+
+    impl<T: fmt::Display> fmt::Display for Inline<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::object::Object;
+
+    #[test]
+    fn object_size() {
+        let words = if cfg!(feature = "compact-object") {
+            2
+        } else {
+            6
+        };
+        assert_eq!(
+            std::mem::size_of::<Object>(),
+            words * std::mem::size_of::<usize>()
+        );
+    }
+}
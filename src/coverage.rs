@@ -0,0 +1,223 @@
+use std::{collections::BTreeMap, fmt::Write, path::PathBuf};
+
+use crate::{expr::Expr, stack, stmt::Stmt};
+
+// how many times the statements on every line of a script ran, lines with statements
+// that never ran are kept with no hits
+#[derive(Debug, Default)]
+pub(crate) struct Coverage {
+    hits: BTreeMap<usize, u64>,
+    // print the summary after running
+    summary: bool,
+    // file the lcov report is written to and the script named in it
+    lcov: Option<(PathBuf, String)>,
+}
+
+// line of the first token of `e`, literals have none
+fn expr_line(e: &Expr) -> Option<usize> {
+    let line = match e {
+        Expr::Binary(binary) | Expr::Logical(binary) => {
+            return stack::grow(|| expr_line(&binary.left))
+                .or(Some(binary.operator.start_pos().line + 1))
+        }
+        Expr::Call(call) => {
+            return stack::grow(|| expr_line(&call.callee))
+                .or(Some(call.paren.start_pos().line + 1))
+        }
+        Expr::Get(get) => {
+            return stack::grow(|| expr_line(&get.object)).or(Some(get.name.start_pos().line + 1))
+        }
+        Expr::Set(set) => {
+            return stack::grow(|| expr_line(&set.object)).or(Some(set.name.start_pos().line + 1))
+        }
+        Expr::Grouping(group) => return expr_line(&group.expr),
+        Expr::Literal(_) => return None,
+        Expr::Unary(unary) => unary.operator.start_pos().line,
+        Expr::Variable(var) => var.name.start_pos().line,
+        Expr::Assign(assign) => assign.name.start_pos().line,
+        Expr::This(this) => this.keyword.start_pos().line,
+        Expr::Super(super_call) => super_call.keyword.start_pos().line,
+    };
+    Some(line + 1)
+}
+
+// line a statement is counted on, blocks only count their statements
+pub(crate) fn stmt_line(s: &Stmt) -> Option<usize> {
+    let line = match s {
+        Stmt::Expression(e) => return expr_line(e),
+        Stmt::Block(_) => return None,
+        Stmt::Print(print) => print.keyword.start_pos().line,
+        Stmt::Var(var) => var.identifier.start_pos().line,
+        Stmt::Return(r) => r.keyword.start_pos().line,
+        Stmt::If(if_statement) => if_statement.keyword.start_pos().line,
+        Stmt::While(while_loop) => while_loop.keyword.start_pos().line,
+        Stmt::ForIn(for_in) => for_in.keyword.start_pos().line,
+        Stmt::Function(function) => function.name.start_pos().line,
+        Stmt::Class(class) => class.name.start_pos().line,
+    };
+    Some(line + 1)
+}
+
+impl Coverage {
+    pub fn new(summary: bool, lcov: Option<(PathBuf, String)>) -> Self {
+        Self {
+            summary,
+            lcov,
+            ..Self::default()
+        }
+    }
+
+    // every line with a statement in `statements`, before they run
+    pub fn instrument(&mut self, statements: &[Stmt]) {
+        for s in statements {
+            if let Some(line) = stmt_line(s) {
+                self.hits.entry(line).or_default();
+            }
+            match s {
+                Stmt::Block(block) => self.instrument(&block.statements),
+                Stmt::If(if_statement) => {
+                    self.instrument(std::slice::from_ref(&if_statement.then_branch));
+                    if let Some(else_branch) = &if_statement.else_branch {
+                        self.instrument(std::slice::from_ref(else_branch));
+                    }
+                }
+                Stmt::While(while_loop) => self.instrument(std::slice::from_ref(&while_loop.body)),
+                Stmt::ForIn(for_in) => self.instrument(std::slice::from_ref(&for_in.body)),
+                Stmt::Function(function) => self.instrument(std::slice::from_ref(&function.body)),
+                // methods are not run when the class is declared, only their bodies count
+                Stmt::Class(class) => {
+                    for method in &class.methods {
+                        self.instrument(std::slice::from_ref(&method.body));
+                    }
+                }
+                Stmt::Expression(_) | Stmt::Print(_) | Stmt::Var(_) | Stmt::Return(_) => (),
+            }
+        }
+    }
+
+    pub fn ran(&mut self, s: &Stmt) {
+        if let Some(line) = stmt_line(s) {
+            *self.hits.entry(line).or_default() += 1;
+        }
+    }
+
+    fn lines_run(&self) -> usize {
+        self.hits.values().filter(|hits| **hits > 0).count()
+    }
+
+    // lines that never ran, consecutive ones joined into ranges
+    fn not_run(&self) -> Vec<String> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (&line, _) in self.hits.iter().filter(|(_, hits)| **hits == 0) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == line => *end = line,
+                _ => ranges.push((line, line)),
+            }
+        }
+        ranges
+            .into_iter()
+            .map(|(start, end)| match start == end {
+                true => start.to_string(),
+                false => format!("{start}-{end}"),
+            })
+            .collect()
+    }
+
+    // `None` unless the summary was asked for
+    pub fn report(&self) -> Option<String> {
+        if !self.summary {
+            return None;
+        }
+        let (run, lines) = (self.lines_run(), self.hits.len());
+        let percent = match lines {
+            0 => 100.0,
+            lines => run as f64 * 100.0 / lines as f64,
+        };
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "{:<24}{run} of {lines} ({percent:.1}%)",
+            "lines run"
+        );
+        let not_run = self.not_run();
+        if !not_run.is_empty() {
+            let _ = writeln!(report, "{:<24}{}", "lines not run", not_run.join(", "));
+        }
+        Some(report)
+    }
+
+    fn lcov_report(&self, source: &str) -> String {
+        let mut report = format!("TN:\nSF:{source}\n");
+        for (line, hits) in &self.hits {
+            let _ = writeln!(report, "DA:{line},{hits}");
+        }
+        let _ = writeln!(report, "LF:{}", self.hits.len());
+        let _ = writeln!(report, "LH:{}", self.lines_run());
+        report.push_str("end_of_record\n");
+        report
+    }
+
+    // does nothing unless an lcov file was asked for
+    pub fn write_lcov(&self) -> std::io::Result<()> {
+        match &self.lcov {
+            Some((path, source)) => std::fs::write(path, self.lcov_report(source)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{interpreter::Interpreter, parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(source);
+        Parser::new(&mut scanner).parse()
+    }
+
+    #[test]
+    fn report_lines_not_run() {
+        let statements = parse(
+            "fun f(x) {
+  if (x)
+    print \"yes\";
+  else
+    print \"no\";
+}
+f(true);
+f(true);
+class A {
+  m() {
+    return 1;
+  }
+}",
+        );
+        let mut result = Vec::new();
+        let mut interpreter =
+            Interpreter::new(&mut result).with_coverage(Some(Coverage::new(true, None)));
+        Resolver::new(&mut interpreter).resolve(&statements);
+        interpreter.interpret(&statements);
+        assert_eq!(
+            interpreter.coverage_report().unwrap(),
+            "lines run               6 of 8 (75.0%)\nlines not run           5, 11\n"
+        );
+    }
+
+    #[test]
+    fn lcov_hits_per_line() {
+        let statements = parse("var a = 1;\nwhile (a < 3)\n  a = a + 1;\nprint a;\n{}");
+        let mut coverage = Coverage::new(false, None);
+        coverage.instrument(&statements);
+        coverage.ran(&statements[0]);
+        coverage.ran(&statements[1]);
+        coverage.ran(&statements[1]);
+        assert_eq!(coverage.report(), None);
+        assert_eq!(
+            coverage.lcov_report("a.lox"),
+            "TN:\nSF:a.lox\nDA:1,1\nDA:2,2\nDA:3,0\nDA:4,0\nLF:4\nLH:2\nend_of_record\n"
+        );
+        assert_eq!(coverage.not_run(), ["3-4"]);
+    }
+}
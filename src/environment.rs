@@ -1,137 +1,253 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::collections::HashMap;
 
-use crate::{callable::LoxCallable, function::NativeFunction, object::Object};
+use crate::{
+    callable::{Callable, LoxCallable},
+    gc::{Heap, Tracer},
+    memory::Sizer,
+    native::{math, NativeFunction},
+    object::{Number, Object},
+    shared::{Mutable, Shared},
+    symbol::Symbol,
+};
 
-type EnvironmentLink = Rc<RefCell<EnvironmentNode>>;
+type EnvironmentLink = Shared<Mutable<EnvironmentNode>>;
 
+// where the resolver found a local variable:
+// `depth` scopes above the current one, `index`-th variable declared in that scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+impl Slot {
+    pub fn new(depth: usize, index: usize) -> Self {
+        Self { depth, index }
+    }
+}
+
+// local variables are stored in declaration order, which is the order the resolver
+// gave out slots, names are only kept for introspection
 #[derive(Debug, Clone, Default, PartialEq)]
-struct EnvironmentNode {
-    values: HashMap<String, Object>,
+pub(crate) struct EnvironmentNode {
+    names: Vec<Symbol>,
+    values: Vec<Object>,
     parent: Option<EnvironmentLink>,
 }
 
 impl EnvironmentNode {
-    pub fn define(&mut self, name: &str, value: Object) {
-        self.values.insert(name.to_string(), value);
+    pub fn define(&mut self, name: Symbol, value: Object) {
+        self.names.push(name);
+        self.values.push(value);
     }
 
-    pub fn get(&self, name: &str) -> Option<Object> {
-        let value = self.values.get(name);
-        if value.is_some() {
-            value.cloned()
-        } else {
-            self.parent.as_ref().and_then(|env| env.borrow().get(name))
+    fn ancestor(&self, depth: usize) -> Option<EnvironmentLink> {
+        let mut env = self.parent.clone()?;
+        for _ in 1..depth {
+            let parent = env.borrow().parent.clone()?;
+            env = parent;
         }
+        Some(env)
     }
 
-    pub fn get_at(&self, name: &str, depth: usize) -> Option<Object> {
-        if depth == 0 {
-            self.values.get(name).cloned()
+    pub fn get_at(&self, slot: Slot) -> Option<Object> {
+        if slot.depth == 0 {
+            self.values.get(slot.index).cloned()
         } else {
-            self.parent
-                .as_ref()
-                .and_then(|env| env.borrow().get_at(name, depth - 1))
+            self.ancestor(slot.depth)
+                .and_then(|env| env.borrow().values.get(slot.index).cloned())
         }
     }
 
-    pub fn assign(&mut self, name: &str, value: Object) -> Option<Object> {
-        if let Some(object) = self.values.get_mut(name) {
-            *object = value;
-            return Some(object.clone());
-        }
-        self.parent
-            .as_mut()
-            .and_then(|env| env.borrow_mut().assign(name, value))
-    }
-
-    pub fn assign_at(&mut self, name: &str, value: Object, depth: usize) -> Option<Object> {
-        if depth == 0 {
-            self.values.get_mut(name).map(|object| {
+    pub fn assign_at(&mut self, slot: Slot, value: Object) -> Option<Object> {
+        if slot.depth == 0 {
+            self.values.get_mut(slot.index).map(|object| {
                 *object = value;
                 object.clone()
             })
         } else {
-            self.parent
-                .as_mut()
-                .and_then(|env| env.borrow_mut().assign_at(name, value, depth - 1))
+            self.ancestor(slot.depth).and_then(|env| {
+                env.borrow_mut().values.get_mut(slot.index).map(|object| {
+                    *object = value;
+                    object.clone()
+                })
+            })
+        }
+    }
+
+    // references that keep other environments, instances and lists alive
+    pub fn trace(&self, tracer: &mut Tracer) {
+        if let Some(parent) = self.parent.as_ref() {
+            tracer.reference(parent);
+        }
+        for value in &self.values {
+            tracer.object(value);
+        }
+    }
+
+    pub fn measure(&self, sizer: &mut Sizer) {
+        for value in &self.values {
+            sizer.object(value);
+        }
+    }
+
+    // names defined in this node and every parent node
+    fn names(&self, names: &mut Vec<String>) {
+        names.extend(self.names.iter().map(Symbol::to_string));
+        if let Some(env) = self.parent.as_ref() {
+            env.borrow().names(names);
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub(crate) struct EnvironmentTree {
     env: Option<EnvironmentLink>,
-    global: EnvironmentLink,
+    global: Shared<Mutable<HashMap<Symbol, Object>>>,
+    heap: Shared<Mutable<Heap>>,
+}
+
+impl PartialEq for EnvironmentTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.env == other.env && self.global == other.global
+    }
 }
 
 impl Default for EnvironmentTree {
     fn default() -> Self {
         Self {
             env: None,
-            global: EnvironmentLink::default(),
+            global: Shared::new(Mutable::new(prelude())),
+            heap: Default::default(),
         }
-        .with_prelude()
     }
 }
 
+fn build_prelude() -> HashMap<Symbol, Object> {
+    let constants = math::CONSTANTS
+        .map(|(name, value)| (Symbol::intern(name), Object::Number(Number::from(value))));
+    NativeFunction::prelude()
+        .into_iter()
+        .map(|native| {
+            let name = Symbol::intern(native.name());
+            (name, Object::callable(LoxCallable::native_function(native)))
+        })
+        .chain(constants)
+        .collect()
+}
+
+// natives, and constants like `pi` unless a script defined its own
+fn is_prelude(name: &Symbol, value: &Object) -> bool {
+    matches!(value.as_callable(), Some(LoxCallable::NativeFunction(_)))
+        || math::CONSTANTS.iter().any(|(constant, number)| {
+            *name == Symbol::intern(constant) && *value == Object::Number(Number::from(*number))
+        })
+}
+
+// the natives every global environment starts with, built once per thread and copied.
+// environments still get their own map, removing a native from one leaves the others alone
+#[cfg(not(feature = "sync"))]
+fn prelude() -> HashMap<Symbol, Object> {
+    thread_local! {
+        static PRELUDE: HashMap<Symbol, Object> = build_prelude();
+    }
+    PRELUDE.with(HashMap::clone)
+}
+
+// built once for the whole program, interpreters can move between threads
+#[cfg(feature = "sync")]
+fn prelude() -> HashMap<Symbol, Object> {
+    static PRELUDE: std::sync::LazyLock<HashMap<Symbol, Object>> =
+        std::sync::LazyLock::new(build_prelude);
+    PRELUDE.clone()
+}
+
 impl EnvironmentTree {
-    fn with_prelude(self) -> Self {
-        // define global environment
-        let clock = NativeFunction::clock();
-        self.global.borrow_mut().define(
-            "clock",
-            Object::Callable(LoxCallable::native_function(clock)),
-        );
-        self
-    }
-
-    pub fn define(&mut self, name: &str, value: Object) {
-        let env = match self.env.as_ref() {
-            Some(env) => env,
-            None => self.global.as_ref(),
-        };
-        env.borrow_mut().define(name, value);
-    }
-
-    pub fn get_at(&self, name: &str, depth: usize) -> Option<Object> {
-        let result = self
-            .env
-            .as_ref()
-            .and_then(|env| env.borrow().get_at(name, depth));
-        if result.is_some() {
-            result
-        } else {
-            self.get_global(name)
+    pub fn define(&mut self, name: Symbol, value: Object) {
+        match self.env.as_ref() {
+            Some(env) => env.borrow_mut().define(name, value),
+            None => self.define_global(name, value),
         }
     }
 
-    pub fn get_global(&self, name: &str) -> Option<Object> {
-        self.global.borrow().get(name)
+    pub fn define_global(&mut self, name: Symbol, value: Object) {
+        self.global.borrow_mut().insert(name, value);
     }
 
-    pub fn assign_at(&mut self, name: &str, value: Object, depth: usize) -> Option<Object> {
-        let result = self
-            .env
-            .as_mut()
-            .and_then(|env| env.borrow_mut().assign_at(name, value.clone(), depth));
-        if result.is_some() {
-            result
-        } else {
-            self.assign_global(name, value)
+    pub fn get_at(&self, slot: Slot) -> Option<Object> {
+        self.env.as_ref().and_then(|env| env.borrow().get_at(slot))
+    }
+
+    pub fn get_global(&self, name: Symbol) -> Option<Object> {
+        self.global.borrow().get(&name).cloned()
+    }
+
+    // natives for which `keep` is false are no longer defined
+    pub fn retain_natives(&mut self, keep: impl Fn(&NativeFunction) -> bool) {
+        self.global
+            .borrow_mut()
+            .retain(|_, value| match value.as_callable() {
+                Some(LoxCallable::NativeFunction(native)) => keep(native),
+                _ => true,
+            });
+    }
+
+    // sorted names of user defined globals, natives are left out unless shadowed
+    pub fn global_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .global
+            .borrow()
+            .iter()
+            .filter(|(name, value)| !is_prelude(name, value))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    // sorted names visible from the current scope, excluding globals
+    pub fn local_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        if let Some(env) = self.env.as_ref() {
+            env.borrow().names(&mut names);
         }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // sorted names of everything that can be referred to from the current scope
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self.local_names();
+        names.extend(self.global.borrow().keys().map(Symbol::to_string));
+        names.sort();
+        names.dedup();
+        names
     }
 
-    pub fn assign_global(&mut self, name: &str, value: Object) -> Option<Object> {
-        self.global.borrow_mut().assign(name, value)
+    pub fn assign_at(&mut self, slot: Slot, value: Object) -> Option<Object> {
+        self.env
+            .as_ref()
+            .and_then(|env| env.borrow_mut().assign_at(slot, value))
+    }
+
+    pub fn assign_global(&mut self, name: Symbol, value: Object) -> Option<Object> {
+        self.global.borrow_mut().get_mut(&name).map(|object| {
+            *object = value;
+            object.clone()
+        })
     }
 
     pub fn append(&self) -> Self {
+        let node = Shared::new(Mutable::new(EnvironmentNode {
+            parent: self.env.clone(),
+            ..Default::default()
+        }));
+        self.heap.borrow_mut().environments.track(&node);
         EnvironmentTree {
-            env: Some(Rc::new(RefCell::new(EnvironmentNode {
-                parent: self.env.clone(),
-                ..Default::default()
-            }))),
-            global: Rc::clone(&self.global),
+            env: Some(node),
+            global: Shared::clone(&self.global),
+            heap: Shared::clone(&self.heap),
         }
     }
 
@@ -142,7 +258,8 @@ impl EnvironmentTree {
             .and_then(|env| env.borrow().parent.clone());
         Self {
             env: parent,
-            global: Rc::clone(&self.global),
+            global: Shared::clone(&self.global),
+            heap: Shared::clone(&self.heap),
         }
     }
 
@@ -153,4 +270,24 @@ impl EnvironmentTree {
     pub fn move_to_outer(&mut self) {
         *self = self.pop();
     }
+
+    // identity of the innermost node, `None` in the global scope
+    pub fn address(&self) -> Option<usize> {
+        self.env.as_ref().map(|env| Shared::as_ptr(env) as usize)
+    }
+
+    // environments and instances the collector knows about
+    // approximate bytes used by globals and every environment on the heap
+    pub fn measure(&self) -> Sizer {
+        let mut sizer = Sizer::default();
+        for value in self.global.borrow().values() {
+            sizer.object(value);
+        }
+        self.heap.borrow().measure(&mut sizer);
+        sizer
+    }
+
+    pub fn heap(&self) -> &Shared<Mutable<Heap>> {
+        &self.heap
+    }
 }
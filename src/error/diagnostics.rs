@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::source::{CharPos, SourcePos};
+
+use super::reporter::ErrorPos;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    // both start from 1
+    pub line: usize,
+    pub column: usize,
+}
+
+// an error or warning found while evaluating a script, for programs embedding lox
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub warning: bool,
+    // like `ParseError`
+    pub kind: &'static str,
+    pub code: &'static str,
+    pub message: String,
+    // where the error starts, some errors are not in the script
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    pub(crate) fn new<E: ErrorPos>(error: &E, source: &SourcePos) -> Self {
+        Self::placed(error, |pos| source.column_of(pos))
+    }
+
+    // `column` finds the 0-based column of a position
+    pub(crate) fn placed<E: ErrorPos>(error: &E, column: impl Fn(CharPos) -> usize) -> Self {
+        let start_pos = error.start_pos();
+        Self {
+            warning: error.is_warning(),
+            kind: error.kind(),
+            code: error.code(),
+            message: error.message(),
+            location: Some(Location {
+                line: start_pos.line + 1,
+                column: column(start_pos) + 1,
+            }),
+        }
+    }
+
+    // the script's result is a function or an instance
+    pub(crate) fn unconvertible_result(type_name: &str) -> Self {
+        Self {
+            warning: false,
+            kind: "RuntimeError",
+            code: "unconvertible_result",
+            message: format!("The result is a {type_name}, it has no value outside the script"),
+            location: None,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = self.location {
+            write!(f, "[line {}]: ", location.line)?;
+        }
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+// everything reported while evaluating a script that failed, warnings included
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoxDiagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LoxDiagnostics {
+    pub(crate) fn extend<E: ErrorPos>(&mut self, errors: &[E], source: &SourcePos) {
+        self.diagnostics
+            .extend(errors.iter().map(|error| Diagnostic::new(error, source)));
+    }
+
+    pub(crate) fn extend_placed<E: ErrorPos>(
+        &mut self,
+        errors: &[E],
+        column: impl Fn(CharPos) -> usize,
+    ) {
+        self.diagnostics.extend(
+            errors
+                .iter()
+                .map(|error| Diagnostic::placed(error, &column)),
+        );
+    }
+
+    pub(crate) fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| !diagnostic.warning)
+    }
+}
+
+impl fmt::Display for LoxDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<_> = self.diagnostics.iter().map(Diagnostic::to_string).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for LoxDiagnostics {}
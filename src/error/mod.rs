@@ -1,6 +1,9 @@
+pub(crate) mod diagnostics;
+pub(crate) mod native_error;
 pub(crate) mod object_error;
 pub(crate) mod parse_error;
 pub(crate) mod reporter;
 pub(crate) mod resolve_error;
 pub(crate) mod runtime_error;
 pub(crate) mod syntax_error;
+pub(crate) mod warning;
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use crate::{object::Object, value::Value};
+
+use super::{reporter::ErrorPos, runtime_error::RuntimeError};
+
+#[derive(PartialEq)]
+pub(crate) enum NativeErrorType {
+    InvalidArgument(String, String),
+    InvalidRange(f64, f64),
+    InvalidFormat(String),
+    InvalidCodePoint(f64),
+    InvalidExitCode(f64),
+    InvalidCharacter(String),
+    InvalidTime(String),
+    IndexOutOfBounds(usize, usize),
+    CallbackArity(usize, String),
+    Custom(String),
+    Io(String),
+    Exit(i32), // this is not error
+    // raised by a function the native called, reported where it happened
+    Callback(Box<RuntimeError>),
+}
+
+impl NativeErrorType {
+    fn msg(&self) -> String {
+        match self {
+            NativeErrorType::InvalidArgument(expected, found) => {
+                format!("Expected {expected} argument. Found {found}")
+            }
+            NativeErrorType::InvalidRange(start, end) => {
+                format!("Invalid range from {start} to {end}")
+            }
+            NativeErrorType::InvalidFormat(reason) => format!("Invalid format string: {reason}"),
+            NativeErrorType::InvalidCodePoint(code) => format!("Invalid code point {code}"),
+            NativeErrorType::InvalidExitCode(code) => {
+                format!("Exit code {code} is out of range 0 to 255")
+            }
+            NativeErrorType::InvalidCharacter(string) => {
+                format!("Expected a single character. Found \"{string}\"")
+            }
+            NativeErrorType::InvalidTime(reason) => format!("Invalid time: {reason}"),
+            NativeErrorType::IndexOutOfBounds(index, len) => {
+                format!("Index {index} is out of bounds for a list of length {len}")
+            }
+            NativeErrorType::CallbackArity(arity, found) => {
+                let noun = if *arity == 1 { "argument" } else { "arguments" };
+                format!("Expected a function taking {arity} {noun}. Found {found}")
+            }
+            NativeErrorType::Custom(msg) => msg.to_string(),
+            NativeErrorType::Io(err) => err.to_string(),
+            NativeErrorType::Exit(_) => unreachable!("this should not be called as error"),
+            NativeErrorType::Callback(err) => err.message(),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+pub(crate) struct NativeError {
+    error_type: NativeErrorType,
+}
+
+impl NativeError {
+    pub fn invalid_argument(expected: &str, found: &Object) -> Self {
+        Self {
+            error_type: NativeErrorType::InvalidArgument(
+                expected.to_string(),
+                found.type_name().to_string(),
+            ),
+        }
+    }
+
+    // a value the host converts into a rust type
+    pub fn invalid_value(expected: &str, found: &Value) -> Self {
+        Self {
+            error_type: NativeErrorType::InvalidArgument(
+                expected.to_string(),
+                found.type_name().to_string(),
+            ),
+        }
+    }
+
+    pub fn invalid_range(start: f64, end: f64) -> Self {
+        Self {
+            error_type: NativeErrorType::InvalidRange(start, end),
+        }
+    }
+
+    pub fn invalid_format(reason: &str) -> Self {
+        Self {
+            error_type: NativeErrorType::InvalidFormat(reason.to_string()),
+        }
+    }
+
+    pub fn invalid_code_point(code: f64) -> Self {
+        Self {
+            error_type: NativeErrorType::InvalidCodePoint(code),
+        }
+    }
+
+    // status codes wider than a byte are truncated by the operating system
+    pub fn invalid_exit_code(code: f64) -> Self {
+        Self {
+            error_type: NativeErrorType::InvalidExitCode(code),
+        }
+    }
+
+    pub fn invalid_character(string: &str) -> Self {
+        Self {
+            error_type: NativeErrorType::InvalidCharacter(string.to_string()),
+        }
+    }
+
+    pub fn invalid_time(reason: &str) -> Self {
+        Self {
+            error_type: NativeErrorType::InvalidTime(reason.to_string()),
+        }
+    }
+
+    pub fn index_out_of_bounds(index: usize, len: usize) -> Self {
+        Self {
+            error_type: NativeErrorType::IndexOutOfBounds(index, len),
+        }
+    }
+
+    // `found` is the function given, like `<fn add>`
+    pub fn callback_arity(arity: usize, found: &Object) -> Self {
+        Self {
+            error_type: NativeErrorType::CallbackArity(arity, found.to_string()),
+        }
+    }
+
+    // errors without a type of their own, like those raised by natives registered from
+    // the host
+    pub fn custom(msg: &str) -> Self {
+        Self {
+            error_type: NativeErrorType::Custom(msg.to_string()),
+        }
+    }
+
+    pub fn exit(code: i32) -> Self {
+        Self {
+            error_type: NativeErrorType::Exit(code),
+        }
+    }
+
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.error_type {
+            NativeErrorType::Exit(code) => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn into_callback_error(self) -> Result<RuntimeError, Self> {
+        match self.error_type {
+            NativeErrorType::Callback(err) => Ok(*err),
+            error_type => Err(Self { error_type }),
+        }
+    }
+}
+
+impl From<RuntimeError> for NativeError {
+    fn from(value: RuntimeError) -> Self {
+        Self {
+            error_type: NativeErrorType::Callback(Box::new(value)),
+        }
+    }
+}
+
+impl From<std::io::Error> for NativeError {
+    fn from(value: std::io::Error) -> Self {
+        Self {
+            error_type: NativeErrorType::Io(value.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for NativeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error_type.msg())
+    }
+}
+
+impl fmt::Debug for NativeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", &self)
+    }
+}
+
+impl std::error::Error for NativeError {}
@@ -9,6 +9,8 @@ pub(crate) enum ObjectErrorType {
     Multiplication,
     Division,
     ZeroDivision,
+    Bitwise,
+    ShiftOutOfRange(i64),
 }
 
 impl ObjectErrorType {
@@ -23,6 +25,12 @@ impl ObjectErrorType {
             ObjectErrorType::Multiplication => "Could not multiply non-number".to_string(),
             ObjectErrorType::Division => "Could not divide non-number".to_string(),
             ObjectErrorType::ZeroDivision => "Division by zero".to_string(),
+            ObjectErrorType::Bitwise => {
+                "Could not apply bitwise operator to non-number".to_string()
+            }
+            ObjectErrorType::ShiftOutOfRange(amount) => {
+                format!("Shift amount {amount} is out of range 0 to 63")
+            }
         }
     }
 }
@@ -74,6 +82,18 @@ impl ObjectError {
             error_type: ObjectErrorType::ZeroDivision,
         }
     }
+
+    pub fn bitwise() -> Self {
+        Self {
+            error_type: ObjectErrorType::Bitwise,
+        }
+    }
+
+    pub fn shift_out_of_range(amount: i64) -> Self {
+        Self {
+            error_type: ObjectErrorType::ShiftOutOfRange(amount),
+        }
+    }
 }
 impl fmt::Display for ObjectError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
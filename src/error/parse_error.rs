@@ -5,17 +5,31 @@ use crate::{
     token::{Token, TokenType},
 };
 
-use super::reporter::impl_error_pos;
+use super::reporter::{impl_error_pos, Details, Note};
 
 #[derive(PartialEq)]
 pub(crate) enum ParseErrorType {
     ExpectedExpression,
     UnexpectedToken(String, String),
     InvalidAssignment,
+    AssignmentInCondition,
     MaximumArguments(usize),
+    TooDeeplyNested(usize),
 }
 
 impl ParseErrorType {
+    // stable name of the error, for tools reading `--diagnostics json`
+    fn code(&self) -> &'static str {
+        match self {
+            ParseErrorType::ExpectedExpression => "expected_expression",
+            ParseErrorType::UnexpectedToken(..) => "unexpected_token",
+            ParseErrorType::InvalidAssignment => "invalid_assignment",
+            ParseErrorType::AssignmentInCondition => "assignment_in_condition",
+            ParseErrorType::MaximumArguments(_) => "too_many_arguments",
+            ParseErrorType::TooDeeplyNested(_) => "too_deeply_nested",
+        }
+    }
+
     fn msg(&self) -> String {
         match self {
             ParseErrorType::UnexpectedToken(found, expected) => {
@@ -23,9 +37,15 @@ impl ParseErrorType {
             }
             ParseErrorType::ExpectedExpression => "Expected expression".to_string(),
             ParseErrorType::InvalidAssignment => "Inavalid assignment target.".to_string(),
+            ParseErrorType::AssignmentInCondition => {
+                "Invalid assignment target in condition".to_string()
+            }
             ParseErrorType::MaximumArguments(argc) => {
                 format!("Could not have more than {argc} arguments")
             }
+            ParseErrorType::TooDeeplyNested(depth) => {
+                format!("Could not nest code more than {depth} levels deep")
+            }
         }
     }
 }
@@ -36,9 +56,10 @@ pub(crate) struct ParseError {
     end_pos: CharPos,
     error_type: ParseErrorType,
     panic_mode: bool,
+    details: Details,
 }
 
-impl_error_pos!(ParseError);
+impl_error_pos!(ParseError, details);
 
 impl ParseError {
     pub fn expected_expression(token: &Token) -> Self {
@@ -47,6 +68,7 @@ impl ParseError {
             end_pos: token.end_pos(),
             error_type: ParseErrorType::ExpectedExpression,
             panic_mode: true,
+            details: Details::default(),
         }
     }
 
@@ -59,6 +81,7 @@ impl ParseError {
                 expected.to_string(),
             ),
             panic_mode: true,
+            details: Details::default(),
         }
     }
 
@@ -68,6 +91,7 @@ impl ParseError {
             end_pos: token.end_pos(),
             error_type: ParseErrorType::InvalidAssignment,
             panic_mode: true,
+            details: Details::default(),
         }
     }
 
@@ -77,6 +101,17 @@ impl ParseError {
             end_pos: token.end_pos(),
             error_type: ParseErrorType::MaximumArguments(size),
             panic_mode: true,
+            details: Details::default(),
+        }
+    }
+
+    pub fn too_deeply_nested(token: &Token, depth: usize) -> Self {
+        Self {
+            start_pos: token.start_pos(),
+            end_pos: token.end_pos(),
+            error_type: ParseErrorType::TooDeeplyNested(depth),
+            panic_mode: true,
+            details: Details::default(),
         }
     }
 
@@ -84,6 +119,19 @@ impl ParseError {
         self.panic_mode
     }
 
+    // an invalid assignment in an `if` or `while` condition was likely meant as `==`
+    pub fn in_condition(self) -> Self {
+        match self.error_type {
+            ParseErrorType::InvalidAssignment => Self {
+                error_type: ParseErrorType::AssignmentInCondition,
+                details: Details::default()
+                    .note(Note::Help("use `==` to compare values".to_string())),
+                ..self
+            },
+            _ => self,
+        }
+    }
+
     pub fn without_panic(self) -> Self {
         Self {
             panic_mode: false,
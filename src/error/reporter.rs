@@ -1,29 +1,111 @@
-use crate::source::{CharPos, SourcePos};
+use serde_json::json;
+
+use crate::{
+    color::Color,
+    source::{CharPos, SourcePos},
+    token::Token,
+};
 
 const ERROR_MARK: char = '^';
+const LABEL_MARK: char = '-';
 const NORMAL_MARK: char = ' ';
 
+pub(crate) const DEFAULT_TAB_WIDTH: usize = 4;
+
 fn string_equal_width(ch: char, width: usize) -> String {
     // endline character has width = 0, but we still need to indicate there is error
     let width = std::cmp::max(1, width);
     vec![ch; width].into_iter().collect::<String>()
 }
 
-fn error_string(pos: &CharPos) -> String {
-    string_equal_width(ERROR_MARK, pos.width)
+pub(crate) trait ErrorPos: std::error::Error {
+    fn start_pos(&self) -> CharPos;
+    fn end_pos(&self) -> CharPos;
+    // without the line, which `start_pos` gives
+    fn message(&self) -> String;
+    // what the error is called in reports, like `ParseError`
+    fn kind(&self) -> &'static str;
+    fn code(&self) -> &'static str;
+    // reported in yellow instead of red
+    fn is_warning(&self) -> bool {
+        false
+    }
+    fn details(&self) -> Option<&Details> {
+        None
+    }
 }
 
-fn normal_string(pos: &CharPos) -> String {
-    string_equal_width(NORMAL_MARK, pos.width)
+// another place an error points at, and what is there
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Label {
+    pub start_pos: CharPos,
+    pub end_pos: CharPos,
+    pub message: String,
 }
 
-pub(crate) trait ErrorPos: std::error::Error {
-    fn start_pos(&self) -> CharPos;
-    fn end_pos(&self) -> CharPos;
+impl Label {
+    pub fn new(token: &Token, message: String) -> Self {
+        Self {
+            start_pos: token.start_pos(),
+            end_pos: token.end_pos(),
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Note {
+    Note(String),
+    Help(String),
 }
 
+impl Note {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Note::Note(_) => "note",
+            Note::Help(_) => "help",
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Note::Note(text) | Note::Help(text) => text,
+        }
+    }
+}
+
+// what an error says besides its message, shown under the source it points at
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Details {
+    pub labels: Vec<Label>,
+    pub notes: Vec<Note>,
+}
+
+impl Details {
+    pub fn label(mut self, label: Label) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn note(mut self, note: Note) -> Self {
+        self.notes.push(note);
+        self
+    }
+}
+
+// errors with a `details` field show it in their reports
 macro_rules! impl_error_pos {
     ($struct:ident) => {
+        impl_error_pos!($struct, {});
+    };
+    ($struct:ident, details) => {
+        impl_error_pos!($struct, {
+            fn details(&self) -> Option<&$crate::error::reporter::Details> {
+                Some(&self.details)
+            }
+        });
+    };
+    ($struct:ident, { $($details:tt)* }) => {
         impl $crate::error::reporter::ErrorPos for $struct {
             fn start_pos(&self) -> $crate::source::CharPos {
                 self.start_pos
@@ -32,6 +114,20 @@ macro_rules! impl_error_pos {
             fn end_pos(&self) -> $crate::source::CharPos {
                 self.end_pos
             }
+
+            fn message(&self) -> String {
+                self.error_type.msg()
+            }
+
+            fn kind(&self) -> &'static str {
+                stringify!($struct)
+            }
+
+            fn code(&self) -> &'static str {
+                self.error_type.code()
+            }
+
+            $($details)*
         }
     };
 }
@@ -49,116 +145,592 @@ where
     }
 
     fn error_msg(&self, reporter: &Reporter) -> String {
-        self.errors()
-            .iter()
-            .map(|err| reporter.report(err))
-            .collect::<Vec<_>>()
-            .join("\n")
+        reporter.report_all(self.errors())
     }
 }
 
 pub(crate) struct Reporter<'a> {
-    source: &'a SourcePos,
+    source: &'a SourcePos<'a>,
+    colored: bool,
+    // file the source was read from, headers then start with its path, line and column
+    path: Option<String>,
+    // lines shown before and after the lines in error, numbered when there are any
+    context: usize,
+    // errors reported at once, the rest are only counted. 0 reports all of them
+    max_errors: usize,
+    format: DiagnosticFormat,
+    // columns a tab takes in the source lines shown, the marks under them take as many
+    tab_width: usize,
 }
 
 impl<'a> Reporter<'a> {
-    pub fn new(source: &'a SourcePos) -> Self {
-        Self { source }
+    pub fn new(source: &'a SourcePos<'a>) -> Self {
+        Self {
+            source,
+            colored: false,
+            path: None,
+            context: 0,
+            max_errors: 0,
+            format: DiagnosticFormat::Human,
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width;
+        self
+    }
+
+    pub fn with_format(mut self, format: DiagnosticFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+
+    pub fn with_context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn with_path(mut self, path: Option<String>) -> Self {
+        self.path = path;
+        self
+    }
+
+    // headers, carets and the part of the source in error are painted
+    pub fn with_color(mut self, colored: bool) -> Self {
+        self.colored = colored;
+        self
     }
 
+    fn paint(&self, color: Option<Color>, text: &str) -> String {
+        match color {
+            Some(color) => color.paint(text),
+            None => text.to_string(),
+        }
+    }
+
+    // the source line and the marks under it, `error_span_index` is inclusive
     fn draw_one_line_error(
         &self,
-        line_span_index: (usize, usize),
+        line: usize,
         error_span_index: (usize, usize),
-    ) -> String {
-        let mut src_string = String::new();
+        mark: Mark,
+    ) -> (String, String) {
+        // every character of the line and whether it is in error
+        let mut src_chars = Vec::new();
         let mut err_string = String::new();
 
         let is_error = |index: usize, error_span_index: (usize, usize)| -> bool {
             index >= error_span_index.0 && index <= error_span_index.1
         };
 
-        for index in line_span_index.0..line_span_index.1 + 1 {
-            let char_pos = self
-                .source
-                .get(index)
-                .expect("Index never exceed source's range");
-            src_string.push(char_pos.ch);
-
-            if is_error(char_pos.index, error_span_index) {
-                err_string.push_str(&error_string(&char_pos))
-            } else {
-                err_string.push_str(&normal_string(&char_pos))
+        for char_pos in self.source.line(line) {
+            // the newline ending the line is only marked when the error ends there
+            if char_pos.ch == '\n' && char_pos.index != error_span_index.1 {
+                break;
+            }
+            let error = is_error(char_pos.index, error_span_index);
+            // tabs are shown as spaces, a terminal would put them anywhere
+            match char_pos.ch {
+                '\t' => src_chars.extend(std::iter::repeat_n((' ', error), self.tab_width)),
+                ch => src_chars.push((ch, error)),
+            }
+
+            let width = match char_pos.ch {
+                '\t' => self.tab_width,
+                _ => char_pos.width,
+            };
+            match error {
+                true => err_string.push_str(&string_equal_width(mark.ch, width)),
+                false => err_string.push_str(&string_equal_width(NORMAL_MARK, width)),
             }
         }
+        while src_chars.last().is_some_and(|(ch, _)| ch.is_whitespace()) {
+            src_chars.pop();
+        }
 
-        format!("{}\n{}", src_string.trim_end(), err_string.trim_end())
-    }
+        let mut src_string = String::new();
+        let mut span = String::new();
+        for (ch, error) in src_chars {
+            if error {
+                span.push(ch);
+                continue;
+            }
+            if !span.is_empty() {
+                src_string.push_str(&self.paint(mark.color, &span));
+                span.clear();
+            }
+            src_string.push(ch);
+        }
+        if !span.is_empty() {
+            src_string.push_str(&self.paint(mark.color, &span));
+        }
 
-    fn error_in_middle(&self, line: usize, start_pos: usize, end_pos: usize) -> String {
-        let newlines_pos = self.source.newlines_pos();
+        let err_string = err_string.trim_end();
+        let marks = err_string.trim_start();
+        let err_string = match marks.is_empty() {
+            true => err_string.to_string(),
+            false => format!(
+                "{}{}",
+                &err_string[..err_string.len() - marks.len()],
+                self.paint(mark.color, marks)
+            ),
+        };
 
-        let start_line_index = newlines_pos[line];
-        let end_line_index = newlines_pos[line + 1] - 1;
+        (src_string, err_string)
+    }
 
-        self.draw_one_line_error((start_line_index, end_line_index), (start_pos, end_pos))
+    // `None` past the end of the source
+    fn context_line(&self, line: usize) -> Option<String> {
+        let text: String = self.source.line(line).map(|char_pos| char_pos.ch).collect();
+        match text.is_empty() {
+            true => None,
+            false => Some(text.trim_end().replace('\t', &" ".repeat(self.tab_width))),
+        }
     }
 
-    fn error_to_end(&self, line: usize, start_pos: usize) -> String {
-        let newlines_pos = self.source.newlines_pos();
+    fn error_in_middle(
+        &self,
+        line: usize,
+        start_pos: usize,
+        end_pos: usize,
+        mark: Mark,
+    ) -> (String, String) {
+        self.draw_one_line_error(line, (start_pos, end_pos), mark)
+    }
 
-        let start_line_index = newlines_pos[line];
-        let end_line_index = newlines_pos[line + 1] - 1;
+    fn error_to_end(&self, line: usize, start_pos: usize, mark: Mark) -> (String, String) {
+        self.draw_one_line_error(line, (start_pos, usize::MAX), mark)
+    }
 
-        self.draw_one_line_error(
-            (start_line_index, end_line_index),
-            (start_pos, end_line_index),
-        )
+    fn error_from_start(&self, line: usize, end_pos: usize, mark: Mark) -> (String, String) {
+        self.draw_one_line_error(line, (0, end_pos), mark)
     }
 
-    fn error_from_start(&self, line: usize, end_pos: usize) -> String {
-        let newlines_pos = self.source.newlines_pos();
+    // every line from `start_pos` to `end_pos` followed by the marks under it, with the
+    // line it shows, marks show none
+    fn draw_span(
+        &self,
+        start_pos: CharPos,
+        end_pos: CharPos,
+        mark: Mark,
+    ) -> Vec<(Option<usize>, String)> {
+        let drawn = if start_pos.line == end_pos.line {
+            vec![self.error_in_middle(start_pos.line, start_pos.index, end_pos.index, mark)]
+        } else {
+            // marked from the start of the span to the end of its first line, every
+            // line in between, and the last line up to the end of the span
+            let mut drawn = vec![self.error_to_end(start_pos.line, start_pos.index, mark)];
+            for line in start_pos.line + 1..end_pos.line {
+                drawn.push(self.error_to_end(line, 0, mark));
+            }
+            drawn.push(self.error_from_start(end_pos.line, end_pos.index, mark));
+            drawn
+        };
+        let mut rows = Vec::new();
+        for (line, (src, marks)) in (start_pos.line..).zip(drawn) {
+            rows.push((Some(line), src));
+            rows.push((None, marks));
+        }
+        rows
+    }
 
-        let start_line_index = newlines_pos[line];
-        let end_line_index = newlines_pos[line + 1] - 1;
+    // 1-based line and column
+    fn json_position(&self, pos: CharPos) -> serde_json::Value {
+        json!({
+            "line": pos.line + 1,
+            "column": self.source.column_of(pos) + 1,
+        })
+    }
 
-        self.draw_one_line_error(
-            (start_line_index, end_line_index),
-            (start_line_index, end_pos),
-        )
+    // `end` is the last character of the span
+    fn json_span(&self, start_pos: CharPos, end_pos: CharPos) -> serde_json::Value {
+        json!({
+            "start": self.json_position(start_pos),
+            "end": self.json_position(end_pos),
+        })
     }
 
-    pub fn report<E>(&self, error: &E) -> String
+    fn json<E>(&self, error: &E) -> serde_json::Value
     where
         E: ErrorPos,
     {
         let start_pos = error.start_pos();
-        let end_pos = error.end_pos();
+        let no_details = Details::default();
+        let details = error.details().unwrap_or(&no_details);
+        let notes = |help: bool| -> Vec<&str> {
+            details
+                .notes
+                .iter()
+                .filter(|note| matches!(note, Note::Help(_)) == help)
+                .map(Note::text)
+                .collect()
+        };
+        let labels: Vec<_> = details
+            .labels
+            .iter()
+            .map(|label| {
+                json!({
+                    "message": label.message,
+                    "span": self.json_span(label.start_pos, label.end_pos),
+                })
+            })
+            .collect();
+        json!({
+            "severity": if error.is_warning() { "warning" } else { "error" },
+            "kind": error.kind(),
+            "code": error.code(),
+            "message": error.message(),
+            "file": self.path,
+            "line": start_pos.line + 1,
+            "column": self.source.column_of(start_pos) + 1,
+            "span": self.json_span(start_pos, error.end_pos()),
+            "labels": labels,
+            "notes": notes(false),
+            "help": notes(true),
+        })
+    }
 
-        let mut result = String::new();
-        result.push_str(&format!("{error}\n"));
+    pub fn report_all<E>(&self, errors: &[E]) -> String
+    where
+        E: ErrorPos,
+    {
+        // tools reading json get every error
+        let shown = match (self.format, self.max_errors) {
+            (DiagnosticFormat::Json, _) | (_, 0) => errors.len(),
+            (_, max_errors) => errors.len().min(max_errors),
+        };
+        let mut reports: Vec<_> = errors[..shown].iter().map(|err| self.report(err)).collect();
+        let suppressed = errors.len() - shown;
+        if suppressed > 0 {
+            let kind = match errors[0].is_warning() {
+                true => "warning",
+                false => "error",
+            };
+            let plural = if suppressed == 1 { "" } else { "s" };
+            reports.push(format!("{suppressed} additional {kind}{plural} suppressed"));
+        }
+        reports.join("\n")
+    }
 
-        if start_pos.line == end_pos.line {
-            // handle one line
-            result.push_str(&self.error_in_middle(start_pos.line, start_pos.index, end_pos.index));
-        } else {
-            // handle multiple line
+    pub fn report<E>(&self, error: &E) -> String
+    where
+        E: ErrorPos,
+    {
+        if self.format == DiagnosticFormat::Json {
+            return self.json(error).to_string();
+        }
 
-            // first line
-            result.push_str(&self.error_to_end(start_pos.line, start_pos.index));
+        let start_pos = error.start_pos();
+        let end_pos = error.end_pos();
+        let color = self.colored.then(|| match error.is_warning() {
+            true => Color::Warning,
+            false => Color::Error,
+        });
+        let label_color = self.colored.then_some(Color::Label);
+
+        let header = match &self.path {
+            Some(path) => format!(
+                "{path}:{}:{}: {}: {}",
+                start_pos.line + 1,
+                self.source.column_of(start_pos) + 1,
+                error.kind(),
+                error.message()
+            ),
+            None => error.to_string(),
+        };
+        let mut result = format!("{}\n", self.paint(color, &header));
 
-            // middle lines
-            // end_pos.line - 1 will never be overflow since end_pos.line > start_pos.line >= 0
-            for line in start_pos.line + 1..end_pos.line - 1 {
-                result.push_str(&self.error_to_end(line, 0));
+        let mut rows = Vec::new();
+        for line in start_pos.line.saturating_sub(self.context)..start_pos.line {
+            rows.extend(self.context_line(line).map(|text| (Some(line), text)));
+        }
+        let mark = Mark {
+            ch: ERROR_MARK,
+            color,
+        };
+        rows.extend(self.draw_span(start_pos, end_pos, mark));
+        for line in end_pos.line + 1..=end_pos.line + self.context {
+            match self.context_line(line) {
+                Some(text) => rows.push((Some(line), text)),
+                None => break,
             }
+        }
 
-            // last line
-            result.push_str(&self.error_from_start(end_pos.line, end_pos.index));
+        // labels are drawn after the error, with their message after their marks
+        let no_details = Details::default();
+        let details = error.details().unwrap_or(&no_details);
+        for label in &details.labels {
+            let mark = Mark {
+                ch: LABEL_MARK,
+                color: label_color,
+            };
+            let mut label_rows = self.draw_span(label.start_pos, label.end_pos, mark);
+            if let Some((_, marks)) = label_rows.last_mut() {
+                marks.push(' ');
+                marks.push_str(&self.paint(label_color, &label.message));
+            }
+            rows.extend(label_rows);
+        }
 
-            todo!("Add test for this case");
+        // numbered rows are indented by the widest line number
+        let width = match self.context {
+            0 => None,
+            _ => {
+                let last = rows.iter().filter_map(|(line, _)| *line).max();
+                Some(last.map_or(0, |line| (line + 1).to_string().len()))
+            }
+        };
+        let mut rows: Vec<String> = match width {
+            None => rows.into_iter().map(|(_, text)| text).collect(),
+            Some(width) => rows
+                .into_iter()
+                .map(|(line, text)| {
+                    let number = line.map(|line| (line + 1).to_string()).unwrap_or_default();
+                    format!("{number:>width$} | {text}").trim_end().to_string()
+                })
+                .collect(),
+        };
+        for note in &details.notes {
+            let gutter = match width {
+                None => String::new(),
+                Some(width) => format!("{:width$} = ", ""),
+            };
+            let prefix = self.paint(label_color, note.prefix());
+            rows.push(format!("{gutter}{prefix}: {}", note.text()));
         }
+        result.push_str(&rows.join("\n"));
 
         result
     }
 }
+
+// how errors are reported
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub(crate) enum DiagnosticFormat {
+    // the header, then the source with carets under the error
+    #[default]
+    Human,
+    // one json object per line
+    Json,
+}
+
+// how the span of an error or label is marked under the source
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    ch: char,
+    color: Option<Color>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::{parse_error::ParseError, resolve_error::ResolveError},
+        source::CharPos,
+        token::{Token, TokenType},
+    };
+
+    use super::*;
+
+    fn report(source: &str, start: CharPos, end: CharPos) -> String {
+        let source_pos = SourcePos::new(source);
+        let token = Token::new(TokenType::Identifier("a".into()), start, end);
+        Reporter::new(&source_pos).report(&ParseError::expected_expression(&token))
+    }
+
+    #[test]
+    fn report_one_line() {
+        assert_eq!(
+            report("print a;\nb;", CharPos::new(6, 0), CharPos::new(6, 0)),
+            "[line 1]: ParseError: Expected expression\nprint a;\n      ^"
+        );
+        // an error at the end of a line points at the newline
+        assert_eq!(
+            report("print\nb;", CharPos::new(5, 0), CharPos::new(5, 0)),
+            "[line 1]: ParseError: Expected expression\nprint\n     ^"
+        );
+    }
+
+    #[test]
+    fn report_with_tabs() {
+        assert_eq!(
+            report("\tprint\t\ta;", CharPos::new(6, 0), CharPos::new(7, 0)),
+            "[line 1]: ParseError: Expected expression\n    print        a;\n         ^^^^^^^^"
+        );
+        let source_pos = SourcePos::new("\ta;");
+        let token = Token::new(
+            TokenType::Identifier("a".into()),
+            CharPos::new(1, 0),
+            CharPos::new(1, 0),
+        );
+        assert_eq!(
+            Reporter::new(&source_pos)
+                .with_tab_width(2)
+                .report(&ParseError::expected_expression(&token)),
+            "[line 1]: ParseError: Expected expression\n  a;\n  ^"
+        );
+    }
+
+    #[test]
+    fn report_multiple_lines() {
+        let source = "var s = \"one\ntwo\nthree\";\nprint s;";
+        let report = report(source, CharPos::new(8, 0), CharPos::new(22, 2));
+        let expected = "[line 1]: ParseError: Expected expression
+var s = \"one
+        ^^^^
+two
+^^^
+three\";
+^^^^^^";
+        assert_eq!(report, expected);
+    }
+
+    #[test]
+    fn report_in_color() {
+        let source_pos = SourcePos::new("print a;");
+        let token = Token::new(
+            TokenType::Identifier("a".into()),
+            CharPos::new(6, 0),
+            CharPos::new(6, 0),
+        );
+        let report = Reporter::new(&source_pos)
+            .with_color(true)
+            .report(&ParseError::expected_expression(&token));
+        let red = |text: &str| Color::Error.paint(text);
+        assert_eq!(
+            report,
+            format!(
+                "{}\nprint {};\n      {}",
+                red("[line 1]: ParseError: Expected expression"),
+                red("a"),
+                red("^")
+            )
+        );
+    }
+
+    #[test]
+    fn report_with_path() {
+        let source_pos = SourcePos::new("var a;\n  print a +;");
+        let token = Token::new(
+            TokenType::Semicolon,
+            CharPos::new(17, 1),
+            CharPos::new(17, 1),
+        );
+        let report = Reporter::new(&source_pos)
+            .with_path(Some("script.lox".to_string()))
+            .report(&ParseError::expected_expression(&token));
+        assert_eq!(
+            report,
+            "script.lox:2:11: ParseError: Expected expression\n  print a +;\n          ^"
+        );
+    }
+
+    #[test]
+    fn report_with_context() {
+        let source = "fun f() {\n  var a = 1;\n\n  print a +;\n}\n";
+        let source_pos = SourcePos::new(source);
+        let token = Token::new(
+            TokenType::Semicolon,
+            CharPos::new(35, 3),
+            CharPos::new(35, 3),
+        );
+        let error = ParseError::expected_expression(&token);
+        let expected = "[line 4]: ParseError: Expected expression
+2 |   var a = 1;
+3 |
+4 |   print a +;
+  |            ^
+5 | }";
+        assert_eq!(
+            Reporter::new(&source_pos).with_context(2).report(&error),
+            expected
+        );
+    }
+
+    #[test]
+    fn report_labels_and_notes() {
+        let source_pos = SourcePos::new("{\n  var a;\n  var a;\n}");
+        let token = |index, line| {
+            Token::new(
+                TokenType::Identifier("a".into()),
+                CharPos::new(index, line),
+                CharPos::new(index, line),
+            )
+        };
+        let error = ResolveError::already_declared(&token(17, 2), Some(&token(8, 1)));
+        let expected = "[line 3]: ResolveError: Already a variable `a` in this scope.
+2 |   var a;
+3 |   var a;
+  |       ^
+4 | }
+2 |   var a;
+  |       - `a` is first declared here
+  = help: assign to `a` instead";
+        assert_eq!(
+            Reporter::new(&source_pos).with_context(1).report(&error),
+            expected
+        );
+    }
+
+    #[test]
+    fn suppress_errors_over_the_limit() {
+        let source = "print ;\nprint ;\nprint ;\nprint ;";
+        let source_pos = SourcePos::new(source);
+        let errors: Vec<_> = (0..4)
+            .map(|line| {
+                let index = line * 8 + 6;
+                let token = Token::new(
+                    TokenType::Semicolon,
+                    CharPos::new(index, line),
+                    CharPos::new(index, line),
+                );
+                ParseError::expected_expression(&token)
+            })
+            .collect();
+        let reporter = Reporter::new(&source_pos);
+        assert_eq!(reporter.report_all(&errors).lines().count(), 12);
+
+        let report = reporter.with_max_errors(3).report_all(&errors);
+        assert_eq!(report.lines().count(), 10);
+        assert!(report.ends_with("\n1 additional error suppressed"));
+    }
+
+    #[test]
+    fn report_json() {
+        let source_pos = SourcePos::new("{\n  var a;\n  var a;\n}");
+        let token = |index, line| {
+            Token::new(
+                TokenType::Identifier("a".into()),
+                CharPos::new(index, line),
+                CharPos::new(index, line),
+            )
+        };
+        let error = ResolveError::already_declared(&token(17, 2), Some(&token(8, 1)));
+        let report = Reporter::new(&source_pos)
+            .with_path(Some("a.lox".to_string()))
+            .with_format(DiagnosticFormat::Json)
+            .report(&error);
+        let span = |line| json!({"start": {"line": line, "column": 7}, "end": {"line": line, "column": 7}});
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&report).unwrap(),
+            json!({
+                "severity": "error",
+                "kind": "ResolveError",
+                "code": "already_declared",
+                "message": "Already a variable `a` in this scope.",
+                "file": "a.lox",
+                "line": 3,
+                "column": 7,
+                "span": span(3),
+                "labels": [{"message": "`a` is first declared here", "span": span(2)}],
+                "notes": [],
+                "help": ["assign to `a` instead"],
+            })
+        );
+    }
+}
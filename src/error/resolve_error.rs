@@ -1,8 +1,8 @@
 use std::fmt;
 
-use crate::{source::CharPos, stmt::Return, token::Token};
+use crate::{expr::Super, source::CharPos, stmt::Return, token::Token};
 
-use super::reporter::impl_error_pos;
+use super::reporter::{impl_error_pos, Details, Label, Note};
 
 #[derive(PartialEq)]
 pub(crate) enum ResolveErrorType {
@@ -11,11 +11,32 @@ pub(crate) enum ResolveErrorType {
     ReturnFromTopLevel,
     ReturnInsideInit,
     CallThisOutsideClass,
-    CallSuperOutsideSubclass,
+    CallSuperOutsideClass,
+    CallSuperWithoutSuperclass,
     ClassInheritItself,
+    DuplicateMethod(String),
+    UndeclaredGlobal(String),
+    NumberArgumentsMismatch(usize, usize),
 }
 
 impl ResolveErrorType {
+    // stable name of the error, for tools reading `--diagnostics json`
+    fn code(&self) -> &'static str {
+        match self {
+            ResolveErrorType::ReadDuringInitializer(_) => "read_in_own_initializer",
+            ResolveErrorType::VarAlreadyExistInScope(_) => "already_declared",
+            ResolveErrorType::ReturnFromTopLevel => "return_from_top_level",
+            ResolveErrorType::ReturnInsideInit => "return_inside_init",
+            ResolveErrorType::CallThisOutsideClass => "this_outside_class",
+            ResolveErrorType::CallSuperOutsideClass => "super_outside_class",
+            ResolveErrorType::CallSuperWithoutSuperclass => "super_without_superclass",
+            ResolveErrorType::ClassInheritItself => "class_inherits_itself",
+            ResolveErrorType::DuplicateMethod(..) => "duplicate_method",
+            ResolveErrorType::UndeclaredGlobal(_) => "undeclared_global",
+            ResolveErrorType::NumberArgumentsMismatch(..) => "wrong_number_of_arguments",
+        }
+    }
+
     fn msg(&self) -> String {
         match self {
             ResolveErrorType::ReadDuringInitializer(name) => {
@@ -30,13 +51,25 @@ impl ResolveErrorType {
             ResolveErrorType::CallThisOutsideClass => {
                 "Could not use `this` outside of a class".to_string()
             }
-            ResolveErrorType::CallSuperOutsideSubclass => {
-                "Could not use `super` outside of a subclass".to_string()
+            ResolveErrorType::CallSuperOutsideClass => {
+                "Could not use `super` outside of a class".to_string()
+            }
+            ResolveErrorType::CallSuperWithoutSuperclass => {
+                "Could not use `super` in a class without a superclass".to_string()
             }
             ResolveErrorType::ReturnInsideInit => "Could not return inside constructor".to_string(),
             ResolveErrorType::ClassInheritItself => {
                 "A class could not inherit from itself".to_string()
             }
+            ResolveErrorType::DuplicateMethod(method) => {
+                format!("Method `{method}` is already defined in this class")
+            }
+            ResolveErrorType::UndeclaredGlobal(name) => {
+                format!("Global variable `{name}` is never declared")
+            }
+            ResolveErrorType::NumberArgumentsMismatch(paramc, argc) => {
+                format!("Expected {paramc} arguments. Found {argc} arguments")
+            }
         }
     }
 }
@@ -46,24 +79,37 @@ pub(crate) struct ResolveError {
     start_pos: CharPos,
     end_pos: CharPos,
     error_type: ResolveErrorType,
+    details: Details,
 }
 
-impl_error_pos!(ResolveError);
+impl_error_pos!(ResolveError, details);
 
 impl ResolveError {
     pub fn read_during_initializer(token: &Token) -> Self {
         Self {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
-            error_type: ResolveErrorType::ReadDuringInitializer(token.lexeme().to_string()),
+            details: Details::default(),
+            error_type: ResolveErrorType::ReadDuringInitializer(token.name().to_string()),
         }
     }
 
-    pub fn already_declared(token: &Token) -> Self {
+    // `previous` is where the name was declared first
+    pub fn already_declared(token: &Token, previous: Option<&Token>) -> Self {
+        let name = token.name();
+        let mut details =
+            Details::default().note(Note::Help(format!("assign to `{name}` instead")));
+        if let Some(previous) = previous {
+            details = details.label(Label::new(
+                previous,
+                format!("`{name}` is first declared here"),
+            ));
+        }
         Self {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
-            error_type: ResolveErrorType::VarAlreadyExistInScope(token.lexeme().to_string()),
+            details,
+            error_type: ResolveErrorType::VarAlreadyExistInScope(name.to_string()),
         }
     }
 
@@ -71,6 +117,7 @@ impl ResolveError {
         Self {
             start_pos: return_expr.keyword.start_pos(),
             end_pos: return_expr.semicolon.end_pos(),
+            details: Details::default(),
             error_type: ResolveErrorType::ReturnFromTopLevel,
         }
     }
@@ -79,6 +126,9 @@ impl ResolveError {
         Self {
             start_pos: return_expr.keyword.start_pos(),
             end_pos: return_expr.semicolon.end_pos(),
+            details: Details::default()
+                .note(Note::Note("`init` always returns `this`".to_string()))
+                .note(Note::Help("use `return;` to return early".to_string())),
             error_type: ResolveErrorType::ReturnInsideInit,
         }
     }
@@ -87,15 +137,86 @@ impl ResolveError {
         Self {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
+            details: Details::default(),
             error_type: ResolveErrorType::CallThisOutsideClass,
         }
     }
 
-    pub fn call_super_outside_subclass(token: &Token) -> Self {
+    pub fn call_super_outside_class(super_call: &Super) -> Self {
+        Self {
+            start_pos: super_call.keyword.start_pos(),
+            end_pos: super_call.method.end_pos(),
+            details: Details::default(),
+            error_type: ResolveErrorType::CallSuperOutsideClass,
+        }
+    }
+
+    pub fn call_super_without_superclass(super_call: &Super, class: &Token) -> Self {
+        Self {
+            start_pos: super_call.keyword.start_pos(),
+            end_pos: super_call.method.end_pos(),
+            details: Details::default().label(Label::new(
+                class,
+                format!("`{}` has no superclass", class.name()),
+            )),
+            error_type: ResolveErrorType::CallSuperWithoutSuperclass,
+        }
+    }
+
+    // `previous` is the first method with the same name
+    pub fn duplicate_method(class: &Token, method: &Token, previous: &Token) -> Self {
+        let name = method.name();
+        Self {
+            start_pos: method.start_pos(),
+            end_pos: method.end_pos(),
+            details: Details::default()
+                .label(Label::new(
+                    previous,
+                    format!("`{name}` is first defined here"),
+                ))
+                .note(Note::Note(format!(
+                    "class `{}` keeps only one method named `{name}`",
+                    class.name()
+                ))),
+            error_type: ResolveErrorType::DuplicateMethod(name.to_string()),
+        }
+    }
+
+    pub fn undeclared_global(token: &Token) -> Self {
+        let name = token.name();
         Self {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
-            error_type: ResolveErrorType::CallSuperOutsideSubclass,
+            details: Details::default()
+                .note(Note::Note(
+                    "`--strict` requires every global to be declared".to_string(),
+                ))
+                .note(Note::Help(format!("declare it with `var {name};`"))),
+            error_type: ResolveErrorType::UndeclaredGlobal(name.to_string()),
+        }
+    }
+
+    // `function` is the name in the declaration of the function called by `name`
+    pub fn arity_mismatch(
+        name: &Token,
+        function: &Token,
+        params_count: usize,
+        args_count: usize,
+    ) -> Self {
+        Self {
+            start_pos: name.start_pos(),
+            end_pos: name.end_pos(),
+            details: Details::default().label(Label::new(
+                function,
+                match params_count {
+                    1 => format!("`{}` is declared with 1 parameter", function.name()),
+                    _ => format!(
+                        "`{}` is declared with {params_count} parameters",
+                        function.name()
+                    ),
+                },
+            )),
+            error_type: ResolveErrorType::NumberArgumentsMismatch(params_count, args_count),
         }
     }
 
@@ -103,6 +224,7 @@ impl ResolveError {
         Self {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
+            details: Details::default(),
             error_type: ResolveErrorType::ClassInheritItself,
         }
     }
@@ -2,31 +2,70 @@ use std::fmt;
 
 use crate::{object::Object, source::CharPos, token::Token};
 
-use super::{object_error::ObjectError, reporter::impl_error_pos};
+use super::{
+    native_error::NativeError,
+    object_error::ObjectError,
+    reporter::{impl_error_pos, Details, Note},
+};
 
 #[derive(PartialEq)]
 pub(crate) enum RuntimeErrorType {
     ObjectError(ObjectError),
+    NativeError(NativeError),
     UndefinedVariable(String),
     WriteError(String),
     NumberArgumentsMismatch(usize, usize),
+    TooFewArguments(usize, usize),
     ObjectNotCallable(String),
-    ReturnValue(Object), // this is not error
+    Exit(i32), // this is not error
     OnlyClassInstanceHasField(String, String),
     SuperclassMustBeClass,
     UndefinedProperty(String),
+    NotIterable(String),
+    StackOverflow(usize),
+    BudgetExceeded,
+    MemoryLimitExceeded(usize),
+    Interrupted,
 }
 
 impl RuntimeErrorType {
+    // stable name of the error, for tools reading `--diagnostics json`
+    fn code(&self) -> &'static str {
+        match self {
+            RuntimeErrorType::ObjectError(_) => "invalid_operand",
+            RuntimeErrorType::NativeError(_) => "native_function",
+            RuntimeErrorType::UndefinedVariable(_) => "undefined_variable",
+            RuntimeErrorType::WriteError(_) => "write_failed",
+            RuntimeErrorType::NumberArgumentsMismatch(..) => "wrong_number_of_arguments",
+            RuntimeErrorType::TooFewArguments(..) => "too_few_arguments",
+            RuntimeErrorType::ObjectNotCallable(_) => "not_callable",
+            RuntimeErrorType::Exit(_) => "exit",
+            RuntimeErrorType::OnlyClassInstanceHasField(..) => "field_on_non_instance",
+            RuntimeErrorType::SuperclassMustBeClass => "superclass_not_a_class",
+            RuntimeErrorType::UndefinedProperty(_) => "undefined_property",
+            RuntimeErrorType::NotIterable(_) => "not_iterable",
+            RuntimeErrorType::StackOverflow(_) => "stack_overflow",
+            RuntimeErrorType::BudgetExceeded => "budget_exceeded",
+            RuntimeErrorType::MemoryLimitExceeded(_) => "memory_limit_exceeded",
+            RuntimeErrorType::Interrupted => "interrupted",
+        }
+    }
+
     fn msg(&self) -> String {
         match self {
             RuntimeErrorType::ObjectError(e) => e.to_string(),
+            RuntimeErrorType::NativeError(e) => e.to_string(),
             RuntimeErrorType::UndefinedVariable(name) => format!("Undefined variable `{name}`"),
             RuntimeErrorType::WriteError(err) => err.to_string(),
             RuntimeErrorType::NumberArgumentsMismatch(paramc, argc) => {
                 format!("Expected {paramc} arguments. Found {argc} arguments")
             }
-            RuntimeErrorType::ReturnValue(_) => unreachable!("this should not be called as error"),
+            RuntimeErrorType::TooFewArguments(paramc, argc) => {
+                format!("Expected at least {paramc} arguments. Found {argc} arguments")
+            }
+            RuntimeErrorType::Exit(_) => {
+                unreachable!("this should not be called as error")
+            }
             RuntimeErrorType::ObjectNotCallable(name) => format!("`{name}` is not a function"),
             RuntimeErrorType::OnlyClassInstanceHasField(object, field) => {
                 format!("`{object}` is not class instance. It cannot have field `{field}`")
@@ -35,6 +74,17 @@ impl RuntimeErrorType {
             RuntimeErrorType::UndefinedProperty(property) => {
                 format!("Undefined property `{property}`")
             }
+            RuntimeErrorType::NotIterable(type_name) => {
+                format!("Only lists and maps can be looped over. Found {type_name}")
+            }
+            RuntimeErrorType::StackOverflow(depth) => {
+                format!("Stack overflow: maximum call depth {depth} exceeded")
+            }
+            RuntimeErrorType::BudgetExceeded => "Script exceeded execution budget".to_string(),
+            RuntimeErrorType::MemoryLimitExceeded(limit) => {
+                format!("Memory limit of {limit} bytes exceeded")
+            }
+            RuntimeErrorType::Interrupted => "Interrupted".to_string(),
         }
     }
 }
@@ -44,16 +94,19 @@ pub(crate) struct RuntimeError {
     start_pos: CharPos,
     end_pos: CharPos,
     error_type: RuntimeErrorType,
+    // boxed, runtime errors are returned through every call in the interpreter
+    details: Box<Details>,
 }
 
-impl_error_pos!(RuntimeError);
+impl_error_pos!(RuntimeError, details);
 
 impl RuntimeError {
     pub fn undefined_variable(token: &Token) -> Self {
         Self {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
-            error_type: RuntimeErrorType::UndefinedVariable(token.lexeme().to_string()),
+            error_type: RuntimeErrorType::UndefinedVariable(token.name().to_string()),
+            details: Box::default(),
         }
     }
 
@@ -66,6 +119,16 @@ impl RuntimeError {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
             error_type: RuntimeErrorType::NumberArgumentsMismatch(params_count, args_count),
+            details: Box::default(),
+        }
+    }
+
+    pub fn too_few_arguments(token: &Token, params_count: usize, args_count: usize) -> Self {
+        Self {
+            start_pos: token.start_pos(),
+            end_pos: token.end_pos(),
+            error_type: RuntimeErrorType::TooFewArguments(params_count, args_count),
+            details: Box::default(),
         }
     }
 
@@ -74,6 +137,7 @@ impl RuntimeError {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
             error_type: RuntimeErrorType::ObjectNotCallable(object.to_string()),
+            details: Box::default(),
         }
     }
 
@@ -83,8 +147,9 @@ impl RuntimeError {
             end_pos: field.end_pos(),
             error_type: RuntimeErrorType::OnlyClassInstanceHasField(
                 object.to_string(),
-                field.lexeme().to_string(),
+                field.name().to_string(),
             ),
+            details: Box::default(),
         }
     }
 
@@ -92,7 +157,18 @@ impl RuntimeError {
         Self {
             start_pos: property.start_pos(),
             end_pos: property.end_pos(),
-            error_type: RuntimeErrorType::UndefinedProperty(property.lexeme().to_string()),
+            error_type: RuntimeErrorType::UndefinedProperty(property.name().to_string()),
+            details: Box::default(),
+        }
+    }
+
+    // `keyword` is the `for` of the loop
+    pub fn not_iterable(keyword: &Token, object: &Object) -> Self {
+        Self {
+            start_pos: keyword.start_pos(),
+            end_pos: keyword.end_pos(),
+            error_type: RuntimeErrorType::NotIterable(object.type_name().to_string()),
+            details: Box::default(),
         }
     }
 
@@ -101,20 +177,70 @@ impl RuntimeError {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
             error_type: RuntimeErrorType::SuperclassMustBeClass,
+            details: Box::default(),
+        }
+    }
+
+    pub fn stack_overflow(token: &Token, max_depth: usize) -> Self {
+        Self {
+            start_pos: token.start_pos(),
+            end_pos: token.end_pos(),
+            error_type: RuntimeErrorType::StackOverflow(max_depth),
+            details: Box::default(),
         }
     }
-    pub fn return_value(token: &Token, value: Object) -> Self {
+
+    pub fn budget_exceeded(token: &Token) -> Self {
         Self {
             start_pos: token.start_pos(),
             end_pos: token.end_pos(),
-            error_type: RuntimeErrorType::ReturnValue(value),
+            error_type: RuntimeErrorType::BudgetExceeded,
+            details: Box::default(),
         }
     }
 
-    pub fn get_value_from_return(self) -> Object {
+    pub fn memory_limit_exceeded(token: &Token, limit: usize) -> Self {
+        Self {
+            start_pos: token.start_pos(),
+            end_pos: token.end_pos(),
+            error_type: RuntimeErrorType::MemoryLimitExceeded(limit),
+            details: Box::default(),
+        }
+    }
+
+    // the rest of the script is not run after these errors
+    pub fn interrupted(token: &Token) -> Self {
+        Self {
+            start_pos: token.start_pos(),
+            end_pos: token.end_pos(),
+            error_type: RuntimeErrorType::Interrupted,
+            details: Box::default(),
+        }
+    }
+
+    pub fn aborts(&self) -> bool {
+        matches!(
+            self.error_type,
+            RuntimeErrorType::BudgetExceeded
+                | RuntimeErrorType::MemoryLimitExceeded(_)
+                | RuntimeErrorType::Interrupted
+        )
+    }
+
+    // only the innermost function is named, the callers unwinding through it keep its note
+    pub fn in_function(mut self, function: String) -> Self {
+        if self.details.notes.is_empty() && self.exit_code().is_none() {
+            self.details
+                .notes
+                .push(Note::Note(format!("in {function}")));
+        }
+        self
+    }
+
+    pub fn exit_code(&self) -> Option<i32> {
         match self.error_type {
-            RuntimeErrorType::ReturnValue(object) => object,
-            _ => Object::Null,
+            RuntimeErrorType::Exit(code) => Some(code),
+            _ => None,
         }
     }
 }
@@ -125,6 +251,39 @@ impl From<(&Token, ObjectError)> for RuntimeError {
             start_pos: value.0.start_pos(),
             end_pos: value.0.end_pos(),
             error_type: RuntimeErrorType::ObjectError(value.1),
+            details: Box::default(),
+        }
+    }
+}
+
+impl From<(&Token, NativeError)> for RuntimeError {
+    fn from(value: (&Token, NativeError)) -> Self {
+        match value.1.into_callback_error() {
+            Ok(err) => err,
+            Err(err) => Self {
+                start_pos: value.0.start_pos(),
+                end_pos: value.0.end_pos(),
+                ..RuntimeError::from(err)
+            },
+        }
+    }
+}
+
+impl From<NativeError> for RuntimeError {
+    fn from(value: NativeError) -> Self {
+        let value = match value.into_callback_error() {
+            Ok(err) => return err,
+            Err(value) => value,
+        };
+        let error_type = match value.exit_code() {
+            Some(code) => RuntimeErrorType::Exit(code),
+            None => RuntimeErrorType::NativeError(value),
+        };
+        Self {
+            start_pos: CharPos::default(),
+            end_pos: CharPos::default(),
+            error_type,
+            details: Box::default(),
         }
     }
 }
@@ -135,6 +294,7 @@ impl From<std::io::Error> for RuntimeError {
             start_pos: CharPos::default(),
             end_pos: CharPos::default(),
             error_type: RuntimeErrorType::WriteError(value.to_string()),
+            details: Box::default(),
         }
     }
 }
@@ -7,16 +7,26 @@ use super::reporter::impl_error_pos;
 #[derive(PartialEq)]
 enum SyntaxErrorType {
     UnterminatedString,
-    UnexpectedCharacter(char),
+    // adjacent characters that start no token are one error
+    UnexpectedCharacters(String),
 }
 
 impl SyntaxErrorType {
+    // stable name of the error, for tools reading `--diagnostics json`
+    fn code(&self) -> &'static str {
+        match self {
+            SyntaxErrorType::UnterminatedString => "unterminated_string",
+            SyntaxErrorType::UnexpectedCharacters(_) => "unexpected_character",
+        }
+    }
+
     fn msg(&self) -> String {
         match self {
             SyntaxErrorType::UnterminatedString => "Unterminated string".to_string(),
-            SyntaxErrorType::UnexpectedCharacter(c) => {
-                format!("Unexpected character `{c}`")
-            }
+            SyntaxErrorType::UnexpectedCharacters(characters) => match characters.chars().count() {
+                1 => format!("Unexpected character `{characters}`"),
+                _ => format!("Unexpected characters `{characters}`"),
+            },
         }
     }
 }
@@ -39,11 +49,15 @@ impl SyntaxError {
         }
     }
 
-    pub fn unexpected_character(pos: CharPos) -> Self {
+    pub fn is_unterminated_string(&self) -> bool {
+        self.error_type == SyntaxErrorType::UnterminatedString
+    }
+
+    pub fn unexpected_characters(characters: String, start_pos: CharPos, end_pos: CharPos) -> Self {
         Self {
-            start_pos: pos,
-            end_pos: pos,
-            error_type: SyntaxErrorType::UnexpectedCharacter(pos.ch),
+            start_pos,
+            end_pos,
+            error_type: SyntaxErrorType::UnexpectedCharacters(characters),
         }
     }
 }
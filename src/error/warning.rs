@@ -0,0 +1,188 @@
+use std::fmt;
+
+use crate::{
+    source::CharPos,
+    stmt::{If, Return},
+    token::Token,
+};
+
+use super::reporter::{Details, ErrorPos, Label, Note};
+
+#[derive(PartialEq)]
+pub(crate) enum WarningType {
+    UnreachableAfterReturn,
+    UnreachableAfterIf,
+    IncrementAfterReturn,
+    AssignmentInCondition(String),
+    UnusedVariable(String),
+    UnusedParameter(String),
+    UnusedFunction(String),
+    UnusedClass(String),
+}
+
+impl WarningType {
+    // stable name of the warning, for tools reading `--diagnostics json`
+    fn code(&self) -> &'static str {
+        match self {
+            WarningType::UnreachableAfterReturn => "unreachable_after_return",
+            WarningType::UnreachableAfterIf => "unreachable_after_if",
+            WarningType::IncrementAfterReturn => "increment_after_return",
+            WarningType::AssignmentInCondition(_) => "assignment_in_condition",
+            WarningType::UnusedVariable(_) => "unused_variable",
+            WarningType::UnusedParameter(_) => "unused_parameter",
+            WarningType::UnusedFunction(_) => "unused_function",
+            WarningType::UnusedClass(_) => "unused_class",
+        }
+    }
+
+    fn msg(&self) -> String {
+        match self {
+            WarningType::UnreachableAfterReturn => {
+                "Statements after `return` never run".to_string()
+            }
+            WarningType::UnreachableAfterIf => {
+                "Statements after this `if` never run, all of its branches return".to_string()
+            }
+            WarningType::IncrementAfterReturn => {
+                "The loop increment never runs after `return`".to_string()
+            }
+            WarningType::AssignmentInCondition(name) => {
+                format!("Condition assigns a constant to `{name}` and is always the same")
+            }
+            WarningType::UnusedVariable(name) => format!("Variable `{name}` is never used"),
+            WarningType::UnusedParameter(name) => format!("Parameter `{name}` is never used"),
+            WarningType::UnusedFunction(name) => format!("Function `{name}` is never used"),
+            WarningType::UnusedClass(name) => format!("Class `{name}` is never used"),
+        }
+    }
+}
+
+// something that is likely a mistake, the script still runs unless warnings are denied
+#[derive(PartialEq)]
+pub(crate) struct Warning {
+    start_pos: CharPos,
+    end_pos: CharPos,
+    warning_type: WarningType,
+    details: Details,
+}
+
+impl ErrorPos for Warning {
+    fn start_pos(&self) -> CharPos {
+        self.start_pos
+    }
+
+    fn end_pos(&self) -> CharPos {
+        self.end_pos
+    }
+
+    fn message(&self) -> String {
+        self.warning_type.msg()
+    }
+
+    fn kind(&self) -> &'static str {
+        "Warning"
+    }
+
+    fn code(&self) -> &'static str {
+        self.warning_type.code()
+    }
+
+    fn is_warning(&self) -> bool {
+        true
+    }
+
+    fn details(&self) -> Option<&Details> {
+        Some(&self.details)
+    }
+}
+
+impl Warning {
+    pub fn unreachable_after_return(return_stmt: &Return) -> Self {
+        Self {
+            start_pos: return_stmt.keyword.start_pos(),
+            end_pos: return_stmt.semicolon.end_pos(),
+            warning_type: WarningType::UnreachableAfterReturn,
+            details: Details::default(),
+        }
+    }
+
+    pub fn unreachable_after_if(if_statement: &If) -> Self {
+        Self {
+            start_pos: if_statement.keyword.start_pos(),
+            end_pos: if_statement.keyword.end_pos(),
+            warning_type: WarningType::UnreachableAfterIf,
+            details: Details::default(),
+        }
+    }
+
+    // the body of a `for` loop always returns
+    pub fn increment_after_return(return_stmt: &Return, for_keyword: &Token) -> Self {
+        Self {
+            start_pos: return_stmt.keyword.start_pos(),
+            end_pos: return_stmt.semicolon.end_pos(),
+            warning_type: WarningType::IncrementAfterReturn,
+            details: Details::default()
+                .label(Label::new(for_keyword, "in this `for` loop".to_string())),
+        }
+    }
+
+    // `name` is the variable assigned in the condition
+    pub fn assignment_in_condition(name: &Token) -> Self {
+        Self {
+            start_pos: name.start_pos(),
+            end_pos: name.end_pos(),
+            warning_type: WarningType::AssignmentInCondition(name.name().to_string()),
+            details: Details::default().note(Note::Help(format!(
+                "use `==` to compare `{}` instead",
+                name.name()
+            ))),
+        }
+    }
+
+    pub fn unused_variable(name: &Token) -> Self {
+        Self::unused(name, WarningType::UnusedVariable(name.name().to_string()))
+    }
+
+    pub fn unused_parameter(name: &Token) -> Self {
+        Self::unused(name, WarningType::UnusedParameter(name.name().to_string()))
+    }
+
+    pub fn unused_function(name: &Token) -> Self {
+        Self::unused(name, WarningType::UnusedFunction(name.name().to_string()))
+    }
+
+    pub fn unused_class(name: &Token) -> Self {
+        Self::unused(name, WarningType::UnusedClass(name.name().to_string()))
+    }
+
+    fn unused(name: &Token, warning_type: WarningType) -> Self {
+        Self {
+            start_pos: name.start_pos(),
+            end_pos: name.end_pos(),
+            warning_type,
+            details: Details::default().note(Note::Help(format!(
+                "name it `_{}` if this is intended",
+                name.name()
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}]: Warning: {}",
+            self.start_pos.line + 1,
+            self.warning_type.msg()
+        )
+    }
+}
+
+impl fmt::Debug for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", &self)
+    }
+}
+
+impl std::error::Error for Warning {}
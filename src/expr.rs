@@ -1,9 +1,14 @@
-use crate::{object::Object, token::Token, visitor::Visitor};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+use serde::{Deserialize, Serialize};
+
+use crate::{object::Object, stack, token::Token, visitor::Visitor};
+
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) enum Expr {
     Binary(Binary),
     Unary(Unary),
+    #[serde(with = "literal")]
     Literal(Object),
     Grouping(Grouping),
     Variable(Variable),
@@ -18,19 +23,85 @@ pub(crate) enum Expr {
 
 impl Eq for Expr {}
 
+// identify expressions resolved to a scope, clones share the same id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ExprId(usize);
+
+impl ExprId {
+    // unique across parsers, so ids from different prompt lines never collide
+    fn next() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        ExprId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// only nil, numbers, strings and booleans are written in scripts
+mod literal {
+    use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::object::{Number, Object};
+
+    #[derive(Serialize, Deserialize)]
+    enum Literal {
+        Nil,
+        Number(Number),
+        String(String),
+        Bool(bool),
+    }
+
+    pub fn serialize<S: Serializer>(object: &Object, serializer: S) -> Result<S::Ok, S::Error> {
+        let literal = match object {
+            Object::Null => Literal::Nil,
+            Object::Number(number) => Literal::Number(*number),
+            Object::String(string) => Literal::String(string.to_string()),
+            Object::Bool(b) => Literal::Bool(*b),
+            object => {
+                return Err(ser::Error::custom(format!(
+                    "a {} is not a literal",
+                    object.type_name()
+                )))
+            }
+        };
+        literal.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Object, D::Error> {
+        let object = match Literal::deserialize(deserializer)? {
+            Literal::Nil => Object::Null,
+            Literal::Number(number) => Object::Number(number),
+            Literal::String(string) => Object::string(string),
+            Literal::Bool(b) => Object::Bool(b),
+        };
+        Ok(object)
+    }
+}
+
 impl Expr {
     pub fn walk_epxr<E, S>(&self, visitor: &mut impl Visitor<E, S>) -> E {
         visitor.visit_expr(self)
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+// chains like `a + b + c` or `a.b.c` nest as deep as they are long, so the left of a link
+// is dropped on a stack grown when it runs low, like the passes walking the chain
+fn drop_chained(expr: &mut Expr) {
+    let expr = std::mem::replace(expr, Expr::Literal(Object::Null));
+    stack::grow(|| drop(expr));
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Binary {
     pub left: Box<Expr>,
     pub operator: Token,
     pub right: Box<Expr>,
 }
 
+impl Drop for Binary {
+    fn drop(&mut self) {
+        drop_chained(&mut self.left);
+    }
+}
+
 impl Binary {
     pub fn new(left: Expr, operator: Token, right: Expr) -> Self {
         Binary {
@@ -41,7 +112,7 @@ impl Binary {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Unary {
     pub operator: Token,
     pub right: Box<Expr>,
@@ -56,7 +127,7 @@ impl Unary {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Grouping {
     pub expr: Box<Expr>,
 }
@@ -69,19 +140,26 @@ impl Grouping {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Variable {
+    #[serde(skip, default = "ExprId::next")]
+    pub id: ExprId,
     pub name: Token,
 }
 
 impl Variable {
     pub fn new(name: Token) -> Self {
-        Variable { name }
+        Variable {
+            id: ExprId::next(),
+            name,
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Assign {
+    #[serde(skip, default = "ExprId::next")]
+    pub id: ExprId,
     pub name: Token,
     pub value: Box<Expr>,
 }
@@ -89,19 +167,26 @@ pub(crate) struct Assign {
 impl Assign {
     pub fn new(name: Token, value: Expr) -> Self {
         Assign {
+            id: ExprId::next(),
             name,
             value: Box::new(value),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Call {
     pub callee: Box<Expr>,
     pub paren: Token,
     pub arguments: Vec<Expr>,
 }
 
+impl Drop for Call {
+    fn drop(&mut self) {
+        drop_chained(&mut self.callee);
+    }
+}
+
 impl Call {
     pub fn new(callee: Expr, paren: Token, arguments: Vec<Expr>) -> Self {
         Self {
@@ -112,12 +197,18 @@ impl Call {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Get {
     pub object: Box<Expr>,
     pub name: Token,
 }
 
+impl Drop for Get {
+    fn drop(&mut self) {
+        drop_chained(&mut self.object);
+    }
+}
+
 impl Get {
     pub fn new(object: Expr, name: Token) -> Self {
         Self {
@@ -125,9 +216,15 @@ impl Get {
             name,
         }
     }
+
+    // `a.b = value`, where `a.b` was parsed as a get
+    pub fn into_set(mut self, value: Expr) -> Set {
+        let object = std::mem::replace(&mut *self.object, Expr::Literal(Object::Null));
+        Set::new(object, self.name.clone(), value)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Set {
     pub object: Box<Expr>,
     pub name: Token,
@@ -144,25 +241,36 @@ impl Set {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct This {
+    #[serde(skip, default = "ExprId::next")]
+    pub id: ExprId,
     pub keyword: Token,
 }
 
 impl This {
     pub fn new(keyword: Token) -> Self {
-        Self { keyword }
+        Self {
+            id: ExprId::next(),
+            keyword,
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Super {
+    #[serde(skip, default = "ExprId::next")]
+    pub id: ExprId,
     pub keyword: Token,
     pub method: Token,
 }
 
 impl Super {
     pub fn new(keyword: Token, method: Token) -> Self {
-        Self { keyword, method }
+        Self {
+            id: ExprId::next(),
+            keyword,
+            method,
+        }
     }
 }
@@ -0,0 +1,466 @@
+use std::collections::VecDeque;
+
+use crate::{
+    coverage,
+    expr::Expr,
+    object::Object,
+    stack,
+    stmt::{Block, Class, Function, Stmt, While},
+    token::Comment,
+    visitor::Visitor,
+};
+
+pub(crate) const DEFAULT_INDENT: usize = 2;
+
+// writes statements back as lox source, one statement per line, blocks indented by
+// `indent` spaces and a blank line around top level functions and classes and
+// between methods. comments on their own line are written above the statement after
+// them, trailing comments at the end of the line the code before them ends up on
+struct Formatter {
+    indent: usize,
+    depth: usize,
+    out: String,
+    // write `for` loops as they were written instead of what the parser rewrote them into
+    resugar: bool,
+    // not written yet, in source order
+    comments: VecDeque<Comment>,
+}
+
+// line of the first token of `s`
+fn first_line(s: &Stmt) -> Option<usize> {
+    match s {
+        Stmt::Block(block) => block
+            .for_keyword
+            .as_ref()
+            .or(block.braces.as_ref().map(|(left_brace, _)| left_brace))
+            .map(|token| token.start_pos().line + 1),
+        s => coverage::stmt_line(s),
+    }
+}
+
+impl Formatter {
+    fn new_line(&mut self) {
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(self.indent * self.depth));
+    }
+
+    // writes the comments on lines before `line`. `blank_line` is written before the
+    // first comment on its own line, and cleared once written
+    fn comments_before(&mut self, line: usize, blank_line: &mut bool) {
+        while let Some(comment) = self.comments.pop_front_if(|comment| comment.line < line) {
+            if comment.trailing {
+                self.out.push(' ');
+            } else {
+                if std::mem::take(blank_line) {
+                    self.out.push('\n');
+                }
+                self.new_line();
+            }
+            self.out.push_str(&comment.text);
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        let declaration = |s: &Stmt| matches!(s, Stmt::Function(_) | Stmt::Class(_));
+        for (i, s) in statements.iter().enumerate() {
+            let mut blank_line =
+                i > 0 && self.depth == 0 && (declaration(s) || declaration(&statements[i - 1]));
+            if let Some(line) = first_line(s) {
+                self.comments_before(line, &mut blank_line);
+            }
+            if blank_line {
+                self.out.push('\n');
+            }
+            self.new_line();
+            self.visit_stmt(s);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.out.push('{');
+        let start = self.out.len();
+        self.depth += 1;
+        self.statements(&block.statements);
+        // comments after the last statement stay inside the block
+        if let Some((_, right_brace)) = &block.braces {
+            self.comments_before(right_brace.start_pos().line + 1, &mut false);
+        }
+        self.depth -= 1;
+        if self.out.len() > start {
+            self.new_line();
+        }
+        self.out.push('}');
+    }
+
+    // the body of `if`, `while` and `for`, blocks start on the same line
+    fn body(&mut self, s: &Stmt) {
+        self.out.push(' ');
+        self.visit_stmt(s);
+    }
+
+    fn function(&mut self, function: &Function) {
+        let params = function
+            .params
+            .iter()
+            .map(|param| param.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.out
+            .push_str(&format!("{}({params}) ", function.name.name()));
+        self.visit_stmt(&function.body);
+    }
+
+    fn class(&mut self, class: &Class) {
+        self.out.push_str(&format!("class {}", class.name.name()));
+        if let Some(superclass) = &class.superclass {
+            self.out.push_str(&format!(" : {}", superclass.name.name()));
+        }
+        self.out.push_str(" {");
+        if !class.methods.is_empty() {
+            self.depth += 1;
+            for (i, method) in class.methods.iter().enumerate() {
+                let mut blank_line = i > 0;
+                self.comments_before(method.name.start_pos().line + 1, &mut blank_line);
+                if blank_line {
+                    self.out.push('\n');
+                }
+                self.new_line();
+                self.function(method);
+            }
+            self.depth -= 1;
+            self.new_line();
+        }
+        self.out.push('}');
+    }
+
+    // `initializer` is the statement the parser put before the loop
+    fn for_loop(&mut self, initializer: Option<&Stmt>, while_loop: &While) {
+        let clauses = while_loop.clauses.expect("a for loop");
+        self.out.push_str("for (");
+        match initializer {
+            Some(initializer) => self.visit_stmt(initializer),
+            None => self.out.push(';'),
+        }
+        if clauses.condition {
+            let condition = self.visit_expr(&while_loop.condition);
+            self.out.push_str(&format!(" {condition}"));
+        }
+        self.out.push(';');
+        let body = match (clauses.increment, &*while_loop.body) {
+            (true, Stmt::Block(Block { statements, .. })) => {
+                if let Stmt::Expression(increment) = &statements[1] {
+                    let increment = self.visit_expr(increment);
+                    self.out.push_str(&format!(" {increment}"));
+                }
+                &statements[0]
+            }
+            (_, body) => body,
+        };
+        self.out.push(')');
+        self.body(body);
+    }
+}
+
+impl Visitor<String, ()> for Formatter {
+    fn visit_expr(&mut self, e: &Expr) -> String {
+        match e {
+            Expr::Binary(binary) | Expr::Logical(binary) => format!(
+                "{} {} {}",
+                stack::grow(|| self.visit_expr(&binary.left)),
+                binary.operator.lexeme(),
+                self.visit_expr(&binary.right)
+            ),
+            Expr::Unary(unary) => {
+                format!(
+                    "{}{}",
+                    unary.operator.lexeme(),
+                    self.visit_expr(&unary.right)
+                )
+            }
+            // strings have no escapes, what is between the quotes is written as it is
+            Expr::Literal(Object::String(s)) => format!("\"{s}\""),
+            Expr::Literal(object) => object.repr(),
+            Expr::Grouping(group) => format!("({})", self.visit_expr(&group.expr)),
+            Expr::Variable(var) => var.name.name().to_string(),
+            Expr::Assign(assign) => {
+                format!(
+                    "{} = {}",
+                    assign.name.name(),
+                    self.visit_expr(&assign.value)
+                )
+            }
+            Expr::Call(call) => {
+                let arguments = call
+                    .arguments
+                    .iter()
+                    .map(|arg| self.visit_expr(arg))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "{}({arguments})",
+                    stack::grow(|| self.visit_expr(&call.callee))
+                )
+            }
+            Expr::Get(get) => format!(
+                "{}.{}",
+                stack::grow(|| self.visit_expr(&get.object)),
+                get.name.name()
+            ),
+            Expr::Set(set) => format!(
+                "{}.{} = {}",
+                stack::grow(|| self.visit_expr(&set.object)),
+                set.name.name(),
+                self.visit_expr(&set.value)
+            ),
+            Expr::This(_) => "this".to_string(),
+            Expr::Super(super_call) => format!("super.{}", super_call.method.name()),
+        }
+    }
+
+    fn visit_stmt(&mut self, s: &Stmt) {
+        match s {
+            Stmt::Expression(e) => {
+                let expr = self.visit_expr(e);
+                self.out.push_str(&format!("{expr};"));
+            }
+            Stmt::Print(print) => {
+                let value = self.visit_expr(&print.expression);
+                self.out.push_str(&format!("print {value};"));
+            }
+            // `var a;` and `var a = nil;` are the same declaration
+            Stmt::Var(var) => {
+                let name = var.identifier.name();
+                match &var.expression {
+                    Expr::Literal(Object::Null) => self.out.push_str(&format!("var {name};")),
+                    e => {
+                        let value = self.visit_expr(e);
+                        self.out.push_str(&format!("var {name} = {value};"));
+                    }
+                }
+            }
+            Stmt::Return(r) => match &r.value {
+                Expr::Literal(Object::Null) => self.out.push_str("return;"),
+                e => {
+                    let value = self.visit_expr(e);
+                    self.out.push_str(&format!("return {value};"));
+                }
+            },
+            Stmt::Block(block) => match block.statements.as_slice() {
+                // a `for` loop with an initializer
+                [initializer, Stmt::While(while_loop)]
+                    if self.resugar
+                        && while_loop
+                            .clauses
+                            .is_some_and(|clauses| clauses.initializer) =>
+                {
+                    self.for_loop(Some(initializer), while_loop)
+                }
+                _ => self.block(block),
+            },
+            Stmt::If(if_statement) => {
+                let condition = self.visit_expr(&if_statement.condition);
+                self.out.push_str(&format!("if ({condition})"));
+                self.body(&if_statement.then_branch);
+                if let Some(else_branch) = &if_statement.else_branch {
+                    match &*if_statement.then_branch {
+                        Stmt::Block(_) => self.out.push(' '),
+                        _ => self.new_line(),
+                    }
+                    self.out.push_str("else");
+                    self.body(else_branch);
+                }
+            }
+            Stmt::While(while_loop) if self.resugar && while_loop.clauses.is_some() => {
+                self.for_loop(None, while_loop)
+            }
+            Stmt::While(while_loop) => {
+                let condition = self.visit_expr(&while_loop.condition);
+                self.out.push_str(&format!("while ({condition})"));
+                self.body(&while_loop.body);
+            }
+            Stmt::ForIn(for_in) => {
+                let iterable = self.visit_expr(&for_in.iterable);
+                let name = for_in.name.name();
+                self.out
+                    .push_str(&format!("for (var {name} in {iterable})"));
+                self.body(&for_in.body);
+            }
+            Stmt::Function(function) => {
+                self.out.push_str("fun ");
+                self.function(function);
+            }
+            Stmt::Class(class) => self.class(class),
+        }
+    }
+}
+
+// canonical source of `statements` with the `comments` of their source, ending with a
+// newline unless there is nothing
+pub(crate) fn format(statements: &[Stmt], comments: Vec<Comment>, indent: usize) -> String {
+    render(statements, comments, indent, true)
+}
+
+// source of what the interpreter runs, `for` loops are written as the block and
+// `while` loop they are rewritten into
+pub(crate) fn desugar(statements: &[Stmt], indent: usize) -> String {
+    render(statements, Vec::new(), indent, false)
+}
+
+fn render(statements: &[Stmt], comments: Vec<Comment>, indent: usize, resugar: bool) -> String {
+    let mut formatter = Formatter {
+        indent,
+        depth: 0,
+        out: String::new(),
+        resugar,
+        comments: comments.into(),
+    };
+    formatter.statements(statements);
+    formatter.comments_before(usize::MAX, &mut false);
+    // every statement starts on a new line, including the first
+    match formatter.out.strip_prefix('\n') {
+        Some(source) => format!("{source}\n"),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::reporter::ErrorReporter,
+        parser::Parser,
+        scanner::{self, Scanner},
+    };
+
+    use super::*;
+
+    fn reformat(source: &str) -> String {
+        let mut scanner = Scanner::new(source);
+        let mut parser = Parser::new(&mut scanner);
+        let statements = parser.parse();
+        assert!(!parser.had_error());
+        format(&statements, scanner::comments(source), 2)
+    }
+
+    #[test]
+    fn format_statements() {
+        let source = "var a;var b=nil ;print -(a+b)*2;
+fun f(x,y){if(x)return;else if (y) {return x.z=y;}else print \"a  b\";}
+class B:A{init(){super.init();this.n=0;}m(){}}
+while(!f(1,2))for(var i=0;i<3;i=i+1)print i;
+for(;;){}for(a=0;;){a;}for(;;a=a or b){a;}for(var k in keys(m)){print k;}{}";
+        let expected = "var a;
+var b;
+print -(a + b) * 2;
+
+fun f(x, y) {
+  if (x) return;
+  else if (y) {
+    return x.z = y;
+  } else print \"a  b\";
+}
+
+class B : A {
+  init() {
+    super.init();
+    this.n = 0;
+  }
+
+  m() {}
+}
+
+while (!f(1, 2)) for (var i = 0; i < 3; i = i + 1) print i;
+for (;;) {}
+for (a = 0;;) {
+  a;
+}
+for (;; a = a or b) {
+  a;
+}
+for (var k in keys(m)) {
+  print k;
+}
+{}
+";
+        assert_eq!(reformat(source), expected);
+        assert_eq!(reformat(expected), expected);
+        assert_eq!(reformat(""), "");
+    }
+
+    #[test]
+    fn keep_comments() {
+        let source = "// header
+
+var a = 1;   // one
+// about f
+fun f(x) { // opens
+  // inside
+  return x +   // split
+    1;
+  // last
+}
+class A {
+  // about m
+  m() {}
+  n() {
+    // todo
+  }
+}
+for (var i = 0; i < 1; i = i + 1) // loop
+  print i;
+print a; // before a block
+{ print a; }
+// end
+";
+        let expected = "// header
+var a = 1; // one
+
+// about f
+fun f(x) { // opens
+  // inside
+  return x + 1; // split
+  // last
+}
+
+class A {
+  // about m
+  m() {}
+
+  n() {
+    // todo
+  }
+}
+
+for (var i = 0; i < 1; i = i + 1) print i; // loop
+print a; // before a block
+{
+  print a;
+}
+// end
+";
+        assert_eq!(reformat(source), expected);
+        assert_eq!(reformat(expected), expected);
+    }
+
+    #[test]
+    fn desugar_for_loops() {
+        let mut scanner = Scanner::new("for (var i = 0; i < 2; i = i + 1) print i; for (;;) {}");
+        let statements = Parser::new(&mut scanner).parse();
+        let expected = "{
+  var i = 0;
+  while (i < 2) {
+    print i;
+    i = i + 1;
+  }
+}
+while (true) {}
+";
+        assert_eq!(desugar(&statements, 2), expected);
+    }
+
+    #[test]
+    fn indent_width() {
+        let mut scanner = Scanner::new("{print 1;}");
+        let statements = Parser::new(&mut scanner).parse();
+        assert_eq!(format(&statements, Vec::new(), 4), "{\n    print 1;\n}\n");
+    }
+}
@@ -1,27 +1,26 @@
-use std::{
-    hash::{Hash, Hasher},
-    time::SystemTime,
-};
+use std::hash::{Hash, Hasher};
 
 use crate::{
     callable::Callable,
     class::LoxInstance,
-    environment::EnvironmentTree,
+    environment::{EnvironmentTree, Slot},
     error::runtime_error::RuntimeError,
-    interpreter::Interpreter,
-    object::{Number, Object},
+    interpreter::{ControlFlow, Interpreter},
+    object::Object,
+    shared::Shared,
     stmt::Function,
+    symbol::Symbol,
 };
 
 #[derive(Debug, Clone)]
 pub(crate) struct LoxFunction {
-    declaration: Function,
+    declaration: Shared<Function>,
     closure: EnvironmentTree,
     initializer: bool,
 }
 
 impl LoxFunction {
-    pub fn new(declaration: Function, closure: EnvironmentTree, initializer: bool) -> Self {
+    pub fn new(declaration: Shared<Function>, closure: EnvironmentTree, initializer: bool) -> Self {
         Self {
             declaration,
             closure,
@@ -29,18 +28,41 @@ impl LoxFunction {
         }
     }
 
+    pub fn closure(&self) -> &EnvironmentTree {
+        &self.closure
+    }
+
     pub fn bind(&self, instance: LoxInstance) -> Self {
         let env = {
-            let mut env = self.closure.clone();
-            env.define("this", Object::LoxInstance(instance));
+            let mut env = self.closure.append();
+            env.define(Symbol::THIS, Object::instance(instance));
             env
         };
         Self {
-            declaration: self.declaration.clone(),
+            declaration: Shared::clone(&self.declaration),
             closure: env,
             initializer: self.initializer,
         }
     }
+
+    // how runtime errors name the function they happened in
+    fn describe(&self) -> String {
+        // `bind` puts the instance first in the closure of a method, the class declaring the
+        // method is looked up from there
+        if let Some(Object::LoxInstance(instance)) = self.closure.get_at(Slot::new(0, 0)) {
+            let mut class = Some(instance.class());
+            while let Some(lox_class) = class {
+                if lox_class
+                    .methods()
+                    .any(|method| Shared::ptr_eq(&method.declaration, &self.declaration))
+                {
+                    return format!("method `{}.{}`", lox_class.name(), self.name());
+                }
+                class = lox_class.superclass();
+            }
+        }
+        format!("function `{}`", self.name())
+    }
 }
 
 impl PartialEq for LoxFunction {
@@ -58,7 +80,7 @@ impl Hash for LoxFunction {
 
 impl Callable for LoxFunction {
     fn name(&self) -> &str {
-        self.declaration.name.lexeme()
+        self.declaration.name.name()
     }
 
     fn arity(&self) -> usize {
@@ -73,89 +95,30 @@ impl Callable for LoxFunction {
     where
         W: std::io::Write,
     {
-        self.closure.move_to_inner();
-
+        // a new frame for every call, the closure itself is left untouched
+        let mut frame = self.closure.append();
         for (param, arg) in self.declaration.params.iter().zip(arguments) {
-            self.closure.define(param.lexeme(), arg);
+            frame.define(param.symbol(), arg);
         }
 
-        std::mem::swap(interpreter.environment_mut(), &mut self.closure);
-
-        let result = interpreter.stmt(&self.declaration.body);
-        let result = if self.initializer {
-            interpreter.environment_mut().get_at("this", 0).unwrap()
-        } else {
-            result
-                .map(|_| Object::Null)
-                .unwrap_or_else(|err| err.get_value_from_return())
+        std::mem::swap(interpreter.environment_mut(), &mut frame);
+        interpreter.count(|stats| stats.environment_pushes += 1);
+
+        let result = match interpreter.stmt(&self.declaration.body) {
+            // errors and `exit` keep unwinding
+            Err(ControlFlow::Error(err)) => Err(err.in_function(self.describe())),
+            // `this` is bound in the scope right above the parameters
+            _ if self.initializer => Ok(interpreter
+                .environment_mut()
+                .get_at(Slot::new(1, 0))
+                .unwrap()),
+            Ok(_) => Ok(Object::Null),
+            Err(ControlFlow::Return(value)) => Ok(value),
         };
 
-        std::mem::swap(interpreter.environment_mut(), &mut self.closure);
-
-        self.closure.move_to_outer();
-
-        Ok(result)
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Hash)]
-pub(crate) enum NativeFunction {
-    Clock(Clock),
-}
-
-impl NativeFunction {
-    pub fn clock() -> Self {
-        NativeFunction::Clock(Clock::default())
-    }
-}
-
-impl Callable for NativeFunction {
-    fn name(&self) -> &str {
-        match self {
-            NativeFunction::Clock(clock) => clock.name(),
-        }
-    }
+        std::mem::swap(interpreter.environment_mut(), &mut frame);
+        interpreter.count(|stats| stats.environment_pops += 1);
 
-    fn arity(&self) -> usize {
-        match self {
-            NativeFunction::Clock(clock) => clock.arity(),
-        }
-    }
-
-    fn call<W>(
-        &mut self,
-        interpreter: &mut Interpreter<W>,
-        arguments: Vec<Object>,
-    ) -> Result<Object, RuntimeError>
-    where
-        W: std::io::Write,
-    {
-        match self {
-            NativeFunction::Clock(clock) => clock.call(interpreter, arguments),
-        }
-    }
-}
-
-// native clock function
-#[derive(Debug, Clone, PartialEq, Default, Hash)]
-pub(crate) struct Clock;
-
-impl Callable for Clock {
-    fn name(&self) -> &str {
-        "clock"
-    }
-
-    fn arity(&self) -> usize {
-        0
-    }
-
-    fn call<W>(&mut self, _: &mut Interpreter<W>, _: Vec<Object>) -> Result<Object, RuntimeError>
-    where
-        W: std::io::Write,
-    {
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap();
-        Ok(Object::Number(Number::from(now.as_millis() as f64)))
+        result
     }
 }
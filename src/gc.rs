@@ -0,0 +1,507 @@
+use std::collections::{HashMap, HashSet};
+
+use indexmap::IndexMap;
+
+use crate::{
+    callable::LoxCallable,
+    class::{Fields, LoxClass, LoxInstance},
+    compact,
+    environment::EnvironmentNode,
+    list::LoxList,
+    map::{LoxMap, MapKey},
+    memory::Sizer,
+    object::Object,
+    shared::{Mutable, Shared, Weak},
+};
+
+pub(crate) const DEFAULT_THRESHOLD: usize = 10_000;
+
+// environments captured by closures, instances, lists and maps holding each other form
+// reference cycles that `Rc` never frees.
+//
+// collections run between statements. nothing has to be known about the rust stack,
+// references held there are simply counted as references from outside.
+#[derive(Debug)]
+pub(crate) struct Gc {
+    threshold: usize,
+    // objects alive after the last collection
+    live: usize,
+    requested: bool,
+    collections: u64,
+}
+
+impl Default for Gc {
+    fn default() -> Self {
+        Self::new(DEFAULT_THRESHOLD)
+    }
+}
+
+impl Gc {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            live: 0,
+            requested: false,
+            collections: 0,
+        }
+    }
+
+    // collect at the next safe point, whatever the threshold is
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    pub fn should_collect(&self, allocated: usize) -> bool {
+        self.requested || allocated >= self.live.saturating_add(self.threshold)
+    }
+
+    pub fn collected(&mut self, live: usize) {
+        self.live = live;
+        self.requested = false;
+        self.collections += 1;
+    }
+
+    pub fn collections(&self) -> u64 {
+        self.collections
+    }
+}
+
+// every object of one kind ever created, so the collector can find the unreachable ones.
+// they are kept by address, an address is only reused once its object was freed
+#[derive(Debug)]
+pub(crate) struct Tracked<T> {
+    objects: HashMap<usize, Weak<Mutable<T>>>,
+    // objects already freed by `Rc` are dropped from the list once it reaches this size
+    prune_at: usize,
+    created: u64,
+}
+
+impl<T> Default for Tracked<T> {
+    fn default() -> Self {
+        Self {
+            objects: HashMap::new(),
+            prune_at: 1024,
+            created: 0,
+        }
+    }
+}
+
+impl<T> Tracked<T> {
+    pub fn track(&mut self, object: &Shared<Mutable<T>>) {
+        if self.objects.len() >= self.prune_at {
+            self.prune();
+            self.prune_at = self.prune_at.max(self.objects.len() * 2);
+        }
+        self.objects
+            .insert(address(object), Shared::downgrade(object));
+        self.created += 1;
+    }
+
+    pub fn contains(&self, object: &Shared<Mutable<T>>) -> bool {
+        self.objects
+            .get(&address(object))
+            .is_some_and(|tracked| tracked.strong_count() > 0)
+    }
+
+    // objects not freed yet, including those no one pruned
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    // objects not freed yet, without counting
+    pub fn alive_count(&self) -> usize {
+        self.objects
+            .values()
+            .filter(|object| object.strong_count() > 0)
+            .count()
+    }
+
+    // objects ever tracked, freed or not
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    fn prune(&mut self) {
+        self.objects.retain(|_, object| object.strong_count() > 0);
+    }
+
+    fn alive(&self) -> Vec<Shared<Mutable<T>>> {
+        self.objects.values().filter_map(Weak::upgrade).collect()
+    }
+}
+
+// live objects and what they hold, for scripts looking for leaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MemoryStats {
+    pub environments: usize,
+    pub instances: usize,
+    pub strings: usize,
+    pub bytes: usize,
+    pub collections: u64,
+}
+
+// shared by every copy of the environment tree
+#[derive(Debug, Default)]
+pub(crate) struct Heap {
+    pub environments: Tracked<EnvironmentNode>,
+    pub instances: Tracked<Fields>,
+    pub lists: Tracked<Vec<Object>>,
+    pub maps: Tracked<IndexMap<MapKey, Object>>,
+}
+
+impl Heap {
+    pub fn len(&self) -> usize {
+        self.environments.len() + self.instances.len() + self.lists.len() + self.maps.len()
+    }
+
+    // lists and maps are created by natives, which have no access to the heap. they are
+    // tracked once the interpreter gets them, with the new ones they hold
+    pub fn track(&mut self, object: &Object) {
+        match object {
+            Object::List(list) if !self.lists.contains(list.storage()) => {
+                self.lists.track(list.storage());
+                for element in list.elements().iter() {
+                    self.track(element);
+                }
+            }
+            Object::Map(map) if !self.maps.contains(map.storage()) => {
+                self.maps.track(map.storage());
+                for value in map.entries().values() {
+                    self.track(value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    pub fn created(&self) -> u64 {
+        self.environments.created() + self.instances.created()
+    }
+
+    // approximate bytes used by every environment, instance, list and map still alive
+    pub fn measure(&self, sizer: &mut Sizer) {
+        for node in self.environments.alive() {
+            sizer.environment_or_instance();
+            node.borrow().measure(sizer);
+        }
+        for fields in self.instances.alive() {
+            sizer.environment_or_instance();
+            for (name, value) in fields.borrow().iter() {
+                sizer.add(name.capacity());
+                sizer.object(value);
+            }
+        }
+        for list in self.lists.alive() {
+            sizer.list(&LoxList::from(list));
+        }
+        for map in self.maps.alive() {
+            sizer.map(&LoxMap::from(map));
+        }
+    }
+}
+
+fn address<T>(object: &Shared<Mutable<T>>) -> usize {
+    Shared::as_ptr(object) as usize
+}
+
+// functions and instances behind a shared pointer, see `compact`.
+// every copy of the object adds an edge but only one strong reference to what the
+// payload holds, so the payload is a vertex of its own
+fn shared(object: &Object) -> Option<(usize, usize)> {
+    match object {
+        Object::Callable(callable) => compact::shared(callable),
+        Object::LoxInstance(instance) => compact::shared(instance),
+        _ => None,
+    }
+}
+
+// strong references held by one object, found by address.
+// classes are created without access to the heap, they are found here instead. so are
+// lists and maps not tracked yet
+#[derive(Default)]
+pub(crate) struct Tracer {
+    edges: Vec<usize>,
+    lists: Vec<LoxList>,
+    maps: Vec<LoxMap>,
+    classes: Vec<LoxClass>,
+    shared: Vec<Object>,
+}
+
+impl Tracer {
+    pub fn reference<T>(&mut self, object: &Shared<Mutable<T>>) {
+        self.edges.push(address(object));
+    }
+
+    pub fn object(&mut self, object: &Object) {
+        if let Some((address, _)) = shared(object) {
+            self.edges.push(address);
+            self.shared.push(object.clone());
+            return;
+        }
+        self.payload(object);
+    }
+
+    fn payload(&mut self, object: &Object) {
+        match object {
+            Object::Callable(callable) => self.callable(callable),
+            Object::LoxInstance(instance) => self.instance(instance),
+            Object::List(list) => {
+                self.edges.push(list.address());
+                self.lists.push(list.clone());
+            }
+            Object::Map(map) => {
+                self.edges.push(map.address());
+                self.maps.push(map.clone());
+            }
+            Object::Null | Object::Number(_) | Object::String(_) | Object::Bool(_) => (),
+        }
+    }
+
+    fn callable(&mut self, callable: &LoxCallable) {
+        match callable {
+            LoxCallable::LoxFunction(function) => {
+                self.edges.extend(function.closure().address());
+            }
+            LoxCallable::LoxClass(class) => self.class(class),
+            LoxCallable::NativeFunction(_) => (),
+        }
+    }
+
+    fn instance(&mut self, instance: &LoxInstance) {
+        self.class(instance.class());
+        self.reference(instance.fields());
+    }
+
+    fn class(&mut self, class: &LoxClass) {
+        self.edges.push(class.address());
+        self.classes.push(class.clone());
+    }
+}
+
+struct Vertex {
+    // strong references, not counting the one the graph holds
+    strong: usize,
+    edges: Vec<usize>,
+}
+
+// cycle collection by trial deletion.
+// classes can't be cleared, but every cycle through a class also goes through the
+// environment its methods closed over.
+// a vertex with more strong references than edges pointing at it is held from outside
+// the graph: by globals, the interpreter or the rust stack. everything reachable from
+// those is alive, the rest is only kept alive by cycles
+#[derive(Default)]
+struct CycleGraph {
+    vertices: HashMap<usize, Vertex>,
+    lists: Vec<LoxList>,
+    maps: Vec<LoxMap>,
+    classes: Vec<LoxClass>,
+    shared: Vec<Object>,
+    pending_lists: Vec<LoxList>,
+    pending_maps: Vec<LoxMap>,
+    pending_classes: Vec<LoxClass>,
+    pending_shared: Vec<Object>,
+}
+
+impl CycleGraph {
+    // `strong` must not count the reference the caller holds
+    fn add(&mut self, address: usize, strong: usize, tracer: Tracer) {
+        self.pending_lists.extend(tracer.lists);
+        self.pending_maps.extend(tracer.maps);
+        self.pending_classes.extend(tracer.classes);
+        self.pending_shared.extend(tracer.shared);
+        self.vertices.insert(
+            address,
+            Vertex {
+                strong,
+                edges: tracer.edges,
+            },
+        );
+    }
+
+    // lists, maps, classes and shared payloads found in other vertices, each is added once
+    fn add_found(&mut self) {
+        loop {
+            if let Some(list) = self.pending_lists.pop() {
+                if self.vertices.contains_key(&list.address()) {
+                    continue;
+                }
+                let mut tracer = Tracer::default();
+                for element in list.elements().iter() {
+                    tracer.object(element);
+                }
+                // counted below, once no other copy made by the graph is left
+                self.add(list.address(), 0, tracer);
+                self.lists.push(list);
+            } else if let Some(map) = self.pending_maps.pop() {
+                if self.vertices.contains_key(&map.address()) {
+                    continue;
+                }
+                let mut tracer = Tracer::default();
+                for value in map.entries().values() {
+                    tracer.object(value);
+                }
+                self.add(map.address(), 0, tracer);
+                self.maps.push(map);
+            } else if let Some(class) = self.pending_classes.pop() {
+                if self.vertices.contains_key(&class.address()) {
+                    continue;
+                }
+                let mut tracer = Tracer::default();
+                for method in class.methods() {
+                    tracer.edges.extend(method.closure().address());
+                }
+                if let Some(superclass) = class.superclass() {
+                    tracer.class(superclass);
+                }
+                self.add(class.address(), 0, tracer);
+                self.classes.push(class);
+            } else if let Some(object) = self.pending_shared.pop() {
+                let Some((address, _)) = shared(&object) else {
+                    continue;
+                };
+                if self.vertices.contains_key(&address) {
+                    continue;
+                }
+                let mut tracer = Tracer::default();
+                tracer.payload(&object);
+                self.add(address, 0, tracer);
+                self.shared.push(object);
+            } else {
+                break;
+            }
+        }
+
+        let counts = self
+            .lists
+            .iter()
+            .map(|list| (list.address(), list.strong_count()))
+            .chain(
+                self.maps
+                    .iter()
+                    .map(|map| (map.address(), map.strong_count())),
+            )
+            .chain(
+                self.classes
+                    .iter()
+                    .map(|class| (class.address(), class.strong_count())),
+            )
+            .chain(self.shared.iter().filter_map(shared));
+        for (address, count) in counts {
+            if let Some(vertex) = self.vertices.get_mut(&address) {
+                vertex.strong = count - 1;
+            }
+        }
+    }
+
+    // addresses only reachable through cycles
+    fn garbage(&mut self) -> HashSet<usize> {
+        self.add_found();
+
+        let mut internal: HashMap<usize, usize> = HashMap::new();
+        for vertex in self.vertices.values() {
+            for edge in &vertex.edges {
+                *internal.entry(*edge).or_default() += 1;
+            }
+        }
+
+        let mut pending: Vec<usize> = self
+            .vertices
+            .iter()
+            .filter(|(address, vertex)| {
+                vertex.strong > internal.get(address).copied().unwrap_or_default()
+            })
+            .map(|(address, _)| *address)
+            .collect();
+        let mut alive = HashSet::new();
+        while let Some(address) = pending.pop() {
+            if !alive.insert(address) {
+                continue;
+            }
+            if let Some(vertex) = self.vertices.get(&address) {
+                pending.extend(&vertex.edges);
+            }
+        }
+
+        self.vertices
+            .keys()
+            .filter(|address| !alive.contains(address))
+            .copied()
+            .collect()
+    }
+}
+
+// clearing unreachable objects breaks the cycles and lets `Rc` free everything.
+// values are dropped outside of the borrow, they might hold the same objects again
+fn clear<T: Default>(objects: &[Shared<Mutable<T>>], garbage: &HashSet<usize>) -> usize {
+    let mut count = 0;
+    for object in objects
+        .iter()
+        .filter(|object| garbage.contains(&address(object)))
+    {
+        let cleared = std::mem::take(&mut *object.borrow_mut());
+        drop(cleared);
+        count += 1;
+    }
+    count
+}
+
+// returns the number of environments, instances, lists and maps cleared
+pub(crate) fn collect(heap: &Mutable<Heap>) -> usize {
+    let mut graph = CycleGraph::default();
+    let (environments, instances) = {
+        let heap = heap.borrow();
+        // the graph holds its own copy of lists and maps, it is counted once they are all found
+        graph
+            .pending_lists
+            .extend(heap.lists.alive().into_iter().map(LoxList::from));
+        graph
+            .pending_maps
+            .extend(heap.maps.alive().into_iter().map(LoxMap::from));
+        (heap.environments.alive(), heap.instances.alive())
+    };
+
+    for node in &environments {
+        let mut tracer = Tracer::default();
+        node.borrow().trace(&mut tracer);
+        graph.add(address(node), Shared::strong_count(node) - 1, tracer);
+    }
+    for fields in &instances {
+        let mut tracer = Tracer::default();
+        for value in fields.borrow().values() {
+            tracer.object(value);
+        }
+        graph.add(address(fields), Shared::strong_count(fields) - 1, tracer);
+    }
+
+    let garbage = graph.garbage();
+    let mut count = 0;
+    for list in graph
+        .lists
+        .iter()
+        .filter(|list| garbage.contains(&list.address()))
+    {
+        list.clear();
+        count += 1;
+    }
+    for map in graph
+        .maps
+        .iter()
+        .filter(|map| garbage.contains(&map.address()))
+    {
+        map.clear();
+        count += 1;
+    }
+    count += clear(&environments, &garbage);
+    count += clear(&instances, &garbage);
+    drop(graph);
+    drop(environments);
+    drop(instances);
+
+    let mut heap = heap.borrow_mut();
+    heap.environments.prune();
+    heap.instances.prune();
+    heap.lists.prune();
+    heap.maps.prune();
+    count
+}
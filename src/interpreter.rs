@@ -1,31 +1,101 @@
-use std::{collections::HashMap, io::StdoutLock};
+use std::{
+    collections::HashMap,
+    io::StdoutLock,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use crate::{
+    budget::Budget,
     callable::{Callable, LoxCallable},
-    class::{LoxInstance, LoxInstanceFields},
-    environment::EnvironmentTree,
+    class::LoxInstance,
+    compact,
+    coverage::Coverage,
+    environment::{EnvironmentTree, Slot},
     error::{reporter::ErrorReporter, runtime_error::RuntimeError},
-    expr::Expr,
+    expr::{Expr, ExprId},
     function::LoxFunction,
+    gc::{self, Gc, MemoryStats},
+    memory::{self, MemoryLimit},
+    native::{
+        host::{HostFn, HostFunction},
+        random::Rng,
+        Capability, NativeFunction,
+    },
     object::Object,
+    profile::Profiler,
+    shared::MaybeSend,
+    stack,
+    stats::Stats,
     stmt::Stmt,
+    symbol::Symbol,
+    time::Instant,
+    timing::Timings,
     token::{Token, TokenType},
     visitor::Visitor,
 };
 
+// what scripts read their input from
+pub trait Reader: std::io::BufRead + MaybeSend {}
+
+impl<R: std::io::BufRead + MaybeSend> Reader for R {}
+
 pub(crate) struct Interpreter<W>
 where
     W: std::io::Write,
 {
     writer: W,
+    reader: Box<dyn Reader>,
+    rng: Rng,
+    args: Vec<String>,
+    clock_seconds: bool,
+    // like the book's interpreter, the script stops at its first runtime error
+    book: bool,
+    // globals have to be declared before the script runs, see `Resolver`
+    strict: bool,
+    // global functions and classes that are never used are not run, see `Lox`
+    prune: bool,
+    start: Instant,
     environment: EnvironmentTree,
     errors: Vec<RuntimeError>,
-    locals: HashMap<Expr, usize>,
-    lox_instances: HashMap<LoxInstance, LoxInstanceFields>,
+    exit_code: Option<i32>,
+    gc: Gc,
+    locals: HashMap<ExprId, Slot>,
+    next_instance_id: usize,
+    call_depth: usize,
+    max_call_depth: usize,
+    budget: Budget,
+    // set from a signal handler to stop the running script
+    interrupt: Option<Arc<AtomicBool>>,
+    memory: Option<MemoryLimit>,
+    profiler: Option<Profiler>,
+    stats: Option<Stats>,
+    timings: Option<Timings>,
+    coverage: Option<Coverage>,
 }
 
 type InterpreterResult<T> = Result<T, RuntimeError>;
 
+// the stack grows for deeper calls, see `stack`
+pub(crate) const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+// why a statement stopped before reaching its end, `return` is not an error
+pub(crate) enum ControlFlow {
+    Return(Object),
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for ControlFlow {
+    fn from(error: RuntimeError) -> Self {
+        ControlFlow::Error(error)
+    }
+}
+
+pub(crate) type StmtResult = Result<Object, ControlFlow>;
+
 impl<W> ErrorReporter<RuntimeError> for Interpreter<W>
 where
     W: std::io::Write,
@@ -40,14 +110,219 @@ impl<W> Interpreter<W>
 where
     W: std::io::Write,
 {
+    // keywords, interned names and natives are shared by every interpreter, so creating one for
+    // every script, or every request of a server, costs little more than its writer
     pub fn new(writer: W) -> Self {
         Self {
             writer,
+            reader: Box::new(std::io::BufReader::new(std::io::stdin())),
+            rng: Rng::default(),
+            args: Vec::new(),
+            clock_seconds: false,
+            book: false,
+            strict: false,
+            prune: false,
+            start: Instant::now(),
             environment: EnvironmentTree::default(),
             errors: Default::default(),
+            exit_code: None,
+            gc: Gc::default(),
             locals: Default::default(),
-            lox_instances: Default::default(),
+            next_instance_id: 0,
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            budget: Budget::default(),
+            interrupt: None,
+            memory: None,
+            profiler: None,
+            stats: None,
+            timings: None,
+            coverage: None,
+        }
+    }
+
+    pub fn with_reader(mut self, reader: impl Reader + 'static) -> Self {
+        self.reader = Box::new(reader);
+        self
+    }
+
+    // fixed seed for `random` and `randomInt`, so runs are reproducible
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    // script arguments returned by `args`
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    // `clock` and `monotonic` return seconds instead of milliseconds
+    pub fn with_clock_seconds(mut self, clock_seconds: bool) -> Self {
+        self.clock_seconds = clock_seconds;
+        self
+    }
+
+    // behave like the book's interpreter, `clock` returns seconds too
+    pub fn with_book(mut self, book: bool) -> Self {
+        self.book = book;
+        self.clock_seconds |= book;
+        self
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    // seed a global before running a script, like a configuration value or a function
+    // taken from an earlier script. `--strict` counts it as declared
+    pub fn set_global(&mut self, name: &str, value: impl Into<Object>) {
+        let value = value.into();
+        self.track(&value);
+        self.environment.define_global(Symbol::intern(name), value);
+    }
+
+    // define a global native function backed by host code
+    pub fn register_native(&mut self, name: &str, arity: usize, function: impl HostFn + 'static) {
+        let native = NativeFunction::host(HostFunction::new(name, arity, function));
+        self.environment.define_global(
+            Symbol::intern(name),
+            Object::callable(LoxCallable::native_function(native)),
+        );
+    }
+
+    // scripts can only call the natives named in `natives` and those registered later
+    pub fn with_natives(mut self, natives: &[&str]) -> Self {
+        self.environment
+            .retain_natives(|native| natives.contains(&native.name()));
+        self
+    }
+
+    // `exec` runs other programs, it is only defined when asked for and still needs the
+    // process capability
+    pub fn with_exec(mut self, exec: bool) -> Self {
+        if exec {
+            self.environment.define_global(
+                Symbol::intern("exec"),
+                Object::callable(LoxCallable::native_function(NativeFunction::exec())),
+            );
         }
+        self
+    }
+
+    // natives needing a capability not in `capabilities` can't be called, the others only compute
+    pub fn with_capabilities(mut self, capabilities: &[Capability]) -> Self {
+        self.environment.retain_natives(|native| {
+            native
+                .capability()
+                .is_none_or(|capability| capabilities.contains(&capability))
+        });
+        self
+    }
+
+    // calls nested deeper than this fail with a runtime error instead of overflowing the stack
+    pub fn with_max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = max_call_depth;
+        self
+    }
+
+    // stop scripts after this many loop iterations and calls, or after running this long
+    pub fn with_budget(mut self, max_steps: Option<u64>, timeout: Option<Duration>) -> Self {
+        self.budget = Budget::new(max_steps, timeout);
+        self
+    }
+
+    // stop the running script whenever `interrupt` is set, it is cleared for the next one
+    pub fn with_interrupt(mut self, interrupt: Arc<AtomicBool>) -> Self {
+        self.interrupt = Some(interrupt);
+        self
+    }
+
+    // stop scripts once lox objects take roughly this many bytes
+    pub fn with_memory_limit(mut self, bytes: Option<usize>) -> Self {
+        self.memory = bytes.map(MemoryLimit::new);
+        self
+    }
+
+    // count calls and time spent in every function, see `profile_report`
+    pub fn with_profiler(mut self, profile: bool) -> Self {
+        self.profiler = profile.then(Profiler::default);
+        self
+    }
+
+    pub fn profile_report(&self) -> Option<String> {
+        self.profiler.as_ref().map(Profiler::report)
+    }
+
+    // count statements, expressions, calls, allocations and environments, see `stats_report`
+    pub fn with_stats(mut self, stats: bool) -> Self {
+        self.stats = stats.then(Stats::default);
+        self
+    }
+
+    pub fn stats_report(&self) -> Option<String> {
+        let heap_allocations = self.environment.heap().borrow().created();
+        self.stats
+            .as_ref()
+            .map(|stats| stats.report(heap_allocations))
+    }
+
+    // time scanning, parsing, resolving and interpreting, see `timings_report`
+    pub fn with_timings(mut self, time: bool) -> Self {
+        self.timings = time.then(Timings::default);
+        self
+    }
+
+    pub fn timings_report(&self) -> Option<String> {
+        self.timings.as_ref().map(Timings::report)
+    }
+
+    pub fn is_timed(&self) -> bool {
+        self.timings.is_some()
+    }
+
+    // record which statements run, see `coverage_report`
+    pub fn with_coverage(mut self, coverage: Option<Coverage>) -> Self {
+        self.coverage = coverage;
+        self
+    }
+
+    pub fn coverage_report(&self) -> Option<String> {
+        self.coverage.as_ref().and_then(Coverage::report)
+    }
+
+    pub fn write_lcov(&self) -> std::io::Result<()> {
+        match &self.coverage {
+            Some(coverage) => coverage.write_lcov(),
+            None => Ok(()),
+        }
+    }
+
+    // does nothing unless `--time` is on
+    pub fn time(&mut self, record: impl FnOnce(&mut Timings)) {
+        if let Some(timings) = self.timings.as_mut() {
+            record(timings);
+        }
+    }
+
+    // does nothing unless `--stats` is on
+    pub fn count(&mut self, counter: impl FnOnce(&mut Stats)) {
+        if let Some(stats) = self.stats.as_mut() {
+            counter(stats);
+        }
+    }
+
+    // collect after this many objects were allocated since the last collection
+    pub fn with_gc_threshold(mut self, threshold: usize) -> Self {
+        self.gc = Gc::new(threshold);
+        self
     }
 
     pub fn flush_error(&mut self) {
@@ -58,44 +333,201 @@ where
         e.walk_epxr(self)
     }
 
-    pub fn stmt(&mut self, s: &Stmt) -> InterpreterResult<Object> {
+    pub fn stmt(&mut self, s: &Stmt) -> StmtResult {
         s.walk_stmt(self)
     }
 
-    pub fn locals(&self) -> &HashMap<Expr, usize> {
+    pub fn locals(&self) -> &HashMap<ExprId, Slot> {
         &self.locals
     }
 
-    pub fn resolve(&mut self, expr: Expr, depth: usize) {
-        self.locals.insert(expr, depth);
+    pub fn resolve(&mut self, id: ExprId, slot: Slot) {
+        self.locals.insert(id, slot);
     }
 
-    pub fn lookup_variable(&self, expr: &Expr, token: &Token) -> InterpreterResult<Object> {
-        let result = match self.locals.get(expr) {
-            Some(depth) => self.environment.get_at(token.lexeme(), *depth),
-            None => self.environment.get_global(token.lexeme()),
+    pub fn lookup_variable(&self, id: ExprId, token: &Token) -> InterpreterResult<Object> {
+        let result = match self.locals.get(&id) {
+            Some(slot) => self.environment.get_at(*slot),
+            None => self.environment.get_global(token.symbol()),
         };
         result.ok_or_else(|| RuntimeError::undefined_variable(token))
     }
 
     pub fn interpret(&mut self, statements: &[Stmt]) -> Object {
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.instrument(statements);
+        }
+        self.budget.start();
+        if let Some(interrupt) = &self.interrupt {
+            interrupt.store(false, Ordering::Relaxed);
+        }
         let mut object = Object::Null;
         for stmt in statements {
             match self.stmt(stmt) {
-                Ok(o) => object = o,
-                Err(error) => self.errors.push(error),
+                // the resolver rejects `return` outside of functions
+                Ok(o) | Err(ControlFlow::Return(o)) => object = o,
+                Err(ControlFlow::Error(error)) => match error.exit_code() {
+                    Some(code) => {
+                        self.exit_code = Some(code);
+                        break;
+                    }
+                    None => {
+                        let aborts = error.aborts() || self.book;
+                        self.errors.push(error);
+                        if aborts {
+                            break;
+                        }
+                    }
+                },
             }
+            self.safe_point();
         }
+        let objects = self.allocated();
+        self.time(|timings| timings.seen(objects));
         object
     }
 
-    pub fn add_new_instance(&mut self, lox_instance: LoxInstance) {
-        self.lox_instances
-            .insert(lox_instance.clone(), LoxInstanceFields::from(lox_instance));
+    // a loop iteration or a call, where long running scripts can be stopped
+    fn step(&mut self, token: &Token) -> InterpreterResult<()> {
+        if self
+            .interrupt
+            .as_ref()
+            .is_some_and(|interrupt| interrupt.load(Ordering::Relaxed))
+        {
+            return Err(RuntimeError::interrupted(token));
+        }
+        if !self.budget.step() {
+            return Err(RuntimeError::budget_exceeded(token));
+        }
+        Ok(())
+    }
+
+    fn allocated(&self) -> usize {
+        self.environment.heap().borrow().len()
+    }
+
+    // returns the number of objects freed
+    pub fn collect_garbage(&mut self) -> usize {
+        let start = Instant::now();
+        let objects = self.allocated();
+        let freed = gc::collect(self.environment.heap());
+        self.gc.collected(self.allocated());
+        self.time(|timings| timings.collected(start.elapsed(), objects));
+        freed
+    }
+
+    // between statements nothing is borrowed, so objects can be cleared
+    fn safe_point(&mut self) {
+        if self.gc.should_collect(self.allocated()) {
+            self.collect_garbage();
+        }
+    }
+
+    // `allocated` bytes were just created, the heap is only measured once the
+    // estimate gets close to the limit, and only what is left after a collection counts
+    fn check_memory(&mut self, token: &Token, allocated: usize) -> InterpreterResult<()> {
+        let objects = self.allocated();
+        let Some(memory) = self.memory.as_mut() else {
+            return Ok(());
+        };
+        memory.allocate(allocated);
+        if !memory.should_measure(objects) {
+            return Ok(());
+        }
+        self.collect_garbage();
+        let live = self.environment.measure().bytes();
+        let objects = self.allocated();
+        let Some(memory) = self.memory.as_mut() else {
+            return Ok(());
+        };
+        if memory.measured(live, objects) {
+            return Err(RuntimeError::memory_limit_exceeded(token, memory.limit()));
+        }
+        Ok(())
+    }
+
+    // bytes a native added to a list or map it was given, they only count toward the memory
+    // limit, which is checked once the call returns
+    pub fn charge(&mut self, bytes: usize) {
+        if let Some(memory) = self.memory.as_mut() {
+            memory.allocate(bytes);
+        }
+    }
+
+    // called by the `gc` native, the collection waits until the current statement ends
+    pub fn request_gc(&mut self) {
+        self.gc.request();
+    }
+
+    // what the `memoryStats` native reports, found by walking every live object
+    pub fn memory_stats(&self) -> MemoryStats {
+        let sizer = self.environment.measure();
+        let heap = self.environment.heap().borrow();
+        MemoryStats {
+            environments: heap.environments.alive_count(),
+            instances: heap.instances.alive_count(),
+            strings: sizer.strings(),
+            bytes: sizer.bytes(),
+            collections: self.gc.collections(),
+        }
+    }
+
+    // status code requested by the `exit` native
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    pub fn track_instance(&mut self, lox_instance: &LoxInstance) {
+        self.environment
+            .heap()
+            .borrow_mut()
+            .instances
+            .track(lox_instance.fields());
+    }
+
+    // lists and maps natives create, see `Heap::track`
+    fn track(&mut self, object: &Object) {
+        self.environment.heap().borrow_mut().track(object);
+    }
+
+    // ids are never reused, not even after the instance was collected
+    pub fn instance_id(&mut self) -> usize {
+        let id = self.next_instance_id;
+        self.next_instance_id += 1;
+        id
+    }
+
+    pub fn clock_seconds(&self) -> bool {
+        self.clock_seconds
+    }
+
+    pub fn book(&self) -> bool {
+        self.book
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn prune(&self) -> bool {
+        self.prune
+    }
+
+    // time elapsed since the interpreter was created, unaffected by wall-clock changes
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn rng_mut(&mut self) -> &mut Rng {
+        &mut self.rng
     }
 
-    pub fn instance_id(&self) -> usize {
-        self.lox_instances.len()
+    pub fn environment(&self) -> &EnvironmentTree {
+        &self.environment
     }
 
     pub fn environment_mut(&mut self) -> &mut EnvironmentTree {
@@ -105,28 +537,72 @@ where
     pub fn write(&mut self, s: &str) -> Result<(), std::io::Error> {
         writeln!(self.writer, "{s}")
     }
+
+    pub fn write_str(&mut self, s: &str) -> Result<(), std::io::Error> {
+        write!(self.writer, "{s}")
+    }
+
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.writer.flush()
+    }
+
+    // read one line without its line ending, `None` at the end of input
+    pub fn read_line(&mut self) -> Result<Option<String>, std::io::Error> {
+        self.writer.flush()?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
 }
 
 impl<'a> Default for Interpreter<StdoutLock<'a>> {
     fn default() -> Self {
         Self {
             writer: std::io::stdout().lock(),
+            reader: Box::new(std::io::BufReader::new(std::io::stdin())),
+            rng: Rng::default(),
+            args: Vec::new(),
+            clock_seconds: false,
+            book: false,
+            strict: false,
+            prune: false,
+            start: Instant::now(),
             environment: EnvironmentTree::default(),
             errors: Default::default(),
+            exit_code: None,
+            gc: Gc::default(),
             locals: Default::default(),
-            lox_instances: Default::default(),
+            next_instance_id: 0,
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            budget: Budget::default(),
+            interrupt: None,
+            memory: None,
+            profiler: None,
+            stats: None,
+            timings: None,
+            coverage: None,
         }
     }
 }
 
-impl<W> Visitor<InterpreterResult<Object>, InterpreterResult<Object>> for Interpreter<W>
+impl<W> Visitor<InterpreterResult<Object>, StmtResult> for Interpreter<W>
 where
     W: std::io::Write,
 {
     fn visit_expr(&mut self, e: &Expr) -> InterpreterResult<Object> {
+        self.count(|stats| stats.expressions += 1);
         match e {
             Expr::Binary(binary) => {
-                let lhs = self.visit_expr(&binary.left)?;
+                let lhs = stack::grow(|| self.visit_expr(&binary.left))?;
                 let rhs = self.visit_expr(&binary.right)?;
                 let operator = &binary.operator;
                 match operator.token_type() {
@@ -140,7 +616,13 @@ where
                         Ok((lhs / rhs).map_err(|err| RuntimeError::from((operator, err)))?)
                     }
                     TokenType::Plus => {
-                        Ok((lhs + rhs).map_err(|err| RuntimeError::from((operator, err)))?)
+                        let result =
+                            (lhs + rhs).map_err(|err| RuntimeError::from((operator, err)))?;
+                        if let Object::String(_) = result {
+                            self.count(|stats| stats.allocations += 1);
+                        }
+                        self.check_memory(operator, memory::allocated_by(&result))?;
+                        Ok(result)
                     }
                     TokenType::Greater => {
                         Ok((lhs.gt(&rhs)).map_err(|err| RuntimeError::from((operator, err)))?)
@@ -160,6 +642,21 @@ where
                     TokenType::EqualEqual => {
                         Ok((lhs.eq(&rhs)).map_err(|err| RuntimeError::from((operator, err)))?)
                     }
+                    TokenType::Ampersand => {
+                        Ok((lhs & rhs).map_err(|err| RuntimeError::from((operator, err)))?)
+                    }
+                    TokenType::Pipe => {
+                        Ok((lhs | rhs).map_err(|err| RuntimeError::from((operator, err)))?)
+                    }
+                    TokenType::Caret => {
+                        Ok((lhs ^ rhs).map_err(|err| RuntimeError::from((operator, err)))?)
+                    }
+                    TokenType::LessLess => {
+                        Ok((lhs << rhs).map_err(|err| RuntimeError::from((operator, err)))?)
+                    }
+                    TokenType::GreaterGreater => {
+                        Ok((lhs >> rhs).map_err(|err| RuntimeError::from((operator, err)))?)
+                    }
                     _ => unimplemented!(),
                 }
             }
@@ -171,23 +668,26 @@ where
                         Ok((-rhs).map_err(|err| RuntimeError::from((operator, err)))?)
                     }
                     TokenType::Bang => Ok(Object::Bool(!rhs.is_truthy())),
+                    TokenType::Tilde => {
+                        Ok((rhs.bit_not()).map_err(|err| RuntimeError::from((operator, err)))?)
+                    }
                     _ => unimplemented!(),
                 }
             }
             Expr::Literal(object) => Ok(object.clone()),
             Expr::Grouping(group) => Ok(self.visit_expr(&group.expr)?),
-            Expr::Variable(var) => self.lookup_variable(e, &var.name),
+            Expr::Variable(var) => self.lookup_variable(var.id, &var.name),
             Expr::Assign(assign) => {
                 let name = &assign.name;
                 let value = self.visit_expr(&assign.value)?;
-                let result = match self.locals.get(e) {
-                    Some(depth) => self.environment.assign_at(name.lexeme(), value, *depth),
-                    None => self.environment.assign_global(name.lexeme(), value),
+                let result = match self.locals.get(&assign.id) {
+                    Some(slot) => self.environment.assign_at(*slot, value),
+                    None => self.environment.assign_global(name.symbol(), value),
                 };
                 result.ok_or_else(|| RuntimeError::undefined_variable(name))
             }
             Expr::Logical(logical) => {
-                let left = self.visit_expr(&logical.left)?;
+                let left = stack::grow(|| self.visit_expr(&logical.left))?;
                 if logical.operator.token_type() == &TokenType::Or {
                     if left.is_truthy() {
                         return Ok(left);
@@ -198,37 +698,73 @@ where
                 self.visit_expr(&logical.right)
             }
             Expr::Call(call) => {
-                let callee = self.visit_expr(&call.callee)?;
+                let callee = stack::grow(|| self.visit_expr(&call.callee))?;
                 match callee {
-                    Object::Callable(mut callee) => {
+                    Object::Callable(callee) => {
+                        let mut callee = compact::into_inner(callee);
                         let arguments: InterpreterResult<Vec<_>> = call
                             .arguments
                             .iter()
                             .map(|arg| self.visit_expr(arg))
                             .collect();
                         let arguments = arguments?;
-                        if arguments.len() != callee.arity() {
+                        if callee.variadic() && arguments.len() < callee.arity() {
+                            return Err(RuntimeError::too_few_arguments(
+                                &call.paren,
+                                callee.arity(),
+                                arguments.len(),
+                            ));
+                        }
+                        if !callee.variadic() && arguments.len() != callee.arity() {
                             return Err(RuntimeError::number_arguments_mismatch(
                                 &call.paren,
                                 callee.arity(),
                                 arguments.len(),
                             ));
                         }
-                        callee.call(self, arguments)
+                        self.step(&call.paren)?;
+                        if self.call_depth >= self.max_call_depth {
+                            return Err(RuntimeError::stack_overflow(
+                                &call.paren,
+                                self.max_call_depth,
+                            ));
+                        }
+                        self.call_depth += 1;
+                        self.count(|stats| stats.calls += 1);
+                        if let Some(profiler) = self.profiler.as_mut() {
+                            profiler.enter(callee.to_string());
+                        }
+                        let result = match &mut callee {
+                            LoxCallable::NativeFunction(native) => native
+                                .call_native(self, arguments)
+                                .map_err(|err| RuntimeError::from((&call.paren, err)))
+                                .inspect(|result| {
+                                    if let Object::String(_) | Object::List(_) | Object::Map(_) =
+                                        result
+                                    {
+                                        self.count(|stats| stats.allocations += 1);
+                                    }
+                                }),
+                            callee => stack::grow(|| callee.call(self, arguments)),
+                        };
+                        if let Some(profiler) = self.profiler.as_mut() {
+                            profiler.exit();
+                        }
+                        self.call_depth -= 1;
+                        let result = result?;
+                        self.track(&result);
+                        self.check_memory(&call.paren, memory::allocated_by(&result))?;
+                        Ok(result)
                     }
                     _ => Err(RuntimeError::object_not_callable(&call.paren, &callee)),
                 }
             }
             Expr::Get(get) => {
-                let object = self.visit_expr(&get.object)?;
+                let object = stack::grow(|| self.visit_expr(&get.object))?;
                 match object {
-                    Object::LoxInstance(instance_no_fields) => {
-                        // we always sure that instance is exist
-                        let instance = self.lox_instances.get(&instance_no_fields).unwrap();
-                        instance
-                            .get(&get.name)
-                            .ok_or_else(|| RuntimeError::undefined_property(&get.name))
-                    }
+                    Object::LoxInstance(instance) => instance
+                        .get(&get.name)
+                        .ok_or_else(|| RuntimeError::undefined_property(&get.name)),
                     _ => Err(RuntimeError::only_class_instance_has_field(
                         &object, &get.name,
                     )),
@@ -236,12 +772,10 @@ where
             }
 
             Expr::Set(set) => {
-                let object = self.visit_expr(&set.object)?;
+                let object = stack::grow(|| self.visit_expr(&set.object))?;
                 match object {
-                    Object::LoxInstance(instance_no_fields) => {
+                    Object::LoxInstance(instance) => {
                         let value = self.visit_expr(&set.value)?;
-                        // we always sure that instance is exist
-                        let instance = self.lox_instances.get_mut(&instance_no_fields).unwrap();
                         instance.set(&set.name, value.clone());
                         Ok(value)
                     }
@@ -250,26 +784,29 @@ where
                     )),
                 }
             }
-            Expr::This(this) => self.lookup_variable(e, &this.keyword),
+            Expr::This(this) => self.lookup_variable(this.id, &this.keyword),
             Expr::Super(super_call) => {
-                let distance = self.locals.get(e);
-                if distance.is_none() {
+                let slot = self.locals.get(&super_call.id);
+                if slot.is_none() {
                     todo!();
                 }
 
-                let distance = distance.unwrap();
-                let superclass = self.environment.get_at("super", *distance - 1);
-                let class = self.environment.get_at("this", *distance - 1);
+                // `this` is always declared in the scope right below `super`
+                let slot = slot.unwrap();
+                let superclass = self.environment.get_at(*slot);
+                let class = self.environment.get_at(Slot::new(slot.depth - 1, 0));
 
-                match superclass {
-                    Some(Object::Callable(LoxCallable::LoxClass(superclass))) => match class {
+                match superclass.as_ref().and_then(Object::as_callable) {
+                    Some(LoxCallable::LoxClass(superclass)) => match class {
                         Some(Object::LoxInstance(instance)) => {
-                            let method = superclass.get_method(super_call.method.lexeme());
+                            let method = superclass.get_method(super_call.method.name());
                             if method.is_none() {
                                 return Err(RuntimeError::undefined_property(&super_call.method));
                             }
-                            let method = method.map(|m| m.bind(instance)).unwrap();
-                            Ok(Object::Callable(LoxCallable::LoxFunction(method)))
+                            let method = method
+                                .map(|m| m.bind(compact::into_inner(instance)))
+                                .unwrap();
+                            Ok(Object::callable(LoxCallable::LoxFunction(method)))
                         }
                         _ => todo!(),
                     },
@@ -279,28 +816,37 @@ where
         }
     }
 
-    fn visit_stmt(&mut self, s: &Stmt) -> InterpreterResult<Object> {
+    fn visit_stmt(&mut self, s: &Stmt) -> StmtResult {
+        self.count(|stats| stats.statements += 1);
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.ran(s);
+        }
         let res = match s {
             Stmt::Expression(e) => self.visit_expr(e)?,
-            Stmt::Print(e) => {
-                let value = self.visit_expr(e)?;
-                self.write(&value.to_string())?;
+            Stmt::Print(print) => {
+                let value = self.visit_expr(&print.expression)?;
+                self.write(&value.to_string()).map_err(RuntimeError::from)?;
                 Object::Null
             }
             Stmt::Var(var) => {
                 let value = self.visit_expr(&var.expression)?;
-                let name = var.identifier.lexeme();
-                self.environment.define(name, value);
+                self.environment.define(var.identifier.symbol(), value);
                 Object::Null
             }
             Stmt::Block(block) => {
                 self.environment.move_to_inner();
+                self.count(|stats| stats.environment_pushes += 1);
                 let error = block
                     .statements
                     .iter()
-                    .map(|s| self.visit_stmt(s))
+                    .map(|s| {
+                        let result = self.visit_stmt(s);
+                        self.safe_point();
+                        result
+                    })
                     .find(|r| r.is_err());
                 self.environment.move_to_outer();
+                self.count(|stats| stats.environment_pops += 1);
                 if let Some(error) = error {
                     return error;
                 }
@@ -322,6 +868,32 @@ where
                         break;
                     }
                     self.visit_stmt(&while_statement.body)?;
+                    self.safe_point();
+                    self.step(&while_statement.keyword)?;
+                    self.check_memory(&while_statement.keyword, 0)?;
+                }
+                Object::Null
+            }
+            // the body may change what it loops over, it runs over a copy
+            Stmt::ForIn(for_in) => {
+                let items = match self.visit_expr(&for_in.iterable)? {
+                    Object::List(list) => list.elements().clone(),
+                    Object::Map(map) => map.keys(),
+                    object => {
+                        return Err(RuntimeError::not_iterable(&for_in.keyword, &object).into())
+                    }
+                };
+                for item in items {
+                    self.environment.move_to_inner();
+                    self.count(|stats| stats.environment_pushes += 1);
+                    self.environment.define(for_in.name.symbol(), item);
+                    let result = self.visit_stmt(&for_in.body);
+                    self.environment.move_to_outer();
+                    self.count(|stats| stats.environment_pops += 1);
+                    result?;
+                    self.safe_point();
+                    self.step(&for_in.keyword)?;
+                    self.check_memory(&for_in.keyword, 0)?;
                 }
                 Object::Null
             }
@@ -329,15 +901,15 @@ where
             Stmt::Function(fun) => {
                 let closure = self.environment.clone();
                 self.environment.define(
-                    fun.name.lexeme(),
-                    Object::Callable(LoxCallable::lox_function(fun.clone(), closure)),
+                    fun.name.symbol(),
+                    Object::callable(LoxCallable::lox_function(fun.clone(), closure)),
                 );
                 Object::Null
             }
 
             Stmt::Return(return_statement) => {
                 let value = self.visit_expr(&return_statement.value)?;
-                return Err(RuntimeError::return_value(&return_statement.keyword, value));
+                return Err(ControlFlow::Return(value));
             }
 
             Stmt::Class(class) => {
@@ -347,13 +919,11 @@ where
                 };
 
                 let superclass = match &superclass {
-                    Some(inner) => match &inner {
-                        &Object::Callable(LoxCallable::LoxClass(lox_class)) => {
-                            Some(lox_class.clone())
-                        }
+                    Some(inner) => match inner.as_callable() {
+                        Some(LoxCallable::LoxClass(lox_class)) => Some(lox_class.clone()),
                         _ => {
                             let name = &class.superclass.clone().unwrap().name;
-                            return Err(RuntimeError::superclass_must_be_class(name));
+                            return Err(RuntimeError::superclass_must_be_class(name).into());
                         }
                     },
                     _ => None,
@@ -361,30 +931,29 @@ where
 
                 if let Some(lox_class) = superclass.clone() {
                     self.environment.move_to_inner();
-                    self.environment
-                        .define("super", Object::Callable(LoxCallable::LoxClass(lox_class)));
+                    self.count(|stats| stats.environment_pushes += 1);
+                    self.environment.define(
+                        Symbol::SUPER,
+                        Object::callable(LoxCallable::LoxClass(lox_class)),
+                    );
                 }
 
                 let mut methods = HashMap::new();
                 for method in &class.methods {
-                    if let Stmt::Function(method) = method {
-                        let method_name = method.name.lexeme();
-                        let initializer = method_name == "init";
-                        let lox_function =
-                            LoxFunction::new(method.clone(), self.environment.clone(), initializer);
-                        methods.insert(lox_function.name().to_string(), lox_function);
-                    } else {
-                        todo!("class should only contain methods")
-                    }
+                    let initializer = method.name.name() == "init";
+                    let lox_function =
+                        LoxFunction::new(method.clone(), self.environment.clone(), initializer);
+                    methods.insert(lox_function.name().to_string(), lox_function);
                 }
 
                 if superclass.is_some() {
                     self.environment.move_to_outer();
+                    self.count(|stats| stats.environment_pops += 1);
                 };
 
                 self.environment.define(
-                    class.name.lexeme(),
-                    Object::Callable(LoxCallable::lox_class(class.clone(), superclass, methods)),
+                    class.name.symbol(),
+                    Object::callable(LoxCallable::lox_class(class.clone(), superclass, methods)),
                 );
 
                 Object::Null
@@ -402,883 +971,722 @@ mod test {
 
     use crate::{
         error::reporter::Reporter, parser::Parser, resolver::Resolver, scanner::Scanner,
-        source::SourcePos,
+        source::SourcePos, value::Value,
     };
 
     use super::*;
 
-    fn test_interpreter(source: &str, expected_output: &str) -> Result<(), std::io::Error> {
+    // runs `source` with a host configured interpreter, the errors of every step are written
+    // after what the script printed. scripts that need no host setup belong in `tests/cases`
+    fn run<W>(interpreter: &mut Interpreter<W>, source: &str) -> Result<(), std::io::Error>
+    where
+        W: std::io::Write,
+    {
         let source_pos = SourcePos::new(source);
         let reporter = Reporter::new(&source_pos);
 
-        let mut result = Vec::new();
-        let mut interpreter = Interpreter::new(&mut result);
-
         let mut scanner = Scanner::new(source);
         scanner.scan_tokens();
-        interpreter.write(&scanner.error_msg(&reporter))?;
-
         let mut parser = Parser::from(&scanner);
         let statements = parser.parse();
-        interpreter.write(&parser.error_msg(&reporter))?;
-
-        let mut resolver = Resolver::new(&mut interpreter);
+        let mut resolver = Resolver::new(interpreter);
         resolver.resolve(&statements);
-        let error_msg = resolver.error_msg(&reporter);
-        interpreter.write(&error_msg)?;
+        let mut errors = vec![
+            scanner.error_msg(&reporter),
+            parser.error_msg(&reporter),
+            resolver.error_msg(&reporter),
+        ];
 
         interpreter.interpret(&statements);
-        let error_msg = interpreter.error_msg(&reporter);
-        interpreter.write(&error_msg)?;
-
-        let result = String::from_utf8(result).unwrap();
-        assert_eq!(result.trim(), expected_output.trim());
-
+        errors.push(interpreter.error_msg(&reporter));
+        for error in errors.iter().filter(|error| !error.is_empty()) {
+            interpreter.write(error)?;
+        }
         Ok(())
     }
 
     #[test]
-    fn only_number_could_be_negation() -> Result<(), std::io::Error> {
-        let source = r#"
--1; 
--nil; 
--true; 
--false; 
--"a";
-"#;
-        let expected_output = r#"
-[line 3]: RuntimeError: Could not negative non-number
--nil;
-^
-[line 4]: RuntimeError: Could not negative non-number
--true;
-^
-[line 5]: RuntimeError: Could not negative non-number
--false;
-^
-[line 6]: RuntimeError: Could not negative non-number
--"a";
-^
-"#;
-        test_interpreter(source, expected_output)
-    }
+    fn native_clock_in_seconds() -> Result<(), std::io::Error> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
 
-    #[test]
-    fn nil_and_false_are_false() -> Result<(), std::io::Error> {
-        let source = r#"
-print !1; 
-print !nil;
-print !true;
-print !false;
-print !"a";
-"#;
-        let expected_output = r#"
-false
-true
-false
-true
-false
-"#;
-        test_interpreter(source, expected_output)
-    }
+        let source = format!(
+            "
+print clock() >= {};
+print clock() < {};
+",
+            now.as_secs(),
+            now.as_millis()
+        );
 
-    #[test]
-    fn only_number_can_subtract() -> Result<(), std::io::Error> {
-        let source = r#"
-print 1 - 3;
-"a" - true;
-true - nil;
-"#;
-        let expected_output = r#"
--2
-[line 3]: RuntimeError: Could not subtract non-number
-"a" - true;
-    ^
-[line 4]: RuntimeError: Could not subtract non-number
-true - nil;
-     ^
-"#;
-        test_interpreter(source, expected_output)
-    }
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_clock_seconds(true);
+        run(&mut interpreter, &source)?;
+        drop(interpreter);
 
-    #[test]
-    fn only_number_can_multiply() -> Result<(), std::io::Error> {
-        let source = r#"
-print 5 * 3;
-"a" * true;
-true * nil;
-"#;
-        let expected_output = r#"
-15
-[line 3]: RuntimeError: Could not multiply non-number
-"a" * true;
-    ^
-[line 4]: RuntimeError: Could not multiply non-number
-true * nil;
-     ^
-"#;
-        test_interpreter(source, expected_output)
+        assert_eq!(String::from_utf8(result).unwrap(), "true\ntrue\n");
+        Ok(())
     }
 
     #[test]
-    fn only_number_can_divide() -> Result<(), std::io::Error> {
-        let source = r#"
-print 6 / 3;
-"a" / true;
-true / nil;
-"#;
-        let expected_output = r#"
-2
-[line 3]: RuntimeError: Could not divide non-number
-"a" / true;
-    ^
-[line 4]: RuntimeError: Could not divide non-number
-true / nil;
-     ^
-"#;
-        test_interpreter(source, expected_output)
-    }
+    fn seeded_random_is_deterministic() -> Result<(), std::io::Error> {
+        let run = |seed| -> Result<String, std::io::Error> {
+            let mut result = Vec::new();
+            let mut interpreter = Interpreter::new(&mut result).with_seed(seed);
+            run(
+                &mut interpreter,
+                "print random(); print randomInt(0, 1000);",
+            )?;
+            drop(interpreter);
+            Ok(String::from_utf8(result).unwrap())
+        };
 
-    #[test]
-    fn divide_by_zero() -> Result<(), std::io::Error> {
-        let source = r#"
-1/0;
-"#;
-        let expected_output = r#"
-[line 2]: RuntimeError: Division by zero
-1/0;
- ^
-    "#;
-        test_interpreter(source, expected_output)
+        assert_eq!(run(7)?, run(7)?);
+        assert_ne!(run(7)?, run(8)?);
+        Ok(())
     }
 
     #[test]
-    fn only_number_or_string_could_add_together() -> Result<(), std::io::Error> {
+    fn register_host_native_function() -> Result<(), std::io::Error> {
         let source = r#"
-print 6 + 2;
-print "Hello" + " World";
-true + 1;
-nil + false;
+print double(21);
+print greet();
+double("x");
+double(1, 2);
 "#;
-        let expected_output = r#"
-8
-Hello World
-[line 4]: RuntimeError: Could not add non-number or non-string together
-true + 1;
-     ^
-[line 5]: RuntimeError: Could not add non-number or non-string together
-nil + false;
-    ^
-"#;
-        test_interpreter(source, expected_output)
-    }
 
-    #[test]
-    fn only_number_can_be_compare_using_ge_gt_le_lt() -> Result<(), std::io::Error> {
-        let source = r#"
-print 1 > 2;
-print 1 >= 2;
-print 2 < 3;
-print 2 <= 2;
-true > false;
-"a" > "b";
-"a" > false;
-nil > nil;
-"#;
         let expected_output = r#"
-false
-false
-true
-true
-[line 6]: RuntimeError: Could not compare non-number together
-true > false;
-     ^
-[line 7]: RuntimeError: Could not compare non-number together
-"a" > "b";
-    ^
-[line 8]: RuntimeError: Could not compare non-number together
-"a" > false;
-    ^
-[line 9]: RuntimeError: Could not compare non-number together
-nil > nil;
-    ^
+42
+hello from host
+[line 4]: RuntimeError: Expected number argument. Found string
+double("x");
+          ^
+[line 5]: RuntimeError: Expected 1 arguments. Found 2 arguments
+double(1, 2);
+           ^
 "#;
-        test_interpreter(source, expected_output)
-    }
 
-    #[test]
-    fn same_kind_object_can_be_true_or_false() -> Result<(), std::io::Error> {
-        let source = r#"
-print 1 == 1;
-print 1 != 2;
-print "Hello" == "Hello";
-print "Hello" != "World";
-print nil == nil;
-print true == false;
-"#;
-        let expected_output = r#"
-true
-true
-true
-true
-true
-false
-"#;
-        test_interpreter(source, expected_output)
-    }
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result);
+        interpreter.register_native("double", 1, |arguments| {
+            let number = f64::try_from(arguments[0].clone())?;
+            Ok(Value::from(number * 2.0))
+        });
+        interpreter.register_native("greet", 0, |_| Ok(Value::from("hello from host")));
 
-    #[test]
-    fn compare_different_kind_object_always_false() -> Result<(), std::io::Error> {
-        let source = r#"
-print nil != true;
-print 1 == true;
-"#;
-        let expected_output = r#"
-true
-false
-"#;
-        test_interpreter(source, expected_output)
+        run(&mut interpreter, source)?;
+
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(result.trim(), expected_output.trim());
+        Ok(())
     }
 
     #[test]
-    fn assignment() -> Result<(), std::io::Error> {
-        let source = r#"
-var x = 1;
-x = 2;
-print x;
-x = y;
-"#;
-        let expected_output = r#"
-2
-[line 5]: RuntimeError: Undefined variable `y`
-x = y;
-    ^
-"#;
-        test_interpreter(source, expected_output)
+    fn set_host_globals() -> Result<(), std::io::Error> {
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_strict(true);
+        interpreter.set_global("name", Value::from("lox"));
+        interpreter.set_global("sizes", Value::from(vec![1.0, 2.0]));
+        interpreter.set_global("debug", Value::from(true));
+        run(&mut interpreter, "fun greet(x) { return \"hi \" + x; }")?;
+        let greet = interpreter
+            .environment()
+            .get_global(Symbol::intern("greet"));
+        interpreter.set_global("callback", greet.unwrap());
+
+        run(
+            &mut interpreter,
+            "if (debug) print callback(name) + \" \" + str(sizes);",
+        )?;
+        drop(interpreter);
+        assert_eq!(String::from_utf8(result).unwrap(), "hi lox [1, 2]\n");
+        Ok(())
     }
 
     #[test]
-    fn nested_block() -> Result<(), std::io::Error> {
-        let source = r#"
-var a = "global a";
-var b = "global b";
-var c = "global c";
-{
-    var a = "outer a";
-    var b = "outer b";
-    {
-        var a = "inner a";
-        print a;
-        print b;
-        print c;
-    }
-    print a;
-    print b;
-    print c;
-}
-print a;
-print b;
-print c;
-"#;
-
-        let expected_output = r#"
-inner a
-outer b
-global c
-outer a
-outer b
-global c
-global a
-global b
-global c
-"#;
-
-        test_interpreter(source, expected_output)
+    fn capabilities_limit_natives() {
+        let interpreter = Interpreter::new(Vec::new()).with_capabilities(&[Capability::Time]);
+        let defined = |name| {
+            interpreter
+                .environment()
+                .get_global(Symbol::intern(name))
+                .is_some()
+        };
+        assert!(defined("clock"));
+        assert!(defined("sqrt"));
+        assert!(defined("write"));
+        assert!(!defined("readLine"));
+        assert!(!defined("env"));
+        assert!(!defined("exit"));
     }
 
     #[test]
-    fn if_then_statement() -> Result<(), std::io::Error> {
-        let source = r#"
-if (true) 
-    print "if then";
-"#;
-        let expected_output = r#"if then"#;
-        test_interpreter(source, expected_output)
+    fn exec_is_only_defined_when_asked_for() {
+        let defined = |interpreter: Interpreter<Vec<u8>>| {
+            interpreter
+                .environment()
+                .get_global(Symbol::intern("exec"))
+                .is_some()
+        };
+        assert!(!defined(Interpreter::new(Vec::new())));
+        assert!(defined(Interpreter::new(Vec::new()).with_exec(true)));
+        let sandboxed = Interpreter::new(Vec::new())
+            .with_exec(true)
+            .with_capabilities(&[Capability::Time, Capability::Io]);
+        assert!(!defined(sandboxed));
     }
 
+    #[cfg(unix)]
     #[test]
-    fn if_then_else_statement() -> Result<(), std::io::Error> {
+    fn native_exec_function() -> Result<(), std::io::Error> {
         let source = r#"
-if (false) 
-    print "if then";
-else
-    print "if then else";
+var result = exec("sh", list("-c", "echo out; exit 3"));
+print get(result, "code");
+write(get(result, "stdout"));
+exec("sh", list(1));
 "#;
-        let expected_output = r#"if then else"#;
-        test_interpreter(source, expected_output)
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_exec(true);
+        run(&mut interpreter, source)?;
+        drop(interpreter);
+
+        let result = String::from_utf8(result).unwrap();
+        assert!(result.starts_with("3\nout\n"));
+        assert!(result.contains("Expected string argument. Found number"));
+        Ok(())
     }
 
     #[test]
-    fn nested_if_then_else() -> Result<(), std::io::Error> {
-        let source = r#"
-if (false)
-    print "if then";
-    if (true)
-        print "nested if then";
-    else
-        print "nested if then else";
-"#;
-        let expected_output = r#"nested if then"#;
-        test_interpreter(source, expected_output)
+    fn interpreters_have_their_own_globals() {
+        let defined = |interpreter: &Interpreter<Vec<u8>>, name| {
+            interpreter
+                .environment()
+                .get_global(Symbol::intern(name))
+                .is_some()
+        };
+        let sandboxed = Interpreter::new(Vec::new()).with_capabilities(&[]);
+        let mut other = Interpreter::new(Vec::new());
+        other.set_global("answer", Value::from(42.0));
+        assert!(!defined(&sandboxed, "clock"));
+        assert!(!defined(&sandboxed, "answer"));
+        assert!(defined(&other, "clock"));
+        assert!(defined(&Interpreter::new(Vec::new()), "clock"));
     }
 
     #[test]
-    fn logical() -> Result<(), std::io::Error> {
+    fn native_env_and_args_functions() -> Result<(), std::io::Error> {
+        std::env::set_var("LOX_NATIVE_ENV_TEST", "lox");
         let source = r#"
-print true or 1; // true
-print false or 1; // 1
-print true and 1;  // 1
-print false and 1; // false
-print 1 and 2 and 3 or 4; // 3
+print env("LOX_NATIVE_ENV_TEST");
+print env("LOX_NATIVE_ENV_UNSET") == nil;
+print args();
+print len(args());
+env(1);
 "#;
 
         let expected_output = r#"
+lox
 true
-1
-1
-false
-3
+["input.txt", "-v"]
+2
+[line 6]: RuntimeError: Expected string argument. Found number
+env(1);
+     ^
 "#;
 
-        test_interpreter(source, expected_output)
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result)
+            .with_args(vec![String::from("input.txt"), String::from("-v")]);
+        run(&mut interpreter, source)?;
+
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(result.trim(), expected_output.trim());
+        Ok(())
     }
 
     #[test]
-    fn while_statement() -> Result<(), std::io::Error> {
+    fn collect_unreachable_environments_and_instances() -> Result<(), std::io::Error> {
         let source = r#"
-var x = 1;
-var y = 100;
-while (x <= 5) {
-    print y;
-    y = y + 1;
-    x = x + 1;
+class Node {}
+fun cycle() {
+    fun f() { return f; }
+    var node = Node();
+    node.self = node;
+    node.f = f;
 }
-"#;
-
-        let expected_output = r#"
-100
-101
-102
-103
-104
-"#;
-
-        test_interpreter(source, expected_output)
+fun counter() {
+    var count = 0;
+    fun increment() {
+        count = count + 1;
+        return count;
     }
-
-    #[test]
-    fn function_call() -> Result<(), std::io::Error> {
-        let source = r#"
-fun f(x) {
-    var y = 1;
-    print x + y;
+    return increment;
 }
-
-f(2);
-f(5);
+var kept = counter();
+var node = Node();
+node.next = Node();
+for (var i = 0; i < 10; i = i + 1) cycle();
 "#;
 
-        let expected_output = r#"
-3
-6
-"#;
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_gc_threshold(usize::MAX);
+        run(&mut interpreter, source)?;
 
-        test_interpreter(source, expected_output)
-    }
+        assert_eq!(interpreter.environment.heap().borrow().instances.len(), 12);
+        let tracked = interpreter.environment.heap().borrow().environments.len();
+        let freed = interpreter.collect_garbage();
+        assert_eq!(interpreter.environment.heap().borrow().instances.len(), 2);
+        assert!(interpreter.environment.heap().borrow().environments.len() < tracked);
+        assert!(freed > 10);
 
-    #[test]
-    fn function_call_arguments_mismatch() -> Result<(), std::io::Error> {
         let source = r#"
-fun f(x) {print x + 1;}
-f(3, 4);
+print kept();
+print kept();
+print node.next;
 "#;
+        run(&mut interpreter, source)?;
+        drop(interpreter);
 
-        let expected_output = r#"
-[line 3]: RuntimeError: Expected 1 arguments. Found 2 arguments
-f(3, 4);
-      ^
-"#;
-
-        test_interpreter(source, expected_output)
-    }
-
-    #[test]
-    fn navtive_clock_function() -> Result<(), std::io::Error> {
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap();
-
-        let source = format!(
-            "
-var x = clock();
-print x >= {};
-",
-            now.as_millis()
+        assert_eq!(
+            String::from_utf8_lossy(&result),
+            "1\n2\n<Node instance, id 1>\n"
         );
-
-        let expected_output = r#"true"#;
-
-        test_interpreter(&source, expected_output)
+        Ok(())
     }
 
     #[test]
-    fn return_statement() -> Result<(), std::io::Error> {
+    fn collect_cycles_keeps_referenced_environments() -> Result<(), std::io::Error> {
         let source = r#"
-// normal return
-fun f1(x) {
-    return x + 5;
+class Node {}
+fun cycle() {
+    fun f() { return f; }
 }
-print f1(2); // 7
-
-// nested return
-fun f2(x) {
-    if (x > 5) 
-        return 5;
-    else 
-        return x;
+fun counter() {
+    var count = 0;
+    fun increment() {
+        count = count + 1;
+        return count;
+    }
+    return increment;
 }
-print f2(8); // 5
-print f2(1); // 1
-
-// no return
-fun f2(x) {
-    print 3;
+var global = counter();
+var node = Node();
+node.field = counter();
+{
+    for (var i = 0; i < 10; i = i + 1) cycle();
+    global();
+    node.field();
 }
-print f2(5); // 3 and nothing
 "#;
 
-        let expected_output = r#"
-7
-5
-1
-3
-"#;
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_gc_threshold(usize::MAX);
+        run(&mut interpreter, source)?;
 
-        test_interpreter(source, expected_output)
-    }
+        // each call to `cycle` leaves its parameter and body environments, the body holds `f`
+        assert_eq!(interpreter.collect_garbage(), 20);
+        assert_eq!(interpreter.collect_garbage(), 0);
 
-    #[test]
-    fn fibonacci() -> Result<(), std::io::Error> {
         let source = r#"
-fun fib(n) {
-    if (n <= 1) return n;
-    return fib(n - 1) + fib(n - 2);
-}
-
-for (var i = 1; i < 9; i = i + 1) {
-    print fib(i);
-}
+print global();
+print node.field();
 "#;
+        run(&mut interpreter, source)?;
+        drop(interpreter);
 
-        let expected_output = r#"
-1
-1
-2
-3
-5
-8
-13
-21
-"#;
-        test_interpreter(source, expected_output)
+        assert_eq!(String::from_utf8_lossy(&result), "2\n2\n");
+        Ok(())
     }
 
     #[test]
-    fn closure() -> Result<(), std::io::Error> {
+    fn collect_maps_in_cycles() -> Result<(), std::io::Error> {
         let source = r#"
-fun makeCounter() {
-  var i = 0;
-  fun count() {
-    i = i + 1;
-    print i;
-  }
-  return count;
+class Box {}
+fun cycle() {
+    var m = newMap();
+    var box = Box();
+    box.map = m;
+    set(m, "box", box);
 }
-var counter = makeCounter();
-counter(); // 1.
-counter(); // 2.
+var kept = newMap();
+var box = Box();
+box.map = kept;
+set(kept, "box", box);
+for (var i = 0; i < 3; i = i + 1) cycle();
 "#;
 
-        let expected_output = r#"
-1
-2
-"#;
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_gc_threshold(usize::MAX);
+        run(&mut interpreter, source)?;
 
-        test_interpreter(source, expected_output)
-    }
+        // each call leaves the map and the instance holding it
+        assert_eq!(interpreter.collect_garbage(), 6);
+        assert_eq!(interpreter.collect_garbage(), 0);
 
-    #[test]
-    fn class_declaration() -> Result<(), std::io::Error> {
-        let source = r#"
-class DevonshireCream {
-  serveOn() {
-    return "Scones";
-  }
-}
-print DevonshireCream;
-"#;
+        run(&mut interpreter, "print get(kept, \"box\").map == kept;")?;
+        drop(interpreter);
 
-        let expected_output = r#"
-<class DevonshireCream>
-"#;
-
-        test_interpreter(source, expected_output)
+        assert_eq!(String::from_utf8_lossy(&result), "true\n");
+        Ok(())
     }
 
     #[test]
-    fn class_instance_declaration() -> Result<(), std::io::Error> {
+    fn collect_lists_and_maps_holding_themselves() -> Result<(), std::io::Error> {
         let source = r#"
-class Hello {}
-var instance1 = Hello();
-print instance1;
-var instance2 = Hello();
-print instance2;
-var instance3 = Hello();
-print instance3;
-"#;
-
-        let expected_output = r#"
-<Hello instance, id 0>
-<Hello instance, id 1>
-<Hello instance, id 2>
+var kept = list();
+push(kept, kept);
+for (var i = 0; i < 10; i = i + 1) {
+    var l = list();
+    push(l, l);
+    var m = newMap();
+    set(m, "m", m);
+}
 "#;
 
-        test_interpreter(source, expected_output)
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_gc_threshold(usize::MAX);
+        run(&mut interpreter, source)?;
+
+        assert_eq!(interpreter.collect_garbage(), 20);
+        assert_eq!(interpreter.collect_garbage(), 0);
+        run(&mut interpreter, "print len(kept);")?;
+        drop(interpreter);
+        assert_eq!(String::from_utf8_lossy(&result), "1\n");
+
+        // lists and maps count toward the threshold too
+        let mut interpreter = Interpreter::new(Vec::new()).with_gc_threshold(10);
+        run(&mut interpreter, source)?;
+        assert!(interpreter.environment.heap().borrow().lists.alive_count() < 10);
+        Ok(())
     }
 
     #[test]
-    fn get_field_on_non_class_instance() -> Result<(), std::io::Error> {
+    fn collect_classes_declared_in_functions() -> Result<(), std::io::Error> {
         let source = r#"
-var x = 1;
-x.name;
-"#;
-
-        let expected_output = r#"
-[line 3]: RuntimeError: `1` is not class instance. It cannot have field `name`
-x.name;
-  ^^^^
-"#;
-
-        test_interpreter(source, expected_output)
+fun make() {
+    class Local {
+        me() { return Local; }
     }
-
-    #[test]
-    fn set_field_on_non_class_instance() -> Result<(), std::io::Error> {
-        let source = r#"
-var x = 1;
-x.name = 2;
+    return Local();
+}
+var kept = make();
+for (var i = 0; i < 5; i = i + 1) make();
 "#;
 
-        let expected_output = r#"
-[line 3]: RuntimeError: `1` is not class instance. It cannot have field `name`
-x.name = 2;
-  ^^^^
-"#;
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_gc_threshold(usize::MAX);
+        run(&mut interpreter, source)?;
 
-        test_interpreter(source, expected_output)
-    }
+        // each discarded call leaves its parameter and body environments,
+        // the body holds `Local`, whose method closed over it
+        assert_eq!(interpreter.collect_garbage(), 10);
 
-    #[test]
-    fn undefined_property() -> Result<(), std::io::Error> {
         let source = r#"
-class Hello {}
-var hello = Hello();
-print hello.name;
+print kept.me();
+print kept.me()();
 "#;
+        run(&mut interpreter, source)?;
+        drop(interpreter);
 
-        let expected_output = r#"
-[line 4]: RuntimeError: Undefined property `name`
-print hello.name;
-            ^^^^
-"#;
-
-        test_interpreter(source, expected_output)
+        assert_eq!(
+            String::from_utf8_lossy(&result),
+            "<class Local>\n<Local instance, id 6>\n"
+        );
+        Ok(())
     }
 
     #[test]
-    fn get_set_field_attribute_on_class_instance() -> Result<(), std::io::Error> {
+    fn collect_on_every_statement() -> Result<(), std::io::Error> {
         let source = r#"
-class Hello {}
-var hello = Hello();
-hello.name = "dqk";
-print hello.name;
-"#;
-
-        let expected_output = r#"
-dqk
-"#;
-
-        test_interpreter(source, expected_output)
+class A {
+    greet() {
+        fun inner() { return "hi " + this.name; }
+        return inner;
     }
-
-    #[test]
-    fn method_on_class() -> Result<(), std::io::Error> {
-        let source = r#"
-class Bacon {
-    eat() {
-      print "Crunch crunch crunch!";
+}
+class B : A {
+    greet() {
+        var greet = super.greet();
+        return greet;
     }
 }
-  
-Bacon().eat(); // Prints "Crunch crunch crunch!".
-"#;
-
-        let expected_output = r#"
-Crunch crunch crunch!
-        "#;
-
-        test_interpreter(source, expected_output)
+fun counter() {
+    var count = 0;
+    fun increment() {
+        count = count + 1;
+        return count;
     }
-
-    #[test]
-    fn this_bind_to_variable() -> Result<(), std::io::Error> {
-        let source = r#"
-class Cake {
-  taste() {
-    var adjective = "delicious";
-    print "The " + this.flavor + " cake is " + adjective + "!";
-  }
+    return increment;
 }
-
-var cake = Cake();
-cake.flavor = "German chocolate";
-cake.taste(); // Prints "The German chocolate cake is delicious!".
+fun apply(f, g) {
+    var result = f() + g();
+    return result;
+}
+fun main() {
+    var b = B();
+    b.name = "b";
+    var greet = b.greet();
+    var c = counter();
+    for (var i = 0; i < 5; i = i + 1) {
+        fun cycle() { return cycle; }
+        c();
+    }
+    print greet();
+    print apply(c, counter());
+    print b.greet()();
+}
+main();
 "#;
 
-        let expected_output = r#"
-The German chocolate cake is delicious!
-"#;
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_gc_threshold(0);
+        run(&mut interpreter, source)?;
+        drop(interpreter);
 
-        test_interpreter(source, expected_output)
+        assert_eq!(String::from_utf8_lossy(&result), "hi b\n7\nhi b\n");
+        Ok(())
     }
 
     #[test]
-    fn this_support_closure() -> Result<(), std::io::Error> {
+    fn native_gc_collects_at_next_statement() -> Result<(), std::io::Error> {
         let source = r#"
-class Human {
-  getCallback() {
-    fun localFunction() {
-      print this.name;
-    }
-
-    return localFunction;
-  }
+class A {}
+fun make() {
+    var a = A();
+    a.self = a;
 }
-
-var thing = Human();
-thing.name = "dqk";
-var callback = thing.getCallback();
-callback();
+make();
+make();
+gc();
+print "done";
 "#;
 
-        let expected_output = r#"
-dqk
-"#;
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_gc_threshold(usize::MAX);
+        run(&mut interpreter, source)?;
 
-        test_interpreter(source, expected_output)
+        assert_eq!(interpreter.environment.heap().borrow().instances.len(), 0);
+        drop(interpreter);
+        assert_eq!(String::from_utf8_lossy(&result), "done\n");
+        Ok(())
     }
 
     #[test]
-    fn call_init_inside_class() -> Result<(), std::io::Error> {
+    fn call_depth_limit() -> Result<(), std::io::Error> {
         let source = r#"
-class Hello {
-    init(name) {
-        print "hello " + name;
-    }
+fun depth(n) {
+    if (n == 0) return 0;
+    return depth(n - 1) + 1;
 }
-var hello = Hello("dqk");
+print depth(9);
+print depth(10);
+print depth(9);
 "#;
 
         let expected_output = r#"
-hello dqk
+9
+9
+[line 4]: RuntimeError: Stack overflow: maximum call depth 10 exceeded
+    return depth(n - 1) + 1;
+                      ^
+note: in function `depth`
 "#;
 
-        test_interpreter(source, expected_output)
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_max_call_depth(10);
+        run(&mut interpreter, source)?;
+
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(result.trim(), expected_output.trim());
+        Ok(())
     }
 
     #[test]
-    fn implicit_return_this() -> Result<(), std::io::Error> {
+    fn execution_budget_stops_the_script() -> Result<(), std::io::Error> {
         let source = r#"
-class Foo {
-  init() {
-    print this;
-  }
+print "start";
+fun spin() {
+    for (;;) {}
 }
-
-var foo = Foo();
-print foo.init();
+spin();
+print "never";
 "#;
 
         let expected_output = r#"
-<Foo instance, id 0>
-<Foo instance, id 0>
-<Foo instance, id 0>
+start
+[line 4]: RuntimeError: Script exceeded execution budget
+    for (;;) {}
+    ^^^
+note: in function `spin`
 "#;
 
-        test_interpreter(source, expected_output)
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_budget(Some(100), None);
+        run(&mut interpreter, source)?;
+
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(result.trim(), expected_output.trim());
+        Ok(())
     }
 
     #[test]
-    fn empty_return_inside_init_should_return_this() -> Result<(), std::io::Error> {
+    fn interrupt_stops_the_script() -> Result<(), std::io::Error> {
         let source = r#"
-class Foo {
-  init() {
-    this.x = 1;
-    return;
-  }
-}
-
-var foo = Foo();
-print foo.x;
+print "start";
+while (true) {}
+print "never";
 "#;
 
         let expected_output = r#"
-1
+start
+[line 3]: RuntimeError: Interrupted
+while (true) {}
+^^^^^
 "#;
 
-        test_interpreter(source, expected_output)
+        let interrupt = Arc::new(AtomicBool::new(true));
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_interrupt(interrupt.clone());
+        // an interrupt before the script starts is dropped
+        let signal = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            interrupt.store(true, Ordering::Relaxed);
+        });
+        run(&mut interpreter, source)?;
+        signal.join().unwrap();
+
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(result.trim(), expected_output.trim());
+        Ok(())
     }
 
     #[test]
-    fn empty_return_inside_init_should_return_early() -> Result<(), std::io::Error> {
+    fn memory_limit_stops_the_script() -> Result<(), std::io::Error> {
         let source = r#"
-class Foo {
-  init() {
-    return;
-    this.x = 1;
-  }
+var small = "";
+for (var i = 0; i < 1000; i = i + 1) {
+    small = small + "x";
 }
-
-var foo = Foo();
-print foo.x;
+print len(small);
+var s = "ab";
+while (true) {
+    s = s + s;
+}
+print "never";
 "#;
 
         let expected_output = r#"
-[line 10]: RuntimeError: Undefined property `x`
-print foo.x;
+1000
+[line 9]: RuntimeError: Memory limit of 1000000 bytes exceeded
+    s = s + s;
           ^
 "#;
 
-        test_interpreter(source, expected_output)
-    }
-
-    #[test]
-    fn superclass_must_be_class() -> Result<(), std::io::Error> {
-        let source = r#"
-class Hello : NotAClass {}
-"#;
-
-        let expected_output = r#"
-[line 2]: RuntimeError: Undefined variable `NotAClass`
-class Hello : NotAClass {}
-              ^^^^^^^^^
-"#;
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_memory_limit(Some(1_000_000));
+        run(&mut interpreter, source)?;
 
-        test_interpreter(source, expected_output)
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(result.trim(), expected_output.trim());
+        Ok(())
     }
 
     #[test]
-    fn subclass_can_inherit_valid_superclass() -> Result<(), std::io::Error> {
-        let source = r#"
-class A {}
-class B : A {}
-class C : B {}
-class D : A {}
-"#;
-
-        let expected_output = r#""#;
-
-        test_interpreter(source, expected_output)
+    fn memory_limit_counts_what_containers_grow_by() -> Result<(), std::io::Error> {
+        let cases = [
+            (
+                r#"
+var l = list();
+for (var i = 0; i < 1000000; i = i + 1) push(l, "a");
+"#,
+                r#"
+[line 3]: RuntimeError: Memory limit of 1000000 bytes exceeded
+for (var i = 0; i < 1000000; i = i + 1) push(l, "a");
+                                                   ^
+"#,
+            ),
+            (
+                r#"
+var m = newMap();
+for (var i = 0; i < 1000000; i = i + 1) set(m, i, "a");
+"#,
+                r#"
+[line 3]: RuntimeError: Memory limit of 1000000 bytes exceeded
+for (var i = 0; i < 1000000; i = i + 1) set(m, i, "a");
+                                                     ^
+"#,
+            ),
+        ];
+
+        for (source, expected_output) in cases {
+            let mut result = Vec::new();
+            let mut interpreter = Interpreter::new(&mut result).with_memory_limit(Some(1_000_000));
+            run(&mut interpreter, source)?;
+            drop(interpreter);
+
+            let result = String::from_utf8(result).unwrap();
+            assert_eq!(result.trim(), expected_output.trim());
+        }
+        Ok(())
     }
 
     #[test]
-    fn subclass_inherit_method_from_superclass() -> Result<(), std::io::Error> {
+    fn profiler_counts_calls() -> Result<(), std::io::Error> {
         let source = r#"
-class A {
-    f() {
-        print "`f` is called from super class";
-    }
-
-    g() {
-        print "`g` is called from super class";
-    }
-}
-
-class B : A {
-    g() {
-        print "`g` is called from sub class";
-    }
+fun fib(n) {
+    if (n < 2) return n;
+    return fib(n - 1) + fib(n - 2);
 }
-
-B().g(); // call g from sub
-B().f(); // call f from super
+print fib(10);
+print len("abc");
 "#;
 
-        let expected_output = r#"
-`g` is called from sub class
-`f` is called from super class
-"#;
-
-        test_interpreter(source, expected_output)
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_profiler(true);
+        run(&mut interpreter, source)?;
+
+        let report = interpreter.profile_report().unwrap();
+        let calls = |function: &str| {
+            report
+                .lines()
+                .find(|line| line.ends_with(function))
+                .and_then(|line| line.split_whitespace().next())
+                .map(str::to_string)
+        };
+        assert_eq!(calls("<fn fib>").as_deref(), Some("177"));
+        assert_eq!(calls("<fn len>").as_deref(), Some("1"));
+        assert_eq!(report.lines().count(), 3);
+        Ok(())
     }
 
     #[test]
-    fn subclass_call_the_right_super() -> Result<(), std::io::Error> {
+    fn stats_count_work_done() -> Result<(), std::io::Error> {
         let source = r#"
-class A {
-    method() { print "A method"; }
+fun add(a, b) {
+    return a + b;
 }
-
-class B : A {
-    method() { print "B method"; }
-    test() { super.method(); }
+var s = "a" + "b";
+{
+    print add(1, 2);
 }
-
-class C : B {}
-
-C().test();
-"#;
-
-        let expected_output = r#"
-A method
 "#;
 
-        test_interpreter(source, expected_output)
-    }
-
-    #[test]
-    fn subclass_call_undefined_method() -> Result<(), std::io::Error> {
-        let source = r#"
-class A {
-    method() { print "A method"; }
-}
-
-class B : A {
-    method() { print "B method"; }
-    test() { super.method(); }
-}
-
-class C : B {}
-
-C().no_method();
+        let expected_report = r#"
+statements executed                6
+expressions evaluated             10
+function calls                     1
+allocations                        4
+environment pushes                 3
+environment pops                   3
 "#;
 
-        let expected_output = r#"
-[line 13]: RuntimeError: Undefined property `no_method`
-C().no_method();
-    ^^^^^^^^^
-"#;
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result).with_stats(true);
+        run(&mut interpreter, source)?;
 
-        test_interpreter(source, expected_output)
+        let report = interpreter.stats_report().unwrap();
+        assert_eq!(report.trim(), expected_report.trim());
+        Ok(())
     }
 }
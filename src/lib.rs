@@ -1,5 +1,14 @@
+// without the command line, most of what runs scripts from files is not used
+#![cfg_attr(target_family = "wasm", allow(dead_code))]
+
 pub(crate) mod lox;
 
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod prompt;
+
+pub(crate) mod color;
+
+#[cfg(not(target_family = "wasm"))]
 pub(crate) mod cli;
 
 pub(crate) mod token;
@@ -14,8 +23,24 @@ pub(crate) mod parser;
 
 pub(crate) mod ast_repr;
 
+pub(crate) mod call_graph;
+
+pub(crate) mod formatter;
+
+pub(crate) mod test_runner;
+
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod lsp;
+
+#[cfg(test)]
+mod snapshot;
+
 pub(crate) mod object;
 
+pub(crate) mod compact;
+
+pub(crate) mod shared;
+
 pub(crate) mod visitor;
 
 pub(crate) mod interpreter;
@@ -34,4 +59,48 @@ pub(crate) mod source;
 
 pub(crate) mod class;
 
+pub(crate) mod list;
+
+pub(crate) mod map;
+
+pub(crate) mod value;
+
+pub(crate) mod sink;
+
+pub(crate) mod native;
+
+pub(crate) mod cache;
+
+pub(crate) mod symbol;
+
+pub(crate) mod gc;
+
+pub(crate) mod budget;
+
+pub(crate) mod memory;
+
+pub(crate) mod profile;
+
+pub(crate) mod stats;
+
+pub(crate) mod time;
+
+pub(crate) mod stack;
+
+pub(crate) mod timing;
+
+pub(crate) mod coverage;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(not(target_family = "wasm"))]
 pub use cli::exec;
+
+// the embedding api
+pub use error::diagnostics::{Diagnostic, Location, LoxDiagnostics};
+pub use interpreter::Reader;
+pub use lox::{Lox, LoxBuilder};
+pub use native::{host::HostFn, Capability};
+pub use sink::LineSink;
+pub use value::Value;
@@ -0,0 +1,94 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    object::{print_nested, Object},
+    shared::{Mutable, Ref, RefMut, Shared},
+};
+
+// lists are shared by reference, like class instances
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LoxList {
+    elements: Shared<Mutable<Vec<Object>>>,
+}
+
+impl LoxList {
+    pub fn new(elements: Vec<Object>) -> Self {
+        Self {
+            elements: Shared::new(Mutable::new(elements)),
+        }
+    }
+
+    // a new list holding the same elements
+    pub fn copy(&self) -> Self {
+        Self::new(self.elements().clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.borrow().len()
+    }
+
+    pub fn elements(&self) -> Ref<'_, Vec<Object>> {
+        self.elements.borrow()
+    }
+
+    // natives changing the list must not call back into the script while holding this
+    pub fn elements_mut(&self) -> RefMut<'_, Vec<Object>> {
+        self.elements.borrow_mut()
+    }
+
+    // the storage every copy shares, tracked by the collector
+    pub fn storage(&self) -> &Shared<Mutable<Vec<Object>>> {
+        &self.elements
+    }
+
+    // identity of the shared storage
+    pub fn address(&self) -> usize {
+        Shared::as_ptr(&self.elements) as usize
+    }
+
+    pub fn strong_count(&self) -> usize {
+        Shared::strong_count(&self.elements)
+    }
+
+    // drops the elements outside of the borrow, they might hold this list again
+    pub fn clear(&self) {
+        let elements = std::mem::take(&mut *self.elements.borrow_mut());
+        drop(elements);
+    }
+}
+
+impl From<Shared<Mutable<Vec<Object>>>> for LoxList {
+    fn from(elements: Shared<Mutable<Vec<Object>>>) -> Self {
+        Self { elements }
+    }
+}
+
+impl PartialEq for LoxList {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.elements, &other.elements)
+    }
+}
+
+impl Hash for LoxList {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Shared::as_ptr(&self.elements).hash(state)
+    }
+}
+
+impl fmt::Display for LoxList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        print_nested(f, self.address(), "[...]", |f| {
+            let elements = self
+                .elements
+                .borrow()
+                .iter()
+                .map(|element| element.repr())
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "[{elements}]")
+        })
+    }
+}
@@ -1,70 +1,630 @@
-use std::{io::StdoutLock, process::exit};
+use std::{
+    collections::BTreeMap,
+    io::{StdoutLock, Write},
+    process::exit,
+    time::Duration,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
 use crate::{
-    error::reporter::{ErrorReporter, Reporter},
-    interpreter::Interpreter,
+    ast_repr::{self, AstFormat},
+    cache,
+    call_graph::{CallGraph, GraphFormat},
+    color,
+    error::{
+        diagnostics::{Diagnostic, LoxDiagnostics},
+        reporter::{DiagnosticFormat, ErrorPos, ErrorReporter, Reporter, DEFAULT_TAB_WIDTH},
+    },
+    formatter,
+    interpreter::{Interpreter, Reader},
+    native::{host::HostFn, Capability},
     object::Object,
     parser::Parser,
     resolver::Resolver,
-    scanner::Scanner,
-    source::SourcePos,
+    scanner::{self, Scanner},
+    shared::Weak,
+    source::{self, CharPos, Lines, SourcePos},
+    stmt::{Function, Stmt},
+    symbol::Symbol,
+    test_runner::{self, Expectations, Outcome},
+    time::Instant,
+    token::Token,
+    value::Value,
 };
 
-pub fn run_file(path: &std::path::PathBuf) -> Result<()> {
-    let mut lox = Lox::default();
+#[cfg(not(target_family = "wasm"))]
+use crate::prompt::{Input, Prompt};
+
+const STDIN_PATH: &str = "-";
+
+// how errors are reported
+#[derive(Debug, Clone, Copy)]
+pub struct ReportStyle {
+    pub colored: bool,
+    // lines shown around the lines in error
+    pub context: usize,
+    // warnings stop the script and fail it like errors
+    pub deny_warnings: bool,
+    // errors reported at once, 0 reports all of them
+    pub max_errors: usize,
+    pub format: DiagnosticFormat,
+    // columns of a tab in the lines shown
+    pub tab_width: usize,
+}
+
+impl Default for ReportStyle {
+    fn default() -> Self {
+        Self {
+            colored: false,
+            context: 0,
+            deny_warnings: false,
+            max_errors: 0,
+            format: DiagnosticFormat::default(),
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+}
+
+pub fn run_file(
+    path: &std::path::PathBuf,
+    interpreter: Interpreter<StdoutLock<'static>>,
+    style: ReportStyle,
+) -> Result<()> {
+    let mut lox = Lox::new(interpreter)
+        .with_style(style)
+        .with_path(path)
+        .whole_script();
+    if path.extension().is_some_and(|ext| ext == cache::EXTENSION) {
+        run_compiled(&mut lox, path)?;
+    } else if path
+        .extension()
+        .is_some_and(|ext| ext == cache::AST_EXTENSION)
+    {
+        run_exported(&mut lox, path)?;
+    } else {
+        let source = read_source(path)?;
+        lox.run(&source)?;
+    }
+    lox.finish()
+}
+
+// what errors in `path` are reported at, the script read from stdin has no path
+fn script_path(path: &std::path::Path) -> Option<String> {
+    (path.as_os_str() != STDIN_PATH).then(|| path.display().to_string())
+}
+
+// `-` reads the whole program from stdin
+fn read_source(path: &std::path::PathBuf) -> Result<String> {
+    if path.as_os_str() == STDIN_PATH {
+        return std::io::read_to_string(std::io::stdin()).context("Could not read stdin");
+    }
+    std::fs::read_to_string(path).with_context(|| format!("Could not read file `{path:?}`"))
+}
+
+// run code given on the command line like a file
+pub fn run_source(
+    source: &str,
+    interpreter: Interpreter<StdoutLock<'static>>,
+    style: ReportStyle,
+) -> Result<()> {
+    let mut lox = Lox::new(interpreter).with_style(style).whole_script();
+    lox.run(source)?;
+    lox.finish()
+}
+
+// caches are stale after upgrading or once the `.lox` script next to them changed, they are
+// rebuilt from that script or else from the stored source
+fn run_compiled<W>(lox: &mut Lox<W>, path: &std::path::PathBuf) -> Result<()>
+where
+    W: std::io::Write,
+{
+    let bytes = std::fs::read(path).with_context(|| format!("Could not read file `{path:?}`"))?;
+    let script = std::fs::read_to_string(path.with_extension("lox")).ok();
+    let compiled = cache::load(&bytes, script)
+        .with_context(|| format!("Could not load compiled file `{path:?}`"))?;
+    match compiled.tokens {
+        Some(tokens) => {
+            lox.run_tokens(&compiled.source, tokens)?;
+        }
+        None => {
+            if let Some(scanner) = lox.scan(&compiled.source)? {
+                write_compiled(path, &compiled.source, &scanner)?;
+                lox.run_tokens(&compiled.source, scanner.into_tokens())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// trees exported by another version are parsed again from the stored source
+fn run_exported<W>(lox: &mut Lox<W>, path: &std::path::PathBuf) -> Result<()>
+where
+    W: std::io::Write,
+{
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("Could not read file `{path:?}`"))?;
+    let exported = cache::import_ast(&text)
+        .with_context(|| format!("Could not load syntax tree `{path:?}`"))?;
+    match exported.statements {
+        Some(statements) => lox.run_statements(&exported.source, &statements)?,
+        None => lox.run(&exported.source)?,
+    };
+    Ok(())
+}
+
+// scan `path` and write the tokens next to it, without running the script
+pub fn compile_file(path: &std::path::PathBuf, style: ReportStyle) -> Result<()> {
+    let mut lox = Lox::new(Interpreter::default())
+        .with_style(style)
+        .with_path(path);
     let source =
         std::fs::read_to_string(path).with_context(|| format!("Could not read file `{path:?}`"))?;
-    lox.run(&source)?;
-    if lox.had_scan_error || lox.had_parse_error || lox.had_resolve_error {
+    match lox.scan(&source)? {
+        Some(scanner) => write_compiled(&path.with_extension(cache::EXTENSION), &source, &scanner),
+        None => exit(65),
+    }
+}
+
+// scan `path` and print its tokens, without parsing or running it
+pub fn print_tokens(path: &std::path::PathBuf, trivia: bool, style: ReportStyle) -> Result<()> {
+    let mut lox = Lox::new(Interpreter::default())
+        .with_style(style)
+        .with_path(path);
+    let source = read_source(path)?;
+    match lox.scan(&source)? {
+        Some(scanner) => Ok(lox.interpreter.write(scanner.listing(trivia).trim_end())?),
+        None => exit(65),
+    }
+}
+
+// parse `path` and print its syntax tree, without resolving or running it
+pub fn print_ast(path: &std::path::PathBuf, format: AstFormat, style: ReportStyle) -> Result<()> {
+    let mut lox = Lox::new(Interpreter::default())
+        .with_style(style)
+        .with_path(path);
+    let source = read_source(path)?;
+    let source_pos = SourcePos::new(&source);
+    let reporter = lox.reporter(&source_pos);
+    match lox.parse(&reporter, &source, 0)? {
+        Some(statements) => Ok(lox
+            .interpreter
+            .write(ast_repr::render(&source, &statements, format).trim_end())?),
+        None => exit(65),
+    }
+}
+
+pub fn print_call_graph(
+    path: &std::path::PathBuf,
+    format: GraphFormat,
+    style: ReportStyle,
+) -> Result<()> {
+    let mut lox = Lox::new(Interpreter::default())
+        .with_style(style)
+        .with_path(path);
+    let source = read_source(path)?;
+    let source_pos = SourcePos::new(&source);
+    let reporter = lox.reporter(&source_pos);
+    match lox.parse(&reporter, &source, 0)? {
+        Some(statements) => Ok(lox
+            .interpreter
+            .write(CallGraph::new(&statements).render(format).trim_end())?),
+        None => exit(65),
+    }
+}
+
+// top level functions and classes named in `unused` are left out
+fn prune(statements: &[Stmt], unused: &[Symbol]) -> Vec<Stmt> {
+    statements
+        .iter()
+        .filter(|s| {
+            let name = match s {
+                Stmt::Function(function) => &function.name,
+                Stmt::Class(class) => &class.name,
+                _ => return true,
+            };
+            !unused.contains(&name.symbol())
+        })
+        .cloned()
+        .collect()
+}
+
+// what `lox fmt` does with the formatted scripts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatMode {
+    Print,
+    // only tell which scripts are not formatted
+    Check,
+    Write,
+}
+
+// format every script in `paths`, exiting with 65 if one could not be parsed
+// and with 1 if checking found one that is not formatted
+pub fn format_files(
+    paths: &[std::path::PathBuf],
+    indent: usize,
+    mode: FormatMode,
+    style: ReportStyle,
+) -> Result<()> {
+    let mut lox = Lox::new(Interpreter::default()).with_style(style);
+    let mut unparsed = false;
+    let mut unformatted = false;
+    for path in paths {
+        if mode == FormatMode::Write && path.as_os_str() == STDIN_PATH {
+            bail!("Could not write the script read from stdin, print it instead");
+        }
+        let source = read_source(path)?;
+        lox.path = script_path(path);
+        let source_pos = SourcePos::new(&source);
+        let reporter = lox.reporter(&source_pos);
+        let Some(statements) = lox.parse(&reporter, &source, 0)? else {
+            unparsed = true;
+            continue;
+        };
+        let formatted = formatter::format(&statements, scanner::comments(&source), indent);
+        match mode {
+            FormatMode::Print => lox.interpreter.write_str(&formatted)?,
+            FormatMode::Check if formatted != source => {
+                unformatted = true;
+                eprintln!("{} is not formatted", path.display());
+            }
+            FormatMode::Check => (),
+            FormatMode::Write if formatted != source => std::fs::write(path, formatted)
+                .with_context(|| format!("Could not write file `{path:?}`"))?,
+            FormatMode::Write => (),
+        }
+    }
+    lox.interpreter.flush()?;
+    if unparsed {
         exit(65);
-    } else if lox.had_runtime_error {
-        exit(70);
+    }
+    if unformatted {
+        exit(1);
     }
     Ok(())
 }
 
+// run every `.lox` file in `paths` and under them, checking what it does against its
+// expectation comments. exits with 1 if one failed
+pub fn run_tests(
+    paths: &[std::path::PathBuf],
+    ignore_messages: bool,
+    matrix: bool,
+    book: bool,
+) -> Result<()> {
+    let files = test_runner::test_files(paths).context("Could not list test files")?;
+    let mut out = std::io::stdout().lock();
+    // passed and failed tests in every directory
+    let mut directories: BTreeMap<&std::path::Path, (usize, usize)> = BTreeMap::new();
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for path in &files {
+        let source = read_source(path)?;
+        let Some(expectations) = Expectations::parse(&source) else {
+            skipped += 1;
+            continue;
+        };
+        let failures = expectations.check(&run_captured(&source, book), ignore_messages);
+        let directory = directories
+            .entry(path.parent().unwrap_or(path))
+            .or_default();
+        if failures.is_empty() {
+            passed += 1;
+            directory.0 += 1;
+            continue;
+        }
+        failed += 1;
+        directory.1 += 1;
+        writeln!(out, "FAIL {}", path.display())?;
+        for failure in failures {
+            writeln!(out, "  {}", failure.replace('\n', "\n  "))?;
+        }
+    }
+    if matrix {
+        let width = directories
+            .keys()
+            .map(|directory| directory.display().to_string().len())
+            .max()
+            .unwrap_or_default()
+            .max("directory".len());
+        writeln!(
+            out,
+            "{:<width$} {:>8} {:>8}",
+            "directory", "passed", "failed"
+        )?;
+        for (directory, (passed, failed)) in directories {
+            writeln!(
+                out,
+                "{:<width$} {passed:>8} {failed:>8}",
+                directory.display().to_string()
+            )?;
+        }
+    }
+    writeln!(out, "{passed} passed, {failed} failed, {skipped} skipped")?;
+    out.flush()?;
+    if failed > 0 {
+        exit(1);
+    }
+    Ok(())
+}
+
+// line and report of every error
+fn error_reports<E: ErrorPos>(errors: &[E], reporter: &Reporter) -> Vec<(usize, String)> {
+    errors
+        .iter()
+        .map(|error| (error.start_pos().line + 1, reporter.report(error)))
+        .collect()
+}
+
+// run `source` keeping what it printed apart from its errors
+fn run_captured(source: &str, book: bool) -> Outcome {
+    let source_pos = SourcePos::new(source);
+    let reporter = Reporter::new(&source_pos);
+
+    let mut scanner = Scanner::new(source);
+    let mut parser = match book {
+        true => Parser::new(&mut scanner).with_book_syntax(),
+        false => Parser::new(&mut scanner),
+    };
+    let statements = parser.parse();
+    let parse_errors = error_reports(parser.errors(), &reporter);
+    // scan errors are reported instead of the parse errors they cause
+    let mut compile_errors = match scanner.had_error() {
+        true => error_reports(scanner.errors(), &reporter),
+        false => parse_errors,
+    };
+
+    let mut output = Vec::new();
+    let mut runtime_error = None;
+    if compile_errors.is_empty() {
+        let mut interpreter = Interpreter::new(&mut output).with_book(book);
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve(&statements);
+        compile_errors = error_reports(resolver.errors(), &reporter);
+        if compile_errors.is_empty() {
+            interpreter.interpret(&statements);
+            runtime_error = interpreter
+                .errors()
+                .first()
+                .map(|error| (error.start_pos().line + 1, error.message()));
+        }
+    }
+    Outcome {
+        output: String::from_utf8_lossy(&output)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        runtime_error,
+        compile_errors,
+    }
+}
+
+fn write_compiled(path: &std::path::PathBuf, source: &str, scanner: &Scanner) -> Result<()> {
+    std::fs::write(path, cache::compile(source, scanner.tokens()))
+        .with_context(|| format!("Could not write compiled file `{path:?}`"))
+}
+
+#[cfg(not(target_family = "wasm"))]
 pub fn run_prompt(
-    reader: impl std::io::BufRead,
+    mut prompt: impl Prompt,
     mut writer: impl std::io::Write,
-) -> std::io::Result<()> {
-    let mut lox = Lox::default();
+    interpreter: Interpreter<StdoutLock<'static>>,
+    preload: &[std::path::PathBuf],
+    style: ReportStyle,
+) -> Result<()> {
+    let mut lox = Lox::new(interpreter).with_style(style);
 
     const WELCOME_MESSAGES: &str = "Welcome to Lox prompt";
     const PROMPT: &str = ">>>";
+    const CONTINUATION_PROMPT: &str = "...";
+    const LAST_RESULT: &str = "_";
+    const TYPE_COMMAND: &str = ":type";
 
-    write!(writer, "{WELCOME_MESSAGES}\n{PROMPT} ")?;
-    writer.flush()?;
+    // every input that was run, so functions defined earlier can still point at their
+    // source and lines are numbered across the whole session
+    let mut session = String::new();
 
-    for line in reader.lines() {
-        let mut line = line?;
+    // files run into the session, their results are not shown
+    for path in preload {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read file `{path:?}`"))?;
+        lox.run_in_session(&mut session, &source)?;
+        lox.exit_if_requested(&mut writer)?;
+    }
 
-        if !line.ends_with(';') {
-            line.push(';');
-        }
+    writeln!(writer, "{WELCOME_MESSAGES}")?;
 
-        let object = if !line.is_empty() {
-            let object = lox.run(&line)?;
-            lox.reset_error();
-            object
+    // lines are collected until brackets and strings are closed
+    let mut input = String::new();
+    loop {
+        lox.interpreter.flush()?;
+        writer.flush()?;
+        prompt.set_completions(lox.completions());
+        let current = if input.is_empty() {
+            PROMPT
         } else {
-            Object::Null
+            CONTINUATION_PROMPT
         };
+        let line = match prompt.read_line(&format!("{current} "))? {
+            Input::Line(line) => line,
+            Input::Interrupted => {
+                input.clear();
+                continue;
+            }
+            Input::End => break,
+        };
+
+        if !input.is_empty() {
+            input.push('\n');
+        }
+        input.push_str(&line);
+
+        if scanner::is_incomplete(&input) {
+            continue;
+        }
+        if input.trim().is_empty() {
+            input.clear();
+            continue;
+        }
+        prompt.add_history(&input);
+
+        // `:type` followed by an expression describes its value instead of showing it
+        let command = input.strip_prefix(TYPE_COMMAND);
+        if let Some(expression) = command.filter(|rest| rest.starts_with(char::is_whitespace)) {
+            if let Some(object) = lox.run_in_session(&mut session, expression.trim())? {
+                writeln!(writer, "{}", object.describe())?;
+            }
+            input.clear();
+            lox.exit_if_requested(&mut writer)?;
+            continue;
+        }
 
+        let object = lox.run_in_session(&mut session, &input)?;
+        input.clear();
+
+        // only expression statements have a value, shown the way lists show their elements
+        // and the last one shown is kept in `_`
         match object {
-            Object::Null => (),
-            object => writeln!(writer, "{}", object.to_string())?,
+            None | Some(Object::Null) => (),
+            Some(object) => {
+                match prompt.colored() {
+                    true => writeln!(writer, "{}", color::repr(&object))?,
+                    false => writeln!(writer, "{}", object.repr())?,
+                }
+                lox.interpreter
+                    .environment_mut()
+                    .define_global(Symbol::intern(LAST_RESULT), object);
+            }
         };
 
-        write!(writer, "{PROMPT} ")?;
-        writer.flush()?;
+        lox.exit_if_requested(&mut writer)?;
     }
 
+    lox.print_reports();
     Ok(())
 }
 
-pub(crate) struct Lox<W>
+// statements and the report of the errors found while parsing them
+fn parse_tokens<I>(mut parser: Parser<I>, reporter: &Reporter) -> (Vec<Stmt>, Option<String>)
+where
+    I: Iterator<Item = Token>,
+{
+    let statements = parser.parse();
+    let parse_error = parser.had_error().then(|| parser.error_msg(reporter));
+    (statements, parse_error)
+}
+
+// sets up an engine for embedding, everything left out keeps the interpreter's default
+pub struct LoxBuilder<W>
+where
+    W: std::io::Write,
+{
+    interpreter: Interpreter<W>,
+    style: ReportStyle,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+impl<W> LoxBuilder<W>
+where
+    W: std::io::Write,
+{
+    // what scripts print and the errors they have are written to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            interpreter: Interpreter::new(writer),
+            style: ReportStyle::default(),
+            max_steps: None,
+            timeout: None,
+        }
+    }
+
+    pub fn reader(mut self, reader: impl Reader + 'static) -> Self {
+        self.interpreter = self.interpreter.with_reader(reader);
+        self
+    }
+
+    // natives that are not named can't be called
+    pub fn natives(mut self, natives: &[&str]) -> Self {
+        self.interpreter = self.interpreter.with_natives(natives);
+        self
+    }
+
+    // natives reaching outside of the script only with these capabilities, none leaves only
+    // pure computation
+    pub fn capabilities(mut self, capabilities: &[Capability]) -> Self {
+        self.interpreter = self.interpreter.with_capabilities(capabilities);
+        self
+    }
+
+    // define `exec`, before `capabilities` so they can take it away
+    pub fn exec(mut self, exec: bool) -> Self {
+        self.interpreter = self.interpreter.with_exec(exec);
+        self
+    }
+
+    pub fn max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.interpreter = self.interpreter.with_max_call_depth(max_call_depth);
+        self
+    }
+
+    // loop iterations and calls a script may take
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.interpreter = self.interpreter.with_memory_limit(Some(bytes));
+        self
+    }
+
+    pub fn colored(mut self, colored: bool) -> Self {
+        self.style.colored = colored;
+        self
+    }
+
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Self {
+        self.style.deny_warnings = deny_warnings;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.interpreter = self.interpreter.with_strict(strict);
+        self
+    }
+
+    pub fn build(self) -> Lox<W> {
+        let interpreter = self.interpreter.with_budget(self.max_steps, self.timeout);
+        Lox::new(interpreter).with_style(self.style)
+    }
+}
+
+// a source given to `eval`. its tokens are placed after those of the sources before it, so
+// errors in the functions it declared can still be placed once another source runs. it is
+// dropped when none of them is left
+struct Evaluated {
+    start: usize,
+    lines: Lines,
+    functions: Vec<Weak<Function>>,
+}
+
+// 0-based column of a position in any of the sources still kept
+fn column_in(evaluated: &[Evaluated], pos: CharPos) -> usize {
+    let source = evaluated.partition_point(|evaluated| evaluated.start <= pos.index);
+    match source.checked_sub(1).map(|source| &evaluated[source]) {
+        Some(evaluated) => evaluated
+            .lines
+            .column_of(CharPos::new(pos.index - evaluated.start, pos.line)),
+        None => 0,
+    }
+}
+
+pub struct Lox<W>
 where
     W: std::io::Write,
 {
@@ -73,12 +633,129 @@ where
     had_parse_error: bool,
     had_resolve_error: bool,
     had_runtime_error: bool,
+    style: ReportStyle,
+    // file the source was read from
+    path: Option<String>,
+    // the script is run at once instead of line by line in the prompt
+    whole_script: bool,
+    // sources given to `eval` which declared functions still alive, and where the next starts
+    evaluated: Vec<Evaluated>,
+    next_start: usize,
 }
 
 impl<W> Lox<W>
 where
     W: std::io::Write,
 {
+    pub(crate) fn new(interpreter: Interpreter<W>) -> Self {
+        Self {
+            interpreter,
+            had_parse_error: false,
+            had_runtime_error: false,
+            had_scan_error: false,
+            had_resolve_error: false,
+            style: ReportStyle::default(),
+            path: None,
+            whole_script: false,
+            evaluated: Vec::new(),
+            next_start: 0,
+        }
+    }
+
+    fn with_style(mut self, style: ReportStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    fn with_path(mut self, path: &std::path::Path) -> Self {
+        self.path = script_path(path);
+        self
+    }
+
+    pub fn whole_script(mut self) -> Self {
+        self.whole_script = true;
+        self
+    }
+
+    // a global scripts can read, strict mode counts it as declared
+    pub fn set_global(&mut self, name: &str, value: impl Into<Value>) {
+        self.interpreter.set_global(name, value.into());
+    }
+
+    // the closure can capture host state, scripts call it like any other function
+    pub fn define_native(&mut self, name: &str, arity: usize, function: impl HostFn + 'static) {
+        self.interpreter.register_native(name, arity, function);
+    }
+
+    // run `source` after the sources evaluated before, returning the value of its last
+    // expression. nothing but what the script prints is written, lines in diagnostics
+    // count from the start of the source they are in
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxDiagnostics> {
+        self.evaluated.retain(|evaluated| {
+            evaluated
+                .functions
+                .iter()
+                .any(|function| function.strong_count() > 0)
+        });
+        let source = source::normalize(source);
+        let start = self.next_start;
+        // the end of input token is placed right after the last character
+        self.next_start += source.chars().count() + 1;
+        let mut diagnostics = LoxDiagnostics::default();
+
+        let mut scanner = Scanner::new(&source);
+        let mut parser =
+            Parser::new((&mut scanner).map(|token| token.offset(start))).with_implicit_semicolon();
+        let statements = parser.parse();
+        self.evaluated.push(Evaluated {
+            start,
+            lines: Lines::new(&source),
+            functions: parser.functions().to_vec(),
+        });
+        let column = |pos| column_in(&self.evaluated, pos);
+        // scan errors are reported instead of the parse errors they cause
+        let parse_error = parser.had_error();
+        diagnostics.extend_placed(parser.errors(), column);
+        if scanner.had_error() {
+            diagnostics = LoxDiagnostics::default();
+            diagnostics.extend(scanner.errors(), &SourcePos::new(&source));
+            return Err(diagnostics);
+        }
+        if parse_error {
+            return Err(diagnostics);
+        }
+
+        let mut resolver = Resolver::new(&mut self.interpreter);
+        resolver.resolve(&statements);
+        diagnostics.extend_placed(resolver.errors(), column);
+        diagnostics.extend_placed(resolver.warnings(), column);
+        let denied = self.style.deny_warnings && !resolver.warnings().is_empty();
+        if resolver.had_error() || denied {
+            return Err(diagnostics);
+        }
+
+        let object = self.interpreter.interpret(&statements);
+        if self.interpreter.had_error() {
+            diagnostics.extend_placed(self.interpreter.errors(), column);
+            self.interpreter.flush_error();
+            return Err(diagnostics);
+        }
+        Value::try_from(&object).map_err(|_| {
+            diagnostics.push(Diagnostic::unconvertible_result(object.type_name()));
+            diagnostics
+        })
+    }
+
+    fn reporter<'a>(&self, source_pos: &'a SourcePos<'a>) -> Reporter<'a> {
+        Reporter::new(source_pos)
+            .with_color(self.style.colored)
+            .with_context(self.style.context)
+            .with_max_errors(self.style.max_errors)
+            .with_format(self.style.format)
+            .with_tab_width(self.style.tab_width)
+            .with_path(self.path.clone())
+    }
+
     fn reset_error(&mut self) {
         self.had_scan_error = false;
         self.had_parse_error = false;
@@ -87,9 +764,59 @@ where
         self.interpreter.flush_error();
     }
 
-    fn run(&mut self, source: &str) -> Result<Object, std::io::Error> {
+    // whether the last script failed, the errors are already reported
+    pub fn had_error(&self) -> bool {
+        self.had_scan_error
+            || self.had_parse_error
+            || self.had_resolve_error
+            || self.interpreter.had_error()
+    }
+
+    // exits with the script's exit code or the code for the kind of error it had
+    fn finish(&mut self) -> Result<()> {
+        self.print_reports();
+        if let Some(code) = self.interpreter.exit_code() {
+            self.interpreter.flush()?;
+            exit(code);
+        }
+        if self.had_scan_error || self.had_parse_error || self.had_resolve_error {
+            exit(65);
+        } else if self.had_runtime_error {
+            self.interpreter.flush()?;
+            exit(70);
+        }
+        Ok(())
+    }
+
+    // keywords and every name defined so far, for the prompt to complete
+    fn completions(&self) -> Vec<String> {
+        let mut names = self.interpreter.environment().names();
+        names.extend(scanner::reserved_keywords().map(str::to_string));
+        names.sort();
+        names
+    }
+
+    // reports go to stderr so they don't mix with the script's output
+    fn print_reports(&mut self) {
+        let reports = [
+            self.interpreter.profile_report(),
+            self.interpreter.stats_report(),
+            self.interpreter.timings_report(),
+            self.interpreter.coverage_report(),
+        ];
+        for report in reports.into_iter().flatten() {
+            let _ = self.interpreter.flush();
+            eprint!("{report}");
+        }
+        if let Err(err) = self.interpreter.write_lcov() {
+            eprintln!("Could not write coverage report: {err}");
+        }
+    }
+
+    // scanned tokens, `None` if there were errors, which are already reported
+    fn scan(&mut self, source: &str) -> Result<Option<Scanner>, std::io::Error> {
         let source_pos = SourcePos::new(source);
-        let reporter = Reporter::new(&source_pos);
+        let reporter = self.reporter(&source_pos);
 
         let mut scanner = Scanner::new(source);
         scanner.scan_tokens();
@@ -98,29 +825,193 @@ where
 
         if self.had_scan_error {
             self.interpreter.write(&scanner.error_msg(&reporter))?;
-            return Ok(Object::Null);
+            return Ok(None);
         }
+        Ok(Some(scanner))
+    }
 
-        let mut parser = Parser::from(&scanner);
-        let statements = parser.parse();
-        self.had_parse_error = parser.had_error();
-        if self.had_parse_error {
-            self.interpreter.write(&parser.error_msg(&reporter))?;
-            return Ok(Object::Null);
+    // `source` goes at the end of `session`, which is kept for error reports.
+    // `None` when it failed, the errors are already reported
+    fn run_in_session(
+        &mut self,
+        session: &mut String,
+        source: &str,
+    ) -> Result<Option<Object>, std::io::Error> {
+        let start = session.chars().count();
+        session.push_str(&source::normalize(source));
+        session.push('\n');
+        let object = self.run_from(session, start)?;
+        let failed = self.had_error();
+        self.reset_error();
+        Ok((!failed).then_some(object))
+    }
+
+    // the prompt ends as soon as a script calls `exit()`
+    fn exit_if_requested(&mut self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        if let Some(code) = self.interpreter.exit_code() {
+            self.interpreter.flush()?;
+            writer.flush()?;
+            self.print_reports();
+            exit(code);
         }
+        Ok(())
+    }
 
-        let mut resolver = Resolver::new(&mut self.interpreter);
-        resolver.resolve(&statements);
-        self.had_resolve_error = resolver.had_error();
-        if self.had_resolve_error {
-            let error_msg = resolver.error_msg(&reporter);
+    pub(crate) fn run(&mut self, source: &str) -> Result<Object, std::io::Error> {
+        self.run_from(source, 0)
+    }
+
+    // run `source` from the character at `start`, what comes before was already run.
+    // the parser pulls tokens straight from the scanner,
+    // scan errors are still reported instead of the parse errors they cause
+    fn run_from(&mut self, source: &str, start: usize) -> Result<Object, std::io::Error> {
+        let source_pos = SourcePos::new(source);
+        let reporter = self.reporter(&source_pos);
+        match self.parse(&reporter, source, start)? {
+            Some(statements) => self.execute(&reporter, &statements),
+            None => Ok(Object::Null),
+        }
+    }
+
+    // parsed statements, `None` if there were errors, which are already reported
+    // sources run one after another in a session can leave out their last `;`
+    fn parser<I: Iterator<Item = Token>>(&self, parser: Parser<I>) -> Parser<I> {
+        let parser = match self.interpreter.book() {
+            true => parser.with_book_syntax(),
+            false => parser,
+        };
+        match self.whole_script {
+            true => parser,
+            false => parser.with_implicit_semicolon(),
+        }
+    }
+
+    fn parse(
+        &mut self,
+        reporter: &Reporter,
+        source: &str,
+        start: usize,
+    ) -> Result<Option<Vec<Stmt>>, std::io::Error> {
+        let mut scanner = Scanner::new(source).starting_at(start);
+        // scanning only takes its own time when it is done before parsing
+        let (statements, parse_error) = match self.interpreter.is_timed() {
+            true => {
+                let start = Instant::now();
+                scanner.scan_tokens();
+                let scanned = Instant::now();
+                let parsed = parse_tokens(self.parser(Parser::from(&scanner)), reporter);
+                self.interpreter.time(|timings| {
+                    timings.scan += scanned - start;
+                    timings.parse += scanned.elapsed();
+                });
+                parsed
+            }
+            false => parse_tokens(self.parser(Parser::new(&mut scanner)), reporter),
+        };
+
+        self.had_scan_error = scanner.had_error();
+        if self.had_scan_error {
+            self.interpreter.write(&scanner.error_msg(reporter))?;
+            return Ok(None);
+        }
+
+        self.had_parse_error = parse_error.is_some();
+        if let Some(error_msg) = parse_error {
+            self.interpreter.write(&error_msg)?;
+            return Ok(None);
+        }
+        Ok(Some(statements))
+    }
+
+    fn run_statements(
+        &mut self,
+        source: &str,
+        statements: &[Stmt],
+    ) -> Result<Object, std::io::Error> {
+        let source_pos = SourcePos::new(source);
+        let reporter = self.reporter(&source_pos);
+        self.execute(&reporter, statements)
+    }
+
+    fn run_tokens(&mut self, source: &str, tokens: Vec<Token>) -> Result<Object, std::io::Error> {
+        let source_pos = SourcePos::new(source);
+        let reporter = self.reporter(&source_pos);
+
+        let start = Instant::now();
+        let (statements, parse_error) =
+            parse_tokens(self.parser(Parser::new(tokens.into_iter())), &reporter);
+        self.interpreter
+            .time(|timings| timings.parse += start.elapsed());
+        self.had_parse_error = parse_error.is_some();
+        if let Some(error_msg) = parse_error {
             self.interpreter.write(&error_msg)?;
             return Ok(Object::Null);
         }
 
-        let object = self.interpreter.interpret(&statements);
-        if self.interpreter.had_error() {
-            let error_msg = self.interpreter.error_msg(&reporter);
+        self.execute(&reporter, &statements)
+    }
+
+    // global functions and classes that are never used, `None` if there were errors, which
+    // are already reported
+    fn resolve(
+        &mut self,
+        reporter: &Reporter,
+        statements: &[Stmt],
+    ) -> Result<Option<Vec<Symbol>>, std::io::Error> {
+        let start = Instant::now();
+        let mut resolver = Resolver::new(&mut self.interpreter);
+        if self.whole_script {
+            resolver = resolver.with_whole_script();
+        }
+        resolver.resolve(statements);
+        let unused = resolver.unused_globals().to_vec();
+        let denied = self.style.deny_warnings && !resolver.warnings().is_empty();
+        self.had_resolve_error = resolver.had_error() || denied;
+        let resolve_error = resolver.had_error().then(|| resolver.error_msg(reporter));
+        let warning_msg =
+            (!resolver.warnings().is_empty()).then(|| reporter.report_all(resolver.warnings()));
+        self.interpreter
+            .time(|timings| timings.resolve += start.elapsed());
+        if let Some(msg) = resolve_error {
+            self.interpreter.write(&msg)?;
+        }
+        // warnings are not the script's output, unless they are denied and fail it
+        match warning_msg {
+            Some(msg) if denied => self.interpreter.write(&msg)?,
+            Some(msg) => {
+                self.interpreter.flush()?;
+                eprintln!("{msg}");
+            }
+            None => {}
+        }
+        Ok((!self.had_resolve_error).then_some(unused))
+    }
+
+    fn execute(
+        &mut self,
+        reporter: &Reporter,
+        statements: &[Stmt],
+    ) -> Result<Object, std::io::Error> {
+        let Some(unused) = self.resolve(reporter, statements)? else {
+            return Ok(Object::Null);
+        };
+        // the unused functions and classes are left out with `--prune`
+        let pruned: Vec<_>;
+        let statements = match self.interpreter.prune() && !unused.is_empty() {
+            true => {
+                pruned = prune(statements, &unused);
+                &pruned
+            }
+            false => statements,
+        };
+
+        let start = Instant::now();
+        let object = self.interpreter.interpret(statements);
+        self.interpreter
+            .time(|timings| timings.interpret += start.elapsed());
+        self.had_runtime_error = self.interpreter.had_error();
+        if self.had_runtime_error {
+            let error_msg = self.interpreter.error_msg(reporter);
             self.interpreter.write(&error_msg)?;
         }
 
@@ -128,14 +1019,189 @@ where
     }
 }
 
-impl<'a> Default for Lox<StdoutLock<'a>> {
-    fn default() -> Self {
-        Self {
-            interpreter: Interpreter::default(),
-            had_parse_error: false,
-            had_runtime_error: false,
-            had_scan_error: false,
-            had_resolve_error: false,
+#[cfg(test)]
+mod test {
+    use crate::shared::{Mutable, Shared};
+
+    use super::*;
+
+    #[test]
+    fn define_native_with_host_state() -> Result<(), std::io::Error> {
+        let logged = Shared::new(Mutable::new(Vec::new()));
+        let mut result = Vec::new();
+        let mut lox = Lox::new(Interpreter::new(&mut result));
+        let log = Shared::clone(&logged);
+        lox.define_native("log", 1, move |arguments| {
+            log.borrow_mut().push(arguments[0].to_string());
+            Ok(Value::from(log.borrow().len() as f64))
+        });
+        lox.run("print log(\"start\");\nfor (var i = 0; i < 2; i = i + 1) log(i);")?;
+        drop(lox);
+        assert_eq!(*logged.borrow(), ["start", "0", "1"]);
+        assert_eq!(String::from_utf8(result).unwrap(), "1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn run_tests_like_the_book() {
+        let source = "class A { f() { return clock() < 100000000000; } }
+class B < A {}
+print B().f();
+print nil + 1;
+print \"after\";";
+        let outcome = run_captured(source, true);
+        assert_eq!(outcome.output, ["true"]);
+        assert_eq!(outcome.runtime_error.map(|(line, _)| line), Some(4));
+        assert!(outcome.compile_errors.is_empty());
+
+        let outcome = run_captured(source, false);
+        assert_eq!(
+            outcome
+                .compile_errors
+                .iter()
+                .map(|(line, _)| *line)
+                .collect::<Vec<_>>(),
+            [2]
+        );
+    }
+
+    #[test]
+    fn warnings_are_not_printed_with_the_output() -> Result<(), std::io::Error> {
+        let source = "{ var unused = 1; }\nprint 2;";
+        let mut result = Vec::new();
+        let mut lox = Lox::new(Interpreter::new(&mut result));
+        lox.run(source)?;
+        assert!(!lox.had_resolve_error);
+        drop(lox);
+        assert_eq!(String::from_utf8(result).unwrap(), "2\n");
+
+        let mut result = Vec::new();
+        let style = ReportStyle {
+            deny_warnings: true,
+            ..ReportStyle::default()
+        };
+        let mut lox = Lox::new(Interpreter::new(&mut result)).with_style(style);
+        lox.run(source)?;
+        assert!(lox.had_resolve_error);
+        drop(lox);
+        assert!(String::from_utf8(result)
+            .unwrap()
+            .starts_with("[line 1]: Warning: Variable `unused` is never used\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn eval_returns_the_last_value() {
+        let mut result = Vec::new();
+        let mut lox = Lox::new(Interpreter::new(&mut result));
+        assert_eq!(lox.eval("var a = 1;"), Ok(Value::Nil));
+        assert_eq!(
+            lox.eval("fun f(x) { return x + a; }\nf(2);"),
+            Ok(Value::from(3.0))
+        );
+        assert_eq!(
+            lox.eval("print \"a\"; \"b\" + \"c\";"),
+            Ok(Value::from("bc"))
+        );
+
+        let diagnostics = lox.eval("{ var b = 1; }\nf(nil);").unwrap_err();
+        assert_eq!(
+            diagnostics.to_string(),
+            "[line 1]: Warning: Variable `b` is never used\n\
+             [line 1]: RuntimeError: Could not add non-number or non-string together"
+        );
+        assert_eq!(diagnostics.errors().count(), 1);
+        let error = &diagnostics.diagnostics[1];
+        assert_eq!(
+            (error.code, error.location.map(|l| l.column)),
+            ("invalid_operand", Some(21))
+        );
+
+        assert_eq!(
+            lox.eval("f;").unwrap_err().to_string(),
+            "RuntimeError: The result is a function, it has no value outside the script"
+        );
+        assert_eq!(
+            lox.eval("print 1 +;").unwrap_err().to_string(),
+            "[line 1]: ParseError: Expected expression"
+        );
+        drop(lox);
+        assert_eq!(String::from_utf8(result).unwrap(), "a\n");
+    }
+
+    #[test]
+    fn eval_keeps_sources_only_for_live_functions() {
+        let mut lox = Lox::new(Interpreter::new(Vec::new()));
+        assert_eq!(lox.eval("fun f() { return nil + 1; }"), Ok(Value::Nil));
+        for _ in 0..100 {
+            assert_eq!(lox.eval("1;"), Ok(Value::from(1.0)));
         }
+        assert_eq!(lox.evaluated.len(), 2);
+        assert_eq!(
+            lox.eval("\n\nf();").unwrap_err().to_string(),
+            "[line 1]: RuntimeError: Could not add non-number or non-string together"
+        );
+        assert_eq!(lox.eval("fun f() {}"), Ok(Value::Nil));
+        assert_eq!(lox.eval("1;"), Ok(Value::from(1.0)));
+        assert_eq!(lox.evaluated.len(), 2);
+    }
+
+    #[test]
+    fn eval_long_chains() {
+        let mut lox = Lox::new(Interpreter::new(Vec::new()));
+        let sum = format!("{};", vec!["1"; 5000].join(" + "));
+        assert_eq!(lox.eval(&sum), Ok(Value::from(5000.0)));
+        let properties = format!(
+            "class A {{}} var a = A(); a.a = a; a.n = 1; a{}.n;",
+            ".a".repeat(5000)
+        );
+        assert_eq!(lox.eval(&properties), Ok(Value::from(1.0)));
+    }
+
+    // a script started on one thread keeps running on another
+    #[cfg(feature = "sync")]
+    #[test]
+    fn run_on_another_thread() {
+        let mut lox = Lox::new(Interpreter::new(Vec::new()));
+        let counter = "fun counter() {
+  var n = 0;
+  fun count() {
+    n = n + 1;
+    return n;
+  }
+  return count;
+}
+var count = counter();
+count();";
+        assert_eq!(lox.eval(counter), Ok(Value::from(1.0)));
+        let lox = std::thread::spawn(move || {
+            assert_eq!(lox.eval("count();"), Ok(Value::from(2.0)));
+            lox
+        })
+        .join()
+        .unwrap();
+        drop(lox);
+    }
+
+    #[test]
+    fn build_an_engine() {
+        let mut result = Vec::new();
+        let mut lox = LoxBuilder::new(&mut result)
+            .reader(std::io::Cursor::new("typed\n"))
+            .natives(&["readLine", "str", "log"])
+            .max_steps(100)
+            .strict(true)
+            .build();
+        lox.define_native("log", 1, |arguments| Ok(arguments[0].clone()));
+        assert_eq!(
+            lox.eval("log(readLine()) + str(1);"),
+            Ok(Value::from("typed1"))
+        );
+        assert_eq!(
+            lox.eval("clock();").unwrap_err().to_string(),
+            "[line 1]: ResolveError: Global variable `clock` is never declared"
+        );
+        let error = lox.eval("while (true) {}").unwrap_err();
+        assert_eq!(error.diagnostics[0].code, "budget_exceeded");
     }
 }
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as LspNotification, PublishDiagnostics,
+    },
+    request::{DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as LspRequest},
+    Diagnostic, DiagnosticSeverity, DocumentSymbol, DocumentSymbolResponse, GotoDefinitionResponse,
+    Hover, HoverContents, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf,
+    Position, PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+};
+
+use crate::{
+    error::reporter::{ErrorPos, ErrorReporter},
+    interpreter::Interpreter,
+    parser::Parser,
+    resolver::{DeclarationKind, Reference, Resolver},
+    scanner::Scanner,
+    source::{self, CharPos, Lines},
+    stmt::{Function, Stmt},
+    token::Token,
+};
+
+// what is known about an open script, computed again whenever it changes
+struct Document {
+    lines: Lines,
+    text: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    references: Vec<Reference>,
+    symbols: Vec<DocumentSymbol>,
+}
+
+impl Document {
+    fn new(source: &str) -> Self {
+        // lines and columns are those of the scanned source
+        let normalized = source::normalize(source);
+        let source = normalized.as_ref();
+        let mut document = Self {
+            lines: Lines::new(source),
+            text: source.split('\n').map(str::to_string).collect(),
+            diagnostics: Vec::new(),
+            references: Vec::new(),
+            symbols: Vec::new(),
+        };
+
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        let mut parser = Parser::from(&scanner);
+        let statements = parser.parse();
+        // names are still resolved in what could be parsed, but scopes are only
+        // checked once the script parses
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let mut resolver = Resolver::new(&mut interpreter).with_references();
+        resolver.resolve(&statements);
+
+        // scan errors are reported instead of the parse errors they cause
+        document.diagnostics = match (scanner.had_error(), parser.had_error()) {
+            (true, _) => document.diagnose(scanner.errors()),
+            (false, true) => document.diagnose(parser.errors()),
+            (false, false) => {
+                let mut diagnostics = document.diagnose(resolver.errors());
+                diagnostics.extend(document.diagnose(resolver.warnings()));
+                diagnostics
+            }
+        };
+        document.references = resolver.references().to_vec();
+        document.symbols = document.symbols(&statements);
+        document
+    }
+
+    // positions are sent in UTF-16 code units, tokens count characters
+    fn position(&self, pos: CharPos) -> Position {
+        let column = self.lines.column_of(pos);
+        let character: usize = self
+            .text
+            .get(pos.line)
+            .map(|line| line.chars().take(column).map(char::len_utf16).sum())
+            .unwrap_or_default();
+        Position::new(pos.line as u32, character as u32)
+    }
+
+    // `end` is the last character
+    fn range(&self, start: CharPos, end: CharPos) -> Range {
+        let end = CharPos::new(end.index + 1, end.line);
+        Range::new(self.position(start), self.position(end))
+    }
+
+    fn token_range(&self, token: &Token) -> Range {
+        self.range(token.start_pos(), token.end_pos())
+    }
+
+    fn diagnose<E: ErrorPos>(&self, errors: &[E]) -> Vec<Diagnostic> {
+        errors
+            .iter()
+            .map(|error| Diagnostic {
+                range: self.range(error.start_pos(), error.end_pos()),
+                severity: Some(match error.is_warning() {
+                    true => DiagnosticSeverity::WARNING,
+                    false => DiagnosticSeverity::ERROR,
+                }),
+                source: Some("lox".to_string()),
+                message: error.message(),
+                ..Diagnostic::default()
+            })
+            .collect()
+    }
+
+    fn function_symbol(&self, function: &Function, kind: SymbolKind) -> DocumentSymbol {
+        let params = function
+            .params
+            .iter()
+            .map(|param| param.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.symbol(
+            &function.name,
+            kind,
+            format!("({params})"),
+            self.symbols(std::slice::from_ref(&function.body)),
+        )
+    }
+
+    // functions and classes, methods and nested functions are children of what they
+    // are declared in
+    fn symbols(&self, statements: &[Stmt]) -> Vec<DocumentSymbol> {
+        let mut symbols = Vec::new();
+        for s in statements {
+            match s {
+                Stmt::Function(function) => {
+                    symbols.push(self.function_symbol(function, SymbolKind::FUNCTION))
+                }
+                Stmt::Class(class) => {
+                    let detail = match &class.superclass {
+                        Some(superclass) => format!(": {}", superclass.name.name()),
+                        None => String::new(),
+                    };
+                    let methods = class
+                        .methods
+                        .iter()
+                        .map(|method| self.function_symbol(method, SymbolKind::METHOD))
+                        .collect();
+                    symbols.push(self.symbol(&class.name, SymbolKind::CLASS, detail, methods));
+                }
+                Stmt::Block(block) => symbols.extend(self.symbols(&block.statements)),
+                Stmt::If(if_statement) => {
+                    symbols.extend(self.symbols(std::slice::from_ref(&if_statement.then_branch)));
+                    if let Some(else_branch) = &if_statement.else_branch {
+                        symbols.extend(self.symbols(std::slice::from_ref(else_branch)));
+                    }
+                }
+                Stmt::While(while_loop) => {
+                    symbols.extend(self.symbols(std::slice::from_ref(&while_loop.body)))
+                }
+                Stmt::ForIn(for_in) => {
+                    symbols.extend(self.symbols(std::slice::from_ref(&for_in.body)))
+                }
+                Stmt::Expression(_) | Stmt::Print(_) | Stmt::Var(_) | Stmt::Return(_) => (),
+            }
+        }
+        symbols
+    }
+
+    // statements do not know where they end, symbols only cover their name
+    #[allow(deprecated)]
+    fn symbol(
+        &self,
+        name: &Token,
+        kind: SymbolKind,
+        detail: String,
+        children: Vec<DocumentSymbol>,
+    ) -> DocumentSymbol {
+        let range = self.token_range(name);
+        DocumentSymbol {
+            name: name.name().to_string(),
+            detail: (!detail.is_empty()).then_some(detail),
+            kind,
+            tags: None,
+            deprecated: None,
+            range,
+            selection_range: range,
+            children: (!children.is_empty()).then_some(children),
+        }
+    }
+
+    // the variable under the cursor, which can be right after its name
+    fn reference_at(&self, position: Position) -> Option<&Reference> {
+        self.references.iter().find(|reference| {
+            let range = self.token_range(&reference.name);
+            range.start <= position && position <= range.end
+        })
+    }
+
+    fn definition(&self, uri: &Uri, position: Position) -> Option<GotoDefinitionResponse> {
+        let reference = self.reference_at(position)?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            uri.clone(),
+            self.token_range(&reference.declaration.name),
+        )))
+    }
+
+    fn hover(&self, position: Position) -> Option<Hover> {
+        let reference = self.reference_at(position)?;
+        let declaration = &reference.declaration;
+        let kind = match declaration.kind {
+            DeclarationKind::Variable => "variable",
+            DeclarationKind::Parameter => "parameter",
+            DeclarationKind::Function => "function",
+            DeclarationKind::Class => "class",
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!(
+                    "{kind} `{}`, declared on line {}",
+                    declaration.name.name(),
+                    declaration.name.start_pos().line + 1
+                ),
+            }),
+            range: Some(self.token_range(&reference.name)),
+        })
+    }
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+// answer `request` with what `handle` returns for its params
+fn respond<R: LspRequest>(
+    request: Request,
+    handle: impl FnOnce(R::Params) -> R::Result,
+) -> Response {
+    let id = request.id.clone();
+    match request.extract(R::METHOD) {
+        Ok((id, params)) => Response::new_ok(id, handle(params)),
+        Err(err) => Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()),
+    }
+}
+
+fn handle_request(documents: &HashMap<String, Document>, request: Request) -> Response {
+    match request.method.as_str() {
+        GotoDefinition::METHOD => respond::<GotoDefinition>(request, |params| {
+            let params = params.text_document_position_params;
+            let uri = params.text_document.uri;
+            documents
+                .get(uri.as_str())?
+                .definition(&uri, params.position)
+        }),
+        HoverRequest::METHOD => respond::<HoverRequest>(request, |params| {
+            let params = params.text_document_position_params;
+            documents
+                .get(params.text_document.uri.as_str())?
+                .hover(params.position)
+        }),
+        DocumentSymbolRequest::METHOD => respond::<DocumentSymbolRequest>(request, |params| {
+            let document = documents.get(params.text_document.uri.as_str())?;
+            Some(DocumentSymbolResponse::Nested(document.symbols.clone()))
+        }),
+        method => Response::new_err(
+            request.id.clone(),
+            ErrorCode::MethodNotFound as i32,
+            format!("Unknown method `{method}`"),
+        ),
+    }
+}
+
+// the document whose diagnostics changed, with its version
+fn handle_notification(
+    documents: &mut HashMap<String, Document>,
+    notification: Notification,
+) -> Result<Option<(Uri, Option<i32>)>> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params = notification.extract::<<DidOpenTextDocument as LspNotification>::Params>(
+                DidOpenTextDocument::METHOD,
+            )?;
+            let document = params.text_document;
+            documents.insert(document.uri.to_string(), Document::new(&document.text));
+            Ok(Some((document.uri, Some(document.version))))
+        }
+        // the whole text is sent on every change
+        DidChangeTextDocument::METHOD => {
+            let params = notification
+                .extract::<<DidChangeTextDocument as LspNotification>::Params>(
+                    DidChangeTextDocument::METHOD,
+                )?;
+            let Some(change) = params.content_changes.last() else {
+                return Ok(None);
+            };
+            let document = params.text_document;
+            documents.insert(document.uri.to_string(), Document::new(&change.text));
+            Ok(Some((document.uri, Some(document.version))))
+        }
+        DidCloseTextDocument::METHOD => {
+            let params = notification
+                .extract::<<DidCloseTextDocument as LspNotification>::Params>(
+                    DidCloseTextDocument::METHOD,
+                )?;
+            documents.remove(params.text_document.uri.as_str());
+            Ok(Some((params.text_document.uri, None)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn main_loop(connection: &Connection) -> Result<()> {
+    let mut documents = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = handle_request(&documents, request);
+                connection.sender.send(response.into())?;
+            }
+            Message::Notification(notification) => {
+                let Some((uri, version)) = handle_notification(&mut documents, notification)?
+                else {
+                    continue;
+                };
+                // closed documents have their diagnostics cleared
+                let diagnostics = documents
+                    .get(uri.as_str())
+                    .map(|document| document.diagnostics.clone())
+                    .unwrap_or_default();
+                let params = PublishDiagnosticsParams::new(uri, diagnostics, version);
+                let notification =
+                    Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+                connection.sender.send(notification.into())?;
+            }
+            Message::Response(_) => (),
+        }
+    }
+    Ok(())
+}
+
+// serve the language server protocol on stdin and stdout until the editor exits
+pub fn serve() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    main_loop(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_server::RequestId;
+    use lsp_types::{
+        DidOpenTextDocumentParams, DocumentSymbolParams, GotoDefinitionParams, HoverParams,
+        TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    };
+
+    use super::*;
+
+    const SOURCE: &str = "var x = \"é\";
+fun add(a, b) {
+  return a + b + x;
+}
+class A {
+  m() {
+    fun inner() {}
+    return add(1, 2);
+  }
+}
+class B : A {}";
+
+    #[test]
+    fn diagnose_errors() {
+        let document = Document::new("var a = 1;\nprint \"😀\" +;\n{ var b = b; }");
+        assert_eq!(document.diagnostics.len(), 1);
+        // the emoji takes two UTF-16 code units
+        assert_eq!(document.diagnostics[0].range.start, Position::new(1, 12));
+
+        let document = Document::new("{ var b = b; }\nprint \"é\" + c;");
+        assert_eq!(
+            document
+                .diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.range)
+                .collect::<Vec<_>>(),
+            [Range::new(Position::new(0, 10), Position::new(0, 11))]
+        );
+
+        let document = Document::new("fun f() {\n  return;\n  f();\n}");
+        assert_eq!(
+            document.diagnostics[0].severity,
+            Some(DiagnosticSeverity::WARNING)
+        );
+    }
+
+    #[test]
+    fn find_declarations() {
+        let document = Document::new(SOURCE);
+        let uri: Uri = "file:///a.lox".parse().unwrap();
+        let declaration =
+            |line, character| match document.definition(&uri, Position::new(line, character)) {
+                Some(GotoDefinitionResponse::Scalar(location)) => Some(location.range.start),
+                _ => None,
+            };
+        // `a` in `return a + b`
+        assert_eq!(declaration(2, 9), Some(Position::new(1, 8)));
+        // the global `x`, `add` called from a method and the superclass
+        assert_eq!(declaration(2, 18), Some(Position::new(0, 4)));
+        assert_eq!(declaration(7, 12), Some(Position::new(1, 4)));
+        assert_eq!(declaration(10, 10), Some(Position::new(4, 6)));
+        assert_eq!(declaration(0, 0), None);
+
+        let Some(HoverContents::Markup(hover)) = document
+            .hover(Position::new(2, 13))
+            .map(|hover| hover.contents)
+        else {
+            panic!("no hover")
+        };
+        assert_eq!(hover.value, "parameter `b`, declared on line 2");
+    }
+
+    #[test]
+    fn list_symbols() {
+        let document = Document::new(SOURCE);
+        let names = |symbols: &[DocumentSymbol]| {
+            symbols
+                .iter()
+                .map(|symbol| (symbol.name.clone(), symbol.kind))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&document.symbols),
+            [
+                ("add".to_string(), SymbolKind::FUNCTION),
+                ("A".to_string(), SymbolKind::CLASS),
+                ("B".to_string(), SymbolKind::CLASS),
+            ]
+        );
+        assert_eq!(document.symbols[0].detail.as_deref(), Some("(a, b)"));
+        let methods = document.symbols[1].children.as_deref().unwrap();
+        assert_eq!(names(methods), [("m".to_string(), SymbolKind::METHOD)]);
+        assert_eq!(
+            names(methods[0].children.as_deref().unwrap()),
+            [("inner".to_string(), SymbolKind::FUNCTION)]
+        );
+    }
+
+    #[test]
+    fn serve_requests() -> Result<()> {
+        let (server, client) = Connection::memory();
+        let uri: Uri = "file:///a.lox".parse().unwrap();
+        let document = TextDocumentIdentifier::new(uri.clone());
+        let position = TextDocumentPositionParams::new(document.clone(), Position::new(1, 6));
+        let messages: Vec<Message> = vec![
+            Notification::new(
+                DidOpenTextDocument::METHOD.to_string(),
+                DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem::new(
+                        uri.clone(),
+                        "lox".to_string(),
+                        1,
+                        "var a;\nprint a;\nprint b +;".to_string(),
+                    ),
+                },
+            )
+            .into(),
+            Request::new(
+                RequestId::from(1),
+                GotoDefinition::METHOD.to_string(),
+                GotoDefinitionParams {
+                    text_document_position_params: position.clone(),
+                    work_done_progress_params: Default::default(),
+                    partial_result_params: Default::default(),
+                },
+            )
+            .into(),
+            Request::new(
+                RequestId::from(2),
+                HoverRequest::METHOD.to_string(),
+                HoverParams {
+                    text_document_position_params: position,
+                    work_done_progress_params: Default::default(),
+                },
+            )
+            .into(),
+            Request::new(
+                RequestId::from(3),
+                DocumentSymbolRequest::METHOD.to_string(),
+                DocumentSymbolParams {
+                    text_document: document,
+                    work_done_progress_params: Default::default(),
+                    partial_result_params: Default::default(),
+                },
+            )
+            .into(),
+            Request::new(RequestId::from(4), "shutdown".to_string(), ()).into(),
+            Notification::new("exit".to_string(), ()).into(),
+        ];
+        for message in messages {
+            client.sender.send(message)?;
+        }
+        main_loop(&server)?;
+
+        let Message::Notification(diagnostics) = client.receiver.recv()? else {
+            panic!("no diagnostics")
+        };
+        let diagnostics: PublishDiagnosticsParams =
+            diagnostics.extract(PublishDiagnostics::METHOD)?;
+        assert_eq!(diagnostics.version, Some(1));
+        assert_eq!(diagnostics.diagnostics[0].range.start.line, 2);
+
+        let mut results = Vec::new();
+        for _ in 0..4 {
+            let Message::Response(response) = client.receiver.recv()? else {
+                panic!("no response")
+            };
+            results.push(response.result.unwrap());
+        }
+        assert_eq!(
+            results[0]["range"]["start"],
+            serde_json::json!({"line": 0, "character": 4})
+        );
+        assert_eq!(
+            results[1]["contents"]["value"],
+            "variable `a`, declared on line 1"
+        );
+        assert_eq!(results[2], serde_json::json!([]));
+        assert_eq!(results[3], serde_json::Value::Null);
+        Ok(())
+    }
+}
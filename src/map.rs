@@ -0,0 +1,176 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    error::native_error::NativeError,
+    object::{print_nested, Number, Object},
+    shared::{Mutable, Ref, RefMut, Shared},
+};
+
+// numbers, strings and booleans compare by value, so only they can be keys
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub(crate) enum MapKey {
+    Number(Number),
+    String(String),
+    Bool(bool),
+}
+
+// NaN is never a key, every other number equals itself
+impl Eq for MapKey {}
+
+impl MapKey {
+    pub fn new(object: &Object) -> Result<Self, NativeError> {
+        match object {
+            Object::Number(number) if number.is_nan() => {
+                Err(NativeError::custom("NaN cannot be a map key"))
+            }
+            // `0` and `-0` are equal, they have to be the same key
+            Object::Number(number) => Ok(MapKey::Number(Number::from(**number + 0.0))),
+            Object::String(string) => Ok(MapKey::String(string.to_string())),
+            Object::Bool(b) => Ok(MapKey::Bool(*b)),
+            _ => Err(NativeError::invalid_argument(
+                "number, string or bool",
+                object,
+            )),
+        }
+    }
+
+    pub fn to_object(&self) -> Object {
+        match self {
+            MapKey::Number(number) => Object::Number(*number),
+            MapKey::String(string) => Object::string(string.as_str()),
+            MapKey::Bool(b) => Object::Bool(*b),
+        }
+    }
+}
+
+// maps are shared by reference like lists, and keep their keys in insertion order
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LoxMap {
+    entries: Shared<Mutable<IndexMap<MapKey, Object>>>,
+}
+
+impl LoxMap {
+    // a new map holding the same entries, in the same order
+    pub fn copy(&self) -> Self {
+        Self {
+            entries: Shared::new(Mutable::new(self.entries().clone())),
+        }
+    }
+
+    // a map with string keys, like those natives return
+    pub fn from_fields<'a>(fields: impl IntoIterator<Item = (&'a str, Object)>) -> Self {
+        let map = Self::default();
+        for (key, value) in fields {
+            map.entries_mut()
+                .insert(MapKey::String(key.to_string()), value);
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn entries(&self) -> Ref<'_, IndexMap<MapKey, Object>> {
+        self.entries.borrow()
+    }
+
+    pub fn entries_mut(&self) -> RefMut<'_, IndexMap<MapKey, Object>> {
+        self.entries.borrow_mut()
+    }
+
+    pub fn keys(&self) -> Vec<Object> {
+        self.entries
+            .borrow()
+            .keys()
+            .map(MapKey::to_object)
+            .collect()
+    }
+
+    // the storage every copy shares, tracked by the collector
+    pub fn storage(&self) -> &Shared<Mutable<IndexMap<MapKey, Object>>> {
+        &self.entries
+    }
+
+    // identity of the shared storage
+    pub fn address(&self) -> usize {
+        Shared::as_ptr(&self.entries) as usize
+    }
+
+    pub fn strong_count(&self) -> usize {
+        Shared::strong_count(&self.entries)
+    }
+
+    // drops the values outside of the borrow, they might hold this map again
+    pub fn clear(&self) {
+        let entries = std::mem::take(&mut *self.entries.borrow_mut());
+        drop(entries);
+    }
+}
+
+impl From<Shared<Mutable<IndexMap<MapKey, Object>>>> for LoxMap {
+    fn from(entries: Shared<Mutable<IndexMap<MapKey, Object>>>) -> Self {
+        Self { entries }
+    }
+}
+
+impl PartialEq for LoxMap {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.entries, &other.entries)
+    }
+}
+
+impl Hash for LoxMap {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Shared::as_ptr(&self.entries).hash(state)
+    }
+}
+
+impl fmt::Display for LoxMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        print_nested(f, self.address(), "{...}", |f| {
+            let entries = self
+                .entries
+                .borrow()
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key.to_object().repr(), value.repr()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "{{{entries}}}")
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keys_keep_insertion_order() {
+        let map = LoxMap::default();
+        for key in ["b", "a", "c"] {
+            map.entries_mut()
+                .insert(MapKey::new(&Object::string(key)).unwrap(), Object::Null);
+        }
+        map.entries_mut().insert(
+            MapKey::new(&Object::Number((-0.0).into())).unwrap(),
+            Object::Bool(true),
+        );
+        map.entries_mut()
+            .shift_remove(&MapKey::new(&Object::string("a")).unwrap());
+        let zero = MapKey::new(&Object::Number(0.0.into())).unwrap();
+        assert_eq!(map.entries().get(&zero), Some(&Object::Bool(true)));
+        assert_eq!(map.to_string(), r#"{"b": nil, "c": nil, 0: true}"#);
+        assert_eq!(
+            MapKey::new(&Object::Number(f64::NAN.into()))
+                .unwrap_err()
+                .to_string(),
+            "NaN cannot be a map key"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::{collections::HashSet, mem::size_of};
+
+use crate::{
+    list::LoxList,
+    map::{LoxMap, MapKey},
+    object::Object,
+};
+
+// rough cost of an environment or an instance before anything is stored in it
+const OBJECT_OVERHEAD: usize = 128;
+
+// measuring walks every live object, so it only happens once enough memory could have
+// been allocated to go over the limit, and never more often than every this many parts
+// of the limit
+const MIN_MEASURE_FRACTION: usize = 64;
+
+// approximate size of everything reachable from one object
+#[derive(Default)]
+pub(crate) struct Sizer {
+    bytes: usize,
+    strings: usize,
+    containers: HashSet<usize>,
+}
+
+impl Sizer {
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    // string values seen, map keys and names are not counted
+    pub fn strings(&self) -> usize {
+        self.strings
+    }
+
+    pub fn add(&mut self, bytes: usize) {
+        self.bytes += bytes;
+    }
+
+    pub fn object(&mut self, object: &Object) {
+        self.bytes += size_of::<Object>();
+        match object {
+            Object::String(s) => {
+                self.bytes += s.capacity();
+                self.strings += 1;
+            }
+            Object::List(list) => self.list(list),
+            Object::Map(map) => self.map(map),
+            Object::Callable(_)
+            | Object::LoxInstance(_)
+            | Object::Null
+            | Object::Number(_)
+            | Object::Bool(_) => (),
+        }
+    }
+
+    // shared storage is counted once, wherever it is seen first
+    pub fn list(&mut self, list: &LoxList) {
+        if self.containers.insert(list.address()) {
+            let elements = list.elements();
+            self.bytes += (elements.capacity() - elements.len()) * size_of::<Object>();
+            for element in elements.iter() {
+                self.object(element);
+            }
+        }
+    }
+
+    pub fn map(&mut self, map: &LoxMap) {
+        if self.containers.insert(map.address()) {
+            let entries = map.entries();
+            self.bytes += entries.capacity() * size_of::<(MapKey, Object)>();
+            for (key, value) in entries.iter() {
+                if let MapKey::String(key) = key {
+                    self.bytes += key.capacity();
+                }
+                self.object(value);
+            }
+        }
+    }
+
+    pub fn environment_or_instance(&mut self) {
+        self.bytes += OBJECT_OVERHEAD;
+    }
+}
+
+// cheap estimate of what creating a value cost, without following shared storage
+pub(crate) fn allocated_by(object: &Object) -> usize {
+    match object {
+        Object::String(s) => s.capacity(),
+        Object::List(list) => list.len() * size_of::<Object>(),
+        Object::Map(map) => map.len() * size_of::<(MapKey, Object)>(),
+        _ => 0,
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MemoryLimit {
+    limit: usize,
+    // bytes in use at the last measurement
+    live: usize,
+    // estimated bytes allocated since then
+    allocated: usize,
+    // heap objects at the last measurement
+    objects: usize,
+}
+
+impl MemoryLimit {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            live: 0,
+            allocated: 0,
+            objects: 0,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn allocate(&mut self, bytes: usize) {
+        self.allocated = self.allocated.saturating_add(bytes);
+    }
+
+    pub fn should_measure(&self, objects: usize) -> bool {
+        let allocated = self
+            .allocated
+            .saturating_add(objects.saturating_sub(self.objects) * OBJECT_OVERHEAD);
+        allocated >= self.limit / MIN_MEASURE_FRACTION
+            && self.live.saturating_add(allocated) > self.limit
+    }
+
+    // returns whether the limit is exceeded
+    pub fn measured(&mut self, live: usize, objects: usize) -> bool {
+        self.live = live;
+        self.allocated = 0;
+        self.objects = objects;
+        live > self.limit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shared_lists_are_counted_once() {
+        let list = Object::List(LoxList::new(vec![Object::string("abc")]));
+        let mut once = Sizer::default();
+        once.object(&list);
+        let mut twice = Sizer::default();
+        twice.object(&list);
+        twice.object(&list);
+        assert_eq!(twice.bytes(), once.bytes() + size_of::<Object>());
+    }
+
+    #[test]
+    fn measure_only_when_limit_could_be_exceeded() {
+        let mut memory = MemoryLimit::new(64 * 1024);
+        memory.allocate(1024);
+        assert!(!memory.should_measure(0));
+        memory.allocate(64 * 1024);
+        assert!(memory.should_measure(0));
+        assert!(!memory.measured(1024, 0));
+        assert!(!memory.should_measure(0));
+        assert!(memory.should_measure(1024));
+        assert!(memory.measured(128 * 1024, 1024));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{
+    error::native_error::NativeError,
+    interpreter::Interpreter,
+    object::{Number, Object},
+};
+
+use super::{NativeCallable, NativeResult};
+
+// native str function, converts any value to its string representation
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Str;
+
+impl NativeCallable for Str {
+    fn name(&self) -> &str {
+        "str"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let string = match &arguments[0] {
+            // `nil` is printed as an empty string, but that is useless for conversion
+            Object::Null => "nil".to_string(),
+            object => object.to_string(),
+        };
+        Ok(Object::string(string))
+    }
+}
+
+// native number function, returns nil if the string is not a number
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct ParseNumber;
+
+impl NativeCallable for ParseNumber {
+    fn name(&self) -> &str {
+        "number"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        match &arguments[0] {
+            Object::Number(number) => Ok(Object::Number(*number)),
+            Object::String(string) => Ok(string
+                .trim()
+                .parse::<Number>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .map(Object::Number)
+                .unwrap_or(Object::Null)),
+            object => Err(NativeError::invalid_argument("string", object)),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{callable::LoxCallable, interpreter::Interpreter, object::Object};
+
+use super::{call_back, NativeCallable, NativeResult};
+
+// native clone function, a shallow copy of lists, maps and instances. a `clone` method
+// is then called on the copied instance, where it can copy what its fields hold.
+// other values can't be changed, they are returned as they are
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct CloneValue;
+
+impl NativeCallable for CloneValue {
+    fn name(&self) -> &str {
+        "clone"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let object = match &arguments[0] {
+            Object::List(list) => Object::List(list.copy()),
+            Object::Map(map) => Object::Map(map.copy()),
+            Object::LoxInstance(instance) => {
+                let copy = instance.copy(interpreter.instance_id());
+                interpreter.track_instance(&copy);
+                if let Some(method) = copy.find_method("clone") {
+                    let method = LoxCallable::LoxFunction(method.bind(copy.clone()));
+                    call_back(interpreter, &Object::callable(method), Vec::new())?;
+                }
+                Object::instance(copy)
+            }
+            object => object.clone(),
+        };
+        Ok(object)
+    }
+}
@@ -0,0 +1,370 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{
+    error::native_error::NativeError,
+    interpreter::Interpreter,
+    map::LoxMap,
+    object::{Number, Object},
+    time::SystemTime,
+};
+
+use super::{number_argument, string_argument, NativeCallable, NativeResult};
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+// times further than 100 million days from the epoch are rejected, like javascript does
+const MAX_MILLIS: f64 = 8.64e15;
+
+// days since 1970-01-01 of a date in the proleptic gregorian calendar.
+// years start in march so the leap day is the last day of the year
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// a moment in utc, there is no time zone database
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    millisecond: u32,
+}
+
+impl Default for DateTime {
+    fn default() -> Self {
+        Self {
+            year: 1970,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            millisecond: 0,
+        }
+    }
+}
+
+impl DateTime {
+    fn from_millis(millis: i64) -> Self {
+        let (year, month, day) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+        let millis = millis.rem_euclid(MILLIS_PER_DAY) as u32;
+        Self {
+            year,
+            month,
+            day,
+            hour: millis / 3_600_000,
+            minute: millis / 60_000 % 60,
+            second: millis / 1000 % 60,
+            millisecond: millis % 1000,
+        }
+    }
+
+    fn to_millis(self) -> i64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let seconds = (self.hour * 3600 + self.minute * 60 + self.second) as i64;
+        days * MILLIS_PER_DAY + seconds * 1000 + self.millisecond as i64
+    }
+
+    // 0 is sunday
+    fn weekday(self) -> usize {
+        (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7) as usize
+    }
+
+    fn format(self, format: &str) -> Result<String, NativeError> {
+        let mut out = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let field = match chars.next() {
+                Some('Y') => format!("{:04}", self.year),
+                Some('m') => format!("{:02}", self.month),
+                Some('d') => format!("{:02}", self.day),
+                Some('H') => format!("{:02}", self.hour),
+                Some('M') => format!("{:02}", self.minute),
+                Some('S') => format!("{:02}", self.second),
+                Some('f') => format!("{:03}", self.millisecond),
+                Some('a') => WEEKDAYS[self.weekday()].to_string(),
+                Some('b') => MONTHS[self.month as usize - 1].to_string(),
+                Some('%') => "%".to_string(),
+                Some(c) => return Err(unknown_specifier(&format!("%{c}"))),
+                None => return Err(unknown_specifier("%")),
+            };
+            out.push_str(&field);
+        }
+        Ok(out)
+    }
+
+    // fields missing from `format` are those of 1970-01-01 00:00:00
+    fn parse(input: &str, format: &str) -> Result<Self, NativeError> {
+        let mismatch =
+            || NativeError::invalid_time(&format!("`{input}` does not match `{format}`"));
+        let mut date_time = DateTime::default();
+        let mut input_chars = input.chars().peekable();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                if input_chars.next() != Some(c) {
+                    return Err(mismatch());
+                }
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => {
+                    let negative = input_chars.next_if_eq(&'-').is_some();
+                    let year = digits(&mut input_chars, 9).ok_or_else(mismatch)? as i64;
+                    date_time.year = if negative { -year } else { year };
+                }
+                Some('m') => date_time.month = digits(&mut input_chars, 2).ok_or_else(mismatch)?,
+                Some('d') => date_time.day = digits(&mut input_chars, 2).ok_or_else(mismatch)?,
+                Some('H') => date_time.hour = digits(&mut input_chars, 2).ok_or_else(mismatch)?,
+                Some('M') => date_time.minute = digits(&mut input_chars, 2).ok_or_else(mismatch)?,
+                Some('S') => date_time.second = digits(&mut input_chars, 2).ok_or_else(mismatch)?,
+                Some('f') => {
+                    date_time.millisecond = digits(&mut input_chars, 3).ok_or_else(mismatch)?
+                }
+                // the weekday follows from the date, it is only checked to be a name
+                Some('a') => {
+                    name(&mut input_chars, &WEEKDAYS).ok_or_else(mismatch)?;
+                }
+                Some('b') => {
+                    let month = name(&mut input_chars, &MONTHS).ok_or_else(mismatch)?;
+                    date_time.month = month as u32 + 1;
+                }
+                Some('%') => {
+                    if input_chars.next() != Some('%') {
+                        return Err(mismatch());
+                    }
+                }
+                Some(c) => return Err(unknown_specifier(&format!("%{c}"))),
+                None => return Err(unknown_specifier("%")),
+            }
+        }
+        if input_chars.next().is_some() {
+            return Err(mismatch());
+        }
+        date_time.check()?;
+        Ok(date_time)
+    }
+
+    fn check(self) -> Result<(), NativeError> {
+        let out_of_range = |field: &str, value: u32| {
+            Err(NativeError::invalid_time(&format!(
+                "{field} {value} is out of range"
+            )))
+        };
+        if !(1..=12).contains(&self.month) {
+            return out_of_range("month", self.month);
+        }
+        if !(1..=days_in_month(self.year, self.month)).contains(&self.day) {
+            return out_of_range("day", self.day);
+        }
+        if self.hour > 23 {
+            return out_of_range("hour", self.hour);
+        }
+        if self.minute > 59 {
+            return out_of_range("minute", self.minute);
+        }
+        if self.second > 59 {
+            return out_of_range("second", self.second);
+        }
+        Ok(())
+    }
+}
+
+fn unknown_specifier(specifier: &str) -> NativeError {
+    NativeError::invalid_format(&format!("unknown specifier `{specifier}`"))
+}
+
+// at least one and at most `max` digits
+fn digits(chars: &mut Peekable<Chars>, max: usize) -> Option<u32> {
+    let mut value: Option<u32> = None;
+    for _ in 0..max {
+        let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) else {
+            break;
+        };
+        chars.next();
+        value = Some(value.unwrap_or(0) * 10 + digit);
+    }
+    value
+}
+
+// index of the three letter name, in any case
+fn name(chars: &mut Peekable<Chars>, names: &[&str]) -> Option<usize> {
+    let word: String = chars.take(3).collect();
+    names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(&word))
+}
+
+// times are numbers in the unit `clock` returns, milliseconds unless book compatible
+fn to_millis<W>(interpreter: &Interpreter<W>, argument: &Object) -> Result<i64, NativeError>
+where
+    W: std::io::Write,
+{
+    let time = number_argument(argument)?;
+    let millis = if interpreter.clock_seconds() {
+        time * 1000.0
+    } else {
+        time
+    };
+    if !millis.is_finite() || millis.abs() > MAX_MILLIS {
+        return Err(NativeError::invalid_time(&format!(
+            "{time} is out of range"
+        )));
+    }
+    Ok(millis.floor() as i64)
+}
+
+fn from_millis<W>(interpreter: &Interpreter<W>, millis: i64) -> Object
+where
+    W: std::io::Write,
+{
+    let time = if interpreter.clock_seconds() {
+        millis as f64 / 1000.0
+    } else {
+        millis as f64
+    };
+    Object::Number(Number::from(time))
+}
+
+// native date functions, in utc. `formatTime` and `parseTime` take `%Y` year, `%m` month,
+// `%d` day, `%H` hour, `%M` minute, `%S` second, `%f` millisecond, `%a` weekday name,
+// `%b` month name and `%%`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Dates {
+    Now,
+    FormatTime,
+    ParseTime,
+}
+
+impl Dates {
+    pub const ALL: [Dates; 3] = [Dates::Now, Dates::FormatTime, Dates::ParseTime];
+}
+
+impl NativeCallable for Dates {
+    fn name(&self) -> &str {
+        match self {
+            Dates::Now => "now",
+            Dates::FormatTime => "formatTime",
+            Dates::ParseTime => "parseTime",
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Dates::Now => 0,
+            Dates::FormatTime | Dates::ParseTime => 2,
+        }
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let object = match self {
+            Dates::Now => {
+                let millis = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as i64;
+                let now = DateTime::from_millis(millis);
+                let number = |value: i64| Object::Number(Number::from(value as f64));
+                let map = LoxMap::from_fields([
+                    ("year", number(now.year)),
+                    ("month", number(now.month as i64)),
+                    ("day", number(now.day as i64)),
+                    ("hour", number(now.hour as i64)),
+                    ("minute", number(now.minute as i64)),
+                    ("second", number(now.second as i64)),
+                    ("millisecond", number(now.millisecond as i64)),
+                    ("weekday", number(now.weekday() as i64)),
+                    ("time", from_millis(interpreter, millis)),
+                ]);
+                Object::Map(map)
+            }
+            Dates::FormatTime => {
+                let millis = to_millis(interpreter, &arguments[0])?;
+                let format = string_argument(&arguments[1])?;
+                Object::string(DateTime::from_millis(millis).format(format)?)
+            }
+            Dates::ParseTime => {
+                let input = string_argument(&arguments[0])?;
+                let format = string_argument(&arguments[1])?;
+                let millis = DateTime::parse(input, format)?.to_millis();
+                if millis.abs() as f64 > MAX_MILLIS {
+                    return Err(NativeError::invalid_time(&format!(
+                        "`{input}` is out of range"
+                    )));
+                }
+                from_millis(interpreter, millis)
+            }
+        };
+        Ok(object)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn civil_dates_round_trip() {
+        for days in [-719468, -1, 0, 59, 11016, 19358, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+
+        let date_time = DateTime::parse("2024-02-29 13:05:09.042", "%Y-%m-%d %H:%M:%S.%f");
+        let date_time = date_time.unwrap();
+        assert_eq!(DateTime::from_millis(date_time.to_millis()), date_time);
+        assert_eq!(date_time.format("%a %d %b %Y").unwrap(), "Thu 29 Feb 2024");
+        assert!(DateTime::parse("2023-02-29", "%Y-%m-%d").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{error::native_error::NativeError, interpreter::Interpreter, object::Object};
+
+use super::{string_argument, NativeCallable, NativeResult};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+// placeholder specification `{[:][<|>][width][.precision]}`
+#[derive(Debug, Default)]
+struct Spec {
+    align: Option<Align>,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Result<Self, NativeError> {
+        let invalid = || NativeError::invalid_format(&format!("invalid placeholder `{{{spec}}}`"));
+
+        let mut result = Spec::default();
+        let spec = match spec.strip_prefix(':') {
+            Some(spec) => spec,
+            None if spec.is_empty() => return Ok(result),
+            None => return Err(invalid()),
+        };
+
+        let spec = if let Some(spec) = spec.strip_prefix('<') {
+            result.align = Some(Align::Left);
+            spec
+        } else if let Some(spec) = spec.strip_prefix('>') {
+            result.align = Some(Align::Right);
+            spec
+        } else {
+            spec
+        };
+
+        let (width, precision) = match spec.split_once('.') {
+            Some((width, precision)) => (width, Some(precision)),
+            None => (spec, None),
+        };
+        if !width.is_empty() {
+            result.width = Some(width.parse().map_err(|_| invalid())?);
+        }
+        if let Some(precision) = precision {
+            result.precision = Some(precision.parse().map_err(|_| invalid())?);
+        }
+
+        Ok(result)
+    }
+
+    fn apply(&self, value: &Object) -> Result<String, NativeError> {
+        let string = match (self.precision, value) {
+            (Some(precision), Object::Number(number)) => format!("{:.*}", precision, **number),
+            (Some(_), value) => return Err(NativeError::invalid_argument("number", value)),
+            (None, Object::Null) => "nil".to_string(),
+            (None, value) => value.to_string(),
+        };
+
+        // numbers are right aligned by default, everything else is left aligned
+        let align = self.align.unwrap_or(match value {
+            Object::Number(_) => Align::Right,
+            _ => Align::Left,
+        });
+        let width = self.width.unwrap_or(0);
+        let formatted = match align {
+            Align::Left => format!("{string:<width$}"),
+            Align::Right => format!("{string:>width$}"),
+        };
+        Ok(formatted)
+    }
+}
+
+fn read_placeholder(chars: &mut Peekable<Chars>) -> Result<String, NativeError> {
+    let mut spec = String::new();
+    for c in chars.by_ref() {
+        if c == '}' {
+            return Ok(spec);
+        }
+        spec.push(c);
+    }
+    Err(NativeError::invalid_format("unclosed `{`"))
+}
+
+pub(crate) fn format(template: &str, values: &[Object]) -> Result<String, NativeError> {
+    let mut result = String::new();
+    let mut values = values.iter();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(c);
+            }
+            ('{', _) => {
+                let spec = Spec::parse(&read_placeholder(&mut chars)?)?;
+                let value = values
+                    .next()
+                    .ok_or_else(|| NativeError::invalid_format("not enough arguments"))?;
+                result.push_str(&spec.apply(value)?);
+            }
+            ('}', _) => return Err(NativeError::invalid_format("unmatched `}`")),
+            (c, _) => result.push(c),
+        }
+    }
+
+    if values.next().is_some() {
+        return Err(NativeError::invalid_format("too many arguments"));
+    }
+
+    Ok(result)
+}
+
+// native format function, `format("{} = {:.2}", name, value)`
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Format;
+
+impl NativeCallable for Format {
+    fn name(&self) -> &str {
+        "format"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn variadic(&self) -> bool {
+        true
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let template = string_argument(&arguments[0])?;
+        let string = format(template, &arguments[1..])?;
+        Ok(Object::string(string))
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    error::native_error::NativeError,
+    interpreter::Interpreter,
+    object::Object,
+    shared::{MaybeSend, Shared},
+    value::Value,
+};
+
+use super::{NativeCallable, NativeResult};
+
+// what host code can be registered as a native function. it only sees plain values, an
+// error is reported where the script called it
+pub trait HostFn: Fn(&[Value]) -> Result<Value, String> + MaybeSend {}
+
+impl<F: Fn(&[Value]) -> Result<Value, String> + MaybeSend> HostFn for F {}
+
+// native function registered by the embedder through `Interpreter::register_native`
+#[derive(Clone)]
+pub(crate) struct HostFunction {
+    name: String,
+    arity: usize,
+    function: Shared<dyn HostFn>,
+}
+
+impl HostFunction {
+    pub fn new(name: &str, arity: usize, function: impl HostFn + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            function: Shared::new(function),
+        }
+    }
+}
+
+impl PartialEq for HostFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Shared::ptr_eq(&self.function, &other.function)
+    }
+}
+
+// cannot hash closure
+impl Hash for HostFunction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.arity.hash(state);
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+impl NativeCallable for HostFunction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let arguments = arguments
+            .iter()
+            .map(Value::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        (self.function)(&arguments)
+            .map(Object::from)
+            .map_err(|message| NativeError::custom(&message))
+    }
+}
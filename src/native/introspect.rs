@@ -0,0 +1,154 @@
+use crate::{
+    interpreter::Interpreter,
+    list::LoxList,
+    map::LoxMap,
+    object::{Number, Object},
+};
+
+use super::{NativeCallable, NativeResult};
+
+fn names_to_list(names: Vec<String>) -> Object {
+    Object::List(LoxList::new(
+        names.into_iter().map(Object::string).collect(),
+    ))
+}
+
+// native globals function, names of user defined global variables
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Globals;
+
+impl NativeCallable for Globals {
+    fn name(&self) -> &str {
+        "globals"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        Ok(names_to_list(interpreter.environment_mut().global_names()))
+    }
+}
+
+// native locals function, names of variables in the enclosing local scopes
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Locals;
+
+impl NativeCallable for Locals {
+    fn name(&self) -> &str {
+        "locals"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        Ok(names_to_list(interpreter.environment_mut().local_names()))
+    }
+}
+
+// native gc function, collects garbage once the current statement finishes
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Gc;
+
+impl NativeCallable for Gc {
+    fn name(&self) -> &str {
+        "gc"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        interpreter.request_gc();
+        Ok(Object::Null)
+    }
+}
+
+// native collect function, collects garbage right away and returns how many
+// environments and instances were freed. values the running statement holds are kept,
+// they are referenced from outside of the heap
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Collect;
+
+impl NativeCallable for Collect {
+    fn name(&self) -> &str {
+        "collect"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let freed = interpreter.collect_garbage();
+        Ok(Object::Number(Number::from(freed as f64)))
+    }
+}
+
+// native memoryStats function, a map of the live environments, instances, strings,
+// the bytes they hold and the collections so far
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct MemoryStatsValue;
+
+impl NativeCallable for MemoryStatsValue {
+    fn name(&self) -> &str {
+        "memoryStats"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let stats = interpreter.memory_stats();
+        let number = |value: usize| Object::Number(Number::from(value as f64));
+        let map = LoxMap::from_fields([
+            ("environments", number(stats.environments)),
+            ("instances", number(stats.instances)),
+            ("strings", number(stats.strings)),
+            ("bytes", number(stats.bytes)),
+            ("collections", number(stats.collections as usize)),
+        ]);
+        Ok(Object::Map(map))
+    }
+}
+
+// native inspect function, the type of a value, with the fields and methods of instances
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Inspect;
+
+impl NativeCallable for Inspect {
+    fn name(&self) -> &str {
+        "inspect"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        Ok(Object::string(arguments[0].describe()))
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{interpreter::Interpreter, object::Object};
+
+use super::{NativeCallable, NativeResult};
+
+// native readLine function, returns nil at the end of input
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct ReadLine;
+
+impl NativeCallable for ReadLine {
+    fn name(&self) -> &str {
+        "readLine"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let line = interpreter.read_line()?;
+        Ok(line.map(Object::string).unwrap_or(Object::Null))
+    }
+}
+
+// native write function, like `print` but without the trailing newline
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Write;
+
+impl NativeCallable for Write {
+    fn name(&self) -> &str {
+        "write"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        interpreter.write_str(&arguments[0].to_string())?;
+        Ok(Object::Null)
+    }
+}
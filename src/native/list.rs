@@ -0,0 +1,204 @@
+use std::{cmp::Ordering, mem::size_of};
+
+use crate::{
+    error::native_error::NativeError,
+    interpreter::Interpreter,
+    list::LoxList,
+    object::{Number, Object},
+};
+
+use super::{
+    call_back, index_argument, list_argument, string_argument, NativeCallable, NativeResult,
+};
+
+// native list functions. they change the list given, except `map` and `filter`
+// which return a new one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Lists {
+    List,
+    Push,
+    Pop,
+    Insert,
+    RemoveAt,
+    Contains,
+    Sort,
+    Reverse,
+    Map,
+    Filter,
+    Reduce,
+}
+
+impl Lists {
+    pub const ALL: [Lists; 11] = [
+        Lists::List,
+        Lists::Push,
+        Lists::Pop,
+        Lists::Insert,
+        Lists::RemoveAt,
+        Lists::Contains,
+        Lists::Sort,
+        Lists::Reverse,
+        Lists::Map,
+        Lists::Filter,
+        Lists::Reduce,
+    ];
+}
+
+// numbers and strings sort in their natural order, but not together
+fn compare(lhs: &Object, rhs: &Object) -> Result<Ordering, NativeError> {
+    match (lhs, rhs) {
+        (Object::Number(lhs), Object::Number(rhs)) => Ok(lhs.total_cmp(rhs)),
+        (Object::String(lhs), Object::String(rhs)) => Ok(lhs.cmp(rhs)),
+        (Object::Number(_), rhs) => Err(NativeError::invalid_argument("number", rhs)),
+        (Object::String(_), rhs) => Err(NativeError::invalid_argument("string", rhs)),
+        (lhs, _) => Err(NativeError::invalid_argument("number or string", lhs)),
+    }
+}
+
+fn sort(list: &LoxList) -> Result<(), NativeError> {
+    let mut elements = list.elements().clone();
+    if let [first] = elements.as_slice() {
+        compare(first, first)?;
+    }
+    // checked first, `sort_by` cannot stop at an error
+    for pair in elements.windows(2) {
+        compare(&pair[0], &pair[1])?;
+    }
+    elements.sort_by(|lhs, rhs| compare(lhs, rhs).unwrap_or(Ordering::Equal));
+    *list.elements_mut() = elements;
+    Ok(())
+}
+
+// bytes the storage of the list grew by, see `Interpreter::charge`
+fn grow(list: &LoxList, change: impl FnOnce(&mut Vec<Object>)) -> usize {
+    let mut elements = list.elements_mut();
+    let capacity = elements.capacity();
+    change(&mut elements);
+    (elements.capacity() - capacity) * size_of::<Object>()
+}
+
+impl NativeCallable for Lists {
+    fn name(&self) -> &str {
+        match self {
+            Lists::List => "list",
+            Lists::Push => "push",
+            Lists::Pop => "pop",
+            Lists::Insert => "insert",
+            Lists::RemoveAt => "removeAt",
+            Lists::Contains => "contains",
+            Lists::Sort => "sort",
+            Lists::Reverse => "reverse",
+            Lists::Map => "map",
+            Lists::Filter => "filter",
+            Lists::Reduce => "reduce",
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Lists::List => 0,
+            Lists::Insert | Lists::Reduce => 3,
+            Lists::Push | Lists::RemoveAt | Lists::Contains | Lists::Map | Lists::Filter => 2,
+            Lists::Pop | Lists::Sort | Lists::Reverse => 1,
+        }
+    }
+
+    // `list(1, 2, 3)` makes a list of its arguments
+    fn variadic(&self) -> bool {
+        matches!(self, Lists::List)
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        if let Lists::List = self {
+            return Ok(Object::List(LoxList::new(arguments)));
+        }
+
+        if let (Lists::Contains, Object::String(string)) = (&self, &arguments[0]) {
+            let pattern = string_argument(&arguments[1])?;
+            return Ok(Object::Bool(string.contains(pattern)));
+        }
+
+        let list = list_argument(&arguments[0])?;
+        let object = match self {
+            Lists::List => unreachable!("list takes any arguments"),
+            Lists::Push => {
+                let grown = grow(list, |elements| elements.push(arguments[1].clone()));
+                interpreter.charge(grown);
+                Object::Null
+            }
+            Lists::Pop => list.elements_mut().pop().unwrap_or(Object::Null),
+            Lists::Insert => {
+                let index = index_argument(&arguments[1])?;
+                let len = list.len();
+                if index > len {
+                    return Err(NativeError::index_out_of_bounds(index, len));
+                }
+                let grown = grow(list, |elements| {
+                    elements.insert(index, arguments[2].clone())
+                });
+                interpreter.charge(grown);
+                Object::Null
+            }
+            Lists::RemoveAt => {
+                let index = index_argument(&arguments[1])?;
+                let mut elements = list.elements_mut();
+                if index >= elements.len() {
+                    return Err(NativeError::index_out_of_bounds(index, elements.len()));
+                }
+                elements.remove(index)
+            }
+            Lists::Contains => Object::Bool(list.elements().contains(&arguments[1])),
+            Lists::Sort => {
+                sort(list)?;
+                Object::Null
+            }
+            Lists::Reverse => {
+                list.elements_mut().reverse();
+                Object::Null
+            }
+            // the callbacks may change the list, they run over a copy of it
+            Lists::Map => {
+                let elements = list.elements().clone();
+                let mut mapped = Vec::with_capacity(elements.len());
+                for element in elements {
+                    mapped.push(call_back(interpreter, &arguments[1], vec![element])?);
+                }
+                Object::List(LoxList::new(mapped))
+            }
+            Lists::Filter => {
+                let elements = list.elements().clone();
+                let mut kept = Vec::new();
+                for element in elements {
+                    if call_back(interpreter, &arguments[1], vec![element.clone()])?.is_truthy() {
+                        kept.push(element);
+                    }
+                }
+                Object::List(LoxList::new(kept))
+            }
+            Lists::Reduce => {
+                let elements = list.elements().clone();
+                let mut accumulator = arguments[2].clone();
+                for element in elements {
+                    accumulator =
+                        call_back(interpreter, &arguments[1], vec![accumulator, element])?;
+                }
+                accumulator
+            }
+        };
+
+        Ok(object)
+    }
+}
+
+// `indexOf` on a list, -1 when the value is not in it
+pub(crate) fn index_of(list: &LoxList, value: &Object) -> Object {
+    let index = list
+        .elements()
+        .iter()
+        .position(|element| element == value)
+        .map_or(-1.0, |index| index as f64);
+    Object::Number(Number::from(index))
+}
@@ -0,0 +1,112 @@
+use std::mem::size_of;
+
+use crate::{
+    error::native_error::NativeError,
+    interpreter::Interpreter,
+    list::LoxList,
+    map::{LoxMap, MapKey},
+    object::Object,
+};
+
+use super::{NativeCallable, NativeResult};
+
+fn map_argument(argument: &Object) -> Result<&LoxMap, NativeError> {
+    match argument {
+        Object::Map(map) => Ok(map),
+        _ => Err(NativeError::invalid_argument("map", argument)),
+    }
+}
+
+// `newMap()` makes an empty one, `map` is taken by lists
+// `map()` makes an empty one, like `list()` makes a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Maps {
+    New,
+    Get,
+    Set,
+    Has,
+    Remove,
+    Keys,
+    Values,
+}
+
+impl Maps {
+    pub const ALL: [Maps; 7] = [
+        Maps::New,
+        Maps::Get,
+        Maps::Set,
+        Maps::Has,
+        Maps::Remove,
+        Maps::Keys,
+        Maps::Values,
+    ];
+}
+
+impl NativeCallable for Maps {
+    fn name(&self) -> &str {
+        match self {
+            Maps::New => "newMap",
+            Maps::Get => "get",
+            Maps::Set => "set",
+            Maps::Has => "has",
+            Maps::Remove => "remove",
+            Maps::Keys => "keys",
+            Maps::Values => "values",
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Maps::New => 0,
+            Maps::Keys | Maps::Values => 1,
+            Maps::Get | Maps::Has | Maps::Remove => 2,
+            Maps::Set => 3,
+        }
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        if let Maps::New = self {
+            return Ok(Object::Map(LoxMap::default()));
+        }
+
+        let map = map_argument(&arguments[0])?;
+        let object = match self {
+            Maps::New => unreachable!("newMap takes no arguments"),
+            // missing keys are nil
+            Maps::Get => map
+                .entries()
+                .get(&MapKey::new(&arguments[1])?)
+                .cloned()
+                .unwrap_or(Object::Null),
+            Maps::Set => {
+                let key = MapKey::new(&arguments[1])?;
+                let mut entries = map.entries_mut();
+                let capacity = entries.capacity();
+                let key_bytes = match &key {
+                    MapKey::String(string) if !entries.contains_key(&key) => string.capacity(),
+                    _ => 0,
+                };
+                entries.insert(key, arguments[2].clone());
+                let grown = (entries.capacity() - capacity) * size_of::<(MapKey, Object)>();
+                drop(entries);
+                // the storage the map grew by and the new key, see `Interpreter::charge`
+                interpreter.charge(grown + key_bytes);
+                Object::Null
+            }
+            Maps::Has => Object::Bool(map.entries().contains_key(&MapKey::new(&arguments[1])?)),
+            // the others keep their order
+            Maps::Remove => {
+                let key = MapKey::new(&arguments[1])?;
+                let removed = map.entries_mut().shift_remove(&key);
+                removed.unwrap_or(Object::Null)
+            }
+            Maps::Keys => Object::List(LoxList::new(map.keys())),
+            Maps::Values => Object::List(LoxList::new(map.entries().values().cloned().collect())),
+        };
+
+        Ok(object)
+    }
+}
@@ -0,0 +1,91 @@
+use std::f64::consts::PI;
+
+use crate::{
+    interpreter::Interpreter,
+    object::{Number, Object},
+};
+
+use super::{number_argument, NativeCallable, NativeResult};
+
+// numbers defined next to the math functions
+pub(crate) const CONSTANTS: [(&str, f64); 1] = [("pi", PI)];
+
+// native math functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Math {
+    Sqrt,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Min,
+    Max,
+    Pow,
+    Sin,
+    Cos,
+}
+
+impl Math {
+    pub const ALL: [Math; 10] = [
+        Math::Sqrt,
+        Math::Abs,
+        Math::Floor,
+        Math::Ceil,
+        Math::Round,
+        Math::Min,
+        Math::Max,
+        Math::Pow,
+        Math::Sin,
+        Math::Cos,
+    ];
+}
+
+impl NativeCallable for Math {
+    fn name(&self) -> &str {
+        match self {
+            Math::Sqrt => "sqrt",
+            Math::Abs => "abs",
+            Math::Floor => "floor",
+            Math::Ceil => "ceil",
+            Math::Round => "round",
+            Math::Min => "min",
+            Math::Max => "max",
+            Math::Pow => "pow",
+            Math::Sin => "sin",
+            Math::Cos => "cos",
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Math::Min | Math::Max | Math::Pow => 2,
+            _ => 1,
+        }
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let arguments = arguments
+            .iter()
+            .map(number_argument)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let result = match self {
+            Math::Sqrt => arguments[0].sqrt(),
+            Math::Abs => arguments[0].abs(),
+            Math::Floor => arguments[0].floor(),
+            Math::Ceil => arguments[0].ceil(),
+            // half-way cases are rounded away from zero
+            Math::Round => arguments[0].round(),
+            Math::Min => arguments[0].min(arguments[1]),
+            Math::Max => arguments[0].max(arguments[1]),
+            Math::Pow => arguments[0].powf(arguments[1]),
+            Math::Sin => arguments[0].sin(),
+            Math::Cos => arguments[0].cos(),
+        };
+
+        Ok(Object::Number(Number::from(result)))
+    }
+}
@@ -0,0 +1,436 @@
+pub(crate) mod convert;
+pub(crate) mod copy;
+pub(crate) mod date;
+pub(crate) mod format;
+pub(crate) mod host;
+pub(crate) mod introspect;
+pub(crate) mod io;
+pub(crate) mod list;
+pub(crate) mod map;
+pub(crate) mod math;
+pub(crate) mod process;
+pub(crate) mod random;
+pub(crate) mod string;
+pub(crate) mod time;
+
+use crate::{
+    callable::Callable,
+    error::{native_error::NativeError, runtime_error::RuntimeError},
+    interpreter::Interpreter,
+    list::LoxList,
+    object::Object,
+};
+
+use self::{
+    convert::{ParseNumber, Str},
+    copy::CloneValue,
+    date::Dates,
+    format::Format,
+    host::HostFunction,
+    introspect::{Collect, Gc, Globals, Inspect, Locals, MemoryStatsValue},
+    io::{ReadLine, Write},
+    list::Lists,
+    map::Maps,
+    math::Math,
+    process::{Args, Env, Exec, Exit},
+    random::{Random, RandomInt},
+    string::Strings,
+    time::{Clock, Monotonic},
+};
+
+pub(crate) type NativeResult = Result<Object, NativeError>;
+
+fn number_argument(argument: &Object) -> Result<f64, NativeError> {
+    argument
+        .as_number()
+        .map(|number| *number)
+        .ok_or_else(|| NativeError::invalid_argument("number", argument))
+}
+
+fn string_argument(argument: &Object) -> Result<&str, NativeError> {
+    match argument {
+        Object::String(string) => Ok(string),
+        _ => Err(NativeError::invalid_argument("string", argument)),
+    }
+}
+
+fn list_argument(argument: &Object) -> Result<&LoxList, NativeError> {
+    match argument {
+        Object::List(list) => Ok(list),
+        _ => Err(NativeError::invalid_argument("list", argument)),
+    }
+}
+
+fn index_argument(argument: &Object) -> Result<usize, NativeError> {
+    let number = number_argument(argument)?;
+    if number >= 0.0 && number.fract() == 0.0 {
+        Ok(number as usize)
+    } else {
+        Err(NativeError::invalid_argument(
+            "non-negative integer",
+            argument,
+        ))
+    }
+}
+
+// calls a function given to a native, its errors keep their position in the script
+fn call_back<W>(
+    interpreter: &mut Interpreter<W>,
+    function: &Object,
+    arguments: Vec<Object>,
+) -> NativeResult
+where
+    W: std::io::Write,
+{
+    let mut callable = function
+        .as_callable()
+        .ok_or_else(|| NativeError::invalid_argument("function", function))?
+        .clone();
+    if callable.variadic() && arguments.len() < callable.arity()
+        || !callable.variadic() && arguments.len() != callable.arity()
+    {
+        return Err(NativeError::callback_arity(arguments.len(), function));
+    }
+    Ok(callable.call(interpreter, arguments)?)
+}
+
+// native functions don't know where they are called,
+// errors are located at the call site by the interpreter
+pub(crate) trait NativeCallable {
+    fn name(&self) -> &str;
+    fn arity(&self) -> usize;
+    // variadic functions take `arity` or more arguments
+    fn variadic(&self) -> bool {
+        false
+    }
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write;
+}
+
+// what natives can reach outside of the script, each can be turned off so untrusted scripts
+// only compute. no natives touch files or the network yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Capability {
+    Time,
+    Io,
+    Fs,
+    Net,
+    Process,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [
+        Capability::Time,
+        Capability::Io,
+        Capability::Fs,
+        Capability::Net,
+        Capability::Process,
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub(crate) enum NativeFunction {
+    Clock(Clock),
+    ReadLine(ReadLine),
+    Str(Str),
+    ParseNumber(ParseNumber),
+    Math(Math),
+    Random(Random),
+    RandomInt(RandomInt),
+    Strings(Strings),
+    Lists(Lists),
+    Maps(Maps),
+    Dates(Dates),
+    Exit(Exit),
+    Write(Write),
+    Format(Format),
+    Env(Env),
+    Args(Args),
+    Monotonic(Monotonic),
+    Globals(Globals),
+    Locals(Locals),
+    Gc(Gc),
+    Collect(Collect),
+    MemoryStats(MemoryStatsValue),
+    Inspect(Inspect),
+    Clone(CloneValue),
+    Exec(Exec),
+    Host(HostFunction),
+}
+
+impl NativeFunction {
+    pub fn clock() -> Self {
+        NativeFunction::Clock(Clock)
+    }
+
+    pub fn read_line() -> Self {
+        NativeFunction::ReadLine(ReadLine)
+    }
+
+    pub fn str() -> Self {
+        NativeFunction::Str(Str)
+    }
+
+    pub fn parse_number() -> Self {
+        NativeFunction::ParseNumber(ParseNumber)
+    }
+
+    pub fn math(math: Math) -> Self {
+        NativeFunction::Math(math)
+    }
+
+    pub fn random() -> Self {
+        NativeFunction::Random(Random)
+    }
+
+    pub fn random_int() -> Self {
+        NativeFunction::RandomInt(RandomInt)
+    }
+
+    pub fn strings(strings: Strings) -> Self {
+        NativeFunction::Strings(strings)
+    }
+
+    pub fn lists(lists: Lists) -> Self {
+        NativeFunction::Lists(lists)
+    }
+
+    pub fn maps(maps: Maps) -> Self {
+        NativeFunction::Maps(maps)
+    }
+
+    pub fn dates(dates: Dates) -> Self {
+        NativeFunction::Dates(dates)
+    }
+
+    pub fn exit() -> Self {
+        NativeFunction::Exit(Exit)
+    }
+
+    pub fn write() -> Self {
+        NativeFunction::Write(Write)
+    }
+
+    pub fn format() -> Self {
+        NativeFunction::Format(Format)
+    }
+
+    pub fn env() -> Self {
+        NativeFunction::Env(Env)
+    }
+
+    pub fn args() -> Self {
+        NativeFunction::Args(Args)
+    }
+
+    pub fn monotonic() -> Self {
+        NativeFunction::Monotonic(Monotonic)
+    }
+
+    pub fn globals() -> Self {
+        NativeFunction::Globals(Globals)
+    }
+
+    pub fn locals() -> Self {
+        NativeFunction::Locals(Locals)
+    }
+
+    pub fn gc() -> Self {
+        NativeFunction::Gc(Gc)
+    }
+
+    pub fn collect() -> Self {
+        NativeFunction::Collect(Collect)
+    }
+
+    pub fn memory_stats() -> Self {
+        NativeFunction::MemoryStats(MemoryStatsValue)
+    }
+
+    pub fn inspect() -> Self {
+        NativeFunction::Inspect(Inspect)
+    }
+
+    pub fn clone_value() -> Self {
+        NativeFunction::Clone(CloneValue)
+    }
+
+    pub fn exec() -> Self {
+        NativeFunction::Exec(Exec)
+    }
+
+    pub fn host(host: HostFunction) -> Self {
+        NativeFunction::Host(host)
+    }
+
+    // `None` for natives that only compute. `write` goes where `print` does, so it needs
+    // nothing, and host functions are trusted by whoever registered them
+    pub fn capability(&self) -> Option<Capability> {
+        match self {
+            NativeFunction::Clock(_)
+            | NativeFunction::Monotonic(_)
+            | NativeFunction::Dates(Dates::Now) => Some(Capability::Time),
+            NativeFunction::ReadLine(_) => Some(Capability::Io),
+            NativeFunction::Exit(_)
+            | NativeFunction::Env(_)
+            | NativeFunction::Args(_)
+            | NativeFunction::Exec(_) => Some(Capability::Process),
+            _ => None,
+        }
+    }
+
+    // native functions defined in the global environment, `exec` is only defined when
+    // asked for
+    pub fn prelude() -> Vec<Self> {
+        let mut natives = vec![
+            NativeFunction::clock(),
+            NativeFunction::read_line(),
+            NativeFunction::str(),
+            NativeFunction::parse_number(),
+            NativeFunction::random(),
+            NativeFunction::random_int(),
+            NativeFunction::exit(),
+            NativeFunction::write(),
+            NativeFunction::format(),
+            NativeFunction::env(),
+            NativeFunction::args(),
+            NativeFunction::monotonic(),
+            NativeFunction::globals(),
+            NativeFunction::locals(),
+            NativeFunction::gc(),
+            NativeFunction::collect(),
+            NativeFunction::memory_stats(),
+            NativeFunction::inspect(),
+            NativeFunction::clone_value(),
+        ];
+        natives.extend(Math::ALL.map(NativeFunction::math));
+        natives.extend(Strings::ALL.map(NativeFunction::strings));
+        natives.extend(Lists::ALL.map(NativeFunction::lists));
+        natives.extend(Maps::ALL.map(NativeFunction::maps));
+        natives.extend(Dates::ALL.map(NativeFunction::dates));
+        natives
+    }
+
+    pub fn call_native<W>(
+        &mut self,
+        interpreter: &mut Interpreter<W>,
+        arguments: Vec<Object>,
+    ) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        match self {
+            NativeFunction::Clock(native) => native.call(interpreter, arguments),
+            NativeFunction::ReadLine(native) => native.call(interpreter, arguments),
+            NativeFunction::Str(native) => native.call(interpreter, arguments),
+            NativeFunction::ParseNumber(native) => native.call(interpreter, arguments),
+            NativeFunction::Math(native) => native.call(interpreter, arguments),
+            NativeFunction::Random(native) => native.call(interpreter, arguments),
+            NativeFunction::RandomInt(native) => native.call(interpreter, arguments),
+            NativeFunction::Strings(native) => native.call(interpreter, arguments),
+            NativeFunction::Lists(native) => native.call(interpreter, arguments),
+            NativeFunction::Maps(native) => native.call(interpreter, arguments),
+            NativeFunction::Dates(native) => native.call(interpreter, arguments),
+            NativeFunction::Exit(native) => native.call(interpreter, arguments),
+            NativeFunction::Write(native) => native.call(interpreter, arguments),
+            NativeFunction::Format(native) => native.call(interpreter, arguments),
+            NativeFunction::Env(native) => native.call(interpreter, arguments),
+            NativeFunction::Args(native) => native.call(interpreter, arguments),
+            NativeFunction::Monotonic(native) => native.call(interpreter, arguments),
+            NativeFunction::Globals(native) => native.call(interpreter, arguments),
+            NativeFunction::Locals(native) => native.call(interpreter, arguments),
+            NativeFunction::Gc(native) => native.call(interpreter, arguments),
+            NativeFunction::Collect(native) => native.call(interpreter, arguments),
+            NativeFunction::MemoryStats(native) => native.call(interpreter, arguments),
+            NativeFunction::Inspect(native) => native.call(interpreter, arguments),
+            NativeFunction::Clone(native) => native.call(interpreter, arguments),
+            NativeFunction::Exec(native) => native.call(interpreter, arguments),
+            NativeFunction::Host(native) => native.call(interpreter, arguments),
+        }
+    }
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        match self {
+            NativeFunction::Clock(native) => native.name(),
+            NativeFunction::ReadLine(native) => native.name(),
+            NativeFunction::Str(native) => native.name(),
+            NativeFunction::ParseNumber(native) => native.name(),
+            NativeFunction::Math(native) => native.name(),
+            NativeFunction::Random(native) => native.name(),
+            NativeFunction::RandomInt(native) => native.name(),
+            NativeFunction::Strings(native) => native.name(),
+            NativeFunction::Lists(native) => native.name(),
+            NativeFunction::Maps(native) => native.name(),
+            NativeFunction::Dates(native) => native.name(),
+            NativeFunction::Exit(native) => native.name(),
+            NativeFunction::Write(native) => native.name(),
+            NativeFunction::Format(native) => native.name(),
+            NativeFunction::Env(native) => native.name(),
+            NativeFunction::Args(native) => native.name(),
+            NativeFunction::Monotonic(native) => native.name(),
+            NativeFunction::Globals(native) => native.name(),
+            NativeFunction::Locals(native) => native.name(),
+            NativeFunction::Gc(native) => native.name(),
+            NativeFunction::Collect(native) => native.name(),
+            NativeFunction::MemoryStats(native) => native.name(),
+            NativeFunction::Inspect(native) => native.name(),
+            NativeFunction::Clone(native) => native.name(),
+            NativeFunction::Exec(native) => native.name(),
+            NativeFunction::Host(native) => native.name(),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            NativeFunction::Clock(native) => native.arity(),
+            NativeFunction::ReadLine(native) => native.arity(),
+            NativeFunction::Str(native) => native.arity(),
+            NativeFunction::ParseNumber(native) => native.arity(),
+            NativeFunction::Math(native) => native.arity(),
+            NativeFunction::Random(native) => native.arity(),
+            NativeFunction::RandomInt(native) => native.arity(),
+            NativeFunction::Strings(native) => native.arity(),
+            NativeFunction::Lists(native) => native.arity(),
+            NativeFunction::Maps(native) => native.arity(),
+            NativeFunction::Dates(native) => native.arity(),
+            NativeFunction::Exit(native) => native.arity(),
+            NativeFunction::Write(native) => native.arity(),
+            NativeFunction::Format(native) => native.arity(),
+            NativeFunction::Env(native) => native.arity(),
+            NativeFunction::Args(native) => native.arity(),
+            NativeFunction::Monotonic(native) => native.arity(),
+            NativeFunction::Globals(native) => native.arity(),
+            NativeFunction::Locals(native) => native.arity(),
+            NativeFunction::Gc(native) => native.arity(),
+            NativeFunction::Collect(native) => native.arity(),
+            NativeFunction::MemoryStats(native) => native.arity(),
+            NativeFunction::Inspect(native) => native.arity(),
+            NativeFunction::Clone(native) => native.arity(),
+            NativeFunction::Exec(native) => native.arity(),
+            NativeFunction::Host(native) => native.arity(),
+        }
+    }
+
+    fn variadic(&self) -> bool {
+        match self {
+            NativeFunction::Format(native) => native.variadic(),
+            NativeFunction::Lists(native) => native.variadic(),
+            _ => false,
+        }
+    }
+
+    fn call<W>(
+        &mut self,
+        interpreter: &mut Interpreter<W>,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError>
+    where
+        W: std::io::Write,
+    {
+        self.call_native(interpreter, arguments)
+            .map_err(RuntimeError::from)
+    }
+}
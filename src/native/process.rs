@@ -0,0 +1,129 @@
+use std::process::{Command, Stdio};
+
+use crate::{
+    error::native_error::NativeError,
+    interpreter::Interpreter,
+    list::LoxList,
+    map::LoxMap,
+    object::{Number, Object},
+};
+
+use super::{list_argument, number_argument, string_argument, NativeCallable, NativeResult};
+
+// native exit function, unwinds the whole program with the given status code
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Exit;
+
+impl NativeCallable for Exit {
+    fn name(&self) -> &str {
+        "exit"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let code = number_argument(&arguments[0])?;
+        if code.fract() != 0.0 {
+            return Err(NativeError::invalid_argument("integer", &arguments[0]));
+        }
+        if !(0.0..=255.0).contains(&code) {
+            return Err(NativeError::invalid_exit_code(code));
+        }
+        Err(NativeError::exit(code as i32))
+    }
+}
+
+// native env function, returns nil if the variable is not set
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Env;
+
+impl NativeCallable for Env {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let name = string_argument(&arguments[0])?;
+        Ok(std::env::var(name)
+            .map(Object::string)
+            .unwrap_or(Object::Null))
+    }
+}
+
+// native args function, returns arguments passed after the script path
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Args;
+
+impl NativeCallable for Args {
+    fn name(&self) -> &str {
+        "args"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let args = interpreter
+            .args()
+            .iter()
+            .cloned()
+            .map(Object::string)
+            .collect();
+        Ok(Object::List(LoxList::new(args)))
+    }
+}
+
+// native exec function, runs a program and waits for it. returns a map with its exit
+// `code`, nil when it was killed by a signal, and what it wrote to `stdout`.
+// its stderr goes where ours does. only defined when the host asks for it
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Exec;
+
+impl NativeCallable for Exec {
+    fn name(&self) -> &str {
+        "exec"
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let program = string_argument(&arguments[0])?;
+        let args = list_argument(&arguments[1])?
+            .elements()
+            .iter()
+            .map(|arg| string_argument(arg).map(str::to_string))
+            .collect::<Result<Vec<_>, _>>()?;
+        let output = Command::new(program)
+            .args(args)
+            .stderr(Stdio::inherit())
+            .output()?;
+
+        let code = output.status.code().map_or(Object::Null, |code| {
+            Object::Number(Number::from(code as f64))
+        });
+        let stdout = Object::string(String::from_utf8_lossy(&output.stdout));
+        let result = LoxMap::from_fields([("code", code), ("stdout", stdout)]);
+        Ok(Object::Map(result))
+    }
+}
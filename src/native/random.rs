@@ -0,0 +1,127 @@
+use crate::{
+    error::native_error::NativeError,
+    interpreter::Interpreter,
+    object::{Number, Object},
+    time::SystemTime,
+};
+
+use super::{number_argument, NativeCallable, NativeResult};
+
+// splitmix64 generator, good enough for scripts and cheap to seed
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // uniformly distributed in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        Self::new(now.as_nanos() as u64)
+    }
+}
+
+// native random function, returns a number in [0, 1)
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Random;
+
+impl NativeCallable for Random {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let number = interpreter.rng_mut().next_f64();
+        Ok(Object::Number(Number::from(number)))
+    }
+}
+
+// native randomInt function, returns an integer in [lo, hi], both ends included
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct RandomInt;
+
+impl NativeCallable for RandomInt {
+    fn name(&self) -> &str {
+        "randomInt"
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let lo = number_argument(&arguments[0])?;
+        let hi = number_argument(&arguments[1])?;
+        if lo > hi {
+            return Err(NativeError::invalid_range(lo, hi));
+        }
+
+        let (lo, hi) = (lo.ceil() as i64, hi.floor() as i64);
+        if lo > hi {
+            // there is no integer in between
+            return Err(NativeError::invalid_range(lo as f64, hi as f64));
+        }
+
+        let span = hi.abs_diff(lo).wrapping_add(1);
+        let offset = match span {
+            // the whole i64 range
+            0 => interpreter.rng_mut().next_u64(),
+            span => interpreter.rng_mut().next_u64() % span,
+        };
+        let number = lo.wrapping_add(offset as i64);
+        Ok(Object::Number(Number::from(number)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut first = Rng::new(42);
+        let mut second = Rng::new(42);
+        for _ in 0..10 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+    }
+
+    #[test]
+    fn float_in_unit_interval() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let number = rng.next_f64();
+            assert!((0.0..1.0).contains(&number));
+        }
+    }
+}
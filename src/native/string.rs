@@ -0,0 +1,131 @@
+use crate::{
+    error::native_error::NativeError,
+    interpreter::Interpreter,
+    list::LoxList,
+    object::{Number, Object},
+};
+
+use super::{index_argument, list, number_argument, string_argument, NativeCallable, NativeResult};
+
+// native string functions, indices count characters rather than bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Strings {
+    Len,
+    Substring,
+    IndexOf,
+    Split,
+    ToUpper,
+    ToLower,
+    Trim,
+    Chr,
+    Ord,
+}
+
+impl Strings {
+    pub const ALL: [Strings; 9] = [
+        Strings::Len,
+        Strings::Substring,
+        Strings::IndexOf,
+        Strings::Split,
+        Strings::ToUpper,
+        Strings::ToLower,
+        Strings::Trim,
+        Strings::Chr,
+        Strings::Ord,
+    ];
+}
+
+impl NativeCallable for Strings {
+    fn name(&self) -> &str {
+        match self {
+            Strings::Len => "len",
+            Strings::Substring => "substring",
+            Strings::IndexOf => "indexOf",
+            Strings::Split => "split",
+            Strings::ToUpper => "toUpper",
+            Strings::ToLower => "toLower",
+            Strings::Trim => "trim",
+            Strings::Chr => "chr",
+            Strings::Ord => "ord",
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Strings::Substring => 3,
+            Strings::IndexOf | Strings::Split => 2,
+            _ => 1,
+        }
+    }
+
+    fn call<W>(&mut self, _: &mut Interpreter<W>, arguments: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        match (&self, &arguments[0]) {
+            (Strings::Len, Object::List(list)) => {
+                return Ok(Object::Number(Number::from(list.len() as f64)))
+            }
+            (Strings::Len, Object::Map(map)) => {
+                return Ok(Object::Number(Number::from(map.len() as f64)))
+            }
+            _ => (),
+        }
+        if let (Strings::IndexOf, Object::List(list)) = (&self, &arguments[0]) {
+            return Ok(list::index_of(list, &arguments[1]));
+        }
+
+        if let Strings::Chr = self {
+            let code = number_argument(&arguments[0])?;
+            let character = (code.fract() == 0.0 && code >= 0.0 && code <= u32::MAX as f64)
+                .then(|| char::from_u32(code as u32))
+                .flatten()
+                .ok_or_else(|| NativeError::invalid_code_point(code))?;
+            return Ok(Object::string(character));
+        }
+
+        let string = string_argument(&arguments[0])?;
+        let object = match self {
+            Strings::Len => Object::Number(Number::from(string.chars().count() as f64)),
+            Strings::Substring => {
+                let start = index_argument(&arguments[1])?;
+                let end = index_argument(&arguments[2])?;
+                if start > end || end > string.chars().count() {
+                    return Err(NativeError::invalid_range(start as f64, end as f64));
+                }
+                let substring: String = string.chars().skip(start).take(end - start).collect();
+                Object::string(substring)
+            }
+            Strings::IndexOf => {
+                let pattern = string_argument(&arguments[1])?;
+                let index = string
+                    .find(pattern)
+                    .map(|byte_index| string[..byte_index].chars().count() as f64)
+                    .unwrap_or(-1.0);
+                Object::Number(Number::from(index))
+            }
+            Strings::Split => {
+                let separator = string_argument(&arguments[1])?;
+                let parts = if separator.is_empty() {
+                    string.chars().map(Object::string).collect()
+                } else {
+                    string.split(separator).map(Object::string).collect()
+                };
+                Object::List(LoxList::new(parts))
+            }
+            Strings::ToUpper => Object::string(string.to_uppercase()),
+            Strings::ToLower => Object::string(string.to_lowercase()),
+            Strings::Trim => Object::string(string.trim()),
+            Strings::Chr => unreachable!("chr takes a number"),
+            Strings::Ord => {
+                let mut chars = string.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Object::Number(Number::from(c as u32 as f64)),
+                    _ => return Err(NativeError::invalid_character(string)),
+                }
+            }
+        };
+
+        Ok(object)
+    }
+}
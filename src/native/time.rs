@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use crate::{
+    interpreter::Interpreter,
+    object::{Number, Object},
+    time::SystemTime,
+};
+
+use super::{NativeCallable, NativeResult};
+
+// milliseconds by default, seconds in book compatible mode. fractions are kept so short
+// intervals measured with `monotonic` are not rounded to zero
+fn duration_to_object<W>(interpreter: &Interpreter<W>, duration: Duration) -> Object
+where
+    W: std::io::Write,
+{
+    let number = if interpreter.clock_seconds() {
+        duration.as_secs_f64()
+    } else {
+        duration.as_secs_f64() * 1000.0
+    };
+    Object::Number(Number::from(number))
+}
+
+// native clock function, time since the unix epoch
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Clock;
+
+impl NativeCallable for Clock {
+    fn name(&self) -> &str {
+        "clock"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        Ok(duration_to_object(interpreter, now))
+    }
+}
+
+// native monotonic function, time since the interpreter started,
+// only differences between two calls are meaningful
+#[derive(Debug, Clone, PartialEq, Default, Hash)]
+pub(crate) struct Monotonic;
+
+impl NativeCallable for Monotonic {
+    fn name(&self) -> &str {
+        "monotonic"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call<W>(&mut self, interpreter: &mut Interpreter<W>, _: Vec<Object>) -> NativeResult
+    where
+        W: std::io::Write,
+    {
+        Ok(duration_to_object(interpreter, interpreter.elapsed()))
+    }
+}
@@ -1,15 +1,47 @@
 use std::{
+    fmt,
     hash::{Hash, Hasher},
     num::ParseFloatError,
-    ops::{Add, Deref, DerefMut, Div, Mul, Neg, Sub},
+    ops::{Add, BitAnd, BitOr, BitXor, Deref, DerefMut, Div, Mul, Neg, Shl, Shr, Sub},
     str::FromStr,
 };
 
-use crate::{callable::LoxCallable, class::LoxInstance, error::object_error::ObjectError};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    callable::{Callable, LoxCallable},
+    class::LoxInstance,
+    compact::Compact,
+    error::object_error::ObjectError,
+    list::LoxList,
+    map::LoxMap,
+};
 
 type ObjectOperationResult = Result<Object, ObjectError>;
 
-#[derive(Debug, Clone, Copy, PartialOrd)]
+thread_local! {
+    // addresses of the lists and maps being printed, innermost last
+    static PRINTING: std::cell::RefCell<Vec<usize>> = std::cell::RefCell::default();
+}
+
+// prints a list or map holding other objects. one holding itself, directly or through
+// others, prints `placeholder` where it repeats instead of recursing forever
+pub(crate) fn print_nested(
+    f: &mut fmt::Formatter<'_>,
+    address: usize,
+    placeholder: &str,
+    print: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+) -> fmt::Result {
+    if PRINTING.with(|printing| printing.borrow().contains(&address)) {
+        return write!(f, "{placeholder}");
+    }
+    PRINTING.with(|printing| printing.borrow_mut().push(address));
+    let result = print(f);
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, Serialize, Deserialize)]
 pub(crate) struct Number(f64);
 
 impl PartialEq for Number {
@@ -72,6 +104,20 @@ impl DerefMut for Number {
     }
 }
 
+impl Number {
+    // integer part used by bitwise operators:
+    // truncated toward zero, saturated to the i64 range, NaN becomes 0
+    pub fn to_integer(self) -> i64 {
+        self.0 as i64
+    }
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Self {
+        Self(value as f64)
+    }
+}
+
 impl From<f64> for Number {
     fn from(value: f64) -> Self {
         Self(value)
@@ -89,14 +135,35 @@ impl FromStr for Number {
 pub(crate) enum Object {
     Null,
     Number(Number),
-    String(String),
+    String(Compact<String>),
     Bool(bool),
-    Callable(LoxCallable),
-    LoxInstance(LoxInstance),
+    Callable(Compact<LoxCallable>),
+    LoxInstance(Compact<LoxInstance>),
+    List(LoxList),
+    Map(LoxMap),
 }
 
 #[allow(dead_code)]
 impl Object {
+    pub fn string(string: impl Into<String>) -> Self {
+        Object::String(Compact::new(string.into()))
+    }
+
+    pub fn callable(callable: LoxCallable) -> Self {
+        Object::Callable(Compact::new(callable))
+    }
+
+    pub fn instance(instance: LoxInstance) -> Self {
+        Object::LoxInstance(Compact::new(instance))
+    }
+
+    pub fn as_callable(&self) -> Option<&LoxCallable> {
+        match self {
+            Object::Callable(callable) => Some(callable),
+            _ => None,
+        }
+    }
+
     pub fn as_null(&self) -> Option<Object> {
         match self {
             Object::Null => Some(Object::Null),
@@ -119,9 +186,9 @@ impl Object {
         self.as_number().is_some()
     }
 
-    pub fn as_string(&self) -> Option<String> {
+    pub fn as_string(&self) -> Option<&str> {
         match self {
-            Object::String(string) => Some(string.clone()),
+            Object::String(string) => Some(string),
             _ => None,
         }
     }
@@ -141,6 +208,40 @@ impl Object {
         self.as_bool().is_some()
     }
 
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Null => "nil",
+            Object::Number(_) => "number",
+            Object::String(_) => "string",
+            Object::Bool(_) => "bool",
+            Object::Callable(callable) => match **callable {
+                LoxCallable::LoxClass(_) => "class",
+                _ => "function",
+            },
+            Object::LoxInstance(_) => "instance",
+            Object::List(_) => "list",
+            Object::Map(_) => "map",
+        }
+    }
+
+    // what `inspect()` and `:type` in the prompt show, instances list their fields and methods
+    pub fn describe(&self) -> String {
+        match self {
+            Object::LoxInstance(instance) => instance.describe(),
+            Object::Callable(callable) => format!("{} {}", self.type_name(), callable.name()),
+            object => object.type_name().to_string(),
+        }
+    }
+
+    // representation used inside containers, strings are quoted and nil is visible
+    pub fn repr(&self) -> String {
+        match self {
+            Object::Null => "nil".to_string(),
+            Object::String(string) => format!("{string:?}"),
+            object => object.to_string(),
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
         match self {
             Object::Null => false,
@@ -173,6 +274,26 @@ impl Object {
         Ok(Object::Bool(lhs <= rhs))
     }
 
+    pub fn bit_not(&self) -> ObjectOperationResult {
+        let value = self.as_number().ok_or_else(ObjectError::bitwise)?;
+        Ok(Object::Number(Number::from(!value.to_integer())))
+    }
+
+    fn bitwise_operands(&self, rhs: &Self) -> Result<(i64, i64), ObjectError> {
+        let lhs = self.as_number().ok_or_else(ObjectError::bitwise)?;
+        let rhs = rhs.as_number().ok_or_else(ObjectError::bitwise)?;
+        Ok((lhs.to_integer(), rhs.to_integer()))
+    }
+
+    // shifting a 64-bit integer by 64 or more bits has no meaningful result
+    fn shift_operands(&self, rhs: &Self) -> Result<(i64, u32), ObjectError> {
+        let (lhs, rhs) = self.bitwise_operands(rhs)?;
+        match u32::try_from(rhs) {
+            Ok(amount) if amount < i64::BITS => Ok((lhs, amount)),
+            _ => Err(ObjectError::shift_out_of_range(rhs)),
+        }
+    }
+
     pub fn ne(&self, rhs: &Self) -> ObjectOperationResult {
         Ok(Object::Bool(self != rhs))
     }
@@ -182,15 +303,17 @@ impl Object {
     }
 }
 
-impl ToString for Object {
-    fn to_string(&self) -> String {
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Object::Null => "".to_string(),
-            Object::Number(number) => number.clone().to_string(),
-            Object::String(string) => string.clone(),
-            Object::Bool(b) => b.to_string(),
-            Object::Callable(callable) => callable.to_string(),
-            Object::LoxInstance(instance) => instance.to_string(),
+            Object::Null => Ok(()),
+            Object::Number(number) => write!(f, "{}", number.0),
+            Object::String(string) => write!(f, "{string}"),
+            Object::Bool(b) => write!(f, "{b}"),
+            Object::Callable(callable) => write!(f, "{callable}"),
+            Object::LoxInstance(instance) => write!(f, "{instance}"),
+            Object::List(list) => write!(f, "{list}"),
+            Object::Map(map) => write!(f, "{map}"),
         }
     }
 }
@@ -210,8 +333,8 @@ impl Add for Object {
             Ok(Object::Number(
                 self.as_number().unwrap() + rhs.as_number().unwrap(),
             ))
-        } else if self.is_string() && rhs.is_string() {
-            Ok(Object::String(self.to_string() + &rhs.to_string()))
+        } else if let (Some(lhs), Some(rhs)) = (self.as_string(), rhs.as_string()) {
+            Ok(Object::string([lhs, rhs].concat()))
         } else {
             Err(ObjectError::addition())
         }
@@ -248,3 +371,44 @@ impl Div for Object {
         }
     }
 }
+
+impl BitAnd for Object {
+    type Output = ObjectOperationResult;
+    fn bitand(self, rhs: Self) -> Self::Output {
+        let (lhs, rhs) = self.bitwise_operands(&rhs)?;
+        Ok(Object::Number(Number::from(lhs & rhs)))
+    }
+}
+
+impl BitOr for Object {
+    type Output = ObjectOperationResult;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        let (lhs, rhs) = self.bitwise_operands(&rhs)?;
+        Ok(Object::Number(Number::from(lhs | rhs)))
+    }
+}
+
+impl BitXor for Object {
+    type Output = ObjectOperationResult;
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        let (lhs, rhs) = self.bitwise_operands(&rhs)?;
+        Ok(Object::Number(Number::from(lhs ^ rhs)))
+    }
+}
+
+impl Shl for Object {
+    type Output = ObjectOperationResult;
+    fn shl(self, rhs: Self) -> Self::Output {
+        let (lhs, rhs) = self.shift_operands(&rhs)?;
+        Ok(Object::Number(Number::from(lhs << rhs)))
+    }
+}
+
+// arithmetic shift, the sign bit is preserved
+impl Shr for Object {
+    type Output = ObjectOperationResult;
+    fn shr(self, rhs: Self) -> Self::Output {
+        let (lhs, rhs) = self.shift_operands(&rhs)?;
+        Ok(Object::Number(Number::from(lhs >> rhs)))
+    }
+}
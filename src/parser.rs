@@ -1,47 +1,97 @@
-use std::{iter::Peekable, vec::IntoIter};
+use std::{
+    iter::{Cloned, Peekable},
+    slice::Iter,
+};
 
 const MAXIMUM_ARGUMENTS: usize = 255;
 
+// every nested expression or statement takes several rust frames in the parser,
+// the resolver and the interpreter, deeper code is rejected instead of overflowing the stack
+const MAXIMUM_NESTING: usize = 256;
+
 use crate::{
     error::{parse_error::ParseError, reporter::ErrorReporter},
-    expr::{Assign, Binary, Call, Expr, Get, Grouping, Set, Super, This, Unary, Variable},
+    expr::{Assign, Binary, Call, Expr, Get, Grouping, Super, This, Unary, Variable},
     object::Object,
     scanner::Scanner,
-    stmt::{Block, Class, Function, If, Return, Stmt, Var, While},
+    shared::{Shared, Weak},
+    source::Span,
+    stmt::{Block, Class, ForClauses, ForIn, Function, If, Print, Return, Stmt, Var, While},
     token::{Token, TokenType},
 };
 
 type ParseResult<T> = Result<T, ParseError>;
 
-pub(crate) struct Parser {
-    it: Peekable<IntoIter<Token>>,
+// tokens are pulled from the iterator only when needed,
+// so the parser can consume the scanner directly
+pub(crate) struct Parser<I>
+where
+    I: Iterator<Item = Token>,
+{
+    it: Peekable<I>,
     errors: Vec<ParseError>,
+    depth: usize,
+    // the last `;` can be left out, a made up one takes its place
+    implicit_semicolon: bool,
+    // `class B < A` is accepted like in the book, besides `class B : A`
+    book: bool,
+    // the token `next` returned last, what a made up token comes from
+    previous: Option<Span>,
+    // every function and method declared, see `functions`
+    functions: Vec<Weak<Function>>,
 }
 
-impl From<&Scanner> for Parser {
-    fn from(scanner: &Scanner) -> Self {
-        Parser::new(scanner.tokens())
+impl<'a> From<&'a Scanner> for Parser<Cloned<Iter<'a, Token>>> {
+    fn from(scanner: &'a Scanner) -> Self {
+        Parser::new(scanner.tokens().iter().cloned())
     }
 }
 
-impl ErrorReporter<ParseError> for Parser {
+impl<I> ErrorReporter<ParseError> for Parser<I>
+where
+    I: Iterator<Item = Token>,
+{
     fn errors(&self) -> &[ParseError] {
         &self.errors
     }
 }
 
 #[allow(clippy::result_large_err)]
-impl Parser {
-    #[allow(clippy::unnecessary_to_owned)]
-    fn new(tokens: &[Token]) -> Self {
+impl<I> Parser<I>
+where
+    I: Iterator<Item = Token>,
+{
+    // `tokens` must end with `Eof`
+    pub fn new(tokens: I) -> Self {
         Parser {
-            it: tokens.to_vec().into_iter().peekable(),
+            it: tokens.peekable(),
             errors: Vec::new(),
+            depth: 0,
+            implicit_semicolon: false,
+            book: false,
+            previous: None,
+            functions: Vec::new(),
         }
     }
 
-    fn is_end(&self) -> bool {
-        self.it.len() == 1 // the last one if eof
+    // functions declared in the source, alive as long as something can still call them
+    pub fn functions(&self) -> &[Weak<Function>] {
+        &self.functions
+    }
+
+    // for the prompt, where `1 + 2` is shown without a `;`
+    pub fn with_implicit_semicolon(mut self) -> Self {
+        self.implicit_semicolon = true;
+        self
+    }
+
+    pub fn with_book_syntax(mut self) -> Self {
+        self.book = true;
+        self
+    }
+
+    fn is_end(&mut self) -> bool {
+        self.peek_type() == &TokenType::Eof
     }
 
     fn peek(&mut self) -> &Token {
@@ -57,7 +107,9 @@ impl Parser {
         if self.is_end() {
             None
         } else {
-            self.it.next()
+            let token = self.it.next();
+            self.previous = token.as_ref().map(Token::span);
+            token
         }
     }
 
@@ -74,6 +126,19 @@ impl Parser {
         }
     }
 
+    // runs `rule` one level deeper, the depth is restored even when parsing fails
+    // chains like `a + b + c` or `a.b.c` are parsed in a loop and don't count, the passes
+    // walking the tree they make grow the stack as they go
+    fn nested<T>(&mut self, rule: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.depth >= MAXIMUM_NESTING {
+            return Err(ParseError::too_deeply_nested(self.peek(), MAXIMUM_NESTING));
+        }
+        self.depth += 1;
+        let result = rule(self);
+        self.depth -= 1;
+        result
+    }
+
     pub fn parse(&mut self) -> Vec<Stmt> {
         let mut statements = Vec::new();
         loop {
@@ -85,7 +150,7 @@ impl Parser {
                 Ok(stmt) => statements.push(stmt),
                 Err(err) => {
                     if err.panic() {
-                        self.synchronize();
+                        self.synchronize(false);
                     }
                     self.errors.push(err)
                 }
@@ -95,6 +160,10 @@ impl Parser {
     }
 
     fn declaration(&mut self) -> ParseResult<Stmt> {
+        self.nested(Self::declaration_inner)
+    }
+
+    fn declaration_inner(&mut self) -> ParseResult<Stmt> {
         if self
             .match_peek_type_then_advance(&[TokenType::Class])
             .is_some()
@@ -118,8 +187,11 @@ impl Parser {
     fn class_declaration(&mut self) -> ParseResult<Stmt> {
         let class_name = self.consume_identifier("class name")?;
 
-        let superclass = if self.peek_type() == &TokenType::Colon {
-            self.consume(TokenType::Colon)?;
+        let inherits = match self.book {
+            true => &[TokenType::Colon, TokenType::Less][..],
+            false => &[TokenType::Colon],
+        };
+        let superclass = if self.match_peek_type_then_advance(inherits).is_some() {
             let name = self.consume_identifier("superclass name")?;
             Some(Variable::new(name))
         } else {
@@ -133,10 +205,24 @@ impl Parser {
                 break;
             }
 
-            if let &TokenType::Identifier(_) = self.peek_type() {
-                methods.push(self.fun_declaration()?);
-            } else {
-                break;
+            // a broken method is reported and skipped, the other methods are still parsed
+            let method = match self.peek_type() {
+                TokenType::Identifier(_) => self.function(),
+                _ => Err(ParseError::unexpected_token(
+                    self.peek(),
+                    &TokenType::RightBrace,
+                )),
+            };
+            match method {
+                Ok(method) => methods.push(method),
+                // nothing is left to recover at the end of the script
+                Err(err) => {
+                    self.synchronize_class_body();
+                    if self.is_end() {
+                        return Err(err);
+                    }
+                    self.errors.push(err);
+                }
             }
         }
 
@@ -146,6 +232,11 @@ impl Parser {
     }
 
     fn fun_declaration(&mut self) -> ParseResult<Stmt> {
+        Ok(Stmt::Function(self.function()?))
+    }
+
+    // a function or a method, after `fun`
+    fn function(&mut self) -> ParseResult<Shared<Function>> {
         let name = self.consume_identifier("function name")?;
         self.consume(TokenType::LeftParen)?;
         let mut params = Vec::new();
@@ -165,61 +256,47 @@ impl Parser {
             }
         }
         self.consume(TokenType::RightParen)?;
-        self.consume(TokenType::LeftBrace)?;
-        let body = self.block()?;
-        Ok(Stmt::Function(Function::new(name, params, body)))
+        let left_brace = self.consume(TokenType::LeftBrace)?;
+        let body = self.block(left_brace)?;
+        let function = Shared::new(Function::new(name, params, body));
+        self.functions.push(Shared::downgrade(&function));
+        Ok(function)
     }
 
     fn var_declaration(&mut self) -> ParseResult<Stmt> {
-        match self.peek_type() {
-            TokenType::Identifier(_) => {
-                let token = self.next().unwrap();
-                let initializer = match self
-                    .match_peek_type_then_advance(&[TokenType::Equal])
-                    .is_some()
-                {
-                    true => self.expression()?,
-                    false => Expr::Literal(Object::Null),
-                };
-                self.consume(TokenType::Semicolon)?;
-                Ok(Stmt::Var(Var::new(token, initializer)))
-            }
-            _ => {
-                let error = ParseError::unexpected_token(
-                    self.peek(),
-                    &TokenType::Identifier("variable name".to_string()),
-                );
-                Err(error)
-            }
-        }
+        let token = self.consume_identifier("variable name")?;
+        self.var_initializer(token)
+    }
+
+    // what follows the name of a variable
+    fn var_initializer(&mut self, token: Token) -> ParseResult<Stmt> {
+        let initializer = match self
+            .match_peek_type_then_advance(&[TokenType::Equal])
+            .is_some()
+        {
+            true => self.expression()?,
+            false => Expr::Literal(Object::Null),
+        };
+        self.consume(TokenType::Semicolon)?;
+        Ok(Stmt::Var(Var::new(token, initializer)))
     }
 
     fn statement(&mut self) -> ParseResult<Stmt> {
         match self.peek_type() {
-            TokenType::If => {
-                self.next();
-                self.if_statement()
-            }
-            TokenType::Print => {
-                self.next();
-                self.print_statement()
-            }
+            // like `return`, `if` and `print` keep their keyword for coverage
+            TokenType::If => self.if_statement(),
+            TokenType::Print => self.print_statement(),
             TokenType::Return => {
                 // we need keyword return to find the line
                 // so we don't call self.next() here
                 self.return_statement()
             }
-            TokenType::While => {
-                self.next();
-                self.while_statement()
-            }
-            TokenType::For => {
-                self.next();
-                self.for_statement()
-            }
+            // loops keep their keyword to locate errors raised while looping
+            TokenType::While => self.while_statement(),
+            TokenType::For => self.for_statement(),
             TokenType::LeftBrace => {
-                self.next();
-                self.block()
+                let left_brace = self.next().expect("the peeked `{`");
+                self.block(left_brace)
             }
             _ => self.expression_statement(),
         }
@@ -236,21 +313,34 @@ impl Parser {
     }
 
     fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::Print)?;
         let expr = self.expression()?;
         self.consume(TokenType::Semicolon)?;
-        Ok(Stmt::Print(expr))
+        Ok(Stmt::Print(Print::new(keyword, expr)))
     }
 
-    fn block(&mut self) -> ParseResult<Stmt> {
+    // the statements after `left_brace` up to the `}` closing it
+    fn block(&mut self, left_brace: Token) -> ParseResult<Stmt> {
         let mut statements = Vec::new();
         loop {
             if self.is_end() || self.peek_type() == &TokenType::RightBrace {
                 break;
             }
-            statements.push(self.declaration()?);
+            // a bad statement is reported and skipped, the rest of the block is still parsed
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(err) => {
+                    if err.panic() {
+                        self.synchronize(true);
+                    }
+                    self.errors.push(err)
+                }
+            }
         }
-        self.consume(TokenType::RightBrace)?;
-        Ok(Stmt::Block(Block::new(statements)))
+        let right_brace = self.consume(TokenType::RightBrace)?;
+        Ok(Stmt::Block(
+            Block::new(statements).with_braces(left_brace, right_brace),
+        ))
     }
 
     fn expression_statement(&mut self) -> ParseResult<Stmt> {
@@ -265,8 +355,9 @@ impl Parser {
     }
 
     fn if_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::If)?;
         self.consume(TokenType::LeftParen)?;
-        let condition = self.expression()?;
+        let condition = self.condition()?;
         self.consume(TokenType::RightParen)?;
 
         let then_branch = self.declaration()?;
@@ -279,18 +370,37 @@ impl Parser {
             _ => None,
         };
 
-        Ok(Stmt::If(If::new(condition, then_branch, else_branch)))
+        Ok(Stmt::If(If::new(
+            keyword,
+            condition,
+            then_branch,
+            else_branch,
+        )))
+    }
+
+    // the whole assignment was read when `=` is used where `==` was meant,
+    // so it is reported and the statement is still parsed
+    fn condition(&mut self) -> ParseResult<Expr> {
+        match self.expression() {
+            Err(err) if !err.panic() => {
+                self.errors.push(err.in_condition());
+                Ok(Expr::Literal(Object::Null))
+            }
+            result => result,
+        }
     }
 
     fn while_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::While)?;
         self.consume(TokenType::LeftParen)?;
-        let condition = self.expression()?;
+        let condition = self.condition()?;
         self.consume(TokenType::RightParen)?;
         let body = self.declaration()?;
-        Ok(Stmt::While(While::new(condition, body)))
+        Ok(Stmt::While(While::new(keyword, condition, body)))
     }
 
     fn for_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::For)?;
         self.consume(TokenType::LeftParen)?;
         let initializer = match self.peek_type() {
             TokenType::Semicolon => {
@@ -299,14 +409,21 @@ impl Parser {
             }
             TokenType::Var => {
                 self.next();
-                Some(self.var_declaration())
+                match self.consume_identifier("variable name") {
+                    Ok(name) if self.peek_is_in() => {
+                        self.next();
+                        return self.for_in_statement(keyword, name);
+                    }
+                    Ok(name) => Some(self.var_initializer(name)),
+                    Err(err) => Some(Err(err)),
+                }
             }
             _ => Some(self.expression_statement()),
         };
 
         let condition = match self.peek_type() {
             TokenType::Semicolon => None,
-            _ => Some(self.expression()?),
+            _ => Some(self.condition()?),
         };
         self.consume(TokenType::Semicolon)?;
 
@@ -316,14 +433,22 @@ impl Parser {
         };
         self.consume(TokenType::RightParen)?;
 
-        let body = self.statement()?;
+        let body = self.nested(Self::statement)?;
 
+        let clauses = ForClauses {
+            initializer: initializer.is_some(),
+            condition: condition.is_some(),
+            increment: increment.is_some(),
+        };
+
+        // the blocks and the loop made up below keep the `for` keyword, what is reported
+        // about them points at it instead of at code the user didn't write
         // attach increment to tail of the body
         let body = match increment {
             None => body,
             Some(inc) => {
                 let inc = Stmt::Expression(inc?);
-                Stmt::Block(Block::new(vec![body, inc]))
+                Stmt::Block(Block::new(vec![body, inc]).with_for_keyword(keyword.clone()))
             }
         };
 
@@ -331,32 +456,47 @@ impl Parser {
         let condition = condition.unwrap_or(Expr::Literal(Object::Bool(true)));
 
         // make a while loop
-        let while_statement = Stmt::While(While::new(condition, body));
+        let while_statement =
+            Stmt::While(While::new(keyword.clone(), condition, body).with_clauses(clauses));
 
         // attach initializer at the head of the while statement
         let for_statement = match initializer {
             None => while_statement,
-            Some(init) => Stmt::Block(Block::new(vec![init?, while_statement])),
+            Some(init) => {
+                Stmt::Block(Block::new(vec![init?, while_statement]).with_for_keyword(keyword))
+            }
         };
 
         Ok(for_statement)
     }
 
+    // `in` is only a keyword in `for` loops, it can still name variables
+    fn peek_is_in(&mut self) -> bool {
+        matches!(self.peek_type(), TokenType::Identifier(word) if &**word == "in")
+    }
+
+    fn for_in_statement(&mut self, keyword: Token, name: Token) -> ParseResult<Stmt> {
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen)?;
+        let body = self.nested(Self::statement)?;
+        Ok(Stmt::ForIn(ForIn::new(keyword, name, iterable, body)))
+    }
+
     // @todo this method currently pub, move this to private after all stmts are added
     pub fn expression(&mut self) -> ParseResult<Expr> {
-        self.assignment()
+        self.nested(Self::assignment)
     }
 
     fn assignment(&mut self) -> ParseResult<Expr> {
         let expr = self.logic_or()?;
 
         if let Some(equal) = self.match_peek_type_then_advance(&[TokenType::Equal]) {
-            let value = self.assignment()?;
+            let value = self.nested(Self::assignment)?;
             if let Expr::Variable(var) = expr {
                 Ok(Expr::Assign(Assign::new(var.name, value)))
             } else {
                 match expr {
-                    Expr::Get(get) => Ok(Expr::Set(Set::new(*get.object, get.name, value))),
+                    Expr::Get(get) => Ok(Expr::Set(get.into_set(value))),
                     _ => Err(ParseError::invalid_assignment(&equal).without_panic()),
                 }
             }
@@ -365,76 +505,93 @@ impl Parser {
         }
     }
 
-    fn logic_or(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.logical_and()?;
-        while let Some(operator) = self.match_peek_type_then_advance(&[TokenType::Or]) {
-            let rhs = self.logical_and()?;
-            expr = Expr::Logical(Binary::new(expr, operator, rhs));
+    // operands joined by any of `operators`, grouped from the left
+    fn binary_chain(
+        &mut self,
+        operators: &[TokenType],
+        operand: fn(&mut Self) -> ParseResult<Expr>,
+        expr: fn(Binary) -> Expr,
+    ) -> ParseResult<Expr> {
+        let mut lhs = operand(self)?;
+        while let Some(operator) = self.match_peek_type_then_advance(operators) {
+            let rhs = operand(self)?;
+            lhs = expr(Binary::new(lhs, operator, rhs));
         }
-        Ok(expr)
+        Ok(lhs)
+    }
+
+    fn logic_or(&mut self) -> ParseResult<Expr> {
+        self.binary_chain(&[TokenType::Or], Self::logical_and, Expr::Logical)
     }
 
     fn logical_and(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.equality()?;
-        while let Some(operator) = self.match_peek_type_then_advance(&[TokenType::And]) {
-            let rhs = self.equality()?;
-            expr = Expr::Logical(Binary::new(expr, operator, rhs));
-        }
-        Ok(expr)
+        self.binary_chain(&[TokenType::And], Self::equality, Expr::Logical)
     }
 
     fn equality(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.comparision()?;
-        while let Some(operator) =
-            self.match_peek_type_then_advance(&[TokenType::BangEqual, TokenType::EqualEqual])
-        {
-            let right = self.comparision()?;
-            expr = Expr::Binary(Binary::new(expr, operator, right));
-        }
-        Ok(expr)
+        self.binary_chain(
+            &[TokenType::BangEqual, TokenType::EqualEqual],
+            Self::comparision,
+            Expr::Binary,
+        )
     }
 
     fn comparision(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.term()?;
-        while let Some(operator) = self.match_peek_type_then_advance(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
-            let right = self.term()?;
-            expr = Expr::Binary(Binary::new(expr, operator, right));
-        }
-        Ok(expr)
+        self.binary_chain(
+            &[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ],
+            Self::bit_or,
+            Expr::Binary,
+        )
+    }
+
+    fn bit_or(&mut self) -> ParseResult<Expr> {
+        self.binary_chain(&[TokenType::Pipe], Self::bit_xor, Expr::Binary)
+    }
+
+    fn bit_xor(&mut self) -> ParseResult<Expr> {
+        self.binary_chain(&[TokenType::Caret], Self::bit_and, Expr::Binary)
+    }
+
+    fn bit_and(&mut self) -> ParseResult<Expr> {
+        self.binary_chain(&[TokenType::Ampersand], Self::shift, Expr::Binary)
+    }
+
+    fn shift(&mut self) -> ParseResult<Expr> {
+        self.binary_chain(
+            &[TokenType::LessLess, TokenType::GreaterGreater],
+            Self::term,
+            Expr::Binary,
+        )
     }
 
     fn term(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.factor()?;
-        while let Some(operator) =
-            self.match_peek_type_then_advance(&[TokenType::Minus, TokenType::Plus])
-        {
-            let right = self.factor()?;
-            expr = Expr::Binary(Binary::new(expr, operator, right));
-        }
-        Ok(expr)
+        self.binary_chain(
+            &[TokenType::Minus, TokenType::Plus],
+            Self::factor,
+            Expr::Binary,
+        )
     }
 
     fn factor(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.unary()?;
-        while let Some(operator) =
-            self.match_peek_type_then_advance(&[TokenType::Slash, TokenType::Star])
-        {
-            let right = self.unary()?;
-            expr = Expr::Binary(Binary::new(expr, operator, right));
-        }
-        Ok(expr)
+        self.binary_chain(
+            &[TokenType::Slash, TokenType::Star],
+            Self::unary,
+            Expr::Binary,
+        )
     }
 
     fn unary(&mut self) -> ParseResult<Expr> {
-        if let Some(operator) =
-            self.match_peek_type_then_advance(&[TokenType::Bang, TokenType::Minus])
-        {
-            let right = self.unary()?;
+        if let Some(operator) = self.match_peek_type_then_advance(&[
+            TokenType::Bang,
+            TokenType::Minus,
+            TokenType::Tilde,
+        ]) {
+            let right = self.nested(Self::unary)?;
             Ok(Expr::Unary(Unary::new(operator, right)))
         } else {
             Ok(self.call()?)
@@ -483,7 +640,7 @@ impl Parser {
             TokenType::False => Expr::Literal(Object::Bool(false)),
             TokenType::True => Expr::Literal(Object::Bool(true)),
             TokenType::Number(number) => Expr::Literal(Object::Number(*number)),
-            TokenType::String(string) => Expr::Literal(Object::String(string.clone())),
+            TokenType::String(string) => Expr::Literal(Object::string(string.to_string())),
             TokenType::LeftParen => {
                 self.next();
                 let expr = self.expression()?;
@@ -514,20 +671,26 @@ impl Parser {
         } else {
             Err(ParseError::unexpected_token(
                 self.peek(),
-                &TokenType::Identifier(ident_info.to_string()),
+                &TokenType::Identifier(ident_info.into()),
             ))
         }
     }
 
     fn consume(&mut self, token_type: TokenType) -> ParseResult<Token> {
         if self.peek_type() == &token_type {
-            Ok(self.next().unwrap())
-        } else {
-            Err(ParseError::unexpected_token(self.peek(), &token_type))
+            return Ok(self.next().unwrap());
+        }
+        // only the statement the source ends with can leave out its `;`
+        let implicit =
+            self.implicit_semicolon && token_type == TokenType::Semicolon && self.is_end();
+        match self.previous {
+            Some(previous) if implicit => Ok(Token::synthesized(token_type, previous)),
+            _ => Err(ParseError::unexpected_token(self.peek(), &token_type)),
         }
     }
 
-    fn synchronize(&mut self) {
+    // inside a block the `}` closing it is left for the block
+    fn synchronize(&mut self, in_block: bool) {
         // we explicited call next because we push token back after error
         let start_token_type = [
             TokenType::Class,
@@ -544,6 +707,9 @@ impl Parser {
             if self.is_end() || self.match_peek_type(&start_token_type) {
                 break;
             }
+            if in_block && self.peek_type() == &TokenType::RightBrace {
+                break;
+            }
             if &TokenType::Semicolon == self.peek_type() {
                 self.next(); // eat semicolon
                 break;
@@ -551,6 +717,30 @@ impl Parser {
             self.next();
         }
     }
+
+    // skip the rest of a broken class member, up to its `;` or past its body,
+    // the `}` closing the class is left for it
+    fn synchronize_class_body(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.peek_type() {
+                TokenType::Eof => return,
+                TokenType::RightBrace if depth == 0 => return,
+                TokenType::RightBrace if depth == 1 => {
+                    self.next();
+                    return;
+                }
+                TokenType::RightBrace => depth -= 1,
+                TokenType::LeftBrace => depth += 1,
+                TokenType::Semicolon if depth == 0 => {
+                    self.next();
+                    return;
+                }
+                _ => (),
+            }
+            self.next();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -558,7 +748,11 @@ mod test {
 
     use std::io::Write;
 
-    use crate::{ast_repr::AstRepr, error::reporter::Reporter, source::SourcePos};
+    use crate::{
+        ast_repr::AstRepr,
+        error::reporter::Reporter,
+        source::{CharPos, SourcePos},
+    };
 
     use super::*;
 
@@ -572,7 +766,7 @@ mod test {
         writeln!(&mut result, "{}", scanner.error_msg(&reporter))?;
 
         let mut parser = Parser::from(&scanner);
-        let mut ast_repr = AstRepr::default();
+        let mut ast_repr = AstRepr;
         let statements = parser.parse();
         writeln!(&mut result, "{}", ast_repr.repr(&statements))?;
 
@@ -584,111 +778,6 @@ mod test {
         Ok(())
     }
 
-    #[test]
-    fn primary() -> Result<(), std::io::Error> {
-        let source = r#"
-nil; true; false; "this is string";
-123; 123.456; (nil); variable;
-"#;
-        let expected_output = r#"
-Stmt::Expr(nil)
-Stmt::Expr(true)
-Stmt::Expr(false)
-Stmt::Expr("this is string")
-Stmt::Expr(123)
-Stmt::Expr(123.456)
-Stmt::Expr(Expr::Group(nil))
-Stmt::Expr(Expr::Variable(variable))
-"#;
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn grouping_must_be_closed() -> Result<(), std::io::Error> {
-        let source = r#"(1"#;
-        let expected_output = r#"
-[line 1]: ParseError: Expected `)`. Found `EOF`
-(1
- ^
-"#;
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn unary() -> Result<(), std::io::Error> {
-        let source = r#"
--1.2; !1.2; 
--"a"; !"a";
--nil; !nil;
--true; !true;
--false; !false;
--(1.2); !(1.2);
--x; !x;
-"#;
-
-        let expected_output = r#" 
-Stmt::Expr(Expr::Unary(- 1.2))
-Stmt::Expr(Expr::Unary(! 1.2))
-Stmt::Expr(Expr::Unary(- "a"))
-Stmt::Expr(Expr::Unary(! "a"))
-Stmt::Expr(Expr::Unary(- nil))
-Stmt::Expr(Expr::Unary(! nil))
-Stmt::Expr(Expr::Unary(- true))
-Stmt::Expr(Expr::Unary(! true))
-Stmt::Expr(Expr::Unary(- false))
-Stmt::Expr(Expr::Unary(! false))
-Stmt::Expr(Expr::Unary(- Expr::Group(1.2)))
-Stmt::Expr(Expr::Unary(! Expr::Group(1.2)))
-Stmt::Expr(Expr::Unary(- Expr::Variable(x)))
-Stmt::Expr(Expr::Unary(! Expr::Variable(x)))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn binary() -> Result<(), std::io::Error> {
-        let source = r#"
-1+2; 3-7; true*false; nil/nil;
-"a" == "b"; nil != nil; 3 > 7; true >= false; 2 < 3; true <= false; 
-"#;
-
-        let expected_output = r#"
-Stmt::Expr(Expr::Binary(1 + 2))
-Stmt::Expr(Expr::Binary(3 - 7))
-Stmt::Expr(Expr::Binary(true * false))
-Stmt::Expr(Expr::Binary(nil / nil))
-Stmt::Expr(Expr::Binary("a" == "b"))
-Stmt::Expr(Expr::Binary(nil != nil))
-Stmt::Expr(Expr::Binary(3 > 7))
-Stmt::Expr(Expr::Binary(true >= false))
-Stmt::Expr(Expr::Binary(2 < 3))
-Stmt::Expr(Expr::Binary(true <= false))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn assignment() -> Result<(), std::io::Error> {
-        let source = r#"
-x = 1; x = "string"; x = true; x = nil; x = y; 
-x = y
-"#;
-        let expected_output = r#"
-Stmt::Expr(Expr::Assign(x = 1))
-Stmt::Expr(Expr::Assign(x = "string"))
-Stmt::Expr(Expr::Assign(x = true))
-Stmt::Expr(Expr::Assign(x = nil))
-Stmt::Expr(Expr::Assign(x = Expr::Variable(y)))
-[line 3]: ParseError: Expected `;`. Found `EOF`
-x = y
-     ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
     #[test]
     fn synchronize_with_semicolon() -> Result<(), std::io::Error> {
         // synchronize until semicolon, the next token should be `true`.
@@ -722,259 +811,6 @@ Stmt::Expr(Expr::Binary(true < false))
         test_parser(source, expected_output)
     }
 
-    #[test]
-    fn multiexpressions_with_errors() -> Result<(), std::io::Error> {
-        let source = r#"
-"has semicolon";
-("no right paren";
-("has right paren");
-"no semicolon"
-"#;
-
-        let expected_output = r#"
-Stmt::Expr("has semicolon")
-Stmt::Expr(Expr::Group("has right paren"))
-[line 3]: ParseError: Expected `)`. Found `;`
-("no right paren";
-                 ^
-[line 5]: ParseError: Expected `;`. Found `EOF`
-"no semicolon"
-              ^
-"#;
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn print_expression_without_semicolon() -> Result<(), std::io::Error> {
-        let source = r#"
-print "statement";
-print "statement without semicolon"
-print 1 + 2;
-"#;
-
-        let expected_output = r#"
-Stmt::Print("statement")
-Stmt::Print(Expr::Binary(1 + 2))
-[line 4]: ParseError: Expected `;`. Found `print`
-print 1 + 2;
-^^^^^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn variable_declaration_statement() -> Result<(), std::io::Error> {
-        let source = r#"
-var x = 1; 
-var x = y + 1;
-var x
-print x;
-"#;
-
-        let expected_output = r#"
-Stmt::Var(x = 1)
-Stmt::Var(x = Expr::Binary(Expr::Variable(y) + 1))
-Stmt::Print(Expr::Variable(x))
-[line 5]: ParseError: Expected `;`. Found `print`
-print x;
-^^^^^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn assignment_statement() -> Result<(), std::io::Error> {
-        let source = r#"
-var x = 1;
-x = 2;
-x = y;
-"this is not assignment" = 2
-"#;
-
-        let expected_output = r#"
-Stmt::Var(x = 1)
-Stmt::Expr(Expr::Assign(x = 2))
-Stmt::Expr(Expr::Assign(x = Expr::Variable(y)))
-[line 5]: ParseError: Inavalid assignment target.
-"this is not assignment" = 2
-                         ^
-"#;
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn assignment_statement_dont_run_to_panic_mode() -> Result<(), std::io::Error> {
-        let source = r#"
-2 = 1 // this has error
-"this token should not be eaten";
-true;
-"#;
-
-        let expected_output = r#"
-Stmt::Expr("this token should not be eaten")
-Stmt::Expr(true)
-[line 2]: ParseError: Inavalid assignment target.
-2 = 1 // this has error
-  ^
-"#;
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn block_statement() -> Result<(), std::io::Error> {
-        let source = r#"
-// nested block
-{
-  {
-    var x = 1;
-  }
-
-  var x = 2;
-}
-
-{
-  1 + 2;
-"#;
-
-        let expected_output = r#"
-Stmt::Block(Stmt::Block(Stmt::Var(x = 1)) Stmt::Var(x = 2))
-[line 12]: ParseError: Expected `}`. Found `EOF`
-  1 + 2;
-        ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn if_statement() -> Result<(), std::io::Error> {
-        let source = r#"
-// normal
-if (true) var x = 1;
-else var x = 2;
-
-// nested
-if (1) 
-  if (2) 3;
-  else 4;
-"#;
-
-        let expected_output = r#"
-Stmt::If(cond=true then=Stmt::Var(x = 1) else=Stmt::Var(x = 2))
-Stmt::If(cond=1 then=Stmt::If(cond=2 then=Stmt::Expr(3) else=Stmt::Expr(4)))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn logical_or() -> Result<(), std::io::Error> {
-        let source = r#"
-1 or 2;
-1 or 2 or 3;
-1 and 2 or 3;
-"#;
-
-        let expected_output = r#"
-Stmt::Expr(Expr::Logical(1 or 2))
-Stmt::Expr(Expr::Logical(Expr::Logical(1 or 2) or 3))
-Stmt::Expr(Expr::Logical(Expr::Logical(1 and 2) or 3))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn while_statement() -> Result<(), std::io::Error> {
-        let source = r#"
-while (1 + 2)
-print 1;
-
-while (1 + 2
-"#;
-
-        let expected_output = r#"
-Stmt::While(cond=Expr::Binary(1 + 2), body=Stmt::Print(1))
-[line 5]: ParseError: Expected `)`. Found `EOF`
-while (1 + 2
-            ^
-"#;
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn normal_for_statement() -> Result<(), std::io::Error> {
-        let source = r#"
-for (var i = 0; i < 5; i = i + 1)
-  print i;
-for (1;2;3)
-  4;
-"#;
-
-        let expected_output = r#"
-Stmt::Block(Stmt::Var(i = 0) Stmt::While(cond=Expr::Binary(Expr::Variable(i) < 5), body=Stmt::Block(Stmt::Print(Expr::Variable(i)) Stmt::Expr(Expr::Assign(i = Expr::Binary(Expr::Variable(i) + 1))))))
-Stmt::Block(Stmt::Expr(1) Stmt::While(cond=2, body=Stmt::Block(Stmt::Expr(4) Stmt::Expr(3))))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn missing_parts_for_statement() -> Result<(), std::io::Error> {
-        let source = r#"
-for (; 2; 3) 4; // missing initializer
-for (1; ; 3) 4; // missing condition
-for (1; 2; ) 4; // missing increment
-for (;;) 4;    // miss all      
-"#;
-
-        let expected_output = r#"
-Stmt::While(cond=2, body=Stmt::Block(Stmt::Expr(4) Stmt::Expr(3)))
-Stmt::Block(Stmt::Expr(1) Stmt::While(cond=true, body=Stmt::Block(Stmt::Expr(4) Stmt::Expr(3))))
-Stmt::Block(Stmt::Expr(1) Stmt::While(cond=2, body=Stmt::Expr(4)))
-Stmt::While(cond=true, body=Stmt::Expr(4))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn for_statement_without_semicolon() -> Result<(), std::io::Error> {
-        let source = r#"
-for (;) 4; // missing semicolon
-for () 2; // no semicolon
-"#;
-
-        let expected_output = r#"
-[line 2]: ParseError: Expected expression
-for (;) 4; // missing semicolon
-      ^
-[line 3]: ParseError: Expected expression
-for () 2; // no semicolon
-     ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn for_statement_without_right_paren() -> Result<(), std::io::Error> {
-        let source = r#"
-// no right paren
-for (
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected expression
-for (
-     ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
     #[test]
     fn for_statement_without_right_paren_but_already_parsed_init_cond_inc(
     ) -> Result<(), std::io::Error> {
@@ -993,148 +829,26 @@ for (;;
     }
 
     #[test]
-    fn for_statement_without_left_paren() -> Result<(), std::io::Error> {
-        let source = r#"
-// no left paren
-for )
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `(`. Found `)`
-for )
-    ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn normal_function_declaration() -> Result<(), std::io::Error> {
-        let source = r#"
-fun hello(x, y, z) {
-  print x;
-  print y;
-  print z;
-}
-"#;
-
-        let expected_output = r#"
-Stmt::Function(name=hello params=x,y,z body=Stmt::Block(Stmt::Print(Expr::Variable(x)) Stmt::Print(Expr::Variable(y)) Stmt::Print(Expr::Variable(z))))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn function_declaration_missing_functino_name() -> Result<(), std::io::Error> {
-        let source = r#"
-// missing function name
-fun (; 
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `function name`. Found `(`
-fun (;
-    ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn function_declaration_missing_left_paren() -> Result<(), std::io::Error> {
-        let source = r#"
-// missing left paren
-fun f); 
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `(`. Found `)`
-fun f);
-     ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn function_declaration_missing_right_paren() -> Result<(), std::io::Error> {
-        let source = r#"
-// missing right paren
-fun f(x, y; 
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `)`. Found `;`
-fun f(x, y;
-          ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn function_declaration_missing_parameter_name() -> Result<(), std::io::Error> {
-        let source = r#"
-// missing parameter name
-fun f(,); 
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `parameter name`. Found `,`
-fun f(,);
-      ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn function_declaration_missing_body() -> Result<(), std::io::Error> {
-        let source = r#"
-// missing body
-fun f(); 
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `{`. Found `;`
-fun f();
-       ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn function_declaration_missing_left_brace() -> Result<(), std::io::Error> {
-        let source = r#"
-// missing left brace
-fun f()}; 
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `{`. Found `}`
-fun f()};
-       ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn function_declaration_missing_right_brace() -> Result<(), std::io::Error> {
-        let source = r#"
-// missing right brace
-fun f(){ print x;
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `}`. Found `EOF`
-fun f(){ print x;
-                 ^
-"#;
+    fn implicit_semicolon_spans_what_it_comes_from() {
+        let mut scanner = Scanner::new("print 1; return 23");
+        let mut parser = Parser::new(&mut scanner).with_implicit_semicolon();
+        let statements = parser.parse();
+        assert!(!parser.had_error());
+        let Stmt::Return(return_stmt) = &statements[1] else {
+            panic!("expected a return statement");
+        };
+        let number = Span::new(CharPos::new(16, 0), CharPos::new(17, 0));
+        assert_eq!(return_stmt.semicolon.span(), number);
 
-        test_parser(source, expected_output)
+        // only the last statement can leave it out
+        let mut scanner = Scanner::new("print 1 print 2");
+        let mut parser = Parser::new(&mut scanner).with_implicit_semicolon();
+        parser.parse();
+        assert!(parser.had_error());
+        let mut scanner = Scanner::new("print 1");
+        let mut parser = Parser::new(&mut scanner);
+        parser.parse();
+        assert!(parser.had_error());
     }
 
     #[test]
@@ -1159,233 +873,63 @@ fun f(){ print x;
         test_parser(&source, &expected_output)
     }
 
-    #[test]
-    fn normal_function_call() -> Result<(), std::io::Error> {
-        let source = r#"hello("world"); // this call fuction `hello`"#;
-        let expected_output = r#"
-Stmt::Expr(Expr::Call(callee=Expr::Variable(hello) arguments="world"))
-"#;
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn missing_parts_function_call() -> Result<(), std::io::Error> {
-        let source = r#"
-// missing left paren
-hello);
-
-// missing right paren
-hello(1, 2; 
-
-// missing parameter name
-hello(,); 
-"#;
-
-        let expected_output = r#"
-[line 3]: ParseError: Expected `;`. Found `)`
-hello);
-     ^
-[line 6]: ParseError: Expected `)`. Found `;`
-hello(1, 2;
-          ^
-[line 9]: ParseError: Expected expression
-hello(,);
-      ^ 
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn return_statement() -> Result<(), std::io::Error> {
-        let source = "
-fun f(x) {
-return x;
-}
-
-fun f(x) {
-return;
-}
-";
-
-        let expected_output = "
-Stmt::Function(name=f params=x body=Stmt::Block(Stmt::Return(Expr::Variable(x))))
-Stmt::Function(name=f params=x body=Stmt::Block(Stmt::Return(nil)))
-";
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn class_declaration() -> Result<(), std::io::Error> {
-        let source = r#"
-class Breakfast {
-    cook() {
-        print "Eggs a-fryin!";
-    }
-    
-    serve(who) {
-        print "Enjoy your breakfast," + who + ".";
-    }
-}
-"#;
-
-        let expected_output = r#"
-Stmt::Class(name=Breakfast, superclass=Null, methods=(Stmt::Function(name=cook params= body=Stmt::Block(Stmt::Print("Eggs a-fryin!"))), Stmt::Function(name=serve params=who body=Stmt::Block(Stmt::Print(Expr::Binary(Expr::Binary("Enjoy your breakfast," + Expr::Variable(who)) + "."))))))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn class_declaration_with_superclass() -> Result<(), std::io::Error> {
-        let source = r#"
-class Breakfast : Meal{
-    cook() {
-        print "Eggs a-fryin!";
-    }
-    
-    serve(who) {
-        print "Enjoy your breakfast," + who + ".";
-    }
-}
-"#;
-
-        let expected_output = r#"
-Stmt::Class(name=Breakfast, superclass=Meal, methods=(Stmt::Function(name=cook params= body=Stmt::Block(Stmt::Print("Eggs a-fryin!"))), Stmt::Function(name=serve params=who body=Stmt::Block(Stmt::Print(Expr::Binary(Expr::Binary("Enjoy your breakfast," + Expr::Variable(who)) + "."))))))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn class_declaration_missing_name() -> Result<(), std::io::Error> {
-        let source = r#"
-class {};
-"#;
-
-        let expected_output = r#"
-[line 2]: ParseError: Expected `class name`. Found `{`
-class {};
-      ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn class_declaration_missing_left_brace() -> Result<(), std::io::Error> {
-        let source = r#"
-class Hello };
-"#;
-
-        let expected_output = r#"
-[line 2]: ParseError: Expected `{`. Found `}`
-class Hello };
-            ^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn class_declaration_missing_right_brace() -> Result<(), std::io::Error> {
-        let source = r#"
-class Breakfast {
-    cook() {
-        print "Eggs a-fryin!";
-    }
-;
-"#;
-
-        let expected_output = r#"
-[line 6]: ParseError: Expected `}`. Found `;`
-;
-^
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn expr_get() -> Result<(), std::io::Error> {
-        let source = r#"
-x.y;
-"#;
-
-        let expected_output = r#"
-Stmt::Expr(Expr::Get(object=Expr::Variable(x), name=y))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn expr_set() -> Result<(), std::io::Error> {
-        let source = r#"
-x.y = 1;
-"#;
-
-        let expected_output = r#"
-Stmt::Expr(Expr::Set(object=Expr::Variable(x), name=y, value=1))
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn this() -> Result<(), std::io::Error> {
-        let source = r#"
-this;
-"#;
-
-        let expected_output = r#"
-Stmt::Expr(Expr::This)
-"#;
-
-        test_parser(source, expected_output)
-    }
-
-    #[test]
-    fn super_call() -> Result<(), std::io::Error> {
-        let source = r#"
-super.method;
-"#;
-
-        let expected_output = r#"
-Stmt::Expr(Expr::Super(method=method))
-"#;
-
-        test_parser(source, expected_output)
+    // debug builds take far more stack per level than the default test thread has
+    fn with_big_stack(test: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(test)
+            .unwrap()
+            .join()
+            .unwrap();
     }
 
     #[test]
-    fn super_call_without_dot() -> Result<(), std::io::Error> {
-        let source = r#"
-super method;
-"#;
-
-        let expected_output = r#"
-[line 2]: ParseError: Expected `.`. Found `method`
-super method;
-      ^^^^^^
-"#;
-
-        test_parser(source, expected_output)
+    fn too_deeply_nested() {
+        with_big_stack(|| {
+            let source = format!("{}1{};", "(".repeat(300), ")".repeat(300));
+            let indicated_error = format!("{}^", " ".repeat(MAXIMUM_NESTING - 1));
+            let expected_output = format!(
+                "
+[line 1]: ParseError: Could not nest code more than 256 levels deep
+{source}\n{indicated_error}"
+            );
+            test_parser(&source, &expected_output).unwrap();
+        });
+    }
+
+    #[test]
+    fn long_chains_are_not_nested() {
+        for source in [
+            format!("print {};", vec!["1"; MAXIMUM_NESTING + 1].join(" + ")),
+            format!("print {};", vec!["1"; 5000].join(" + ")),
+            format!("print a{};", ".b".repeat(5000)),
+            format!("f{};", "()".repeat(5000)),
+        ] {
+            let mut scanner = Scanner::new(&source);
+            scanner.scan_tokens();
+            let mut parser = Parser::from(&scanner);
+            parser.parse();
+            assert!(!parser.had_error());
+        }
     }
 
     #[test]
-    fn super_call_without_method() -> Result<(), std::io::Error> {
-        let source = r#"
-super.123;
-"#;
-
-        let expected_output = r#"
-[line 2]: ParseError: Expected `superclass method name`. Found `123`
-super.123;
-      ^^^
-"#;
-
-        test_parser(source, expected_output)
+    fn nesting_below_limit() {
+        with_big_stack(|| {
+            // the statement and its expression take two levels
+            let depth = MAXIMUM_NESTING - 2;
+            let source = format!(
+                "{}1{}; {}2;",
+                "(".repeat(depth),
+                ")".repeat(depth),
+                "-".repeat(depth)
+            );
+            let mut scanner = Scanner::new(&source);
+            scanner.scan_tokens();
+            let mut parser = Parser::from(&scanner);
+            let statements = parser.parse();
+            assert!(!parser.had_error());
+            assert_eq!(statements.len(), 2);
+        });
     }
 }
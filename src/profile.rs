@@ -0,0 +1,125 @@
+use std::{collections::HashMap, fmt::Write, time::Duration};
+
+use crate::time::Instant;
+
+#[derive(Debug, Default)]
+struct Entry {
+    calls: u64,
+    total: Duration,
+    self_time: Duration,
+    // calls of this function still running, recursive calls are only timed once
+    active: usize,
+}
+
+#[derive(Debug)]
+struct Frame {
+    name: String,
+    start: Instant,
+    children: Duration,
+}
+
+// call counts and time spent in every function, keyed by how the callee is displayed
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    entries: HashMap<String, Entry>,
+    stack: Vec<Frame>,
+}
+
+impl Profiler {
+    pub fn enter(&mut self, name: String) {
+        let entry = self.entries.entry(name.clone()).or_default();
+        entry.calls += 1;
+        entry.active += 1;
+        self.stack.push(Frame {
+            name,
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    // every `enter` must be followed by an `exit`, whether the call failed or not
+    pub fn exit(&mut self) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        let elapsed = frame.start.elapsed();
+        if let Some(entry) = self.entries.get_mut(&frame.name) {
+            entry.active -= 1;
+            if entry.active == 0 {
+                entry.total += elapsed;
+            }
+            entry.self_time += elapsed.saturating_sub(frame.children);
+        }
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children += elapsed;
+        }
+    }
+
+    // hottest functions first
+    pub fn report(&self) -> String {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|(a_name, a), (b_name, b)| {
+            b.self_time
+                .cmp(&a.self_time)
+                .then_with(|| a_name.cmp(b_name))
+        });
+
+        let mut report = format!(
+            "{:>10} {:>12} {:>12}  function\n",
+            "calls", "total ms", "self ms"
+        );
+        for (name, entry) in entries {
+            let _ = writeln!(
+                report,
+                "{:>10} {:>12.3} {:>12.3}  {name}",
+                entry.calls,
+                entry.total.as_secs_f64() * 1000.0,
+                entry.self_time.as_secs_f64() * 1000.0,
+            );
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recursive_calls_are_timed_once() {
+        let start = Instant::now();
+        let mut profiler = Profiler::default();
+        profiler.enter("<fn outer>".to_string());
+        profiler.enter("<fn outer>".to_string());
+        profiler.enter("<native fn clock>".to_string());
+        std::thread::sleep(Duration::from_millis(1));
+        profiler.exit();
+        profiler.exit();
+        profiler.exit();
+        let elapsed = start.elapsed();
+
+        let outer = &profiler.entries["<fn outer>"];
+        let clock = &profiler.entries["<native fn clock>"];
+        assert_eq!(outer.calls, 2);
+        assert_eq!(clock.calls, 1);
+        assert!(outer.total <= elapsed);
+        assert!(clock.total >= Duration::from_millis(1));
+        assert!(outer.self_time + clock.self_time <= elapsed);
+    }
+
+    #[test]
+    fn report_is_sorted_by_self_time() {
+        let mut profiler = Profiler::default();
+        profiler.enter("<fn fast>".to_string());
+        profiler.exit();
+        profiler.enter("<fn slow>".to_string());
+        std::thread::sleep(Duration::from_millis(1));
+        profiler.exit();
+
+        let report = profiler.report();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with("<fn slow>"));
+        assert!(lines[2].ends_with("<fn fast>"));
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    borrow::Cow,
+    io::{BufRead, Read, Write},
+    path::PathBuf,
+};
+
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::{CmdKind, Highlighter},
+    hint::Hinter,
+    history::FileHistory,
+    validate::Validator,
+    Context, Helper,
+};
+
+use crate::{
+    color,
+    shared::{Mutable, Shared},
+};
+
+const HISTORY_FILE: &str = ".lox_history";
+
+pub(crate) enum Input {
+    // without its newline
+    Line(String),
+    // ctrl-c, whatever was typed so far is dropped
+    Interrupted,
+    End,
+}
+
+// where the REPL reads its input from
+pub(crate) trait Prompt {
+    fn read_line(&mut self, prompt: &str) -> std::io::Result<Input>;
+
+    // a complete input that was run
+    fn add_history(&mut self, _input: &str) {}
+
+    // keywords and names that are currently defined
+    fn set_completions(&mut self, _names: Vec<String>) {}
+
+    // whether results are shown in color
+    fn colored(&self) -> bool {
+        false
+    }
+}
+
+// start of the word ending at `pos` and the names it could be completed to
+fn complete(line: &str, pos: usize, names: &[String]) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map_or(0, |i| i + 1);
+    let word = &line[start..pos];
+    // numbers are not names
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        return (start, Vec::new());
+    }
+    let candidates = names
+        .iter()
+        .filter(|name| name.starts_with(word))
+        .cloned()
+        .collect();
+    (start, candidates)
+}
+
+// tab completion and syntax highlighting for the editor
+struct EditorHelper {
+    names: Vec<String>,
+    colored: bool,
+}
+
+impl Completer for EditorHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.names))
+    }
+}
+
+impl Hinter for EditorHelper {
+    type Hint = String;
+}
+
+impl Highlighter for EditorHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        match self.colored {
+            true => Cow::Owned(color::highlight(line)),
+            false => Cow::Borrowed(line),
+        }
+    }
+
+    // only moving the cursor leaves the colors as they are
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        self.colored && kind != CmdKind::MoveCursor
+    }
+}
+
+impl Validator for EditorHelper {}
+
+impl Helper for EditorHelper {}
+
+// plain lines, used when stdin is not a terminal
+pub(crate) struct Lines<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R, W> Lines<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    pub fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer }
+    }
+}
+
+impl<R, W> Prompt for Lines<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    fn read_line(&mut self, prompt: &str) -> std::io::Result<Input> {
+        write!(self.writer, "{prompt}")?;
+        self.writer.flush()?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(Input::End);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Input::Line(line))
+    }
+}
+
+// one reader handing out whole lines to each of its copies, so the prompt and `readLine`
+// take turns reading stdin. separate buffers would each read ahead what the other needs
+pub(crate) struct SharedLines<R> {
+    reader: Shared<Mutable<R>>,
+    line: Vec<u8>,
+    consumed: usize,
+}
+
+impl<R> SharedLines<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: Shared::new(Mutable::new(reader)),
+            line: Vec::new(),
+            consumed: 0,
+        }
+    }
+}
+
+impl<R> Clone for SharedLines<R> {
+    fn clone(&self) -> Self {
+        Self {
+            reader: Shared::clone(&self.reader),
+            line: Vec::new(),
+            consumed: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Read for SharedLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.fill_buf()?.read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for SharedLines<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.consumed == self.line.len() {
+            self.line.clear();
+            self.consumed = 0;
+            self.reader.borrow_mut().read_until(b'\n', &mut self.line)?;
+        }
+        Ok(&self.line[self.consumed..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.consumed = (self.consumed + amt).min(self.line.len());
+    }
+}
+
+// line editing with history kept in `~/.lox_history` and tab completion
+pub(crate) struct Editor {
+    editor: rustyline::Editor<EditorHelper, FileHistory>,
+    history: Option<PathBuf>,
+}
+
+impl Editor {
+    pub fn new(colored: bool) -> std::io::Result<Self> {
+        let mut editor = rustyline::Editor::new().map_err(into_io_error)?;
+        editor.set_helper(Some(EditorHelper {
+            names: Vec::new(),
+            colored,
+        }));
+        let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        if let Some(history) = &history {
+            // there is no history before the first session
+            let _ = editor.load_history(history);
+        }
+        Ok(Self { editor, history })
+    }
+}
+
+impl Prompt for Editor {
+    fn read_line(&mut self, prompt: &str) -> std::io::Result<Input> {
+        match self.editor.readline(prompt) {
+            Ok(line) => Ok(Input::Line(line)),
+            Err(ReadlineError::Interrupted) => Ok(Input::Interrupted),
+            Err(ReadlineError::Eof) => Ok(Input::End),
+            Err(err) => Err(into_io_error(err)),
+        }
+    }
+
+    // written right away, the script could call `exit()` at any time
+    fn add_history(&mut self, input: &str) {
+        if matches!(self.editor.add_history_entry(input), Ok(true)) {
+            if let Some(history) = &self.history {
+                let _ = self.editor.append_history(history);
+            }
+        }
+    }
+
+    fn set_completions(&mut self, names: Vec<String>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.names = names;
+        }
+    }
+
+    fn colored(&self) -> bool {
+        self.editor.helper().is_some_and(|helper| helper.colored)
+    }
+}
+
+fn into_io_error(err: ReadlineError) -> std::io::Error {
+    match err {
+        ReadlineError::Io(err) => err,
+        err => std::io::Error::other(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line(input: Input) -> Option<String> {
+        match input {
+            Input::Line(line) => Some(line),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn lines_are_read_without_newlines() -> std::io::Result<()> {
+        let mut output = Vec::new();
+        let mut lines = Lines::new("a\r\nb\nc".as_bytes(), &mut output);
+        assert_eq!(line(lines.read_line(">>> ")?).as_deref(), Some("a"));
+        assert_eq!(line(lines.read_line("... ")?).as_deref(), Some("b"));
+        assert_eq!(line(lines.read_line(">>> ")?).as_deref(), Some("c"));
+        assert!(matches!(lines.read_line(">>> ")?, Input::End));
+        assert_eq!(String::from_utf8(output).unwrap(), ">>> ... >>> >>> ");
+        Ok(())
+    }
+
+    #[test]
+    fn complete_the_word_before_the_cursor() {
+        let names: Vec<String> = ["clock", "class", "counter", "print"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            complete("print cl", 8, &names),
+            (6, vec!["clock".to_string(), "class".to_string()])
+        );
+        assert_eq!(
+            complete("co + 1", 2, &names),
+            (0, vec!["counter".to_string()])
+        );
+        assert_eq!(complete("f(", 2, &names), (2, names.clone()));
+        assert_eq!(complete("1c", 2, &names), (0, Vec::new()));
+    }
+}
@@ -1,11 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
-    error::{reporter::ErrorReporter, resolve_error::ResolveError},
-    expr::Expr,
+    environment::Slot,
+    error::{
+        reporter::{ErrorPos, ErrorReporter},
+        resolve_error::ResolveError,
+        warning::Warning,
+    },
+    expr::{Expr, ExprId},
     interpreter::Interpreter,
     object::Object,
-    stmt::Stmt,
+    stack,
+    stmt::{Function, Stmt},
+    symbol::Symbol,
     token::Token,
     visitor::Visitor,
 };
@@ -43,15 +50,75 @@ enum ClassType {
     SubClass,
 }
 
+// a local variable gets the next slot of its scope when it is declared
+#[derive(Debug, Clone)]
+struct Local {
+    index: usize,
+    defined: bool,
+    // read at least once, assigning to it does not count
+    used: bool,
+    // parameters of the function declared here, `None` for other values and once it is
+    // assigned another one
+    arity: Option<usize>,
+    // the name called and how many arguments it was called with
+    calls: Vec<(Token, usize)>,
+    // `None` for `this` and `super`
+    declaration: Option<Declaration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DeclarationKind {
+    Variable,
+    Parameter,
+    Function,
+    Class,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Declaration {
+    pub name: Token,
+    pub kind: DeclarationKind,
+}
+
+// a variable and the declaration it refers to
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Reference {
+    pub name: Token,
+    pub declaration: Declaration,
+}
+
 pub(crate) struct Resolver<'a, W>
 where
     W: std::io::Write,
 {
-    scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<Symbol, Local>>,
     errors: Vec<ResolveError>,
+    warnings: Vec<Warning>,
     interpreter: &'a mut Interpreter<W>,
     function_type: FunctionType,
     class_type: ClassType,
+    // name of the innermost class being resolved
+    class_name: Option<Token>,
+    // only kept when asked for, see `with_references`
+    references: Option<Vec<Reference>>,
+    // globals are looked up by name once everything is declared, functions can use
+    // globals declared after them
+    globals: HashMap<Symbol, Declaration>,
+    global_uses: Vec<Token>,
+    // global functions that are declared once and never assigned, with their arity, and
+    // the calls to globals like for `Local`
+    global_functions: HashMap<Symbol, Option<(Token, usize)>>,
+    global_calls: Vec<(Token, usize)>,
+    // every global used has to be declared somewhere in the script or already defined
+    strict: bool,
+    // the statements are the whole script, see `with_whole_script`
+    whole_script: bool,
+    // global functions and classes, the one being resolved and the globals read in each of
+    // them, `None` for the code outside of them
+    global_declarations: Vec<Declaration>,
+    current_global: Option<Symbol>,
+    global_reads: HashMap<Option<Symbol>, HashSet<Symbol>>,
+    unused_globals: Vec<Symbol>,
 }
 
 type ResolveResult<T> = Result<T, ResolveError>;
@@ -71,60 +138,362 @@ where
 {
     pub fn new(interpreter: &'a mut Interpreter<W>) -> Self {
         Self {
+            strict: interpreter.strict(),
             interpreter,
             errors: Default::default(),
+            warnings: Vec::new(),
             scopes: Default::default(),
             function_type: FunctionType::Null,
             class_type: ClassType::Null,
+            class_name: None,
+            references: None,
+            globals: HashMap::new(),
+            global_uses: Vec::new(),
+            global_functions: HashMap::new(),
+            global_calls: Vec::new(),
+            whole_script: false,
+            global_declarations: Vec::new(),
+            current_global: None,
+            global_reads: HashMap::new(),
+            unused_globals: Vec::new(),
         }
     }
 
+    // remember which declaration every variable refers to, see `references`
+    pub fn with_references(mut self) -> Self {
+        self.references = Some(Vec::new());
+        self
+    }
+
+    // global functions and classes that the code outside of them never uses, even through
+    // other ones, are reported. not for the prompt, later lines could use them
+    pub fn with_whole_script(mut self) -> Self {
+        self.whole_script = true;
+        self
+    }
+
+    // names of the global functions and classes never used, see `with_whole_script`
+    pub fn unused_globals(&self) -> &[Symbol] {
+        &self.unused_globals
+    }
+
+    pub fn references(&self) -> &[Reference] {
+        self.references.as_deref().unwrap_or_default()
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(Default::default());
     }
 
+    // warn about the variables and parameters of the scope that were never read, unless
+    // their name starts with `_`
     fn end_scope(&mut self) {
-        self.scopes.pop();
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        for local in scope.values() {
+            if let (Some(arity), Some(declaration)) = (local.arity, &local.declaration) {
+                self.check_calls(&declaration.name, arity, &local.calls);
+            }
+        }
+        let mut unused: Vec<_> = scope
+            .into_values()
+            .filter(|local| !local.used)
+            .filter_map(|local| Some((local.index, local.declaration?)))
+            .filter(|(_, declaration)| !declaration.name.name().starts_with('_'))
+            .collect();
+        unused.sort_by_key(|(index, _)| *index);
+        for (_, declaration) in unused {
+            match declaration.kind {
+                DeclarationKind::Variable => self
+                    .warnings
+                    .push(Warning::unused_variable(&declaration.name)),
+                DeclarationKind::Parameter => self
+                    .warnings
+                    .push(Warning::unused_parameter(&declaration.name)),
+                DeclarationKind::Function => self
+                    .warnings
+                    .push(Warning::unused_function(&declaration.name)),
+                DeclarationKind::Class => {
+                    self.warnings.push(Warning::unused_class(&declaration.name))
+                }
+            }
+        }
     }
 
-    fn declare(&mut self, ident: &Token) -> ResolveResult<()> {
-        if let Some(last) = self.scopes.last_mut() {
-            if last.contains_key(ident.lexeme()) {
-                return Err(ResolveError::already_declared(ident));
+    // calls of a function that is never assigned another value, with the wrong number of
+    // arguments
+    fn check_calls(&mut self, function: &Token, arity: usize, calls: &[(Token, usize)]) {
+        for (name, args_count) in calls {
+            if *args_count != arity {
+                self.errors.push(ResolveError::arity_mismatch(
+                    name,
+                    function,
+                    arity,
+                    *args_count,
+                ));
             }
-            last.insert(ident.lexeme().to_string(), false);
         }
+    }
+
+    fn declare(&mut self, ident: &Token, kind: DeclarationKind) -> ResolveResult<()> {
+        let declaration = Declaration {
+            name: ident.clone(),
+            kind,
+        };
+        let Some(last) = self.scopes.last_mut() else {
+            // functions record their arity after being declared
+            if kind != DeclarationKind::Function {
+                self.global_functions.insert(ident.symbol(), None);
+            }
+            if self.whole_script
+                && matches!(kind, DeclarationKind::Function | DeclarationKind::Class)
+            {
+                self.global_declarations.push(declaration.clone());
+            }
+            if self.references.is_some() || self.strict {
+                self.globals.entry(ident.symbol()).or_insert(declaration);
+            }
+            return Ok(());
+        };
+        if let Some(previous) = last.get(&ident.symbol()) {
+            let previous = previous.declaration.as_ref().map(|d| &d.name);
+            return Err(ResolveError::already_declared(ident, previous));
+        }
+        let index = last.len();
+        last.insert(
+            ident.symbol(),
+            Local {
+                index,
+                defined: false,
+                used: false,
+                arity: None,
+                calls: Vec::new(),
+                declaration: Some(declaration),
+            },
+        );
         Ok(())
     }
 
     fn define(&mut self, ident: &Token) {
         if let Some(last) = self.scopes.last_mut() {
-            if let Some(value) = last.get_mut(ident.lexeme()) {
-                *value = true;
+            if let Some(local) = last.get_mut(&ident.symbol()) {
+                local.defined = true;
             }
         }
     }
 
-    fn resolve_local(&mut self, expr: Expr, name: &Token) {
-        if let Some((depth, _)) = self
+    // scope that only holds `this` or `super`
+    fn begin_reserved_scope(&mut self, symbol: Symbol) {
+        let local = Local {
+            index: 0,
+            defined: true,
+            used: true,
+            arity: None,
+            calls: Vec::new(),
+            declaration: None,
+        };
+        self.scopes.push(HashMap::from([(symbol, local)]));
+    }
+
+    // `read` is false for assignments, which do not make a variable used
+    fn resolve_local(&mut self, id: ExprId, name: &Token, read: bool) {
+        let symbol = name.symbol();
+        let Some((depth, local)) = self
             .scopes
-            .iter()
+            .iter_mut()
             .rev()
             .enumerate()
-            .find(|(_, scope)| scope.contains_key(name.lexeme()))
+            .find_map(|(depth, scope)| scope.get_mut(&symbol).map(|local| (depth, local)))
+        else {
+            if !read {
+                self.global_functions.insert(symbol, None);
+            }
+            if read && self.whole_script {
+                self.global_reads
+                    .entry(self.current_global)
+                    .or_default()
+                    .insert(symbol);
+            }
+            if self.references.is_some() || self.strict {
+                self.global_uses.push(name.clone());
+            }
+            return;
+        };
+        local.used |= read;
+        if !read {
+            local.arity = None;
+        }
+        self.interpreter.resolve(id, Slot::new(depth, local.index));
+        if let (Some(references), Some(declaration)) =
+            (self.references.as_mut(), &local.declaration)
         {
-            self.interpreter.resolve(expr, depth)
+            references.push(Reference {
+                name: name.clone(),
+                declaration: declaration.clone(),
+            });
+        }
+    }
+
+    // remember a call of a variable by name, to check its arguments against the function
+    // the variable holds once it cannot be assigned anymore
+    fn record_call(&mut self, name: &Token, args_count: usize) {
+        let symbol = name.symbol();
+        let local = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&symbol));
+        match local {
+            Some(local) => local.calls.push((name.clone(), args_count)),
+            None => self.global_calls.push((name.clone(), args_count)),
+        }
+    }
+
+    // parameters and body of a function or a method, in a scope of their own
+    fn resolve_function(&mut self, fun: &Function) -> ResolveResult<()> {
+        self.begin_scope();
+
+        let old_function_type = self.function_type;
+        self.function_type.next_level(fun.name.name());
+
+        for param in &fun.params {
+            self.declare(param, DeclarationKind::Parameter)?;
+            self.define(param);
+        }
+        let result = self.visit_stmt(&fun.body);
+
+        self.function_type = old_function_type;
+
+        self.end_scope();
+        result
+    }
+
+    // a function declared in the current scope, or globally
+    fn function_declared(&mut self, name: &Token, arity: usize) {
+        let symbol = name.symbol();
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                if let Some(local) = scope.get_mut(&symbol) {
+                    local.arity = Some(arity);
+                }
+            }
+            None => {
+                self.global_functions
+                    .entry(symbol)
+                    .and_modify(|function| *function = None)
+                    .or_insert(Some((name.clone(), arity)));
+            }
+        }
+    }
+
+    // globals reachable from the code outside of functions and classes are used
+    fn find_unused_globals(&mut self) {
+        let mut used = HashSet::new();
+        let mut reached: Vec<_> = self
+            .global_reads
+            .get(&None)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        while let Some(symbol) = reached.pop() {
+            if used.insert(symbol) {
+                reached.extend(self.global_reads.get(&Some(symbol)).into_iter().flatten());
+            }
+        }
+        for declaration in &self.global_declarations {
+            let symbol = declaration.name.symbol();
+            if used.contains(&symbol) || declaration.name.name().starts_with('_') {
+                continue;
+            }
+            self.unused_globals.push(symbol);
+            self.warnings.push(match declaration.kind {
+                DeclarationKind::Class => Warning::unused_class(&declaration.name),
+                _ => Warning::unused_function(&declaration.name),
+            });
+        }
+        self.warnings
+            .sort_by_key(|warning| warning.start_pos().index);
+    }
+
+    // assigning a constant in a condition makes it always the same, `==` was likely meant
+    fn check_condition(&mut self, condition: &Expr) {
+        if let Expr::Assign(assign) = condition {
+            if matches!(*assign.value, Expr::Literal(_)) {
+                self.warnings
+                    .push(Warning::assignment_in_condition(&assign.name));
+            }
         }
     }
 
     pub fn resolve(&mut self, statements: &[Stmt]) {
-        self.errors = statements
+        let errors: Vec<_> = statements
             .iter()
-            .filter_map(|s| match self.visit_stmt(s) {
-                Err(error) => Some(error),
-                _ => None,
+            .filter_map(|s| {
+                self.current_global = match s {
+                    Stmt::Function(function) => Some(function.name.symbol()),
+                    Stmt::Class(class) => Some(class.name.symbol()),
+                    _ => None,
+                };
+                self.visit_stmt(s).err()
             })
             .collect();
+        self.current_global = None;
+        // calls of local functions are checked when their scope ends, before these
+        self.errors.extend(errors);
+        for (name, args_count) in std::mem::take(&mut self.global_calls) {
+            if let Some(Some((function, arity))) = self.global_functions.get(&name.symbol()) {
+                let (function, arity) = (function.clone(), *arity);
+                self.check_calls(&function, arity, &[(name, args_count)]);
+            }
+        }
+        // unused variables are only found when their scope ends
+        self.warnings
+            .sort_by_key(|warning| warning.start_pos().index);
+        if self.strict {
+            for name in &self.global_uses {
+                let symbol = name.symbol();
+                if !self.globals.contains_key(&symbol)
+                    && self.interpreter.environment().get_global(symbol).is_none()
+                {
+                    self.errors.push(ResolveError::undeclared_global(name));
+                }
+            }
+        }
+        self.errors.sort_by_key(|error| error.start_pos().index);
+        if self.whole_script {
+            self.find_unused_globals();
+        }
+        if let Some(references) = self.references.as_mut() {
+            for name in self.global_uses.drain(..) {
+                if let Some(declaration) = self.globals.get(&name.symbol()) {
+                    references.push(Reference {
+                        name,
+                        declaration: declaration.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+// the `return`, or the `if` with every branch returning, that makes `s` always return.
+// loops are not followed, their condition can be false the first time
+fn always_returns(s: &Stmt) -> Option<&Stmt> {
+    match s {
+        Stmt::Return(_) => Some(s),
+        Stmt::Block(block) => block.statements.iter().find_map(always_returns),
+        Stmt::If(i) => {
+            always_returns(&i.then_branch)?;
+            always_returns(i.else_branch.as_ref()?)?;
+            Some(s)
+        }
+        _ => None,
     }
 }
 
@@ -135,7 +504,7 @@ where
     fn visit_expr(&mut self, e: &Expr) -> ResolveResult<()> {
         match e {
             Expr::Binary(binary) => {
-                self.visit_expr(&binary.left)?;
+                stack::grow(|| self.visit_expr(&binary.left))?;
                 self.visit_expr(&binary.right)?;
             }
             Expr::Unary(unary) => {
@@ -146,50 +515,59 @@ where
                 self.visit_expr(&group.expr)?;
             }
             Expr::Variable(var) => {
-                if self
+                if let Some(local) = self
                     .scopes
-                    .last()
-                    .and_then(|scope| scope.get(var.name.lexeme()))
-                    == Some(&false)
+                    .last_mut()
+                    .and_then(|scope| scope.get_mut(&var.name.symbol()))
+                    .filter(|local| !local.defined)
                 {
+                    // the error is enough, the variable is not reported as unused too
+                    local.used = true;
                     return Err(ResolveError::read_during_initializer(&var.name));
                 }
-                // todo: move instead of clone
-                self.resolve_local(e.clone(), &var.name);
+                self.resolve_local(var.id, &var.name, true);
             }
             Expr::Assign(assign) => {
                 self.visit_expr(&assign.value)?;
-                // todo: move instead of clone
-                self.resolve_local(e.clone(), &assign.name);
+                self.resolve_local(assign.id, &assign.name, false);
             }
             Expr::Logical(logical) => {
-                self.visit_expr(&logical.left)?;
+                stack::grow(|| self.visit_expr(&logical.left))?;
                 self.visit_expr(&logical.right)?;
             }
             Expr::Call(call) => {
-                self.visit_expr(&call.callee)?;
+                stack::grow(|| self.visit_expr(&call.callee))?;
                 for arg in &call.arguments {
                     self.visit_expr(arg)?;
                 }
+                if let Expr::Variable(var) = &*call.callee {
+                    self.record_call(&var.name, call.arguments.len());
+                }
             }
-            Expr::Get(get) => self.visit_expr(&get.object)?,
+            Expr::Get(get) => stack::grow(|| self.visit_expr(&get.object))?,
             Expr::Set(set) => {
                 self.visit_expr(&set.value)?;
-                self.visit_expr(&set.object)?;
+                stack::grow(|| self.visit_expr(&set.object))?;
             }
             Expr::This(this) => {
                 if self.class_type == ClassType::Null {
                     return Err(ResolveError::call_this_outside_class(&this.keyword));
                 }
-                self.resolve_local(e.clone(), &this.keyword);
+                self.resolve_local(this.id, &this.keyword, true);
             }
             Expr::Super(super_call) => {
-                if matches!(self.class_type, ClassType::Null | ClassType::Class) {
-                    return Err(ResolveError::call_super_outside_subclass(
-                        &super_call.keyword,
-                    ));
+                match (self.class_type, &self.class_name) {
+                    (ClassType::Null, _) => {
+                        return Err(ResolveError::call_super_outside_class(super_call));
+                    }
+                    (ClassType::Class, Some(class_name)) => {
+                        return Err(ResolveError::call_super_without_superclass(
+                            super_call, class_name,
+                        ));
+                    }
+                    _ => (),
                 }
-                self.resolve_local(e.clone(), &super_call.keyword);
+                self.resolve_local(super_call.id, &super_call.keyword, true);
             }
         }
         Ok(())
@@ -201,47 +579,50 @@ where
                 self.visit_expr(expr)?;
             }
             Stmt::Print(p) => {
-                self.visit_expr(p)?;
+                self.visit_expr(&p.expression)?;
             }
             Stmt::Return(r) => {
                 match self.function_type {
                     FunctionType::Null => {
                         return Err(ResolveError::return_from_top_level(r));
                     }
-                    FunctionType::Initializer => {
-                        if r.value != Expr::Literal(Object::Null) {
-                            return Err(ResolveError::return_inside_init(r));
-                        }
+                    FunctionType::Initializer if r.value != Expr::Literal(Object::Null) => {
+                        return Err(ResolveError::return_inside_init(r));
                     }
                     _ => (),
                 };
                 self.visit_expr(&r.value)?;
             }
             Stmt::Function(fun) => {
-                self.declare(&fun.name)?;
+                self.declare(&fun.name, DeclarationKind::Function)?;
                 self.define(&fun.name);
-                self.begin_scope();
-
-                let old_function_type = self.function_type;
-                self.function_type.next_level(fun.name.lexeme());
-
-                for param in &fun.params {
-                    self.declare(param)?;
-                    self.define(param);
-                }
-                let result = self.visit_stmt(&fun.body);
-
-                self.function_type = old_function_type;
-
-                self.end_scope();
-                result?;
+                self.function_declared(&fun.name, fun.params.len());
+                self.resolve_function(fun)?;
             }
             Stmt::Var(var) => {
-                self.declare(&var.identifier)?;
+                self.declare(&var.identifier, DeclarationKind::Variable)?;
                 self.visit_expr(&var.expression)?;
                 self.define(&var.identifier);
             }
             Stmt::Block(block) => {
+                // statements after one that always returns in the same block never run
+                let unreachable = block
+                    .statements
+                    .windows(2)
+                    .find_map(|pair| always_returns(&pair[0]));
+                // the statement after it in a `for` body is the increment the parser added
+                match (unreachable, &block.for_keyword) {
+                    (Some(Stmt::Return(r)), Some(keyword)) => self
+                        .warnings
+                        .push(Warning::increment_after_return(r, keyword)),
+                    (Some(Stmt::Return(r)), None) => {
+                        self.warnings.push(Warning::unreachable_after_return(r))
+                    }
+                    (Some(Stmt::If(i)), None) => {
+                        self.warnings.push(Warning::unreachable_after_if(i))
+                    }
+                    _ => (),
+                }
                 self.begin_scope();
                 let error = block
                     .statements
@@ -254,6 +635,7 @@ where
                 };
             }
             Stmt::If(i) => {
+                self.check_condition(&i.condition);
                 self.visit_expr(&i.condition)?;
                 self.visit_stmt(&i.then_branch)?;
                 if let Some(else_branch) = &i.else_branch {
@@ -261,44 +643,64 @@ where
                 }
             }
             Stmt::While(w) => {
+                self.check_condition(&w.condition);
                 self.visit_expr(&w.condition)?;
                 self.visit_stmt(&w.body)?;
             }
+            // every iteration gets its own variable, closures keep the value they saw
+            Stmt::ForIn(for_in) => {
+                self.visit_expr(&for_in.iterable)?;
+                self.begin_scope();
+                let result = self
+                    .declare(&for_in.name, DeclarationKind::Variable)
+                    .and_then(|_| {
+                        self.define(&for_in.name);
+                        self.visit_stmt(&for_in.body)
+                    });
+                self.end_scope();
+                result?;
+            }
             Stmt::Class(class) => {
-                self.declare(&class.name)?;
+                self.declare(&class.name, DeclarationKind::Class)?;
                 self.define(&class.name);
 
                 if let Some(superclass) = class.superclass.clone() {
-                    if class.name.lexeme() == superclass.name.lexeme() {
+                    if class.name.name() == superclass.name.name() {
                         return Err(ResolveError::class_inherit_itself(&class.name));
                     }
                     self.visit_expr(&Expr::Variable(superclass))?;
                 }
 
+                let mut method_names = HashMap::new();
+                for method in &class.methods {
+                    if let Some(previous) = method_names.insert(method.name.symbol(), &method.name)
+                    {
+                        return Err(ResolveError::duplicate_method(
+                            &class.name,
+                            &method.name,
+                            previous,
+                        ));
+                    }
+                }
+
                 let old_class_type = self.class_type;
+                let old_class_name = self.class_name.replace(class.name.clone());
 
                 self.class_type = ClassType::Class;
 
                 if class.superclass.is_some() {
-                    self.begin_scope();
-                    self.scopes
-                        .last_mut()
-                        .unwrap()
-                        .insert("super".to_string(), true);
+                    self.begin_reserved_scope(Symbol::SUPER);
                     self.class_type = ClassType::SubClass;
                 }
 
-                self.begin_scope();
-                self.scopes
-                    .last_mut()
-                    .unwrap()
-                    .insert("this".to_string(), true);
+                self.begin_reserved_scope(Symbol::THIS);
 
                 let old_function_type = self.function_type;
 
+                // methods are found through instances, their names don't hide variables
                 for method in &class.methods {
                     self.function_type = FunctionType::Method;
-                    self.visit_stmt(method)?;
+                    self.resolve_function(method)?;
                 }
 
                 self.function_type = old_function_type;
@@ -309,6 +711,7 @@ where
                 }
 
                 self.class_type = old_class_type;
+                self.class_name = old_class_name;
             }
         };
 
@@ -324,11 +727,19 @@ mod test {
     use super::*;
 
     fn test_resolver(source: &str, expected_output: &str) -> Result<(), std::io::Error> {
+        check_resolver(source, expected_output, false)
+    }
+
+    fn check_resolver(
+        source: &str,
+        expected_output: &str,
+        strict: bool,
+    ) -> Result<(), std::io::Error> {
         let source_pos = SourcePos::new(source);
         let reporter = Reporter::new(&source_pos);
 
         let mut result = Vec::new();
-        let mut interpreter = Interpreter::new(&mut result);
+        let mut interpreter = Interpreter::new(&mut result).with_strict(strict);
 
         let mut scanner = Scanner::new(source);
         scanner.scan_tokens();
@@ -341,7 +752,9 @@ mod test {
         let mut resolver = Resolver::new(&mut interpreter);
         resolver.resolve(&statements);
         let error_msg = resolver.error_msg(&reporter);
+        let warning_msg = reporter.report_all(resolver.warnings());
         interpreter.write(&error_msg)?;
+        interpreter.write(&warning_msg)?;
 
         let result = String::from_utf8(result).unwrap();
         assert_eq!(result.trim(), expected_output.trim());
@@ -380,6 +793,94 @@ fun bad() {
 [line 4]: ResolveError: Already a variable `a` in this scope.
     var a = 2;
         ^
+    var a = 1;
+        - `a` is first declared here
+help: assign to `a` instead
+[line 3]: Warning: Variable `a` is never used
+    var a = 1;
+        ^
+help: name it `_a` if this is intended
+"#;
+
+        test_resolver(source, expected_output)
+    }
+
+    #[test]
+    fn duplicate_method_in_class() -> Result<(), std::io::Error> {
+        let source = r#"
+class A {
+    f() {}
+    init() {}
+    f() {}
+}
+"#;
+
+        let expected_output = r#"
+[line 5]: ResolveError: Method `f` is already defined in this class
+    f() {}
+    ^
+    f() {}
+    - `f` is first defined here
+note: class `A` keeps only one method named `f`
+"#;
+
+        test_resolver(source, expected_output)
+    }
+
+    #[test]
+    fn undeclared_globals_in_strict_mode() -> Result<(), std::io::Error> {
+        let source = r#"
+fun count() {
+    return counter + clock();
+}
+var counter = 0;
+countr = 1;
+"#;
+
+        let expected_output = r#"
+[line 6]: ResolveError: Global variable `countr` is never declared
+countr = 1;
+^^^^^^
+note: `--strict` requires every global to be declared
+help: declare it with `var countr;`
+"#;
+
+        check_resolver(source, expected_output, true)?;
+        test_resolver(source, "")
+    }
+
+    #[test]
+    fn call_functions_with_wrong_number_of_arguments() -> Result<(), std::io::Error> {
+        let source = r#"
+fun early() {
+    return add(1);
+}
+fun add(a, b) {
+    return a + b;
+}
+add(1, 2);
+fun replaced() {}
+replaced = add;
+replaced(1, 2);
+{
+    fun one(a) {
+        return a;
+    }
+    one();
+}
+"#;
+
+        let expected_output = r#"
+[line 3]: ResolveError: Expected 2 arguments. Found 1 arguments
+    return add(1);
+           ^^^
+fun add(a, b) {
+    --- `add` is declared with 2 parameters
+[line 16]: ResolveError: Expected 1 arguments. Found 0 arguments
+    one();
+    ^^^
+    fun one(a) {
+        --- `one` is declared with 1 parameter
 "#;
 
         test_resolver(source, expected_output)
@@ -411,6 +912,185 @@ return 1;
         test_resolver(source, expected_output)
     }
 
+    #[test]
+    fn warn_about_statements_after_return() -> Result<(), std::io::Error> {
+        let source = r#"
+fun f(a) {
+    if (a) {
+        return 1;
+    }
+    return 2;
+    print "never";
+    return 3;
+}
+"#;
+
+        let expected_output = r#"
+[line 6]: Warning: Statements after `return` never run
+    return 2;
+    ^^^^^^^^^
+"#;
+
+        test_resolver(source, expected_output)
+    }
+
+    #[test]
+    fn warn_about_statements_after_branches_that_return() -> Result<(), std::io::Error> {
+        let source = r#"
+fun f(a) {
+    if (a) {
+        return 1;
+    } else return 2;
+    print "never";
+}
+fun g() {
+    {
+        return;
+    }
+    print "never";
+}
+fun h(a) {
+    if (a) return 1;
+    while (a) return 2;
+    print "runs";
+}
+"#;
+
+        let expected_output = r#"
+[line 3]: Warning: Statements after this `if` never run, all of its branches return
+    if (a) {
+    ^^
+[line 10]: Warning: Statements after `return` never run
+        return;
+        ^^^^^^^
+"#;
+
+        test_resolver(source, expected_output)
+    }
+
+    #[test]
+    fn warn_about_increment_after_return_in_for_loops() -> Result<(), std::io::Error> {
+        let source = r#"
+fun f() {
+    for (var i = 0; i < 3; i = i + 1) return i;
+}
+"#;
+
+        let expected_output = r#"
+[line 3]: Warning: The loop increment never runs after `return`
+    for (var i = 0; i < 3; i = i + 1) return i;
+                                      ^^^^^^^^^
+    for (var i = 0; i < 3; i = i + 1) return i;
+    --- in this `for` loop
+"#;
+
+        test_resolver(source, expected_output)
+    }
+
+    #[test]
+    fn warn_about_unused_variables_and_parameters() -> Result<(), std::io::Error> {
+        let source = r#"
+var global = 1;
+fun f(a, _b, c) {
+    var d = 1;
+    d = 2;
+    var _e;
+    fun g() {
+        return c;
+    }
+    return g;
+}
+"#;
+
+        let expected_output = r#"
+[line 3]: Warning: Parameter `a` is never used
+fun f(a, _b, c) {
+      ^
+help: name it `_a` if this is intended
+[line 4]: Warning: Variable `d` is never used
+    var d = 1;
+        ^
+help: name it `_d` if this is intended
+"#;
+
+        test_resolver(source, expected_output)
+    }
+
+    #[test]
+    fn warn_about_unused_functions_and_classes() -> Result<(), std::io::Error> {
+        let source = r#"
+fun used() {
+    return helper();
+}
+fun helper() {
+    return 1;
+}
+fun unused() {
+    return other();
+}
+fun other() {}
+class _Kept {}
+{
+    class Local {}
+}
+print used();
+"#;
+
+        let mut result = Vec::new();
+        let mut interpreter = Interpreter::new(&mut result);
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        let statements = Parser::from(&scanner).parse();
+        let mut resolver = Resolver::new(&mut interpreter).with_whole_script();
+        resolver.resolve(&statements);
+        assert_eq!(
+            resolver.unused_globals(),
+            [Symbol::intern("unused"), Symbol::intern("other")]
+        );
+        let warnings: Vec<_> = resolver
+            .warnings()
+            .iter()
+            .map(|warning| format!("{}: {}", warning.start_pos().line + 1, warning.message()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "8: Function `unused` is never used",
+                "11: Function `other` is never used",
+                "14: Class `Local` is never used"
+            ]
+        );
+
+        // only local ones for the prompt
+        test_resolver(
+            "fun f() {}\n{ fun g() {} }",
+            r#"
+[line 2]: Warning: Function `g` is never used
+{ fun g() {} }
+      ^
+help: name it `_g` if this is intended
+"#,
+        )
+    }
+
+    #[test]
+    fn warn_about_assigning_constants_in_conditions() -> Result<(), std::io::Error> {
+        let source = r#"
+var a = 1;
+if (a = 2) print a;
+while (a = a - 1) print a;
+"#;
+
+        let expected_output = r#"
+[line 3]: Warning: Condition assigns a constant to `a` and is always the same
+if (a = 2) print a;
+    ^
+help: use `==` to compare `a` instead
+"#;
+
+        test_resolver(source, expected_output)
+    }
+
     #[test]
     fn call_this_outside_class() -> Result<(), std::io::Error> {
         let source = r#"
@@ -447,6 +1127,8 @@ class Hello {
 [line 4]: ResolveError: Could not return inside constructor
         return "something else";
         ^^^^^^^^^^^^^^^^^^^^^^^^
+note: `init` always returns `this`
+help: use `return;` to return early
 "#;
 
         test_resolver(source, expected_output)
@@ -473,6 +1155,9 @@ class Hello : Hello {
         let source = r#"
 var name = 1;
 print super.name;
+fun f() {
+    super.f();
+}
 class Hello {
     init() {
         super.init();
@@ -481,12 +1166,17 @@ class Hello {
 "#;
 
         let expected_output = r#"
-[line 3]: ResolveError: Could not use `super` outside of a subclass
+[line 3]: ResolveError: Could not use `super` outside of a class
 print super.name;
-      ^^^^^
-[line 6]: ResolveError: Could not use `super` outside of a subclass
+      ^^^^^^^^^^
+[line 5]: ResolveError: Could not use `super` outside of a class
+    super.f();
+    ^^^^^^^
+[line 9]: ResolveError: Could not use `super` in a class without a superclass
         super.init();
-        ^^^^^
+        ^^^^^^^^^^
+class Hello {
+      ----- `Hello` has no superclass
 "#;
 
         test_resolver(source, expected_output)
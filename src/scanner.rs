@@ -1,10 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Write, sync::LazyLock};
 
 use crate::{
     error::{reporter::ErrorReporter, syntax_error::SyntaxError},
     object::Number,
-    source::{CharPos, SourcePos},
-    token::{Token, TokenType},
+    source::{normalize, CharPos, Lines},
+    token::{Comment, Token, TokenType, Trivia, TriviaKind},
 };
 
 // alpha for identifier
@@ -12,43 +12,99 @@ fn is_alpha(c: char) -> bool {
     c.is_ascii_alphabetic() || c == '_'
 }
 
-pub(crate) fn generate_static_reserved_keywords() -> HashMap<String, TokenType> {
-    let mut keywords = HashMap::new();
-    let reserved_token = [
-        TokenType::And,
-        TokenType::Class,
-        TokenType::Else,
-        TokenType::False,
-        TokenType::For,
-        TokenType::Fun,
-        TokenType::If,
-        TokenType::Nil,
-        TokenType::Or,
-        TokenType::Print,
-        TokenType::Return,
-        TokenType::Super,
-        TokenType::This,
-        TokenType::True,
-        TokenType::Var,
-        TokenType::While,
-    ];
-    for token_type in reserved_token {
-        keywords.insert(token_type.to_string(), token_type);
-    }
-    keywords
+// starts no token, whitespace or comment
+fn is_unexpected(c: char) -> bool {
+    !(c.is_ascii_digit() || is_alpha(c) || " \r\t\n\"(){},.-+;*:&|^~!=<>/".contains(c))
+}
+
+// reserved words, `RESERVED_KEYWORDS` maps their lexemes to where they are here
+const KEYWORDS: [TokenType; 16] = [
+    TokenType::And,
+    TokenType::Class,
+    TokenType::Else,
+    TokenType::False,
+    TokenType::For,
+    TokenType::Fun,
+    TokenType::If,
+    TokenType::Nil,
+    TokenType::Or,
+    TokenType::Print,
+    TokenType::Return,
+    TokenType::Super,
+    TokenType::This,
+    TokenType::True,
+    TokenType::Var,
+    TokenType::While,
+];
+
+// built once and shared by every scanner, scanning short sources doesn't rebuild it
+static RESERVED_KEYWORDS: LazyLock<HashMap<String, usize>> = LazyLock::new(|| {
+    KEYWORDS
+        .iter()
+        .enumerate()
+        .map(|(index, keyword)| (keyword.to_string(), index))
+        .collect()
+});
+
+pub(crate) fn keyword(identifier: &str) -> Option<TokenType> {
+    RESERVED_KEYWORDS
+        .get(identifier)
+        .map(|&index| KEYWORDS[index].clone())
+}
+
+pub(crate) fn reserved_keywords() -> impl Iterator<Item = &'static str> {
+    RESERVED_KEYWORDS.keys().map(String::as_str)
 }
 
 type ScanResult<T> = Result<T, SyntaxError>;
 
+// whether `source` stops inside brackets or a string, so the prompt should read more lines
+pub(crate) fn is_incomplete(source: &str) -> bool {
+    let mut scanner = Scanner::new(source);
+    let mut depth = 0;
+    for token in &mut scanner {
+        match token.token_type() {
+            TokenType::LeftParen | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBrace => depth -= 1,
+            _ => (),
+        }
+    }
+    depth > 0
+        || scanner
+            .errors
+            .iter()
+            .any(SyntaxError::is_unterminated_string)
+}
+
+// the comments of `source` in order, which are not tokens
+pub(crate) fn comments(source: &str) -> Vec<Comment> {
+    let mut scanner = Scanner::new(source);
+    for _ in &mut scanner {}
+    scanner
+        .trivia
+        .iter()
+        .filter(|trivia| trivia.kind == TriviaKind::Comment)
+        .map(|trivia| Comment {
+            line: trivia.start_pos.line + 1,
+            text: scanner.text(trivia.start_pos, trivia.end_pos),
+            trailing: trivia.trailing,
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 pub(crate) struct Scanner {
     source: Vec<char>,
-    source_pos: SourcePos,
+    lines: Lines,
     line: usize,
     current: usize,
-    reserved_keywords: HashMap<String, TokenType>,
     tokens: Vec<Token>,
     errors: Vec<SyntaxError>,
+    finished: bool,
+    // in source order, so is every token
+    trivia: Vec<Trivia>,
+    // whether trivia found now follows a token on its line
+    line_has_token: bool,
 }
 
 impl ErrorReporter<SyntaxError> for Scanner {
@@ -59,22 +115,91 @@ impl ErrorReporter<SyntaxError> for Scanner {
 
 impl Scanner {
     pub fn new(source: &str) -> Self {
-        let source_pos = SourcePos::new(source);
+        let source = normalize(source);
         Scanner {
             source: source.chars().collect(),
-            source_pos,
+            lines: Lines::new(&source),
             line: 1,
             current: 0,
-            reserved_keywords: generate_static_reserved_keywords(),
             tokens: Vec::new(),
             errors: Vec::new(),
+            finished: false,
+            trivia: Vec::new(),
+            line_has_token: false,
         }
     }
 
+    // skip the first `index` characters, positions still count from the start of `source`
+    pub fn starting_at(mut self, index: usize) -> Self {
+        self.current = index;
+        self
+    }
+
     pub fn tokens(&self) -> &[Token] {
         &self.tokens
     }
 
+    pub fn into_tokens(self) -> Vec<Token> {
+        self.tokens
+    }
+
+    fn text(&self, start: CharPos, end: CharPos) -> String {
+        self.source[start.index..=end.index].iter().collect()
+    }
+
+    // one line per token with its position, type, source text and the value of literals,
+    // line breaks inside strings are escaped. with `trivia`, whitespace and comments are
+    // listed between the tokens, whitespace quoted so it can be seen
+    pub fn listing(&self, trivia: bool) -> String {
+        let one_line = |text: &str| text.replace('\r', "\\r").replace('\n', "\\n");
+        let mut listing = String::new();
+        let mut trivia = self.trivia.iter().filter(|_| trivia).peekable();
+        for token in &self.tokens {
+            while let Some(trivia) = trivia.next_if(|trivia| {
+                token.token_type() == &TokenType::Eof
+                    || trivia.start_pos.index < token.start_pos().index
+            }) {
+                let start = trivia.start_pos;
+                let text = self.text(start, trivia.end_pos);
+                let text = match trivia.kind {
+                    TriviaKind::Comment => text,
+                    TriviaKind::Whitespace | TriviaKind::Newline => format!("{text:?}"),
+                };
+                let _ = write!(
+                    listing,
+                    "{}:{} {} {text}",
+                    start.line + 1,
+                    self.lines.column_of(start) + 1,
+                    trivia.kind.kind(),
+                );
+                if trivia.trailing {
+                    listing.push_str(" trailing");
+                }
+                listing.push('\n');
+            }
+            let start = token.start_pos();
+            let _ = write!(
+                listing,
+                "{}:{} {}",
+                start.line + 1,
+                self.lines.column_of(start) + 1,
+                token.token_type().kind()
+            );
+            if token.token_type() != &TokenType::Eof {
+                let text = self.text(start, token.end_pos());
+                let _ = write!(listing, " {}", one_line(&text));
+            }
+            match token.token_type() {
+                TokenType::Number(_) | TokenType::String(_) => {
+                    let _ = write!(listing, " {}", one_line(&token.lexeme()));
+                }
+                _ => (),
+            }
+            listing.push('\n');
+        }
+        listing
+    }
+
     fn prev(&mut self) {
         if self.current > 0 {
             self.current -= 1;
@@ -82,11 +207,8 @@ impl Scanner {
     }
 
     fn prev_pos(&self) -> Option<CharPos> {
-        if self.current > 0 {
-            self.source_pos.get(self.current - 1)
-        } else {
-            None
-        }
+        let index = self.current.checked_sub(1)?;
+        Some(CharPos::new(index, self.lines.line_of(index)))
     }
 
     fn peek(&self) -> Option<char> {
@@ -97,10 +219,9 @@ impl Scanner {
         }
     }
 
-    fn next(&mut self) -> Option<char> {
-        self.peek().map(|c| {
+    fn advance(&mut self) -> Option<char> {
+        self.peek().inspect(|_| {
             self.current += 1;
-            c
         })
     }
 
@@ -127,7 +248,7 @@ impl Scanner {
                 break;
             }
 
-            if let Some(c) = self.next() {
+            if let Some(c) = self.advance() {
                 if c == '\n' {
                     self.new_line();
                 }
@@ -143,8 +264,8 @@ impl Scanner {
 
     fn string(&mut self) -> ScanResult<TokenType> {
         let string = self.read_while(|c| c != '"');
-        match self.next() {
-            Some(_) => Ok(TokenType::String(string)),
+        match self.advance() {
+            Some(_) => Ok(TokenType::String(string.into())),
             None => Err(SyntaxError::unterminated_string(self.prev_pos().unwrap())),
         }
     }
@@ -158,7 +279,7 @@ impl Scanner {
                 .unwrap_or_default();
             if has_digit {
                 // skip dot
-                self.next();
+                self.advance();
                 numstr.push('.');
                 let fraction = self.read_while(|c| c.is_ascii_digit());
                 numstr.push_str(&fraction);
@@ -172,10 +293,28 @@ impl Scanner {
 
     fn identifier(&mut self) -> TokenType {
         let identifier = self.read_while(|c| is_alpha(c) || c.is_ascii_digit());
-        match self.reserved_keywords.get(&identifier) {
-            Some(token_type) => token_type.clone(),
-            None => TokenType::Identifier(identifier.clone()),
+        keyword(&identifier).unwrap_or_else(|| TokenType::Identifier(identifier.into()))
+    }
+
+    // trivia from `start` to the last character read, whitespace is joined to the
+    // whitespace right before it
+    fn push_trivia(&mut self, kind: TriviaKind, start: CharPos) {
+        let end = self.prev_pos().unwrap();
+        if let Some(last) = self.trivia.last_mut() {
+            if kind == TriviaKind::Whitespace
+                && last.kind == kind
+                && last.end_pos.index + 1 == start.index
+            {
+                last.end_pos = end;
+                return;
+            }
         }
+        self.trivia.push(Trivia {
+            kind,
+            start_pos: start,
+            end_pos: end,
+            trailing: self.line_has_token,
+        });
     }
 
     fn make_token(&self, token_type: TokenType, prev_pos: CharPos, cur_pos: CharPos) -> Token {
@@ -198,11 +337,15 @@ impl Scanner {
             ';' => TokenType::Semicolon,
             '*' => TokenType::Star,
             ':' => TokenType::Colon,
+            '&' => TokenType::Ampersand,
+            '|' => TokenType::Pipe,
+            '^' => TokenType::Caret,
+            '~' => TokenType::Tilde,
 
             // operators
             '!' => match self.peek() {
                 Some('=') => {
-                    self.next();
+                    self.advance();
 
                     TokenType::BangEqual
                 }
@@ -210,7 +353,7 @@ impl Scanner {
             },
             '=' => match self.peek() {
                 Some('=') => {
-                    self.next();
+                    self.advance();
 
                     TokenType::EqualEqual
                 }
@@ -218,18 +361,28 @@ impl Scanner {
             },
             '<' => match self.peek() {
                 Some('=') => {
-                    self.next();
+                    self.advance();
 
                     TokenType::LessEqual
                 }
+                Some('<') => {
+                    self.advance();
+
+                    TokenType::LessLess
+                }
                 _ => TokenType::Less,
             },
             '>' => match self.peek() {
                 Some('=') => {
-                    self.next();
+                    self.advance();
 
                     TokenType::GreaterEqual
                 }
+                Some('>') => {
+                    self.advance();
+
+                    TokenType::GreaterGreater
+                }
                 _ => TokenType::Greater,
             },
 
@@ -238,8 +391,9 @@ impl Scanner {
             '/' => match self.peek() {
                 Some('/') => {
                     // read until next line
-                    self.next();
+                    self.advance();
                     self.single_line_comment();
+                    self.push_trivia(TriviaKind::Comment, prev_pos);
                     return None;
                 }
                 _ => TokenType::Slash,
@@ -255,10 +409,14 @@ impl Scanner {
             }
 
             // whitespace
-            '\n' | ' ' | '\r' | '\t' => {
-                if c == '\n' {
-                    self.new_line();
-                }
+            '\n' => {
+                self.push_trivia(TriviaKind::Newline, prev_pos);
+                self.new_line();
+                self.line_has_token = false;
+                return None;
+            }
+            ' ' | '\r' | '\t' => {
+                self.push_trivia(TriviaKind::Whitespace, prev_pos);
                 return None;
             }
 
@@ -275,7 +433,15 @@ impl Scanner {
                         self.identifier()
                     }
                     false => {
-                        return Some(Err(SyntaxError::unexpected_character(
+                        // the characters following it that start nothing are reported with it
+                        let mut characters = c.to_string();
+                        while let Some(c) = self.peek().filter(|&c| is_unexpected(c)) {
+                            self.advance();
+                            characters.push(c);
+                        }
+                        return Some(Err(SyntaxError::unexpected_characters(
+                            characters,
+                            prev_pos,
                             self.prev_pos().unwrap(),
                         )));
                     }
@@ -289,22 +455,41 @@ impl Scanner {
         Some(Ok(token))
     }
 
+    // empty sources end where they start
+    fn eof(&self) -> Token {
+        let pos = self.prev_pos().unwrap_or_default();
+        self.make_token(TokenType::Eof, pos, pos)
+    }
+
+    // scan the whole source at once, used when every token is needed
     pub fn scan_tokens(&mut self) {
-        while let Some(c) = self.next() {
-            if let Some(result) = self.scan_token(c) {
-                match result {
-                    Ok(token) => self.tokens.push(token),
-                    Err(err) => self.errors.push(err),
+        while let Some(token) = Iterator::next(self) {
+            self.tokens.push(token);
+        }
+    }
+}
+
+// tokens are scanned lazily, errors are collected and scanning carries on,
+// the last token is always `Eof`
+impl Iterator for Scanner {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        while let Some(c) = self.advance() {
+            match self.scan_token(c) {
+                Some(Ok(token)) => {
+                    self.line_has_token = true;
+                    return Some(token);
                 }
+                Some(Err(err)) => self.errors.push(err),
+                None => (),
             }
         }
-
-        let eof = self.make_token(
-            TokenType::Eof,
-            self.prev_pos().unwrap(),
-            self.prev_pos().unwrap(),
-        );
-        self.tokens.push(eof);
+        self.finished = true;
+        Some(self.eof())
     }
 }
 
@@ -313,7 +498,7 @@ mod test {
 
     use super::*;
 
-    use crate::error::reporter::Reporter;
+    use crate::{error::reporter::Reporter, source::SourcePos};
 
     use std::io::Write;
 
@@ -382,6 +567,27 @@ line: 4, token: EOF
         test_scanner(source, expected_output)
     }
 
+    #[test]
+    fn scan_bitwise_operators() -> Result<(), std::io::Error> {
+        let source = r#"& | ^ ~
+<< >>
+<<= >>>"#;
+        let expected_output = r#"
+line: 1, token: &
+line: 1, token: |
+line: 1, token: ^
+line: 1, token: ~
+line: 2, token: <<
+line: 2, token: >>
+line: 3, token: <<
+line: 3, token: =
+line: 3, token: >>
+line: 3, token: >
+line: 3, token: EOF
+"#;
+        test_scanner(source, expected_output)
+    }
+
     #[test]
     fn scan_comments() -> Result<(), std::io::Error> {
         let source = r#"
@@ -393,6 +599,20 @@ line: 4, token: EOF
         test_scanner(source, expected_output)
     }
 
+    #[test]
+    fn find_comments() {
+        let comment = |line, text: &str, trailing| Comment {
+            line,
+            text: text.to_string(),
+            trailing,
+        };
+        assert_eq!(
+            comments("// top\nprint 1; // one"),
+            [comment(1, "// top", false), comment(2, "// one", true)]
+        );
+        assert!(comments("print 4 / 2; print \"//\";").is_empty());
+    }
+
     #[test]
     fn scan_string() -> Result<(), std::io::Error> {
         let source = r#"
@@ -495,15 +715,32 @@ line: 5, token: EOF
 
     #[test]
     fn scan_unexpected_character() -> Result<(), std::io::Error> {
-        let source = r#"@#"#;
+        let source = r#"@#$% a ?"#;
         let expected_output = r#"
+line: 1, token: a
 line: 1, token: EOF
-[line 1]: SyntaxError: Unexpected character `@`
-@#
-^
-[line 1]: SyntaxError: Unexpected character `#`
-@#
- ^
+[line 1]: SyntaxError: Unexpected characters `@#$%`
+@#$% a ?
+^^^^
+[line 1]: SyntaxError: Unexpected character `?`
+@#$% a ?
+       ^
+"#;
+        test_scanner(source, expected_output)
+    }
+
+    #[test]
+    fn scan_windows_line_endings() -> Result<(), std::io::Error> {
+        let source = "\u{feff}var a;\r\nprint \"abc\r\n";
+        let expected_output = r#"
+line: 1, token: var
+line: 1, token: a
+line: 1, token: ;
+line: 2, token: print
+line: 2, token: EOF
+[line 2]: SyntaxError: Unterminated string
+print "abc
+          ^
 "#;
         test_scanner(source, expected_output)
     }
@@ -517,4 +754,129 @@ line: 1, token: EOF
         "#;
         test_scanner(source, expected_output)
     }
+
+    #[test]
+    fn scan_lazily_as_iterator() {
+        let source = "var a = 1; @ print a;";
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+
+        let mut lazy_scanner = Scanner::new(source);
+        let first = lazy_scanner.next().unwrap();
+        assert_eq!(first.token_type(), &TokenType::Var);
+        assert!(!lazy_scanner.had_error());
+
+        let rest: Vec<Token> = lazy_scanner.by_ref().collect();
+        assert_eq!(rest.last().unwrap().token_type(), &TokenType::Eof);
+        assert_eq!(&scanner.tokens()[1..], rest.as_slice());
+        assert!(lazy_scanner.had_error());
+        assert!(lazy_scanner.next().is_none());
+    }
+
+    #[test]
+    fn incomplete_until_brackets_and_strings_are_closed() {
+        assert!(is_incomplete("fun f() {"));
+        assert!(is_incomplete("fun f() {\n  print (1 +"));
+        assert!(is_incomplete("print \"a\n"));
+        assert!(!is_incomplete("fun f() {\n  print (1 + 2);\n}"));
+        assert!(!is_incomplete("print \"a\nb\";"));
+        assert!(!is_incomplete("print \"{\"; // ("));
+        assert!(!is_incomplete("print 1);"));
+    }
+
+    #[test]
+    fn start_later_in_the_source() {
+        let source = "var a;\nprint a;";
+        let tokens: Vec<Token> = Scanner::new(source).starting_at(7).collect();
+        assert_eq!(tokens[0].token_type(), &TokenType::Print);
+        assert_eq!(tokens[0].start_pos(), CharPos::new(7, 1));
+        assert_eq!(tokens.len(), 4);
+    }
+
+    #[test]
+    fn scan_empty_source() {
+        let tokens: Vec<Token> = Scanner::new("").collect();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type(), &TokenType::Eof);
+    }
+
+    #[test]
+    fn list_tokens() {
+        let mut scanner = Scanner::new("var a = 1.50;\nprint \"hi\nthere\";");
+        scanner.scan_tokens();
+        let expected = r#"
+1:1 VAR var
+1:5 IDENTIFIER a
+1:7 EQUAL =
+1:9 NUMBER 1.50 1.5
+1:13 SEMICOLON ;
+2:1 PRINT print
+2:7 STRING "hi\nthere" hi\nthere
+3:7 SEMICOLON ;
+3:7 EOF
+"#;
+        assert_eq!(scanner.listing(false).trim(), expected.trim());
+    }
+
+    #[test]
+    fn keep_trivia() {
+        let source = "// top\r\nvar a = 1;  // one\n\n\tprint a;\n// end";
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        let mut pieces: Vec<_> = scanner
+            .tokens
+            .iter()
+            .filter(|token| token.token_type() != &TokenType::Eof)
+            .map(|token| (token.start_pos(), token.end_pos()))
+            .chain(
+                scanner
+                    .trivia
+                    .iter()
+                    .map(|trivia| (trivia.start_pos, trivia.end_pos)),
+            )
+            .collect();
+        pieces.sort_by_key(|(start, _)| start.index);
+        let round_trip: String = pieces
+            .into_iter()
+            .map(|(start, end)| scanner.text(start, end))
+            .collect();
+        // line endings are read as `\n`
+        assert_eq!(round_trip, source.replace("\r\n", "\n"));
+
+        let comments: Vec<_> = scanner
+            .trivia
+            .iter()
+            .filter(|trivia| trivia.kind == TriviaKind::Comment)
+            .map(|trivia| {
+                (
+                    scanner.text(trivia.start_pos, trivia.end_pos),
+                    trivia.trailing,
+                )
+            })
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                ("// top".to_string(), false),
+                ("// one".to_string(), true),
+                ("// end".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn list_trivia() {
+        let mut scanner = Scanner::new("a;  // one\n\t");
+        scanner.scan_tokens();
+        let expected = r#"
+1:1 IDENTIFIER a
+1:2 SEMICOLON ;
+1:3 WHITESPACE "  " trailing
+1:5 COMMENT // one trailing
+1:11 NEWLINE "\n" trailing
+2:1 WHITESPACE "\t"
+2:1 EOF
+"#;
+        assert_eq!(scanner.listing(true).trim(), expected.trim());
+    }
 }
@@ -0,0 +1,88 @@
+// ownership of what environments, instances, lists and functions share.
+// with the `sync` feature it is an `Arc` and a read-write lock instead of an `Rc` and a
+// `RefCell`, so an interpreter can be moved to another thread, at the cost of atomic
+// reference counts and locking on every access
+#[cfg(not(feature = "sync"))]
+pub(crate) use std::{
+    cell::{Ref, RefCell as Mutable, RefMut},
+    rc::{Rc as Shared, Weak},
+};
+
+#[cfg(feature = "sync")]
+pub(crate) use std::sync::{Arc as Shared, Weak};
+
+#[cfg(feature = "sync")]
+pub(crate) use lock::{Mutable, Ref, RefMut};
+
+// what host code given to the interpreter has to be, `Send` and `Sync` with the `sync` feature
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSend {}
+
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+#[cfg(feature = "sync")]
+pub trait MaybeSend: Send + Sync {}
+
+#[cfg(feature = "sync")]
+impl<T: ?Sized + Send + Sync> MaybeSend for T {}
+
+#[cfg(feature = "sync")]
+mod lock {
+    use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+    pub(crate) type Ref<'a, T> = RwLockReadGuard<'a, T>;
+    pub(crate) type RefMut<'a, T> = RwLockWriteGuard<'a, T>;
+
+    // the part of `RefCell` the interpreter uses. an interpreter runs on one thread at a
+    // time, so a lock already taken is a borrow the same thread still holds: it panics
+    // like `RefCell` does instead of blocking forever. a lock poisoned by a panicking
+    // thread is still used
+    #[derive(Debug, Default)]
+    pub(crate) struct Mutable<T>(RwLock<T>);
+
+    impl<T> Mutable<T> {
+        pub fn new(value: T) -> Self {
+            Self(RwLock::new(value))
+        }
+
+        pub fn borrow(&self) -> Ref<'_, T> {
+            match self.0.try_read() {
+                Ok(guard) => guard,
+                Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => panic!("already mutably borrowed"),
+            }
+        }
+
+        pub fn borrow_mut(&self) -> RefMut<'_, T> {
+            match self.0.try_write() {
+                Ok(guard) => guard,
+                Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => panic!("already borrowed"),
+            }
+        }
+    }
+
+    // compares the values, like `RefCell`
+    impl<T: PartialEq> PartialEq for Mutable<T> {
+        fn eq(&self, other: &Self) -> bool {
+            *self.borrow() == *other.borrow()
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn borrowing_mutably_while_borrowed_panics() {
+            let mutable = Mutable::new(1);
+            let borrowed = mutable.borrow();
+            let result = std::panic::catch_unwind(|| *mutable.borrow_mut() += 1);
+            assert!(result.is_err());
+            drop(borrowed);
+            *mutable.borrow_mut() += 1;
+            assert_eq!(*mutable.borrow(), 2);
+        }
+    }
+}
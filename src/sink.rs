@@ -0,0 +1,90 @@
+use std::io::Write;
+
+use crate::lox::LoxBuilder;
+
+// a writer handing what scripts print to a callback one line at a time, without the newline,
+// for hosts that show output as it comes instead of reading it from a buffer.
+// a line not finished yet is handed over when the writer is flushed, like before `readLine()`,
+// or dropped
+pub struct LineSink<F>
+where
+    F: FnMut(&str),
+{
+    sink: F,
+    line: Vec<u8>,
+}
+
+impl<F> LineSink<F>
+where
+    F: FnMut(&str),
+{
+    pub fn new(sink: F) -> Self {
+        Self {
+            sink,
+            line: Vec::new(),
+        }
+    }
+
+    fn send(&mut self, line: &[u8]) {
+        (self.sink)(&String::from_utf8_lossy(line));
+    }
+}
+
+impl<F> Write for LineSink<F>
+where
+    F: FnMut(&str),
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|&byte| byte == b'\n') {
+            let mut line = std::mem::take(&mut self.line);
+            line.extend_from_slice(&rest[..end]);
+            self.send(&line);
+            rest = &rest[end + 1..];
+        }
+        self.line.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.send(&line);
+        }
+        Ok(())
+    }
+}
+
+impl<F> Drop for LineSink<F>
+where
+    F: FnMut(&str),
+{
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl<F> LoxBuilder<LineSink<F>>
+where
+    F: FnMut(&str),
+{
+    // an engine handing what scripts print to `sink`
+    pub fn line_sink(sink: F) -> Self {
+        LoxBuilder::new(LineSink::new(sink))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lines_are_sent_as_they_are_printed() {
+        let mut lines = Vec::new();
+        let mut lox = LoxBuilder::line_sink(|line| lines.push(line.to_string())).build();
+        lox.eval("print 1; write(\"a\"); write(\"b\nc\"); print \"\"; write(\"d\");")
+            .unwrap();
+        drop(lox);
+        assert_eq!(lines, ["1", "ab", "c", "d"]);
+    }
+}
@@ -0,0 +1,131 @@
+// runs every `tests/cases/*.lox` through the whole pipeline and every
+// `tests/cases/parser/*.lox` through the parser only, comparing what they print with the
+// `.expected` file next to them. a `.input` file is what the script reads from stdin.
+// `LOX_BLESS=1 cargo test` writes what they printed into the `.expected` files instead
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast_repr::AstRepr,
+    error::reporter::{ErrorReporter, Reporter},
+    interpreter::Interpreter,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    source::SourcePos,
+};
+
+const CASES: &str = "tests/cases";
+const BLESS: &str = "LOX_BLESS";
+
+// every step runs and reports its errors, whatever the previous ones found
+fn run(source: &str, input: &str) -> Result<String, std::io::Error> {
+    let source_pos = SourcePos::new(source);
+    let reporter = Reporter::new(&source_pos);
+
+    let mut result = Vec::new();
+    let mut interpreter =
+        Interpreter::new(&mut result).with_reader(std::io::Cursor::new(input.as_bytes().to_vec()));
+
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    interpreter.write(&scanner.error_msg(&reporter))?;
+
+    let mut parser = Parser::from(&scanner);
+    let statements = parser.parse();
+    interpreter.write(&parser.error_msg(&reporter))?;
+
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve(&statements);
+    let error_msg = resolver.error_msg(&reporter);
+    let warning_msg = reporter.report_all(resolver.warnings());
+    interpreter.write(&error_msg)?;
+    interpreter.write(&warning_msg)?;
+
+    interpreter.interpret(&statements);
+    let error_msg = interpreter.error_msg(&reporter);
+    interpreter.write(&error_msg)?;
+
+    drop(interpreter);
+    Ok(String::from_utf8(result).unwrap())
+}
+
+fn parse(source: &str, _input: &str) -> Result<String, std::io::Error> {
+    let source_pos = SourcePos::new(source);
+    let reporter = Reporter::new(&source_pos);
+    let mut result = Vec::new();
+
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    writeln!(&mut result, "{}", scanner.error_msg(&reporter))?;
+
+    let mut parser = Parser::from(&scanner);
+    let statements = parser.parse();
+    writeln!(&mut result, "{}", AstRepr.repr(&statements))?;
+
+    writeln!(&mut result, "{}", parser.error_msg(&reporter))?;
+
+    Ok(String::from_utf8(result).unwrap())
+}
+
+fn cases(dir: &Path) -> Vec<PathBuf> {
+    let mut cases: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    cases.sort();
+    cases
+}
+
+// output is compared without the blank lines around it
+fn check_cases(
+    dir: &str,
+    run: impl Fn(&str, &str) -> Result<String, std::io::Error>,
+) -> Result<(), std::io::Error> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    let bless = std::env::var_os(BLESS).is_some();
+    let mut failures = Vec::new();
+    for case in cases(&dir) {
+        let source = std::fs::read_to_string(&case)?;
+        let input = std::fs::read_to_string(case.with_extension("input")).unwrap_or_default();
+        let output = run(&source, &input)?;
+        let output = output.trim();
+        let expected_path = case.with_extension("expected");
+        if bless {
+            let expected = match output {
+                "" => String::new(),
+                output => format!("{output}\n"),
+            };
+            std::fs::write(&expected_path, expected)?;
+            continue;
+        }
+        let expected = std::fs::read_to_string(&expected_path).unwrap_or_default();
+        if output != expected.trim() {
+            failures.push(format!(
+                "{}\n--- expected\n{}\n--- got\n{output}\n",
+                case.display(),
+                expected.trim()
+            ));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} cases failed, `{BLESS}=1 cargo test` updates them\n\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+    Ok(())
+}
+
+#[test]
+fn interpreter_cases() -> Result<(), std::io::Error> {
+    check_cases(CASES, run)
+}
+
+#[test]
+fn parser_cases() -> Result<(), std::io::Error> {
+    check_cases(&format!("{CASES}/parser"), parse)
+}
@@ -1,58 +1,180 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
 use unicode_width::UnicodeWidthChar;
 
-#[derive(Debug, Clone, PartialEq, Hash, Copy, Default)]
+// a UTF-8 byte order mark is dropped and `\r\n` becomes `\n`, so scripts saved on Windows
+// have the same positions as others. the scanner and error reports both read the source
+// this way, so the positions of one are valid in the other
+pub(crate) fn normalize(source: &str) -> Cow<'_, str> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    match source.contains("\r\n") {
+        true => Cow::Owned(source.replace("\r\n", "\n")),
+        false => Cow::Borrowed(source),
+    }
+}
+
+// every token keeps two of these, the character itself is read from the source
+// only when an error is drawn
+#[derive(Debug, Clone, PartialEq, Hash, Copy, Default, Serialize, Deserialize)]
 pub(crate) struct CharPos {
-    pub ch: char,
     pub index: usize,
     pub line: usize,
-    pub width: usize,
 }
 
-#[derive(Debug)]
-pub(crate) struct SourcePos {
-    positions: Vec<CharPos>,
-    newlines_pos: Vec<usize>,
+impl CharPos {
+    pub fn new(index: usize, line: usize) -> Self {
+        Self { index, line }
+    }
 }
 
-impl SourcePos {
-    pub fn new(source: &str) -> Self {
-        let mut positions = Vec::with_capacity(source.len());
+// from the first to the last character of some code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Span {
+    pub start: CharPos,
+    pub end: CharPos,
+}
+
+impl Span {
+    pub fn new(start: CharPos, end: CharPos) -> Self {
+        Self { start, end }
+    }
+}
 
-        let mut line = 0;
+// a character of a line being drawn under an error message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SourceChar {
+    pub ch: char,
+    pub index: usize,
+    pub width: usize,
+}
 
-        let mut newlines_pos = vec![0];
+impl SourceChar {
+    fn new(ch: char, index: usize) -> Self {
+        Self {
+            ch,
+            index,
+            width: UnicodeWidthChar::width(ch).unwrap_or(0),
+        }
+    }
+}
 
-        for (index, ch) in source.chars().enumerate() {
-            let width = UnicodeWidthChar::width(ch).unwrap_or(0);
+#[derive(Debug, Clone, Copy)]
+struct LineStart {
+    // char index of the first character
+    index: usize,
+    // byte offset of the first character
+    offset: usize,
+}
 
-            let char_pos = CharPos {
-                ch,
-                index,
-                line,
-                width,
-            };
+// where every line starts, lines are found by binary search
+#[derive(Debug)]
+pub(crate) struct Lines {
+    starts: Vec<LineStart>,
+}
 
+impl Lines {
+    pub fn new(source: &str) -> Self {
+        let mut starts = vec![LineStart {
+            index: 0,
+            offset: 0,
+        }];
+        for (index, (offset, ch)) in source.char_indices().enumerate() {
             if ch == '\n' {
-                line += 1;
-                newlines_pos.push(index + 1);
+                starts.push(LineStart {
+                    index: index + 1,
+                    offset: offset + 1,
+                });
             }
-
-            positions.push(char_pos);
         }
+        Self { starts }
+    }
 
-        newlines_pos.push(positions.len());
+    // line of the character at `index`, a newline belongs to the line it ends
+    pub fn line_of(&self, index: usize) -> usize {
+        self.starts.partition_point(|start| start.index <= index) - 1
+    }
 
-        Self {
-            positions,
-            newlines_pos,
-        }
+    // 0-based column of the character at `pos`
+    pub fn column_of(&self, pos: CharPos) -> usize {
+        self.get(pos.line)
+            .map_or(pos.index, |start| pos.index - start.index)
     }
 
-    pub fn get(&self, index: usize) -> Option<CharPos> {
-        self.positions.get(index).cloned()
+    fn get(&self, line: usize) -> Option<LineStart> {
+        self.starts.get(line).copied()
+    }
+}
+
+// positions are computed from the source when an error is reported,
+// only line starts are kept
+#[derive(Debug)]
+pub(crate) struct SourcePos<'a> {
+    source: Cow<'a, str>,
+    lines: Lines,
+}
+
+impl<'a> SourcePos<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let source = normalize(source);
+        let lines = Lines::new(&source);
+        Self { source, lines }
     }
 
-    pub fn newlines_pos(&self) -> &[usize] {
-        &self.newlines_pos
+    // 0-based column of the character at `pos`
+    pub fn column_of(&self, pos: CharPos) -> usize {
+        self.lines.column_of(pos)
+    }
+
+    // every character of `line`, including the newline ending it
+    pub fn line(&self, line: usize) -> impl Iterator<Item = SourceChar> + '_ {
+        let start = self.lines.get(line);
+        let chars = start.map(|start| self.source[start.offset..].chars());
+        let index = start.map(|start| start.index).unwrap_or_default();
+        chars
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .scan(false, |ended, (i, ch)| {
+                if *ended {
+                    return None;
+                }
+                *ended = ch == '\n';
+                Some((i, ch))
+            })
+            .map(move |(i, ch)| SourceChar::new(ch, index + i))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn positions_are_computed_from_line_starts() {
+        let source = "ab\ncé\n\nd";
+        let source_pos = SourcePos::new(source);
+
+        let chars: Vec<_> = (0..5).flat_map(|line| source_pos.line(line)).collect();
+        assert_eq!(chars.len(), 8);
+        assert_eq!(chars[4], SourceChar::new('é', 4));
+        assert!(chars.iter().enumerate().all(|(i, pos)| pos.index == i));
+        let lines: Vec<_> = (0..8).map(|i| source_pos.lines.line_of(i)).collect();
+        assert_eq!(lines, [0, 0, 0, 1, 1, 1, 2, 3]);
+
+        let line: String = source_pos.line(1).map(|pos| pos.ch).collect();
+        assert_eq!(line, "cé\n");
+        assert_eq!(source_pos.line(4).count(), 0);
+    }
+
+    #[test]
+    fn byte_order_mark_and_crlf_are_normalized() {
+        assert_eq!(normalize("\u{feff}a\r\nb\rc"), "a\nb\rc");
+        assert!(matches!(normalize("a\nb"), Cow::Borrowed(_)));
+
+        let source_pos = SourcePos::new("\u{feff}a\r\nbc\r\n");
+        let line: String = source_pos.line(1).map(|pos| pos.ch).collect();
+        assert_eq!(line, "bc\n");
+        assert_eq!(source_pos.column_of(CharPos::new(3, 1)), 1);
     }
 }
@@ -0,0 +1,17 @@
+// every lox call nests many rust frames, more in debug builds. before each call the stack
+// is grown on the heap when it runs low, so scripts can call as deep as
+// `--max-call-depth` allows on whatever thread runs them. chains like `a + b + c`, which
+// nest as deep as they are long, grow it the same way at every link
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn grow<T>(f: impl FnOnce() -> T) -> T {
+    // more than one call takes between two checks, with the expressions nested in it
+    const RED_ZONE: usize = 256 * 1024;
+    const SEGMENT: usize = 4 * 1024 * 1024;
+    stacker::maybe_grow(RED_ZONE, SEGMENT, f)
+}
+
+// browsers give a fixed stack, `max_call_depth` has to fit in it
+#[cfg(target_family = "wasm")]
+pub(crate) fn grow<T>(f: impl FnOnce() -> T) -> T {
+    f()
+}
@@ -0,0 +1,31 @@
+use std::fmt::Write;
+
+// counts of what the interpreter did, to compare runs across interpreter changes
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    pub statements: u64,
+    pub expressions: u64,
+    pub calls: u64,
+    // strings and lists, environments and instances are counted by the heap
+    pub allocations: u64,
+    pub environment_pushes: u64,
+    pub environment_pops: u64,
+}
+
+impl Stats {
+    pub fn report(&self, heap_allocations: u64) -> String {
+        let rows = [
+            ("statements executed", self.statements),
+            ("expressions evaluated", self.expressions),
+            ("function calls", self.calls),
+            ("allocations", self.allocations + heap_allocations),
+            ("environment pushes", self.environment_pushes),
+            ("environment pops", self.environment_pops),
+        ];
+        let mut report = String::new();
+        for (name, count) in rows {
+            let _ = writeln!(report, "{name:<24}{count:>12}");
+        }
+        report
+    }
+}
@@ -1,20 +1,25 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{
     expr::{Expr, Variable},
+    shared::Shared,
     token::Token,
     visitor::Visitor,
 };
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) enum Stmt {
     Expression(Expr),
     Class(Class),
-    Print(Expr),
+    Print(Print),
     Return(Return),
-    Function(Function),
+    // shared with every function value created from it
+    Function(Shared<Function>),
     Var(Var),
     Block(Block),
     If(If),
     While(While),
+    ForIn(ForIn),
 }
 
 impl Stmt {
@@ -23,7 +28,7 @@ impl Stmt {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct Expression {
     pub expression: Expr,
 }
@@ -35,12 +40,22 @@ impl Expression {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Print {
+    pub keyword: Token,
     pub expression: Expr,
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+impl Print {
+    pub fn new(keyword: Token, expression: Expr) -> Self {
+        Self {
+            keyword,
+            expression,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Var {
     pub identifier: Token,
     pub expression: Expr,
@@ -55,27 +70,52 @@ impl Var {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Default, Hash)]
+#[derive(Debug, Clone, PartialEq, Default, Hash, Serialize, Deserialize)]
 pub(crate) struct Block {
     pub statements: Vec<Stmt>,
+    // the `for` keyword, when the parser made this block out of a `for` loop
+    pub for_keyword: Option<Token>,
+    // the `{` and `}` around the block when it was written in the source
+    pub braces: Option<(Token, Token)>,
 }
 
 impl Block {
     pub fn new(statements: Vec<Stmt>) -> Self {
-        Self { statements }
+        Self {
+            statements,
+            for_keyword: None,
+            braces: None,
+        }
+    }
+
+    pub fn with_braces(mut self, left_brace: Token, right_brace: Token) -> Self {
+        self.braces = Some((left_brace, right_brace));
+        self
+    }
+
+    pub fn with_for_keyword(mut self, keyword: Token) -> Self {
+        self.for_keyword = Some(keyword);
+        self
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct If {
+    pub keyword: Token,
     pub condition: Expr,
     pub then_branch: Box<Stmt>,
     pub else_branch: Option<Box<Stmt>>,
 }
 
 impl If {
-    pub fn new(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>) -> Self {
+    pub fn new(
+        keyword: Token,
+        condition: Expr,
+        then_branch: Stmt,
+        else_branch: Option<Stmt>,
+    ) -> Self {
         Self {
+            keyword,
             condition,
             then_branch: Box::new(then_branch),
             else_branch: else_branch.map(Box::new),
@@ -83,22 +123,62 @@ impl If {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct While {
+    // `while` or `for`
+    pub keyword: Token,
     pub condition: Expr,
     pub body: Box<Stmt>,
+    // `None` for `while` loops
+    pub clauses: Option<ForClauses>,
 }
 
 impl While {
-    pub fn new(condition: Expr, body: Stmt) -> Self {
+    pub fn new(keyword: Token, condition: Expr, body: Stmt) -> Self {
         Self {
+            keyword,
             condition,
             body: Box::new(body),
+            clauses: None,
+        }
+    }
+
+    pub fn with_clauses(mut self, clauses: ForClauses) -> Self {
+        self.clauses = Some(clauses);
+        self
+    }
+}
+
+// `for (var name in iterable) body`, over the elements of a list or the keys of a map
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
+pub(crate) struct ForIn {
+    pub keyword: Token,
+    pub name: Token,
+    pub iterable: Expr,
+    pub body: Box<Stmt>,
+}
+
+impl ForIn {
+    pub fn new(keyword: Token, name: Token, iterable: Expr, body: Stmt) -> Self {
+        Self {
+            keyword,
+            name,
+            iterable,
+            body: Box::new(body),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+// which clauses a `for` loop was written with, so it can be told apart from the
+// blocks and `while` loop the parser rewrites it into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct ForClauses {
+    pub initializer: bool,
+    pub condition: bool,
+    pub increment: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Function {
     pub name: Token,
     pub params: Vec<Token>,
@@ -115,7 +195,7 @@ impl Function {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Return {
     pub keyword: Token,
     pub semicolon: Token,
@@ -132,17 +212,17 @@ impl Return {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) struct Class {
     pub name: Token,
     pub superclass: Option<Variable>,
-    pub methods: Vec<Stmt>,
+    pub methods: Vec<Shared<Function>>,
 }
 
 impl Eq for Class {}
 
 impl Class {
-    pub fn new(name: Token, superclass: Option<Variable>, methods: Vec<Stmt>) -> Self {
+    pub fn new(name: Token, superclass: Option<Variable>, methods: Vec<Shared<Function>>) -> Self {
         Self {
             name,
             superclass,
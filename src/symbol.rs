@@ -0,0 +1,106 @@
+use std::{collections::HashMap, fmt};
+
+use crate::shared::Shared;
+
+// identifiers are interned once while scanning,
+// environments then hash and compare small integers instead of strings.
+// interned names are never freed, they stay for the life of the process (or thread,
+// without `sync`) and are shared by every interpreter in it. a long running server
+// evaluating scripts with ever new identifiers grows with each of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Symbol(u32);
+
+// names used by the interpreter itself, interned first so they have fixed symbols
+const RESERVED: [&str; 2] = ["this", "super"];
+
+struct Interner {
+    symbols: HashMap<Shared<str>, Symbol>,
+    names: Vec<Shared<str>>,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        let mut interner = Self {
+            symbols: HashMap::new(),
+            names: Vec::new(),
+        };
+        for name in RESERVED {
+            interner.intern(name);
+        }
+        interner
+    }
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        let name: Shared<str> = Shared::from(name);
+        self.names.push(Shared::clone(&name));
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+}
+
+#[cfg(not(feature = "sync"))]
+thread_local! {
+    static INTERNER: std::cell::RefCell<Interner> = std::cell::RefCell::default();
+}
+
+#[cfg(not(feature = "sync"))]
+fn with_interner<T>(f: impl FnOnce(&mut Interner) -> T) -> T {
+    INTERNER.with(|interner| f(&mut interner.borrow_mut()))
+}
+
+// interpreters moved to other threads keep using the symbols they were given
+#[cfg(feature = "sync")]
+static INTERNER: std::sync::LazyLock<std::sync::Mutex<Interner>> =
+    std::sync::LazyLock::new(Default::default);
+
+#[cfg(feature = "sync")]
+fn with_interner<T>(f: impl FnOnce(&mut Interner) -> T) -> T {
+    f(&mut INTERNER
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner))
+}
+
+impl Symbol {
+    pub const THIS: Symbol = Symbol(0);
+    pub const SUPER: Symbol = Symbol(1);
+
+    pub fn intern(name: &str) -> Self {
+        with_interner(|interner| interner.intern(name))
+    }
+
+    pub fn name(&self) -> Shared<str> {
+        with_interner(|interner| Shared::clone(&interner.names[self.0 as usize]))
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intern_same_name_once() {
+        let a = Symbol::intern("a");
+        assert_eq!(a, Symbol::intern("a"));
+        assert_ne!(a, Symbol::intern("b"));
+        assert_eq!(a.to_string(), "a");
+    }
+
+    #[test]
+    fn reserved_symbols() {
+        assert_eq!(Symbol::intern("this"), Symbol::THIS);
+        assert_eq!(Symbol::intern("super"), Symbol::SUPER);
+        assert_eq!(Symbol::SUPER.to_string(), "super");
+    }
+}
@@ -0,0 +1,242 @@
+use std::path::{Path, PathBuf};
+
+// the comments of the Crafting Interpreters test suite
+const EXPECT_OUTPUT: &str = "// expect:";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+const EXPECT_ERROR: &str = "// Error";
+// an error reported on another line, `[java line 3]` is only for jlox, which this is
+const EXPECT_ERROR_AT_LINE: &str = "// [line ";
+const EXPECT_JAVA_ERROR_AT_LINE: &str = "// [java line ";
+// files that are only used by other tests
+const NOT_A_TEST: &str = "// nontest";
+
+// what running a test script did
+#[derive(Debug, Default)]
+pub(crate) struct Outcome {
+    // printed lines
+    pub output: Vec<String>,
+    // line and message of the first runtime error
+    pub runtime_error: Option<(usize, String)>,
+    // line and report of every scan, parse or resolve error, the script did not run if
+    // there is one
+    pub compile_errors: Vec<(usize, String)>,
+}
+
+// what a test script says it does, in comments at the end of its lines
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Expectations {
+    // line of the comment and the printed line
+    output: Vec<(usize, String)>,
+    runtime_error: Option<(usize, String)>,
+    // only lines are compared, the messages are worded differently than in jlox
+    compile_errors: Vec<usize>,
+}
+
+// the line number at the start of `text`, up to `]`
+fn line_number(text: &str) -> Option<usize> {
+    text.split_once(']')?.0.parse().ok()
+}
+
+impl Expectations {
+    // `None` for files that are not tests
+    pub fn parse(source: &str) -> Option<Self> {
+        let mut expectations = Self::default();
+        for (line, text) in (1..).zip(source.lines()) {
+            if text.contains(NOT_A_TEST) {
+                return None;
+            }
+            if let Some((_, expected)) = text.split_once(EXPECT_OUTPUT) {
+                let expected = expected.strip_prefix(' ').unwrap_or(expected);
+                expectations.output.push((line, expected.to_string()));
+            } else if let Some((_, message)) = text.split_once(EXPECT_RUNTIME_ERROR) {
+                expectations.runtime_error = Some((line, message.to_string()));
+            } else if text.contains(EXPECT_ERROR) {
+                expectations.compile_errors.push(line);
+            } else if let Some((_, rest)) = text
+                .split_once(EXPECT_ERROR_AT_LINE)
+                .or_else(|| text.split_once(EXPECT_JAVA_ERROR_AT_LINE))
+            {
+                expectations.compile_errors.extend(line_number(rest));
+            }
+        }
+        // jlox and clox expect different errors on the same line
+        expectations.compile_errors.sort();
+        expectations.compile_errors.dedup();
+        Some(expectations)
+    }
+
+    // everything that did not go as expected, nothing if the test passed.
+    // runtime errors are compared by line only when `ignore_messages` is set
+    pub fn check(&self, outcome: &Outcome, ignore_messages: bool) -> Vec<String> {
+        let mut failures = Vec::new();
+        if !outcome.compile_errors.is_empty() || !self.compile_errors.is_empty() {
+            for line in &self.compile_errors {
+                if !outcome
+                    .compile_errors
+                    .iter()
+                    .any(|(actual, _)| actual == line)
+                {
+                    failures.push(format!("Missing expected compile error on line {line}"));
+                }
+            }
+            for (line, report) in &outcome.compile_errors {
+                if !self.compile_errors.contains(line) {
+                    failures.push(format!("Unexpected compile error:\n{}", report.trim_end()));
+                }
+            }
+            return failures;
+        }
+
+        for (i, (line, expected)) in self.output.iter().enumerate() {
+            match outcome.output.get(i) {
+                Some(actual) if actual == expected => (),
+                Some(actual) => failures.push(format!(
+                    "Expected output {expected:?} on line {line}, got {actual:?}"
+                )),
+                None => failures.push(format!(
+                    "Missing expected output {expected:?} on line {line}"
+                )),
+            }
+        }
+        for actual in outcome.output.iter().skip(self.output.len()) {
+            failures.push(format!("Got output {actual:?} when none was expected"));
+        }
+
+        match (&self.runtime_error, &outcome.runtime_error) {
+            (Some(expected), Some(actual))
+                if expected == actual || (ignore_messages && expected.0 == actual.0) => {}
+            (Some((line, expected)), Some((actual_line, actual))) => failures.push(format!(
+                "Expected runtime error {expected:?} on line {line}, got {actual:?} on line {actual_line}"
+            )),
+            (Some((line, expected)), None) => failures.push(format!(
+                "Expected runtime error {expected:?} on line {line}, got none"
+            )),
+            (None, Some((line, actual))) => {
+                failures.push(format!("Unexpected runtime error {actual:?} on line {line}"))
+            }
+            (None, None) => (),
+        }
+        failures
+    }
+}
+
+// `.lox` files in `paths` and in every directory under them, in order
+pub(crate) fn test_files(paths: &[PathBuf]) -> std::io::Result<Vec<PathBuf>> {
+    fn visit(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        if !path.is_dir() {
+            files.push(path.to_path_buf());
+            return Ok(());
+        }
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort();
+        for entry in entries {
+            if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "lox") {
+                visit(&entry, files)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        visit(path, &mut files)?;
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_expectations() {
+        let source = "print 1; // expect: 1
+print \"a b\"; // expect: a b
+// no expectation
+a.b; // expect runtime error: Undefined variable `a`";
+        assert_eq!(
+            Expectations::parse(source),
+            Some(Expectations {
+                output: vec![(1, "1".to_string()), (2, "a b".to_string())],
+                runtime_error: Some((4, "Undefined variable `a`".to_string())),
+                compile_errors: Vec::new(),
+            })
+        );
+        assert_eq!(
+            Expectations::parse("// nontest\nprint 1; // expect: 1"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_book_expectations() {
+        let source = "print \"\"; // expect:
+var a = 1 +; // Error at ';': Expect expression.
+// [line 4] Error at end: Expect '}' after block.
+// [java line 5] Error at end: Expect ';' after value.
+// [c line 6] Error: Unterminated string.";
+        assert_eq!(
+            Expectations::parse(source),
+            Some(Expectations {
+                output: vec![(1, String::new())],
+                runtime_error: None,
+                compile_errors: vec![2, 4, 5],
+            })
+        );
+    }
+
+    #[test]
+    fn check_outcome() {
+        let expectations = Expectations::parse("// expect: 1\n// expect: 2\n").unwrap();
+        let outcome = |output: &[&str], runtime_error: Option<(usize, &str)>| Outcome {
+            output: output.iter().map(|line| line.to_string()).collect(),
+            runtime_error: runtime_error.map(|(line, message)| (line, message.to_string())),
+            compile_errors: Vec::new(),
+        };
+        assert!(expectations
+            .check(&outcome(&["1", "2"], None), false)
+            .is_empty());
+        assert_eq!(
+            expectations.check(&outcome(&["1"], Some((3, "boom"))), false),
+            [
+                "Missing expected output \"2\" on line 2",
+                "Unexpected runtime error \"boom\" on line 3"
+            ]
+        );
+        assert_eq!(
+            expectations.check(&outcome(&["1", "3", "4"], None), false),
+            [
+                "Expected output \"2\" on line 2, got \"3\"",
+                "Got output \"4\" when none was expected"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        let expectations = Expectations::parse(
+            "print a; // expect runtime error: Undefined variable 'a'.\nvar; // Error",
+        )
+        .unwrap();
+        let compile_error = Outcome {
+            compile_errors: vec![(2, "[line 2]: ParseError".to_string())],
+            ..Outcome::default()
+        };
+        assert!(expectations.check(&compile_error, false).is_empty());
+
+        let expectations =
+            Expectations::parse("print a; // expect runtime error: Undefined.").unwrap();
+        let runtime_error = Outcome {
+            runtime_error: Some((1, "Undefined variable `a`".to_string())),
+            ..Outcome::default()
+        };
+        assert_eq!(expectations.check(&runtime_error, false).len(), 1);
+        assert!(expectations.check(&runtime_error, true).is_empty());
+        assert_eq!(
+            expectations.check(&compile_error, true),
+            ["Unexpected compile error:\n[line 2]: ParseError"]
+        );
+    }
+}
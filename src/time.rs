@@ -0,0 +1,6 @@
+// `std::time` panics in browsers, `web-time` reads the time from javascript there
+#[cfg(not(target_family = "wasm"))]
+pub(crate) use std::time::{Instant, SystemTime};
+
+#[cfg(target_family = "wasm")]
+pub(crate) use web_time::{Instant, SystemTime};
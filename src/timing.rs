@@ -0,0 +1,68 @@
+use std::{fmt::Write, time::Duration};
+
+// time spent in every phase of running a script, added up over every prompt input
+#[derive(Debug, Default)]
+pub(crate) struct Timings {
+    pub scan: Duration,
+    pub parse: Duration,
+    pub resolve: Duration,
+    pub interpret: Duration,
+    // part of interpreting
+    pub collect: Duration,
+    pub collections: u64,
+    // environments and instances alive at once, seen before every collection
+    // and after running
+    pub peak_objects: usize,
+}
+
+impl Timings {
+    pub fn seen(&mut self, objects: usize) {
+        self.peak_objects = self.peak_objects.max(objects);
+    }
+
+    pub fn collected(&mut self, elapsed: Duration, objects: usize) {
+        self.collect += elapsed;
+        self.collections += 1;
+        self.seen(objects);
+    }
+
+    pub fn report(&self) -> String {
+        let phases = [
+            ("scanning", self.scan),
+            ("parsing", self.parse),
+            ("resolving", self.resolve),
+            ("interpreting", self.interpret),
+            ("  collecting garbage", self.collect),
+        ];
+        let mut report = String::new();
+        for (name, elapsed) in phases {
+            let _ = writeln!(
+                report,
+                "{name:<24}{:>12.3} ms",
+                elapsed.as_secs_f64() * 1000.0
+            );
+        }
+        let _ = writeln!(report, "{:<24}{:>12}", "collections", self.collections);
+        let _ = writeln!(report, "{:<24}{:>12}", "peak objects", self.peak_objects);
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn peak_is_the_most_objects_seen() {
+        let mut timings = Timings::default();
+        timings.collected(Duration::from_millis(2), 30);
+        timings.collected(Duration::from_millis(1), 10);
+        assert_eq!(timings.collect, Duration::from_millis(3));
+        assert_eq!(timings.collections, 2);
+        assert_eq!(timings.peak_objects, 30);
+        assert!(timings
+            .report()
+            .lines()
+            .any(|line| line.starts_with("  collecting garbage") && line.ends_with("3.000 ms")));
+    }
+}
@@ -1,6 +1,15 @@
-use crate::{object::Number, source::CharPos};
+use std::{borrow::Cow, fmt};
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    object::Number,
+    shared::Shared,
+    source::{CharPos, Span},
+    symbol::Symbol,
+};
+
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub(crate) enum TokenType {
     // Single-character tokens.
     LeftParen,
@@ -15,6 +24,10 @@ pub(crate) enum TokenType {
     Slash,
     Star,
     Colon,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -25,10 +38,12 @@ pub(crate) enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
 
-    // Literals.
-    Identifier(String),
-    String(String),
+    // Literals, shared so cloning a token never copies text.
+    Identifier(Shared<str>),
+    String(Shared<str>),
     Number(Number),
 
     // Keywords.
@@ -58,31 +73,77 @@ impl From<Token> for TokenType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+// lexemes are derived from the token type instead of being stored
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(from = "TokenData")]
 pub(crate) struct Token {
     token_type: TokenType,
-    lexeme: String,
+    // only names have symbols, which are interned again when a token is deserialized
+    #[serde(skip)]
+    symbol: Option<Symbol>,
+    start_pos: CharPos,
+    end_pos: CharPos,
+}
+
+#[derive(Deserialize)]
+struct TokenData {
+    token_type: TokenType,
     start_pos: CharPos,
     end_pos: CharPos,
 }
 
+impl From<TokenData> for Token {
+    fn from(data: TokenData) -> Self {
+        Token::new(data.token_type, data.start_pos, data.end_pos)
+    }
+}
+
 impl Token {
     pub fn new(token_type: TokenType, start_pos: CharPos, end_pos: CharPos) -> Self {
-        let lexeme = token_type.to_string();
+        let symbol = match &token_type {
+            TokenType::Identifier(name) => Some(Symbol::intern(name)),
+            TokenType::This => Some(Symbol::THIS),
+            TokenType::Super => Some(Symbol::SUPER),
+            _ => None,
+        };
         Self {
             token_type,
-            lexeme,
+            symbol,
             start_pos,
             end_pos,
         }
     }
 
+    // a token the parser makes up for code the user left out. it spans the code it comes
+    // from, so errors and warnings about it point at what the user wrote
+    pub fn synthesized(token_type: TokenType, origin: Span) -> Self {
+        Token::new(token_type, origin.start, origin.end)
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.start_pos, self.end_pos)
+    }
+
     pub fn token_type(&self) -> &TokenType {
         &self.token_type
     }
 
-    pub fn lexeme(&self) -> &str {
-        self.lexeme.as_ref()
+    // only numbers are formatted, every other lexeme is borrowed
+    pub fn lexeme(&self) -> Cow<'_, str> {
+        match &self.token_type {
+            TokenType::Number(_) => Cow::Owned(self.token_type.to_string()),
+            token_type => Cow::Borrowed(token_type.as_str()),
+        }
+    }
+
+    // the identifier of a declaration, `this` or `super`
+    pub fn name(&self) -> &str {
+        self.token_type.as_str()
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        self.symbol
+            .unwrap_or_else(|| Symbol::intern(&self.lexeme()))
     }
 
     pub fn start_pos(&self) -> CharPos {
@@ -92,51 +153,186 @@ impl Token {
     pub fn end_pos(&self) -> CharPos {
         self.end_pos
     }
+
+    // a token scanned from a source on its own, placed after the sources before it
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.start_pos.index += offset;
+        self.end_pos.index += offset;
+        self
+    }
 }
 
-impl ToString for TokenType {
-    fn to_string(&self) -> String {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TriviaKind {
+    // spaces, tabs and carriage returns, a run of them is one trivia
+    Whitespace,
+    Newline,
+    Comment,
+}
+
+impl TriviaKind {
+    pub fn kind(self) -> &'static str {
         match self {
-            TokenType::LeftParen => "(".to_string(),
-            TokenType::RightParen => ")".to_string(),
-            TokenType::LeftBrace => "{".to_string(),
-            TokenType::RightBrace => "}".to_string(),
-            TokenType::Comma => ",".to_string(),
-            TokenType::Dot => ".".to_string(),
-            TokenType::Minus => "-".to_string(),
-            TokenType::Plus => "+".to_string(),
-            TokenType::Semicolon => ";".to_string(),
-            TokenType::Slash => "/".to_string(),
-            TokenType::Star => "*".to_string(),
-            TokenType::Colon => ":".to_string(),
-            TokenType::Bang => "!".to_string(),
-            TokenType::BangEqual => "!=".to_string(),
-            TokenType::Equal => "=".to_string(),
-            TokenType::EqualEqual => "==".to_string(),
-            TokenType::Greater => ">".to_string(),
-            TokenType::GreaterEqual => ">=".to_string(),
-            TokenType::Less => "<".to_string(),
-            TokenType::LessEqual => "<=".to_string(),
-            TokenType::Identifier(s) => s.to_string(),
-            TokenType::String(s) => s.to_string(),
-            TokenType::Number(n) => n.to_string(),
-            TokenType::And => "and".to_string(),
-            TokenType::Class => "class".to_string(),
-            TokenType::Else => "else".to_string(),
-            TokenType::False => "false".to_string(),
-            TokenType::Fun => "fun".to_string(),
-            TokenType::For => "for".to_string(),
-            TokenType::If => "if".to_string(),
-            TokenType::Nil => "nil".to_string(),
-            TokenType::Or => "or".to_string(),
-            TokenType::Print => "print".to_string(),
-            TokenType::Return => "return".to_string(),
-            TokenType::Super => "super".to_string(),
-            TokenType::This => "this".to_string(),
-            TokenType::True => "true".to_string(),
-            TokenType::Var => "var".to_string(),
-            TokenType::While => "while".to_string(),
-            TokenType::Eof => "EOF".to_string(),
+            TriviaKind::Whitespace => "WHITESPACE",
+            TriviaKind::Newline => "NEWLINE",
+            TriviaKind::Comment => "COMMENT",
         }
     }
 }
+
+// source between tokens that the parser never sees, kept beside the tokens so tools
+// can put it back. like lexemes, its text is read from the source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Trivia {
+    pub kind: TriviaKind,
+    pub start_pos: CharPos,
+    pub end_pos: CharPos,
+    // after a token on the same line, like the comment in `a = 1; // why`
+    pub trailing: bool,
+}
+
+// a comment with its text and the line it is on, counting from 1
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Comment {
+    pub line: usize,
+    pub text: String,
+    pub trailing: bool,
+}
+
+impl TokenType {
+    // names used by the book's scanner chapter
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TokenType::LeftParen => "LEFT_PAREN",
+            TokenType::RightParen => "RIGHT_PAREN",
+            TokenType::LeftBrace => "LEFT_BRACE",
+            TokenType::RightBrace => "RIGHT_BRACE",
+            TokenType::Comma => "COMMA",
+            TokenType::Dot => "DOT",
+            TokenType::Minus => "MINUS",
+            TokenType::Plus => "PLUS",
+            TokenType::Semicolon => "SEMICOLON",
+            TokenType::Slash => "SLASH",
+            TokenType::Star => "STAR",
+            TokenType::Colon => "COLON",
+            TokenType::Ampersand => "AMPERSAND",
+            TokenType::Pipe => "PIPE",
+            TokenType::Caret => "CARET",
+            TokenType::Tilde => "TILDE",
+            TokenType::Bang => "BANG",
+            TokenType::BangEqual => "BANG_EQUAL",
+            TokenType::Equal => "EQUAL",
+            TokenType::EqualEqual => "EQUAL_EQUAL",
+            TokenType::Greater => "GREATER",
+            TokenType::GreaterEqual => "GREATER_EQUAL",
+            TokenType::Less => "LESS",
+            TokenType::LessEqual => "LESS_EQUAL",
+            TokenType::LessLess => "LESS_LESS",
+            TokenType::GreaterGreater => "GREATER_GREATER",
+            TokenType::Identifier(_) => "IDENTIFIER",
+            TokenType::String(_) => "STRING",
+            TokenType::Number(_) => "NUMBER",
+            TokenType::And => "AND",
+            TokenType::Class => "CLASS",
+            TokenType::Else => "ELSE",
+            TokenType::False => "FALSE",
+            TokenType::Fun => "FUN",
+            TokenType::For => "FOR",
+            TokenType::If => "IF",
+            TokenType::Nil => "NIL",
+            TokenType::Or => "OR",
+            TokenType::Print => "PRINT",
+            TokenType::Return => "RETURN",
+            TokenType::Super => "SUPER",
+            TokenType::This => "THIS",
+            TokenType::True => "TRUE",
+            TokenType::Var => "VAR",
+            TokenType::While => "WHILE",
+            TokenType::Eof => "EOF",
+        }
+    }
+
+    // numbers have no text without formatting, they are empty here
+    fn as_str(&self) -> &str {
+        match self {
+            TokenType::LeftParen => "(",
+            TokenType::RightParen => ")",
+            TokenType::LeftBrace => "{",
+            TokenType::RightBrace => "}",
+            TokenType::Comma => ",",
+            TokenType::Dot => ".",
+            TokenType::Minus => "-",
+            TokenType::Plus => "+",
+            TokenType::Semicolon => ";",
+            TokenType::Slash => "/",
+            TokenType::Star => "*",
+            TokenType::Colon => ":",
+            TokenType::Ampersand => "&",
+            TokenType::Pipe => "|",
+            TokenType::Caret => "^",
+            TokenType::Tilde => "~",
+            TokenType::Bang => "!",
+            TokenType::BangEqual => "!=",
+            TokenType::Equal => "=",
+            TokenType::EqualEqual => "==",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::LessLess => "<<",
+            TokenType::GreaterGreater => ">>",
+            TokenType::Identifier(s) => s,
+            TokenType::String(s) => s,
+            TokenType::Number(_) => "",
+            TokenType::And => "and",
+            TokenType::Class => "class",
+            TokenType::Else => "else",
+            TokenType::False => "false",
+            TokenType::Fun => "fun",
+            TokenType::For => "for",
+            TokenType::If => "if",
+            TokenType::Nil => "nil",
+            TokenType::Or => "or",
+            TokenType::Print => "print",
+            TokenType::Return => "return",
+            TokenType::Super => "super",
+            TokenType::This => "this",
+            TokenType::True => "true",
+            TokenType::Var => "var",
+            TokenType::While => "while",
+            TokenType::Eof => "EOF",
+        }
+    }
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenType::Number(n) => write!(f, "{}", **n),
+            token_type => write!(f, "{}", token_type.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lexemes_are_derived_and_shared() {
+        let pos = CharPos::default();
+        let name = Token::new(TokenType::Identifier("name".into()), pos, pos);
+        let copy = name.clone();
+        match (name.token_type(), copy.token_type()) {
+            (TokenType::Identifier(a), TokenType::Identifier(b)) => assert!(Shared::ptr_eq(a, b)),
+            _ => unreachable!(),
+        }
+        assert_eq!(copy.lexeme(), "name");
+        assert_eq!(copy.symbol(), Symbol::intern("name"));
+
+        let number = Token::new(TokenType::Number(1.5.into()), pos, pos);
+        assert_eq!(number.lexeme(), "1.5");
+        let keyword = Token::new(TokenType::While, pos, pos);
+        assert_eq!(keyword.lexeme(), "while");
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+use crate::{error::native_error::NativeError, list::LoxList, object::Object};
+
+// plain data passed between the host and scripts, without functions or instances.
+// lists are copied, changing a `Value` does not change the list a script sees
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Nil,
+    Number(f64),
+    String(String),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::List(_) => "list",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Object::from(self.clone()))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Nil, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(value: Vec<T>) -> Self {
+        Value::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+// the message of a native's argument error, so natives defined by the host can return
+// them with `?`
+fn expected(expected: &str, found: &Value) -> String {
+    NativeError::invalid_value(expected, found).to_string()
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(number) => Ok(number),
+            value => Err(expected("number", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(string) => Ok(string),
+            value => Err(expected("string", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            value => Err(expected("bool", &value)),
+        }
+    }
+}
+
+impl<T> TryFrom<Value> for Vec<T>
+where
+    T: TryFrom<Value, Error = String>,
+{
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::List(elements) => elements.into_iter().map(T::try_from).collect(),
+            value => Err(expected("list", &value)),
+        }
+    }
+}
+
+impl From<Value> for Object {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Nil => Object::Null,
+            Value::Number(number) => Object::Number(number.into()),
+            Value::String(string) => Object::string(string),
+            Value::Bool(b) => Object::Bool(b),
+            Value::List(elements) => {
+                Object::List(LoxList::new(elements.into_iter().map(Into::into).collect()))
+            }
+        }
+    }
+}
+
+impl TryFrom<&Object> for Value {
+    type Error = NativeError;
+    fn try_from(object: &Object) -> Result<Self, Self::Error> {
+        let value = match object {
+            Object::Null => Value::Nil,
+            Object::Number(number) => Value::Number(**number),
+            Object::String(string) => Value::String(string.to_string()),
+            Object::Bool(b) => Value::Bool(*b),
+            Object::List(list) => Value::List(
+                list.elements()
+                    .iter()
+                    .map(Value::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Object::Callable(_) | Object::LoxInstance(_) | Object::Map(_) => {
+                return Err(NativeError::invalid_argument(
+                    "nil, number, string, bool or list",
+                    object,
+                ))
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{callable::LoxCallable, native::NativeFunction};
+
+    use super::*;
+
+    #[test]
+    fn convert_rust_values() {
+        assert_eq!(Value::from(1.5), Value::Number(1.5));
+        assert_eq!(Value::from("a"), Value::String("a".to_string()));
+        assert_eq!(Value::from(None::<bool>), Value::Nil);
+        let list = Value::from(vec![vec![1.0, 2.0], vec![]]);
+        assert_eq!(list.to_string(), "[[1, 2], []]");
+        assert_eq!(
+            Vec::<Vec<f64>>::try_from(list),
+            Ok(vec![vec![1.0, 2.0], vec![]])
+        );
+        assert_eq!(
+            bool::try_from(Value::from("true")).unwrap_err().to_string(),
+            "Expected bool argument. Found string"
+        );
+        assert_eq!(
+            Vec::<String>::try_from(Value::from(vec![1.0]))
+                .unwrap_err()
+                .to_string(),
+            "Expected string argument. Found number"
+        );
+    }
+
+    #[test]
+    fn convert_objects() {
+        let object = Object::List(LoxList::new(vec![Object::string("a"), Object::Null]));
+        let value = Value::try_from(&object).unwrap();
+        assert_eq!(value, Value::from(vec![Value::from("a"), Value::Nil]));
+        assert_eq!(Object::from(value).to_string(), object.to_string());
+
+        let clock = Object::callable(LoxCallable::native_function(NativeFunction::clock()));
+        let list = Object::List(LoxList::new(vec![Object::Null, clock]));
+        assert_eq!(
+            Value::try_from(&list).unwrap_err().to_string(),
+            "Expected nil, number, string, bool or list argument. Found function"
+        );
+    }
+}
@@ -0,0 +1,70 @@
+// bindings for running scripts from javascript, built with the `wasm` feature:
+// `cargo build --lib --release --target wasm32-unknown-unknown --features wasm`,
+// then `wasm-bindgen` generates the javascript module.
+// what scripts print and the errors they have are handed to an output callback,
+// scripts read nothing and `exit()` only stops them
+use js_sys::{Array, Function};
+use wasm_bindgen::prelude::*;
+
+use crate::{interpreter::Interpreter, lox::Lox, value::Value};
+
+// writes go to a javascript function taking a string
+struct Output(Function);
+
+impl std::io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.0
+            .call1(&JsValue::NULL, &JsValue::from_str(&text))
+            .map_err(|_| std::io::Error::other("Output callback failed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn lox(output: Function) -> Lox<Output> {
+    Lox::new(Interpreter::new(Output(output)).with_reader(std::io::empty()))
+}
+
+fn js_value(value: Value) -> JsValue {
+    match value {
+        Value::Nil => JsValue::NULL,
+        Value::Number(number) => JsValue::from_f64(number),
+        Value::String(string) => JsValue::from_str(&string),
+        Value::Bool(b) => JsValue::from_bool(b),
+        Value::List(elements) => elements.into_iter().map(js_value).collect::<Array>().into(),
+    }
+}
+
+// run a whole script, returns whether it ran without errors
+#[wasm_bindgen]
+pub fn run(source: &str, output: Function) -> Result<bool, JsError> {
+    let mut lox = lox(output).whole_script();
+    lox.run(source)?;
+    Ok(!lox.had_error())
+}
+
+// a session where every evaluated source sees what the previous ones defined
+#[wasm_bindgen]
+pub struct Playground {
+    lox: Lox<Output>,
+}
+
+#[wasm_bindgen]
+impl Playground {
+    #[wasm_bindgen(constructor)]
+    pub fn new(output: Function) -> Self {
+        Self { lox: lox(output) }
+    }
+
+    // value of the last expression, errors are thrown with every diagnostic
+    pub fn eval(&mut self, source: &str) -> Result<JsValue, JsError> {
+        match self.lox.eval(source) {
+            Ok(value) => Ok(js_value(value)),
+            Err(diagnostics) => Err(JsError::new(&diagnostics.to_string())),
+        }
+    }
+}
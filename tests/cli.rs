@@ -0,0 +1,91 @@
+// the exit codes of the `lox` command
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+fn exit_code(args: &[&str]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+#[test]
+fn exit_with_the_kind_of_error() {
+    assert_eq!(exit_code(&["-e", "print 1;"]), Some(0));
+    assert_eq!(exit_code(&["-e", "print 1 +;"]), Some(65));
+    assert_eq!(exit_code(&["-e", "print nil + 1;"]), Some(70));
+    assert_eq!(exit_code(&["-e", "print -nil;"]), Some(70));
+}
+
+#[test]
+fn exit_when_the_script_is_stopped() {
+    assert_eq!(
+        exit_code(&["--max-steps", "10", "-e", "while (true) {}"]),
+        Some(70)
+    );
+    assert_eq!(
+        exit_code(&[
+            "--memory-limit",
+            "100000",
+            "-e",
+            "var s = \"ab\"; while (true) s = s + s;"
+        ]),
+        Some(70)
+    );
+}
+
+#[test]
+fn start_with_any_call_depth() {
+    assert_eq!(
+        exit_code(&["--max-call-depth", "100000", "-e", "print 1;"]),
+        Some(0)
+    );
+}
+
+#[test]
+fn read_lines_piped_to_the_prompt() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"var x = readLine();\nhello\nprint x;\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with(">>> hello\n>>> "), "{stdout}");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn run_a_changed_script_instead_of_its_cache() {
+    let dir = std::env::temp_dir().join(format!("lox-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("script.lox");
+    let compiled = dir.join("script.loxc");
+    let run = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+            .arg(&compiled)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    std::fs::write(&script, "print 1;").unwrap();
+    assert_eq!(exit_code(&["--compile", script.to_str().unwrap()]), Some(0));
+    assert_eq!(run(), "1\n");
+    std::fs::write(&script, "print 2;").unwrap();
+    assert_eq!(run(), "2\n");
+    std::fs::remove_file(&script).unwrap();
+    assert_eq!(run(), "2\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
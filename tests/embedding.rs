@@ -0,0 +1,115 @@
+// the api a program embedding lox sees, only what the crate exports is used here
+use std::sync::{Arc, Mutex};
+
+use lox::{Capability, LoxBuilder, LoxDiagnostics, Value};
+
+#[test]
+fn eval_scripts_in_an_engine() {
+    let mut output = Vec::new();
+    let mut lox = LoxBuilder::new(&mut output)
+        .reader(std::io::Cursor::new("typed\n"))
+        .capabilities(&[Capability::Io])
+        .max_steps(1000)
+        .build();
+
+    assert_eq!(lox.eval("var a = 1;"), Ok(Value::Nil));
+    assert_eq!(
+        lox.eval("fun f(x) { return x + a; }\nf(2);"),
+        Ok(Value::from(3.0))
+    );
+    assert_eq!(lox.eval("print readLine();"), Ok(Value::Nil));
+    assert_eq!(
+        lox.eval("list(1, \"b\");"),
+        Ok(Value::from(vec![Value::from(1.0), Value::from("b")]))
+    );
+
+    let diagnostics: LoxDiagnostics = lox.eval("clock();").unwrap_err();
+    let error = diagnostics.errors().next().unwrap();
+    assert_eq!(error.code, "undefined_variable");
+    assert_eq!(error.location.map(|location| location.line), Some(1));
+
+    let error = lox.eval("while (true) {}").unwrap_err();
+    assert_eq!(error.diagnostics[0].code, "budget_exceeded");
+    assert!(!lox.had_error());
+    drop(lox);
+    assert_eq!(String::from_utf8(output).unwrap(), "typed\n");
+}
+
+#[test]
+fn define_natives_with_host_state() {
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let mut lox = LoxBuilder::new(Vec::new()).build();
+    let log = Arc::clone(&logged);
+    lox.define_native("log", 1, move |arguments| {
+        let mut log = log.lock().unwrap();
+        log.push(arguments[0].clone());
+        Ok(Value::from(log.len() as f64))
+    });
+    lox.define_native("half", 1, |arguments| {
+        let number = f64::try_from(arguments[0].clone())?;
+        match number % 2.0 == 0.0 {
+            true => Ok(Value::from(number / 2.0)),
+            false => Err(format!("{number} is odd")),
+        }
+    });
+
+    assert_eq!(lox.eval("log(\"a\"); log(half(4));"), Ok(Value::from(2.0)));
+    assert_eq!(
+        *logged.lock().unwrap(),
+        [Value::from("a"), Value::from(2.0)]
+    );
+    assert_eq!(
+        lox.eval("half(\"x\");").unwrap_err().to_string(),
+        "[line 1]: RuntimeError: Expected number argument. Found string"
+    );
+    assert_eq!(
+        lox.eval("half(3);").unwrap_err().to_string(),
+        "[line 1]: RuntimeError: 3 is odd"
+    );
+}
+
+#[test]
+fn set_globals_before_running() {
+    let mut lox = LoxBuilder::new(Vec::new()).strict(true).build();
+    lox.set_global("name", "lox");
+    lox.set_global("sizes", vec![1.0, 2.0]);
+    lox.set_global("debug", true);
+    assert_eq!(
+        lox.eval("debug and name + str(sizes);"),
+        Ok(Value::from("lox[1, 2]"))
+    );
+}
+
+#[test]
+fn print_through_a_line_sink() {
+    let mut lines = Vec::new();
+    let mut lox = LoxBuilder::line_sink(|line| lines.push(line.to_string())).build();
+    assert_eq!(
+        lox.eval("print 1; write(\"a\"); print \"b\";"),
+        Ok(Value::Nil)
+    );
+    drop(lox);
+    assert_eq!(lines, ["1", "ab"]);
+}
+
+#[test]
+fn call_as_deep_as_allowed_on_any_thread() {
+    let mut lox = LoxBuilder::new(Vec::new()).build();
+    let source = "fun f(n) { if (n == 0) return 0; return f(n - 1) + 1; }";
+    assert_eq!(lox.eval(source), Ok(Value::Nil));
+    assert_eq!(lox.eval("f(990);"), Ok(Value::from(990.0)));
+    let error = lox.eval("f(1000);").unwrap_err();
+    assert_eq!(error.diagnostics[0].code, "stack_overflow");
+}
+
+#[test]
+fn convert_values() {
+    let sizes = Value::from(vec![1.0, 2.0]);
+    assert_eq!(sizes.type_name(), "list");
+    assert_eq!(sizes.to_string(), "[1, 2]");
+    assert_eq!(Vec::<f64>::try_from(sizes), Ok(vec![1.0, 2.0]));
+    assert_eq!(
+        String::try_from(Value::from(true)),
+        Err("Expected string argument. Found bool".to_string())
+    );
+}